@@ -1,14 +1,9 @@
 use crate::{
-    heartbeat::{cli::ValidatedHeartbeat, CbrsHeartbeat},
-    iot_beacon_report::IotBeaconIngestReport,
-    iot_valid_poc::IotPoc,
-    iot_witness_report::IotWitnessIngestReport,
-    speedtest::{cli::SpeedtestAverage, CellSpeedtest},
-    traits::MsgDecode,
+    registry::{self, Entry},
     Error, FileInfoStream, FileStore, FileType, Result, Settings,
 };
 use chrono::{NaiveDateTime, TimeZone, Utc};
-use futures::{stream::TryStreamExt, StreamExt, TryFutureExt};
+use futures::{future, stream::TryStreamExt, StreamExt, TryFutureExt};
 use helium_crypto::PublicKey;
 use serde::{ser::SerializeSeq, Serializer};
 use std::{
@@ -185,15 +180,11 @@ impl Locate {
     pub async fn run(&self, settings: &Settings) -> Result {
         let store = FileStore::from_settings(settings).await?;
         let file_infos = self.filter.list(&store);
-        let prefix = self.filter.prefix.clone();
-        let gateway = &self.gateway.clone();
+        let entry = registry::entry(FileType::from_str(&self.filter.prefix)?);
+        let gateway = self.gateway.to_string();
         let mut events = store
             .source(file_infos)
-            .map_ok(|buf| (buf, gateway))
-            .try_filter_map(|(buf, gateway)| {
-                let prefix = prefix.clone();
-                async move { locate(&prefix, gateway, &buf) }
-            })
+            .try_filter_map(|buf| future::ready(locate(&entry, &gateway, &buf)))
             .boxed();
         let mut ser = serde_json::Serializer::new(io::stdout());
         let mut seq = ser.serialize_seq(None)?;
@@ -205,103 +196,11 @@ impl Locate {
     }
 }
 
-fn locate(prefix: &str, gateway: &PublicKey, buf: &[u8]) -> Result<Option<serde_json::Value>> {
-    let pub_key = gateway.to_vec();
-    match FileType::from_str(prefix)? {
-        FileType::CbrsHeartbeat => {
-            CbrsHeartbeat::decode(buf).and_then(|event| event.to_value_if(pub_key))
-        }
-        FileType::ValidatedHeartbeat => {
-            ValidatedHeartbeat::decode(buf).and_then(|event| event.to_value_if(pub_key))
-        }
-        FileType::SpeedtestAvg => {
-            SpeedtestAverage::decode(buf).and_then(|event| event.to_value_if(pub_key))
-        }
-        FileType::CellSpeedtest => {
-            CellSpeedtest::decode(buf).and_then(|event| event.to_value_if(pub_key))
-        }
-        FileType::IotBeaconIngestReport => {
-            IotBeaconIngestReport::decode(buf).and_then(|event| event.to_value_if(pub_key))
-        }
-        FileType::IotWitnessIngestReport => {
-            IotWitnessIngestReport::decode(buf).and_then(|event| event.to_value_if(pub_key))
-        }
-        FileType::IotPoc => IotPoc::decode(buf).and_then(|event| event.to_value_if(pub_key)),
-        _ => Ok(None),
-    }
-}
-
-trait ToValue {
-    fn to_value(self) -> Result<serde_json::Value>
-    where
-        Self: serde::Serialize;
-
-    fn to_value_if(self, gateway: Vec<u8>) -> Result<Option<serde_json::Value>>
-    where
-        Self: Gateway,
-        Self: serde::Serialize + Sized,
-    {
-        (self.has_pubkey(&gateway))
-            .then(|| self.to_value())
-            .transpose()
-    }
-}
-
-trait Gateway {
-    fn has_pubkey(&self, pub_key: &[u8]) -> bool;
-}
-
-impl<T> ToValue for T
-where
-    T: serde::Serialize,
-{
-    fn to_value(self) -> Result<serde_json::Value>
-    where
-        Self: serde::Serialize,
-    {
-        self.serialize(serde_json::value::Serializer)
-            .map_err(Error::from)
-    }
-}
-
-impl Gateway for CbrsHeartbeat {
-    fn has_pubkey(&self, pub_key: &[u8]) -> bool {
-        self.pubkey.as_ref() == pub_key
-    }
-}
-
-impl Gateway for CellSpeedtest {
-    fn has_pubkey(&self, pub_key: &[u8]) -> bool {
-        self.pubkey.as_ref() == pub_key
-    }
-}
-
-impl Gateway for IotBeaconIngestReport {
-    fn has_pubkey(&self, pub_key: &[u8]) -> bool {
-        self.report.pub_key.as_ref() == pub_key
-    }
-}
-
-impl Gateway for IotWitnessIngestReport {
-    fn has_pubkey(&self, pub_key: &[u8]) -> bool {
-        self.report.pub_key.as_ref() == pub_key
-    }
-}
-
-impl Gateway for IotPoc {
-    fn has_pubkey(&self, pub_key: &[u8]) -> bool {
-        self.beacon_report.report.pub_key.as_ref() == pub_key
-    }
-}
-
-impl Gateway for ValidatedHeartbeat {
-    fn has_pubkey(&self, pub_key: &[u8]) -> bool {
-        self.pub_key.as_ref() == pub_key
-    }
-}
-
-impl Gateway for SpeedtestAverage {
-    fn has_pubkey(&self, pub_key: &[u8]) -> bool {
-        self.pub_key.as_ref() == pub_key
-    }
+fn locate(entry: &Entry, gateway: &str, buf: &[u8]) -> Result<Option<serde_json::Value>> {
+    let value = (entry.decode)(buf)?;
+    Ok(entry
+        .keys(&value)
+        .iter()
+        .any(|key| key == gateway)
+        .then_some(value))
 }
@@ -1,10 +1,12 @@
 use crate::{
     cli::print_json,
+    dead_letter::DeadLetter,
     file_source,
     heartbeat::{CbrsHeartbeat, CbrsHeartbeatIngestReport},
     iot_packet::IotValidPacket,
     mobile_session::{DataTransferSessionIngestReport, InvalidDataTransferIngestReport},
     mobile_subscriber::{SubscriberLocationIngestReport, VerifiedSubscriberLocationIngestReport},
+    registry,
     speedtest::{CellSpeedtest, CellSpeedtestIngestReport},
     traits::MsgDecode,
     wifi_heartbeat::WifiHeartbeatIngestReport,
@@ -46,6 +48,7 @@ pub struct Cmd {
 
 impl Cmd {
     pub async fn run(&self, _settings: &Settings) -> Result {
+        let entry = registry::entry(self.file_type);
         let mut file_stream = file_source::source([&self.in_path]);
 
         let mut wtr = Writer::from_writer(io::stdout());
@@ -318,7 +321,19 @@ impl Cmd {
                         "status": report.status,
                         "recv_timestamp": report.report.received_timestamp}))?;
                 }
-                _ => (),
+                FileType::DeadLetter => {
+                    let dead_letter = DeadLetter::decode(msg)?;
+                    print_json(&json!({
+                        "file_key": dead_letter.file_key,
+                        "offset": dead_letter.offset,
+                        "error": dead_letter.error,
+                        "data": base64::engine::general_purpose::STANDARD.encode(dead_letter.data),
+                        "timestamp": dead_letter.timestamp,
+                    }))?;
+                }
+                // Types without a format of their own are printed as the json
+                // of their registry entry
+                _ => print_json(&(entry.decode)(&msg)?)?,
             }
         }
 
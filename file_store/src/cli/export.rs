@@ -0,0 +1,403 @@
+use crate::{
+    cli::export_schema::{self, ColumnType, Columns},
+    file_source,
+    registry::{self, Entry},
+    BytesMutStream, FileFilter, FileStore, FileType, Result, Settings,
+};
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray, UInt64Array,
+};
+use arrow_schema::{Field, Schema, SchemaRef};
+use bytes::BytesMut;
+use futures::TryStreamExt;
+use parquet::arrow::ArrowWriter;
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+};
+
+const BATCH_SIZE: usize = 8192;
+
+/// Column of the fields of a record that are not in the schema of its type,
+/// written as json
+const EXTRA_COLUMN: &str = "extra";
+
+/// Export store files of a given type to csv or parquet.
+///
+/// Every record is flattened into a single row with dotted column names for
+/// nested fields. The columns of an export are fixed by the file type, see
+/// [`export_schema`]. Arrays are written as json strings unless exploded, and
+/// fields that are not in the schema are collected as json in an `extra`
+/// column.
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Output format
+    #[clap(long, value_enum, default_value = "csv")]
+    format: Format,
+    /// Path to write the export to
+    #[clap(long, short)]
+    out: PathBuf,
+    /// Write a row per element of the given array column, repeating the
+    /// other columns of the record. For example `selected_witnesses` for
+    /// iot_poc files
+    #[clap(long)]
+    explode: Option<String>,
+    #[clap(subcommand)]
+    source: Source,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Format {
+    Csv,
+    Parquet,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Source {
+    /// Export local files
+    Local {
+        /// Type of the files to export
+        file_type: FileType,
+        /// Paths to the files
+        paths: Vec<PathBuf>,
+    },
+    /// Export files from the bucket
+    Bucket(FileFilter),
+}
+
+impl Source {
+    fn file_type(&self) -> Result<FileType> {
+        match self {
+            Self::Local { file_type, .. } => Ok(*file_type),
+            Self::Bucket(filter) => FileType::from_str(&filter.prefix),
+        }
+    }
+
+    async fn records(&self, settings: &Settings) -> Result<BytesMutStream> {
+        match self {
+            Self::Local { paths, .. } => Ok(file_source::source(paths)),
+            Self::Bucket(filter) => {
+                let store = FileStore::from_settings(settings).await?;
+                Ok(store.source(filter.list(&store)))
+            }
+        }
+    }
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings) -> Result {
+        let file_type = self.source.file_type()?;
+        let entry = registry::entry(file_type);
+        let mut columns = export_schema::schema(file_type).columns(self.explode.as_deref())?;
+        columns.push((EXTRA_COLUMN.to_string(), ColumnType::Text));
+        let rows = Rows::new(&columns, self.explode.clone());
+
+        let records = self.source.records(settings).await?;
+        let count = match self.format {
+            Format::Csv => self.write_csv(&entry, &rows, &columns, records).await?,
+            Format::Parquet => self.write_parquet(&entry, &rows, &columns, records).await?,
+        };
+        eprintln!("exported {count} rows to {}", self.out.display());
+        Ok(())
+    }
+
+    async fn write_csv(
+        &self,
+        entry: &Entry,
+        rows: &Rows,
+        columns: &Columns,
+        mut records: BytesMutStream,
+    ) -> Result<usize> {
+        let mut wtr = csv::Writer::from_path(&self.out)?;
+        wtr.write_record(columns.iter().map(|(name, _)| name))?;
+        let mut count = 0;
+        while let Some(record) = records.try_next().await? {
+            for row in rows.rows(entry, record)? {
+                wtr.write_record(
+                    columns
+                        .iter()
+                        .map(|(name, _)| row.get(name).map(value_to_string).unwrap_or_default()),
+                )?;
+                count += 1;
+            }
+        }
+        wtr.flush()?;
+        Ok(count)
+    }
+
+    async fn write_parquet(
+        &self,
+        entry: &Entry,
+        rows: &Rows,
+        columns: &Columns,
+        mut records: BytesMutStream,
+    ) -> Result<usize> {
+        let schema: SchemaRef = Arc::new(Schema::new(
+            columns
+                .iter()
+                .map(|(name, column_type)| Field::new(name, column_type.data_type(), true))
+                .collect::<Vec<_>>(),
+        ));
+        let mut writer = ArrowWriter::try_new(File::create(&self.out)?, schema.clone(), None)?;
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        let mut count = 0;
+        while let Some(record) = records.try_next().await? {
+            for row in rows.rows(entry, record)? {
+                batch.push(row);
+                if batch.len() == BATCH_SIZE {
+                    writer.write(&record_batch(&schema, columns, &batch)?)?;
+                    count += batch.len();
+                    batch.clear();
+                }
+            }
+        }
+        if !batch.is_empty() {
+            writer.write(&record_batch(&schema, columns, &batch)?)?;
+            count += batch.len();
+        }
+        writer.close()?;
+        Ok(count)
+    }
+}
+
+type Row = BTreeMap<String, Value>;
+
+/// Flattens records into rows of the columns of an export
+struct Rows {
+    columns: HashMap<String, ColumnType>,
+    explode: Option<String>,
+}
+
+impl Rows {
+    fn new(columns: &Columns, explode: Option<String>) -> Self {
+        Self {
+            columns: columns.iter().cloned().collect(),
+            explode,
+        }
+    }
+
+    fn rows(&self, entry: &Entry, record: BytesMut) -> Result<Vec<Row>> {
+        Ok(self.flatten_record((entry.decode)(&record)?))
+    }
+
+    fn flatten_record(&self, mut value: Value) -> Vec<Row> {
+        let Some(column) = self.explode.as_deref() else {
+            let mut row = Row::new();
+            self.flatten(String::new(), value, &mut row);
+            return vec![row];
+        };
+        let items = take(&mut value, column);
+        let mut row = Row::new();
+        self.flatten(String::new(), value, &mut row);
+        match items {
+            Some(Value::Array(items)) if !items.is_empty() => items
+                .into_iter()
+                .map(|item| {
+                    let mut row = row.clone();
+                    self.flatten(column.to_string(), item, &mut row);
+                    row
+                })
+                .collect(),
+            Some(Value::Array(_)) | Some(Value::Null) | None => vec![row],
+            Some(value) => {
+                insert_extra(&mut row, column.to_string(), value);
+                vec![row]
+            }
+        }
+    }
+
+    /// Writes a value to its column, or the columns of its fields if it is an
+    /// object without a column of its own. Values without a column, or that
+    /// do not fit the type of their column, are kept in the extra column
+    fn flatten(&self, path: String, value: Value, row: &mut Row) {
+        match (self.columns.get(&path), value) {
+            (Some(column_type), value) if column_type.accepts(&value) => {
+                row.insert(path, value);
+            }
+            (None, Value::Object(map)) => {
+                for (key, value) in map {
+                    let path = if path.is_empty() {
+                        key
+                    } else {
+                        format!("{path}.{key}")
+                    };
+                    self.flatten(path, value, row);
+                }
+            }
+            (_, Value::Null) => (),
+            (_, value) => insert_extra(row, path, value),
+        }
+    }
+}
+
+/// Removes the value at a dotted path from a json object
+fn take(value: &mut Value, path: &str) -> Option<Value> {
+    match path.split_once('.') {
+        Some((key, path)) => take(value.get_mut(key)?, path),
+        None => value.as_object_mut()?.remove(path),
+    }
+}
+
+fn insert_extra(row: &mut Row, path: String, value: Value) {
+    if let Value::Object(extra) = row
+        .entry(EXTRA_COLUMN.to_string())
+        .or_insert_with(|| Value::Object(Map::new()))
+    {
+        extra.insert(path, value);
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn record_batch(schema: &SchemaRef, columns: &Columns, rows: &[Row]) -> Result<RecordBatch> {
+    let arrays = columns
+        .iter()
+        .map(|(name, column_type)| {
+            let values = rows
+                .iter()
+                .map(|row| row.get(name).filter(|value| !value.is_null()));
+            let array: ArrayRef = match column_type {
+                ColumnType::Bool => Arc::new(
+                    values
+                        .map(|v| v.and_then(Value::as_bool))
+                        .collect::<BooleanArray>(),
+                ),
+                ColumnType::Int => Arc::new(
+                    values
+                        .map(|v| v.and_then(Value::as_i64))
+                        .collect::<Int64Array>(),
+                ),
+                ColumnType::UInt => Arc::new(
+                    values
+                        .map(|v| v.and_then(Value::as_u64))
+                        .collect::<UInt64Array>(),
+                ),
+                ColumnType::Float => Arc::new(
+                    values
+                        .map(|v| v.and_then(Value::as_f64))
+                        .collect::<Float64Array>(),
+                ),
+                ColumnType::Text => Arc::new(
+                    values
+                        .map(|v| v.map(value_to_string))
+                        .collect::<StringArray>(),
+                ),
+            };
+            array
+        })
+        .collect();
+    Ok(RecordBatch::try_new(schema.clone(), arrays)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn poc() -> Value {
+        json!({
+            "poc_id": "abc",
+            "beacon_report": {
+                "location": 631711281837647359_u64,
+                "gain": 12,
+                "report": { "pub_key": "beaconer", "frequency": 904 },
+                "unknown": 1,
+            },
+            "selected_witnesses": [
+                { "report": { "pub_key": "w1", "signal": -90 } },
+                { "report": { "pub_key": "w2", "signal": -100 } },
+            ],
+            "unselected_witnesses": [],
+        })
+    }
+
+    #[test]
+    fn flattens_records_into_the_columns_of_their_type() {
+        let columns = export_schema::schema(FileType::IotPoc)
+            .columns(None)
+            .unwrap();
+        let rows = Rows::new(&columns, None).flatten_record(poc());
+        assert_eq!(1, rows.len());
+        let row = &rows[0];
+        assert_eq!(json!("beaconer"), row["beacon_report.report.pub_key"]);
+        assert_eq!(json!(904), row["beacon_report.report.frequency"]);
+        assert_eq!(json!(631711281837647359_u64), row["beacon_report.location"]);
+        assert_eq!(2, row["selected_witnesses"].as_array().unwrap().len());
+        assert_eq!(json!({ "beacon_report.unknown": 1 }), row[EXTRA_COLUMN]);
+    }
+
+    #[test]
+    fn explodes_declared_array_columns() {
+        let schema = export_schema::schema(FileType::IotPoc);
+        assert!(schema.columns(Some("beacon_report")).is_err());
+
+        let columns = schema.columns(Some("selected_witnesses")).unwrap();
+        assert!(!columns.iter().any(|(name, _)| name == "selected_witnesses"));
+        let rows =
+            Rows::new(&columns, Some("selected_witnesses".to_string())).flatten_record(poc());
+        assert_eq!(2, rows.len());
+        assert_eq!(json!("w2"), rows[1]["selected_witnesses.report.pub_key"]);
+        assert_eq!(json!(-100), rows[1]["selected_witnesses.report.signal"]);
+        assert_eq!(json!("abc"), rows[1]["poc_id"]);
+    }
+
+    #[test]
+    fn keeps_values_of_the_wrong_type_in_the_extra_column() {
+        let columns = export_schema::schema(FileType::IotPoc)
+            .columns(None)
+            .unwrap();
+        let rows = Rows::new(&columns, None).flatten_record(json!({
+            "poc_id": "abc",
+            "beacon_report": { "gain": "high", "location": -1 },
+        }));
+        assert!(!rows[0].contains_key("beacon_report.gain"));
+        assert!(!rows[0].contains_key("beacon_report.location"));
+        assert_eq!(
+            json!({ "beacon_report.gain": "high", "beacon_report.location": -1 }),
+            rows[0][EXTRA_COLUMN]
+        );
+    }
+
+    #[test]
+    fn writes_a_fixed_schema() {
+        let columns = export_schema::schema(FileType::IotPoc)
+            .columns(None)
+            .unwrap();
+        let schema = Arc::new(Schema::new(
+            columns
+                .iter()
+                .map(|(name, column_type)| Field::new(name, column_type.data_type(), true))
+                .collect::<Vec<_>>(),
+        ));
+        let rows = Rows::new(&columns, None);
+        let batch = record_batch(
+            &schema,
+            &columns,
+            &[
+                rows.flatten_record(json!({ "poc_id": "a" })).remove(0),
+                rows.flatten_record(poc()).remove(0),
+            ],
+        )
+        .unwrap();
+        assert_eq!(2, batch.num_rows());
+        assert_eq!(columns.len(), batch.num_columns());
+        let gain = batch
+            .column_by_name("beacon_report.gain")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert!(gain.is_null(0));
+        assert_eq!(12, gain.value(1));
+    }
+}
@@ -0,0 +1,540 @@
+//! The columns of an export of every [`FileType`].
+//!
+//! Columns are the dotted paths of fields in the json of a record, see
+//! [`registry::entry`](crate::registry::entry). Nested messages are
+//! flattened into columns as far as they are listed here, a listed field that
+//! holds an object or array is written as json. [`schema`] matches on every
+//! file type without a wildcard arm so a new file type does not compile until
+//! its columns are listed.
+
+use crate::{FileType, Result};
+use arrow_schema::DataType;
+use serde_json::Value;
+use std::io;
+
+/// Type of the values of a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Bool,
+    Int,
+    UInt,
+    Float,
+    /// Strings, and objects and arrays written as json
+    Text,
+}
+
+impl ColumnType {
+    pub fn data_type(&self) -> DataType {
+        match self {
+            Self::Bool => DataType::Boolean,
+            Self::Int => DataType::Int64,
+            Self::UInt => DataType::UInt64,
+            Self::Float => DataType::Float64,
+            Self::Text => DataType::Utf8,
+        }
+    }
+
+    /// Whether a json value can be written to a column of this type
+    pub fn accepts(&self, value: &Value) -> bool {
+        value.is_null()
+            || match self {
+                Self::Bool => value.is_boolean(),
+                Self::Int => value.is_i64(),
+                Self::UInt => value.is_u64(),
+                Self::Float => value.is_number(),
+                Self::Text => true,
+            }
+    }
+}
+
+pub type Columns = Vec<(String, ColumnType)>;
+
+pub struct Schema {
+    pub columns: Columns,
+    /// Array columns that can be exploded into a row per element, with the
+    /// columns of their elements
+    pub explodable: Vec<(&'static str, Columns)>,
+}
+
+impl Schema {
+    fn new(columns: Columns) -> Self {
+        Self {
+            columns,
+            explodable: Vec::new(),
+        }
+    }
+
+    fn explodable(mut self, column: &'static str, columns: Columns) -> Self {
+        self.explodable.push((column, columns));
+        self
+    }
+
+    /// The columns of an export, with the given column exploded into the
+    /// columns of its elements
+    pub fn columns(&self, explode: Option<&str>) -> Result<Columns> {
+        let Some(explode) = explode else {
+            return Ok(self.columns.clone());
+        };
+        let (column, element_columns) = self
+            .explodable
+            .iter()
+            .find(|(column, _)| *column == explode)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{explode} can not be exploded"),
+                )
+            })?;
+        Ok(self
+            .columns
+            .iter()
+            .filter(|(name, _)| name != column)
+            .cloned()
+            .chain(prefixed(column, element_columns))
+            .collect())
+    }
+}
+
+use ColumnType::{Bool, Float, Int, Text, UInt};
+
+fn columns(columns: &[(&str, ColumnType)]) -> Columns {
+    columns
+        .iter()
+        .map(|(name, column_type)| (name.to_string(), *column_type))
+        .collect()
+}
+
+fn prefixed(prefix: &str, columns: &Columns) -> Columns {
+    columns
+        .iter()
+        .map(|(name, column_type)| (format!("{prefix}.{name}"), *column_type))
+        .collect()
+}
+
+/// Columns of an ingest report, the received timestamp and the report
+fn ingest_report(report: Columns) -> Columns {
+    [
+        columns(&[("received_timestamp", Text)]),
+        prefixed("report", &report),
+    ]
+    .concat()
+}
+
+fn cbrs_heartbeat() -> Columns {
+    columns(&[
+        ("pubkey", Text),
+        ("hotspot_type", Text),
+        ("cell_id", UInt),
+        ("timestamp", Text),
+        ("lon", Float),
+        ("lat", Float),
+        ("operation_mode", Bool),
+        ("cbsd_category", Text),
+        ("cbsd_id", Text),
+        ("coverage_object", Text),
+    ])
+}
+
+fn wifi_heartbeat() -> Columns {
+    columns(&[
+        ("pubkey", Text),
+        ("lat", Float),
+        ("lon", Float),
+        ("operation_mode", Bool),
+        ("location_validation_timestamp", Text),
+        ("coverage_object", Text),
+        ("timestamp", Text),
+    ])
+}
+
+fn cell_speedtest() -> Columns {
+    columns(&[
+        ("pubkey", Text),
+        ("serial", Text),
+        ("timestamp", Text),
+        ("upload_speed", UInt),
+        ("download_speed", UInt),
+        ("latency", UInt),
+    ])
+}
+
+fn beacon_report() -> Columns {
+    columns(&[
+        ("pub_key", Text),
+        ("local_entropy", Text),
+        ("remote_entropy", Text),
+        ("data", Text),
+        ("frequency", UInt),
+        ("channel", Int),
+        ("datarate", Text),
+        ("tx_power", Int),
+        ("timestamp", Text),
+        ("signature", Text),
+        ("tmst", UInt),
+    ])
+}
+
+fn witness_report() -> Columns {
+    columns(&[
+        ("pub_key", Text),
+        ("data", Text),
+        ("timestamp", Text),
+        ("tmst", UInt),
+        ("signal", Int),
+        ("snr", Int),
+        ("frequency", UInt),
+        ("datarate", Text),
+        ("signature", Text),
+    ])
+}
+
+fn verified_witness_report() -> Columns {
+    [
+        columns(&[("received_timestamp", Text), ("status", Text)]),
+        prefixed("report", &witness_report()),
+        columns(&[
+            ("location", UInt),
+            ("gain", Int),
+            ("elevation", Int),
+            ("hex_scale", Text),
+            ("reward_unit", Text),
+            ("invalid_reason", Text),
+            ("participant_side", Text),
+            ("invalid_details", Text),
+        ]),
+    ]
+    .concat()
+}
+
+fn data_transfer_session_ingest_report() -> Columns {
+    ingest_report(
+        [
+            prefixed(
+                "data_transfer_usage",
+                &columns(&[
+                    ("pub_key", Text),
+                    ("upload_bytes", UInt),
+                    ("download_bytes", UInt),
+                    ("radio_access_technology", Text),
+                    ("event_id", Text),
+                    ("payer", Text),
+                    ("timestamp", Text),
+                    ("signature", Text),
+                ]),
+            ),
+            columns(&[
+                ("rewardable_bytes", UInt),
+                ("pub_key", Text),
+                ("signature", Text),
+            ]),
+        ]
+        .concat(),
+    )
+}
+
+fn subscriber_location_ingest_report() -> Columns {
+    ingest_report(columns(&[
+        ("subscriber_id", Text),
+        ("timestamp", Text),
+        ("carrier_pub_key", Text),
+    ]))
+}
+
+fn coverage_object() -> Columns {
+    columns(&[
+        ("pub_key", Text),
+        ("uuid", Text),
+        ("key_type", Text),
+        ("coverage_claim_time", Text),
+        ("coverage", Text),
+        ("indoor", Bool),
+        ("trust_score", UInt),
+        ("signature", Text),
+    ])
+}
+
+fn reward_share() -> Columns {
+    columns(&[
+        ("start_period", UInt),
+        ("end_period", UInt),
+        ("reward", Text),
+    ])
+}
+
+pub fn schema(file_type: FileType) -> Schema {
+    match file_type {
+        FileType::CbrsHeartbeat => Schema::new(cbrs_heartbeat()),
+        FileType::CellSpeedtest => Schema::new(cell_speedtest()),
+        FileType::Entropy | FileType::EntropyReport => Schema::new(columns(&[
+            ("data", Text),
+            ("timestamp", Text),
+            ("version", UInt),
+        ])),
+        FileType::SubnetworkRewards => Schema::new(columns(&[
+            ("start_epoch", UInt),
+            ("end_epoch", UInt),
+            ("rewards", Text),
+        ])),
+        FileType::CbrsHeartbeatIngestReport => Schema::new(ingest_report(cbrs_heartbeat())),
+        FileType::CellSpeedtestIngestReport => Schema::new(ingest_report(cell_speedtest())),
+        FileType::IotBeaconIngestReport => Schema::new(ingest_report(beacon_report())),
+        FileType::IotWitnessIngestReport => Schema::new(ingest_report(witness_report())),
+        FileType::IotPoc => Schema::new(
+            [
+                columns(&[("poc_id", Text)]),
+                prefixed(
+                    "beacon_report",
+                    &[
+                        columns(&[
+                            ("received_timestamp", Text),
+                            ("location", UInt),
+                            ("gain", Int),
+                            ("elevation", Int),
+                            ("hex_scale", Text),
+                        ]),
+                        prefixed("report", &beacon_report()),
+                        columns(&[("reward_unit", Text)]),
+                    ]
+                    .concat(),
+                ),
+                columns(&[("selected_witnesses", Text), ("unselected_witnesses", Text)]),
+            ]
+            .concat(),
+        )
+        .explodable("selected_witnesses", verified_witness_report())
+        .explodable("unselected_witnesses", verified_witness_report()),
+        FileType::IotInvalidBeaconReport => Schema::new(
+            [
+                columns(&[
+                    ("received_timestamp", Text),
+                    ("reason", Text),
+                    ("invalid_details", Text),
+                ]),
+                prefixed("report", &beacon_report()),
+                columns(&[("location", UInt), ("gain", Int), ("elevation", Int)]),
+            ]
+            .concat(),
+        ),
+        FileType::IotInvalidWitnessReport => Schema::new(
+            [
+                columns(&[
+                    ("received_timestamp", Text),
+                    ("reason", Text),
+                    ("invalid_details", Text),
+                ]),
+                prefixed("report", &witness_report()),
+                columns(&[("participant_side", Text)]),
+            ]
+            .concat(),
+        ),
+        FileType::SpeedtestAvg => Schema::new(columns(&[
+            ("pub_key", Text),
+            ("upload_speed_avg_bps", UInt),
+            ("download_speed_avg_bps", UInt),
+            ("latency_avg_ms", UInt),
+            ("validity", Text),
+            ("speedtests", Text),
+            ("timestamp", Text),
+            ("reward_multiplier", Float),
+        ]))
+        .explodable(
+            "speedtests",
+            columns(&[
+                ("upload_speed_bps", UInt),
+                ("download_speed_bps", UInt),
+                ("latency_ms", UInt),
+                ("timestamp", Text),
+            ]),
+        ),
+        FileType::ValidatedHeartbeat => Schema::new(columns(&[
+            ("cbsd_id", Text),
+            ("pub_key", Text),
+            ("timestamp", Text),
+            ("cell_type", Text),
+            ("validity", Text),
+            ("lat", Float),
+            ("lon", Float),
+            ("coverage_object", Text),
+            ("location_validation_timestamp", Text),
+            ("distance_to_asserted", UInt),
+            ("location_trust_score_multiplier", UInt),
+        ])),
+        FileType::SignedPocReceiptTxn => Schema::new(columns(&[("txn", Text)])),
+        FileType::RadioRewardShare => Schema::new(columns(&[
+            ("owner_key", Text),
+            ("hotspot_key", Text),
+            ("cbsd_id", Text),
+            ("amount", UInt),
+            ("start_epoch", UInt),
+            ("end_epoch", UInt),
+        ])),
+        FileType::RewardManifest => Schema::new(columns(&[
+            ("written_files", Text),
+            ("start_timestamp", UInt),
+            ("end_timestamp", UInt),
+        ])),
+        FileType::IotPacketReport => Schema::new(columns(&[
+            ("received_timestamp", Text),
+            ("oui", UInt),
+            ("net_id", UInt),
+            ("rssi", Int),
+            ("free", Bool),
+            ("frequency", UInt),
+            ("snr", Float),
+            ("data_rate", Text),
+            ("region", Text),
+            ("gateway", Text),
+            ("payload_hash", Text),
+            ("payload_size", UInt),
+            ("packet_type", Text),
+        ])),
+        FileType::IotValidPacket => Schema::new(columns(&[
+            ("payload_size", UInt),
+            ("gateway", Text),
+            ("payload_hash", Text),
+            ("num_dcs", UInt),
+            ("packet_timestamp", Text),
+        ])),
+        FileType::InvalidPacket => Schema::new(columns(&[
+            ("payload_size", UInt),
+            ("gateway", Text),
+            ("payload_hash", Text),
+            ("reason", Int),
+        ])),
+        FileType::NonRewardablePacket => Schema::new(
+            [
+                prefixed(
+                    "packet",
+                    &columns(&[
+                        ("payload_size", UInt),
+                        ("gateway", Text),
+                        ("payload_hash", Text),
+                        ("num_dcs", UInt),
+                        ("packet_timestamp", UInt),
+                    ]),
+                ),
+                columns(&[("reason", Int), ("timestamp", UInt)]),
+            ]
+            .concat(),
+        ),
+        FileType::IotRewardShare | FileType::MobileRewardShare => Schema::new(reward_share()),
+        FileType::DataTransferSessionIngestReport => {
+            Schema::new(data_transfer_session_ingest_report())
+        }
+        FileType::InvalidDataTransferSessionIngestReport => Schema::new(
+            [
+                prefixed("report", &data_transfer_session_ingest_report()),
+                columns(&[("reason", Text), ("timestamp", Text)]),
+            ]
+            .concat(),
+        ),
+        FileType::ValidDataTransferSession => Schema::new(columns(&[
+            ("pub_key", Text),
+            ("payer", Text),
+            ("upload_bytes", UInt),
+            ("download_bytes", UInt),
+            ("num_dcs", UInt),
+            ("first_timestamp", Text),
+            ("last_timestamp", Text),
+        ])),
+        FileType::PriceReport => Schema::new(columns(&[
+            ("timestamp", UInt),
+            ("price", UInt),
+            ("token_type", Int),
+        ])),
+        FileType::SubscriberLocationReq => Schema::new(columns(&[
+            ("subscriber_id", Text),
+            ("timestamp", Text),
+            ("carrier_pub_key", Text),
+        ])),
+        FileType::SubscriberLocationIngestReport => {
+            Schema::new(subscriber_location_ingest_report())
+        }
+        FileType::VerifiedSubscriberLocationIngestReport => Schema::new(
+            [
+                prefixed("report", &subscriber_location_ingest_report()),
+                columns(&[("status", Text), ("timestamp", Text)]),
+            ]
+            .concat(),
+        ),
+        FileType::MapperMsg => Schema::new(columns(&[("data", Text)])),
+        FileType::CoverageObject => Schema::new(
+            [
+                prefixed(
+                    "coverage_object",
+                    &columns(&[
+                        ("pub_key", Text),
+                        ("uuid", Text),
+                        ("key_type", Text),
+                        ("coverage_claim_time", UInt),
+                        ("coverage", Text),
+                        ("indoor", Bool),
+                        ("trust_score", UInt),
+                        ("signature", Text),
+                    ]),
+                ),
+                columns(&[("validity", Int)]),
+            ]
+            .concat(),
+        ),
+        FileType::CoverageObjectIngestReport => Schema::new(ingest_report(coverage_object()))
+            .explodable(
+                "report.coverage",
+                columns(&[
+                    ("location", Text),
+                    ("signal_level", Text),
+                    ("signal_power", Int),
+                ]),
+            ),
+        FileType::SeniorityUpdate => Schema::new(columns(&[
+            ("key_type", Text),
+            ("new_seniority_timestamp", UInt),
+            ("reason", Int),
+        ])),
+        FileType::VerifiedSpeedtest => Schema::new(columns(&[
+            ("report", Text),
+            ("result", Int),
+            ("timestamp", UInt),
+        ])),
+        FileType::WifiHeartbeat => Schema::new(wifi_heartbeat()),
+        FileType::WifiHeartbeatIngestReport => Schema::new(ingest_report(wifi_heartbeat())),
+        FileType::BoostedHexUpdate => {
+            Schema::new(columns(&[("timestamp", UInt), ("update", Text)]))
+        }
+        FileType::DeadLetter => Schema::new(columns(&[
+            ("file_key", Text),
+            ("offset", UInt),
+            ("error", Text),
+            ("data", Text),
+            ("timestamp", Text),
+        ])),
+        FileType::IotGatewaySnapshot => Schema::new(columns(&[
+            ("address", Text),
+            ("is_full_hotspot", Bool),
+            ("metadata", Text),
+        ])),
+        FileType::IotRegionParamsSnapshot => {
+            Schema::new(columns(&[("region", Text), ("params", Text)]))
+        }
+        FileType::IotHexDensitySnapshot => Schema::new(columns(&[("hex", UInt), ("scale", Text)])),
+        FileType::IotSnapshotManifest => Schema::new(columns(&[
+            ("gateway_files", Text),
+            ("region_params_files", Text),
+            ("hex_density_files", Text),
+        ])),
+        FileType::IotWitnessAnomaly => Schema::new(columns(&[
+            ("kind", Text),
+            ("score", Float),
+            ("gateways", Text),
+            ("pocs", UInt),
+            ("window_start", Text),
+            ("timestamp", Text),
+        ])),
+        FileType::IotVerificationRules => Schema::new(columns(&[
+            ("poc_id", Text),
+            ("timestamp", Text),
+            ("rules_hash", Text),
+            ("rules", Text),
+        ])),
+    }
+}
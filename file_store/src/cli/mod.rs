@@ -1,6 +1,10 @@
 pub mod bucket;
 pub mod dump;
+pub mod export;
+mod export_schema;
 pub mod info;
+pub mod replay;
+pub mod search;
 
 use crate::Result;
 
@@ -0,0 +1,338 @@
+use crate::{
+    compression::Compression,
+    file_integrity::FileIntegrity,
+    file_sink::{self, FileSinkClient},
+    file_upload::FileUpload,
+    Error, FileFilter, FileInfo, FileSinkBuilder, FileStore, Result, Settings,
+};
+use chrono::Duration;
+use futures::{StreamExt, TryStreamExt};
+use std::path::{Path, PathBuf};
+use tokio::{fs, sync::oneshot};
+
+/// Replay files of a given type in a time range into another bucket and/or
+/// prefix, so a daemon pointed at the target can reprocess them.
+///
+/// Only file keys are rewritten, the records in the files are replayed as is.
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    #[clap(flatten)]
+    filter: FileFilter,
+    /// Settings file for the store to replay into. Defaults to the source
+    /// store
+    #[clap(long)]
+    target: Option<PathBuf>,
+    /// Prefix to replay files under. Defaults to the source prefix
+    #[clap(long)]
+    target_prefix: Option<String>,
+    /// Shift the timestamp of every replayed file by the given number of
+    /// seconds, keeping the spacing between files
+    #[clap(long, allow_hyphen_values = true, conflicts_with = "restamp")]
+    shift_secs: Option<i64>,
+    /// Write the records of all files into new files timestamped at the time
+    /// of the replay instead of copying the files
+    #[clap(long)]
+    restamp: bool,
+    /// Local directory used to stage replayed files
+    #[clap(long, default_value = "/tmp/file_store_replay")]
+    work_dir: PathBuf,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings) -> Result {
+        let source = FileStore::from_settings(settings).await?;
+        let target_settings = match &self.target {
+            Some(path) => Settings::new(path)?,
+            None => settings.clone(),
+        };
+        let target_prefix = self
+            .target_prefix
+            .clone()
+            .unwrap_or_else(|| self.filter.prefix.clone());
+        if source_is_target(settings, &target_settings)
+            && target_prefix == self.filter.prefix
+            && !self.restamp
+            && self.shift_secs.unwrap_or_default() == 0
+        {
+            return Err(Error::from(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "replay would overwrite the source files",
+            )));
+        }
+        fs::create_dir_all(&self.work_dir).await?;
+
+        let count = if self.restamp {
+            self.restamp(&source, &target_settings, &target_prefix)
+                .await?
+        } else {
+            let target = FileStore::from_settings(&target_settings).await?;
+            let shift = Duration::seconds(self.shift_secs.unwrap_or_default());
+            self.copy(&source, &target, &target_prefix, shift).await?
+        };
+        eprintln!("replayed {count} files");
+        Ok(())
+    }
+
+    async fn copy(
+        &self,
+        source: &FileStore,
+        target: &FileStore,
+        target_prefix: &str,
+        shift: Duration,
+    ) -> Result<usize> {
+        let mut file_infos = self.filter.list(source);
+        let mut count = 0;
+        while let Some(info) = file_infos.try_next().await? {
+            let target_info = FileInfo::from((
+                target_prefix.to_string(),
+                info.timestamp + shift,
+                Compression::from_key(&info.key),
+            ));
+            let target_path = self.work_dir.join(&target_info.key);
+            download(source, &info.key, &target_path).await?;
+            // Upload any integrity sidecar ahead of the file it describes
+            if let Some(integrity) = source.get_integrity(&info.key).await? {
+                integrity.write(&target_path).await?;
+                let integrity_path = FileIntegrity::path(&target_path);
+                target.put(&integrity_path).await?;
+                fs::remove_file(&integrity_path).await?;
+            }
+            target.put(&target_path).await?;
+            fs::remove_file(&target_path).await?;
+            eprintln!("{} -> {}", info.key, target_info.key);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    async fn restamp(
+        &self,
+        source: &FileStore,
+        target_settings: &Settings,
+        target_prefix: &str,
+    ) -> Result<usize> {
+        let (file_upload, file_upload_server) =
+            FileUpload::from_settings_tm(target_settings).await?;
+        let (sink_client, sink) =
+            FileSinkBuilder::new(target_prefix, &self.work_dir, "file_store_replay")
+                .file_upload(Some(file_upload))
+                .auto_commit(false)
+                .roll_time(Duration::days(1))
+                .create()
+                .await?;
+
+        let (_upload_trigger, upload_listener) = triggered::trigger();
+        let (sink_trigger, sink_listener) = triggered::trigger();
+        let upload_handle = tokio::spawn(file_upload_server.run(upload_listener));
+        let sink_handle = tokio::spawn(sink.run(sink_listener));
+
+        let mut file_infos = self.filter.list(source);
+        let mut count = 0;
+        while let Some(info) = file_infos.try_next().await? {
+            let mut records = source.stream_file(info.clone()).await?;
+            while let Some(record) = records.next().await {
+                write_record(&sink_client, record?.to_vec()).await?;
+            }
+            eprintln!("{} -> {target_prefix}", info.key);
+            count += 1;
+        }
+
+        let manifest = sink_client.commit().await?.await??;
+        for file in manifest {
+            eprintln!("wrote {file}");
+        }
+
+        // Stopping the sink releases its uploader handle so the upload server
+        // completes once all files have been uploaded
+        sink_trigger.trigger();
+        drop(sink_client);
+        sink_handle.await??;
+        upload_handle.await??;
+        Ok(count)
+    }
+}
+
+fn source_is_target(source: &Settings, target: &Settings) -> bool {
+    source.bucket == target.bucket
+        && source.endpoint == target.endpoint
+        && source.local_path == target.local_path
+}
+
+async fn write_record(client: &FileSinkClient, bytes: Vec<u8>) -> Result {
+    let (on_write_tx, on_write_rx) = oneshot::channel();
+    client
+        .sender
+        .send(file_sink::Message::Data(on_write_tx, bytes))
+        .await
+        .map_err(|_| Error::channel())?;
+    on_write_rx.await.map_err(|_| Error::channel())?
+}
+
+async fn download(store: &FileStore, key: &str, path: &Path) -> Result {
+    let mut file = fs::File::create(path).await?;
+    let mut reader = tokio_util::io::StreamReader::new(store.get_raw(key).await?);
+    tokio::io::copy(&mut reader, &mut file).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileType;
+    use chrono::{DateTime, TimeZone, Utc};
+    use futures::SinkExt;
+    use tempfile::TempDir;
+    use tokio_util::codec::length_delimited::LengthDelimitedCodec;
+
+    fn local_settings(dir: &Path, bucket: &str) -> Settings {
+        Settings {
+            bucket: bucket.to_string(),
+            endpoint: None,
+            region: "us-west-2".to_string(),
+            access_key_id: None,
+            secret_access_key: None,
+            local_path: Some(dir.to_path_buf()),
+            verify_integrity: false,
+        }
+    }
+
+    async fn put_file(store: &FileStore, dir: &Path, info: &FileInfo, records: &[&str]) {
+        let path = dir.join(&info.key);
+        let file = fs::File::create(&path).await.expect("create file");
+        let mut transport = LengthDelimitedCodec::builder()
+            .max_frame_length(file_sink::MAX_FRAME_LENGTH)
+            .new_write(async_compression::tokio::write::GzipEncoder::new(file));
+        for record in records {
+            transport
+                .send(bytes::Bytes::from(record.to_string()))
+                .await
+                .expect("write record");
+        }
+        transport.close().await.expect("close file");
+        FileIntegrity::from_file(&path, records.len() as u64, None, None)
+            .await
+            .expect("integrity")
+            .write(&path)
+            .await
+            .expect("write sidecar");
+        store.put(&path).await.expect("put file");
+        store
+            .put(&FileIntegrity::path(&path))
+            .await
+            .expect("put sidecar");
+    }
+
+    fn cmd(work_dir: &Path, target: Option<PathBuf>, after: DateTime<Utc>) -> Cmd {
+        Cmd {
+            filter: FileFilter {
+                after: Some(after.naive_utc()),
+                before: None,
+                prefix: FileType::EntropyReport.to_string(),
+            },
+            target,
+            target_prefix: None,
+            shift_secs: None,
+            restamp: false,
+            work_dir: work_dir.to_path_buf(),
+        }
+    }
+
+    #[tokio::test]
+    async fn copies_files_in_range_with_shifted_timestamps() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let work_dir = tmp_dir.path().join("work");
+        fs::create_dir_all(&work_dir).await.expect("work dir");
+        let source_settings = local_settings(tmp_dir.path(), "source");
+        let source = FileStore::from_settings(&source_settings)
+            .await
+            .expect("source store");
+
+        // File keys have millisecond timestamps
+        let start = Utc
+            .timestamp_opt((Utc::now() - Duration::hours(3)).timestamp(), 0)
+            .unwrap();
+        let old = FileInfo::from((FileType::EntropyReport, start - Duration::hours(1)));
+        let first = FileInfo::from((FileType::EntropyReport, start));
+        let second = FileInfo::from((FileType::EntropyReport, start + Duration::minutes(5)));
+        for (info, records) in [
+            (&old, &["old"][..]),
+            (&first, &["hello", "world"][..]),
+            (&second, &["again"][..]),
+        ] {
+            put_file(&source, &work_dir, info, records).await;
+        }
+
+        let target_path = tmp_dir.path().join("target.toml");
+        fs::write(
+            &target_path,
+            format!(
+                "bucket = \"target\"\nlocal_path = \"{}\"\n",
+                tmp_dir.path().display()
+            ),
+        )
+        .await
+        .expect("write target settings");
+        let cmd = Cmd {
+            shift_secs: Some(3600),
+            ..cmd(&work_dir, Some(target_path), start - Duration::minutes(1))
+        };
+        cmd.run(&source_settings).await.expect("replay");
+
+        let target = FileStore::from_settings(&local_settings(tmp_dir.path(), "target"))
+            .await
+            .expect("target store")
+            .verify_integrity(true);
+        let replayed = target
+            .list_all(&FileType::EntropyReport.to_string(), start, Utc::now())
+            .await
+            .expect("list target");
+        assert_eq!(
+            vec![
+                first.timestamp + Duration::hours(1),
+                second.timestamp + Duration::hours(1)
+            ],
+            replayed
+                .iter()
+                .map(|info| info.timestamp)
+                .collect::<Vec<_>>()
+        );
+        assert!(target
+            .get_integrity(&replayed[0].key)
+            .await
+            .expect("get integrity")
+            .is_some());
+        let records: Vec<_> = target
+            .stream_file(replayed[0].clone())
+            .await
+            .expect("stream file")
+            .try_collect()
+            .await
+            .expect("verified records");
+        assert_eq!(vec!["hello", "world"], records);
+        // The source is left as is
+        assert_eq!(
+            3,
+            source
+                .list_all(
+                    &FileType::EntropyReport.to_string(),
+                    start - Duration::days(1),
+                    Utc::now()
+                )
+                .await
+                .expect("list source")
+                .len()
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_to_overwrite_the_source() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let settings = local_settings(tmp_dir.path(), "source");
+        let result = cmd(&tmp_dir.path().join("work"), None, Utc::now())
+            .run(&settings)
+            .await;
+        assert!(
+            matches!(result, Err(Error::Io(err)) if err.kind() == std::io::ErrorKind::InvalidInput)
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{registry, FileStore, FileType, Result, Settings};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use futures::TryStreamExt;
+use serde::{ser::SerializeSeq, Serializer};
+use serde_json::json;
+use std::io;
+
+/// Search files of one or more types for the records referring to a public
+/// key or cbsd id.
+///
+/// Files with a key index are only read if they refer to the key, and then
+/// only the matching records are decoded.
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Public key or cbsd id to search for
+    key: String,
+    /// File types to search
+    #[clap(long = "type", required = true)]
+    file_types: Vec<FileType>,
+    /// Optional start time to look for (inclusive). Defaults to the oldest
+    /// timestamp in the bucket.
+    #[clap(long)]
+    after: Option<NaiveDateTime>,
+    /// Optional end time to look for (exclusive). Defaults to the latest
+    /// available timestamp in the bucket.
+    #[clap(long)]
+    before: Option<NaiveDateTime>,
+    /// Scan files without a key index instead of skipping them
+    #[clap(long)]
+    scan: bool,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings) -> Result {
+        let store = FileStore::from_settings(settings).await?;
+        let after = self.after.as_ref().map(|dt| Utc.from_utc_datetime(dt));
+        let before = self.before.as_ref().map(|dt| Utc.from_utc_datetime(dt));
+
+        let mut ser = serde_json::Serializer::new(io::stdout());
+        let mut seq = ser.serialize_seq(None)?;
+        let mut unindexed = 0;
+        for file_type in &self.file_types {
+            let prefix = file_type.to_string();
+            let entry = registry::entry(*file_type);
+            let mut file_infos = store.list(&prefix, after, before);
+            while let Some(info) = file_infos.try_next().await? {
+                // Skip files of other types that share this prefix
+                if info.prefix != prefix {
+                    continue;
+                }
+                let positions = match store.get_index(&info.key).await? {
+                    Some(index) if index.positions(&self.key).is_empty() => continue,
+                    Some(index) => Some(index.positions(&self.key).to_vec()),
+                    None if self.scan => None,
+                    None => {
+                        unindexed += 1;
+                        continue;
+                    }
+                };
+                let last_position = positions.as_ref().and_then(|p| p.last().copied());
+
+                let mut records = store.stream_file(info.clone()).await?;
+                let mut position = 0;
+                while let Some(record) = records.try_next().await? {
+                    let record_position = position;
+                    position += 1;
+                    if let Some(positions) = &positions {
+                        if positions.binary_search(&record_position).is_err() {
+                            continue;
+                        }
+                    }
+                    let value = (entry.decode)(&record)?;
+                    if positions.is_some() || entry.keys(&value).contains(&self.key) {
+                        seq.serialize_element(&json!({
+                            "file": info.key,
+                            "position": record_position,
+                            "record": value,
+                        }))?;
+                    }
+                    if last_position.map_or(false, |last| record_position >= last) {
+                        break;
+                    }
+                }
+            }
+        }
+        seq.end()?;
+
+        if unindexed > 0 {
+            eprintln!("skipped {unindexed} files without a key index, use --scan to search them");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+//! Compression of store files.
+//!
+//! The compression of a file is recorded in the extension of its key, which
+//! is `gz` for gzip and `zst` for zstd. Keys without an extension predate
+//! other codecs and are read as gzip.
+
+use async_compression::tokio::{
+    bufread::{GzipDecoder, ZstdDecoder},
+    write::{GzipEncoder, ZstdEncoder},
+};
+use serde::Deserialize;
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Zstd => "zst",
+        }
+    }
+
+    /// The compression of the file with the given key
+    pub fn from_key(key: &str) -> Self {
+        if key.ends_with(".zst") {
+            Self::Zstd
+        } else {
+            Self::Gzip
+        }
+    }
+
+    pub fn encoder<W: AsyncWrite + Unpin>(&self, writer: W) -> Encoder<W> {
+        match self {
+            Self::Gzip => Encoder::Gzip(GzipEncoder::new(writer)),
+            Self::Zstd => Encoder::Zstd(ZstdEncoder::new(writer)),
+        }
+    }
+
+    pub fn decoder<R>(&self, reader: R) -> Pin<Box<dyn AsyncRead + Send>>
+    where
+        R: AsyncBufRead + Send + 'static,
+    {
+        match self {
+            Self::Gzip => Box::pin(GzipDecoder::new(reader)),
+            Self::Zstd => Box::pin(ZstdDecoder::new(reader)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Encoder<W> {
+    Gzip(GzipEncoder<W>),
+    Zstd(ZstdEncoder<W>),
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Encoder<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Gzip(encoder) => Pin::new(encoder).poll_write(cx, buf),
+            Self::Zstd(encoder) => Pin::new(encoder).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Gzip(encoder) => Pin::new(encoder).poll_flush(cx),
+            Self::Zstd(encoder) => Pin::new(encoder).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Gzip(encoder) => Pin::new(encoder).poll_shutdown(cx),
+            Self::Zstd(encoder) => Pin::new(encoder).poll_shutdown(cx),
+        }
+    }
+}
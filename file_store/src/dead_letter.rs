@@ -0,0 +1,106 @@
+use crate::{
+    file_info::DEAD_LETTER,
+    traits::{MsgDecode, MsgTimestamp, TimestampDecode, TimestampEncode},
+    Error, FileSinkBuilder, Result,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+
+pub const DEAD_LETTER_METRIC: &str = "dead_letter";
+
+/// Wire format of a record that could not be decoded by a
+/// [`FileInfoPollerServer`](crate::file_info_poller::FileInfoPollerServer).
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DeadLetterV1 {
+    /// Key of the file the record was read from
+    #[prost(string, tag = "1")]
+    pub file_key: String,
+    /// Offset of the record's frame in the uncompressed file
+    #[prost(uint64, tag = "2")]
+    pub offset: u64,
+    /// The decode error
+    #[prost(string, tag = "3")]
+    pub error: String,
+    /// The undecodable record
+    #[prost(bytes = "vec", tag = "4")]
+    pub data: Vec<u8>,
+    /// Timestamp in millis the record was dead lettered
+    #[prost(uint64, tag = "5")]
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DeadLetter {
+    pub file_key: String,
+    pub offset: u64,
+    pub error: String,
+    pub data: Vec<u8>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl DeadLetter {
+    pub fn new(file_key: String, offset: u64, error: &Error, data: Vec<u8>) -> Self {
+        Self {
+            file_key,
+            offset,
+            error: format!("{error:?}"),
+            data,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// The file prefix for dead letters of files with the given prefix
+pub fn prefix(prefix: &str) -> String {
+    format!("{prefix}_{DEAD_LETTER}")
+}
+
+/// A file sink builder for dead letters of files with the given prefix
+pub fn file_sink(prefix: &str, target_path: &Path) -> FileSinkBuilder {
+    FileSinkBuilder::new(self::prefix(prefix), target_path, DEAD_LETTER_METRIC)
+}
+
+impl MsgTimestamp<u64> for DeadLetter {
+    fn timestamp(&self) -> u64 {
+        self.timestamp.encode_timestamp_millis()
+    }
+}
+
+impl MsgTimestamp<Result<DateTime<Utc>>> for DeadLetterV1 {
+    fn timestamp(&self) -> Result<DateTime<Utc>> {
+        self.timestamp.to_timestamp_millis()
+    }
+}
+
+impl MsgDecode for DeadLetter {
+    type Msg = DeadLetterV1;
+}
+
+impl TryFrom<DeadLetterV1> for DeadLetter {
+    type Error = Error;
+
+    fn try_from(v: DeadLetterV1) -> Result<Self> {
+        let timestamp = v.timestamp()?;
+        Ok(Self {
+            file_key: v.file_key,
+            offset: v.offset,
+            error: v.error,
+            data: v.data,
+            timestamp,
+        })
+    }
+}
+
+impl From<DeadLetter> for DeadLetterV1 {
+    fn from(v: DeadLetter) -> Self {
+        let timestamp = v.timestamp();
+        Self {
+            file_key: v.file_key,
+            offset: v.offset,
+            error: v.error,
+            data: v.data,
+            timestamp,
+        }
+    }
+}
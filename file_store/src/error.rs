@@ -12,10 +12,16 @@ pub enum Error {
     Decode(#[from] DecodeError),
     #[error("not found")]
     NotFound(String),
+    #[error("file integrity error: {0}")]
+    Integrity(String),
     #[error("crypto error")]
     Crypto(Box<helium_crypto::Error>),
     #[error("csv error")]
     Csv(#[from] csv::Error),
+    #[error("parquet error")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("arrow error")]
+    Arrow(#[from] arrow_schema::ArrowError),
     #[error("aws error")]
     Aws(#[from] aws_sdk_s3::Error),
     #[error("config error")]
@@ -30,6 +36,8 @@ pub enum Error {
     SendTimeout,
     #[error("shutting down")]
     Shutdown,
+    #[error("not committed")]
+    NotCommitted,
     #[error("error building file info poller")]
     FileInfoPollerError(#[from] crate::file_info_poller::FileInfoPollerConfigBuilderError),
     #[cfg(feature = "sqlx-postgres")]
@@ -61,6 +69,8 @@ pub enum DecodeError {
     UnsupportedStatusReason(String, i32),
     #[error("unsupported signal level, type: {0}, value: {1}")]
     UnsupportedSignalLevel(String, i32),
+    #[error("unsupported witness anomaly kind, type: {0}, value: {1}")]
+    UnsupportedWitnessAnomalyKind(String, i32),
     #[error("invalid unix timestamp {0}")]
     InvalidTimestamp(u64),
     #[error("Uuid error: {0}")]
@@ -79,6 +89,8 @@ pub enum EncodeError {
     Prost(#[from] helium_proto::EncodeError),
     #[error("json error")]
     Json(#[from] serde_json::Error),
+    #[error("value out of range: {0}")]
+    OutOfRange(String),
 }
 
 macro_rules! from_err {
@@ -102,6 +114,9 @@ impl Error {
     pub fn not_found<E: ToString>(msg: E) -> Self {
         Self::NotFound(msg.to_string())
     }
+    pub fn integrity<E: ToString>(msg: E) -> Self {
+        Self::Integrity(msg.to_string())
+    }
     pub fn channel() -> Error {
         Error::Channel
     }
@@ -155,11 +170,21 @@ impl DecodeError {
         Error::Decode(Self::UnsupportedSignalLevel(msg1.to_string(), msg2))
     }
 
+    pub fn unsupported_witness_anomaly_kind(msg1: impl ToString, msg2: i32) -> Error {
+        Error::Decode(Self::UnsupportedWitnessAnomalyKind(msg1.to_string(), msg2))
+    }
+
     pub fn file_stream_try_decode<E: ToString>(msg: E) -> Error {
         Error::Decode(Self::FileStreamTryDecode(msg.to_string()))
     }
 }
 
+impl EncodeError {
+    pub fn out_of_range<E: ToString>(msg: E) -> Error {
+        Error::Encode(Self::OutOfRange(msg.to_string()))
+    }
+}
+
 impl From<helium_crypto::Error> for Error {
     fn from(err: helium_crypto::Error) -> Self {
         Self::Crypto(Box::new(err))
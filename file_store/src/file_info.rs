@@ -1,4 +1,4 @@
-use crate::{error::DecodeError, traits::TimestampDecode, Error, Result};
+use crate::{compression::Compression, error::DecodeError, traits::TimestampDecode, Error, Result};
 use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -14,7 +14,7 @@ pub struct FileInfo {
 }
 
 lazy_static! {
-    static ref RE: Regex = Regex::new(r"([a-z,_]+).(\d+)(.gz)?").unwrap();
+    static ref RE: Regex = Regex::new(r"([a-z,_]+).(\d+)(.gz|.zst)?$").unwrap();
 }
 
 impl FromStr for FileInfo {
@@ -68,8 +68,14 @@ impl From<(FileType, DateTime<Utc>)> for FileInfo {
 
 impl From<(String, DateTime<Utc>)> for FileInfo {
     fn from(v: (String, DateTime<Utc>)) -> Self {
+        Self::from((v.0, v.1, Compression::default()))
+    }
+}
+
+impl From<(String, DateTime<Utc>, Compression)> for FileInfo {
+    fn from(v: (String, DateTime<Utc>, Compression)) -> Self {
         Self {
-            key: format!("{}.{}.gz", &v.0, v.1.timestamp_millis()),
+            key: format!("{}.{}.{}", &v.0, v.1.timestamp_millis(), v.2.extension()),
             prefix: v.0,
             timestamp: v.1,
             size: 0,
@@ -146,6 +152,13 @@ pub const COVERAGE_OBJECT_INGEST_REPORT: &str = "coverage_object_ingest_report";
 pub const SENIORITY_UPDATE: &str = "seniority_update";
 
 pub const BOOSTED_HEX_UPDATE: &str = "boosted_hex_update";
+pub const DEAD_LETTER: &str = "dead_letter";
+pub const IOT_GATEWAY_SNAPSHOT: &str = "iot_gateway_snapshot";
+pub const IOT_REGION_PARAMS_SNAPSHOT: &str = "iot_region_params_snapshot";
+pub const IOT_HEX_DENSITY_SNAPSHOT: &str = "iot_hex_density_snapshot";
+pub const IOT_SNAPSHOT_MANIFEST: &str = "iot_snapshot_manifest";
+pub const IOT_WITNESS_ANOMALY: &str = "iot_witness_anomaly";
+pub const IOT_VERIFICATION_RULES: &str = "iot_verification_rules";
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Copy, strum::EnumCount)]
 #[serde(rename_all = "snake_case")]
@@ -188,6 +201,13 @@ pub enum FileType {
     WifiHeartbeat,
     WifiHeartbeatIngestReport,
     BoostedHexUpdate,
+    DeadLetter,
+    IotGatewaySnapshot,
+    IotRegionParamsSnapshot,
+    IotHexDensitySnapshot,
+    IotSnapshotManifest,
+    IotWitnessAnomaly,
+    IotVerificationRules,
 }
 
 impl fmt::Display for FileType {
@@ -235,6 +255,13 @@ impl fmt::Display for FileType {
             Self::CoverageObjectIngestReport => COVERAGE_OBJECT_INGEST_REPORT,
             Self::SeniorityUpdate => SENIORITY_UPDATE,
             Self::BoostedHexUpdate => BOOSTED_HEX_UPDATE,
+            Self::DeadLetter => DEAD_LETTER,
+            Self::IotGatewaySnapshot => IOT_GATEWAY_SNAPSHOT,
+            Self::IotRegionParamsSnapshot => IOT_REGION_PARAMS_SNAPSHOT,
+            Self::IotHexDensitySnapshot => IOT_HEX_DENSITY_SNAPSHOT,
+            Self::IotSnapshotManifest => IOT_SNAPSHOT_MANIFEST,
+            Self::IotWitnessAnomaly => IOT_WITNESS_ANOMALY,
+            Self::IotVerificationRules => IOT_VERIFICATION_RULES,
         };
         f.write_str(s)
     }
@@ -285,6 +312,13 @@ impl FileType {
             Self::CoverageObjectIngestReport => COVERAGE_OBJECT_INGEST_REPORT,
             Self::SeniorityUpdate => SENIORITY_UPDATE,
             Self::BoostedHexUpdate => BOOSTED_HEX_UPDATE,
+            Self::DeadLetter => DEAD_LETTER,
+            Self::IotGatewaySnapshot => IOT_GATEWAY_SNAPSHOT,
+            Self::IotRegionParamsSnapshot => IOT_REGION_PARAMS_SNAPSHOT,
+            Self::IotHexDensitySnapshot => IOT_HEX_DENSITY_SNAPSHOT,
+            Self::IotSnapshotManifest => IOT_SNAPSHOT_MANIFEST,
+            Self::IotWitnessAnomaly => IOT_WITNESS_ANOMALY,
+            Self::IotVerificationRules => IOT_VERIFICATION_RULES,
         }
     }
 }
@@ -335,6 +369,21 @@ impl FromStr for FileType {
             COVERAGE_OBJECT_INGEST_REPORT => Self::CoverageObjectIngestReport,
             SENIORITY_UPDATE => Self::SeniorityUpdate,
             BOOSTED_HEX_UPDATE => Self::BoostedHexUpdate,
+            DEAD_LETTER => Self::DeadLetter,
+            IOT_GATEWAY_SNAPSHOT => Self::IotGatewaySnapshot,
+            IOT_REGION_PARAMS_SNAPSHOT => Self::IotRegionParamsSnapshot,
+            IOT_HEX_DENSITY_SNAPSHOT => Self::IotHexDensitySnapshot,
+            IOT_SNAPSHOT_MANIFEST => Self::IotSnapshotManifest,
+            IOT_WITNESS_ANOMALY => Self::IotWitnessAnomaly,
+            IOT_VERIFICATION_RULES => Self::IotVerificationRules,
+            // Dead letters of a file type are written with that type as a
+            // prefix, see dead_letter::prefix
+            s if s
+                .strip_suffix(DEAD_LETTER)
+                .is_some_and(|prefix| prefix.ends_with('_')) =>
+            {
+                Self::DeadLetter
+            }
             _ => return Err(Error::from(io::Error::from(io::ErrorKind::InvalidInput))),
         };
         Ok(result)
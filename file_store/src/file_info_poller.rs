@@ -1,10 +1,19 @@
-use crate::{traits::MsgDecode, Error, FileInfo, FileStore, Result};
+use crate::{
+    dead_letter::{DeadLetter, DeadLetterV1},
+    file_sink::FileSinkClient,
+    traits::MsgDecode,
+    Error, FileInfo, FileStore, Result,
+};
 use chrono::{DateTime, Duration, Utc};
 use derive_builder::Builder;
-use futures::{future::LocalBoxFuture, stream::BoxStream, StreamExt};
+use futures::{
+    future::LocalBoxFuture,
+    stream::{BoxStream, FuturesOrdered},
+    FutureExt, StreamExt,
+};
 use futures_util::TryFutureExt;
 use retainer::Cache;
-use std::{collections::VecDeque, marker::PhantomData, sync::Arc};
+use std::{collections::VecDeque, io, marker::PhantomData, sync::Arc};
 use task_manager::ManagedTask;
 use tokio::sync::mpsc::{Receiver, Sender};
 
@@ -14,6 +23,11 @@ const DEFAULT_POLL_DURATION: std::time::Duration =
 const CLEAN_DURATION: std::time::Duration = std::time::Duration::from_secs(12 * 60 * 60);
 const CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(3 * 60 * 60);
 
+const REJECTED_FILES_METRIC: &str = "file_info_poller_rejected_files";
+const DEAD_LETTERS_METRIC: &str = "file_info_poller_dead_letters";
+// Size of the length prefix of each frame in a file
+const FRAME_HEADER_LENGTH: u64 = 4;
+
 type MemoryFileCache = Arc<Cache<String, bool>>;
 
 #[async_trait::async_trait]
@@ -83,8 +97,16 @@ pub struct FileInfoPollerConfig<T, S> {
     offset: Duration,
     #[builder(default = "5")]
     queue_size: usize,
+    /// Number of files to download and decode concurrently. Files are still
+    /// delivered, and so recorded, in the order they were listed
+    #[builder(default = "1")]
+    workers: usize,
     #[builder(default = r#""default".to_string()"#)]
     process_name: String,
+    /// Optional sink for records that fail to decode, see
+    /// [`dead_letter::file_sink`](crate::dead_letter::file_sink)
+    #[builder(default)]
+    dead_letter: Option<FileSinkClient>,
     #[builder(setter(skip))]
     p: PhantomData<T>,
 }
@@ -178,6 +200,10 @@ where
                 .await?;
 
             for file in files {
+                // Skip files of other types that share this prefix
+                if file.prefix != self.config.prefix {
+                    continue;
+                }
                 if !self.is_already_processed(&file).await? {
                     self.latest_file_timestamp = Some(file.timestamp);
                     self.file_queue.push_back(file);
@@ -201,6 +227,11 @@ where
         );
 
         let sender = self.sender.clone();
+        let workers = self.config.workers.max(1);
+        // Files being downloaded and decoded, in the order they were listed
+        let mut in_flight = FuturesOrdered::new();
+        // The next parsed file waiting for room in the channel
+        let mut ready: Option<FileInfoStream<T>> = None;
         loop {
             tokio::select! {
                 biased;
@@ -209,11 +240,33 @@ where
                     break;
                 }
                 _ = cleanup_trigger.tick() => self.clean(&self.cache).await?,
-                result = futures::future::try_join(sender.reserve().map_err(Error::from), self.get_next_file()) => {
-                    let (permit, file) = result?;
-                    let data = parse_file(&self.config.store, process_name.clone(), file.clone()).await?;
-                    permit.send(data);
+                permit = sender.reserve(), if ready.is_some() => {
+                    permit?.send(ready.take().expect("ready file"));
+                }
+                Some((file, result)) = in_flight.next(), if ready.is_none() && !in_flight.is_empty() => {
+                    match result {
+                        Ok(data) => ready = Some(data),
+                        Err(Error::Integrity(err)) => {
+                            tracing::error!(r#type = self.config.prefix, %process_name, %file, "rejecting file: {err}");
+                            metrics::increment_counter!(REJECTED_FILES_METRIC, "type" => self.config.prefix.clone(), "process_name" => process_name.clone());
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+                file = self.get_next_file(), if in_flight.len() + usize::from(ready.is_some()) < workers => {
+                    let file = file?;
+                    // Cache the file right away so it is not listed again
+                    // while in flight
                     cache_file(&self.cache, &file).await;
+                    in_flight.push_back(
+                        parse_file(
+                            self.config.store.clone(),
+                            process_name.clone(),
+                            file,
+                            self.config.dead_letter.clone(),
+                        )
+                        .boxed(),
+                    );
                 }
             }
         }
@@ -260,43 +313,96 @@ where
 }
 
 async fn parse_file<T>(
+    store: FileStore,
+    process_name: String,
+    file: FileInfo,
+    dead_letter: Option<FileSinkClient>,
+) -> (FileInfo, Result<FileInfoStream<T>>)
+where
+    T: MsgDecode + TryFrom<T::Msg, Error = Error> + Send + Sync + 'static,
+{
+    let result = parse_file_data(&store, process_name, file.clone(), dead_letter.as_ref()).await;
+    (file, result)
+}
+
+async fn parse_file_data<T>(
     store: &FileStore,
     process_name: String,
     file: FileInfo,
+    dead_letter: Option<&FileSinkClient>,
 ) -> Result<FileInfoStream<T>>
 where
     T: MsgDecode + TryFrom<T::Msg, Error = Error> + Send + Sync + 'static,
 {
-    let stream: Vec<T> = store
-        .stream_file(file.clone())
-        .await?
-        .filter_map(|msg| async {
-            msg.map_err(|err| {
+    let mut msgs = store.stream_file(file.clone()).await?;
+    let mut stream: Vec<T> = Vec::new();
+    let mut offset = 0;
+    while let Some(msg) = msgs.next().await {
+        let msg = match msg {
+            Ok(msg) => msg,
+            // A file failing its integrity check is rejected as a whole
+            Err(err @ Error::Integrity(_)) => return Err(err),
+            // Framing is lost after a bad frame so the rest of the file can
+            // not be read. It is dead lettered from the offset of the bad
+            // frame and the records read so far are delivered, recording the
+            // file as processed
+            Err(err) if is_malformed(&err) => {
                 tracing::error!(
                     "Error streaming entry in file of type {}: {err:?}",
                     std::any::type_name::<T>()
                 );
-                err
-            })
-            .ok()
-        })
-        .filter_map(|msg| async {
-            <T as MsgDecode>::decode(msg)
-                .map_err(|err| {
-                    tracing::error!(
-                        "Error in decoding message of type {}: {err:?}",
-                        std::any::type_name::<T>()
-                    );
-                    err
-                })
-                .ok()
-        })
-        .collect()
-        .await;
+                write_dead_letter(dead_letter, &file, offset, &err, vec![]).await?;
+                break;
+            }
+            // Failing to read the file says nothing about its data, the file
+            // is left unprocessed to be read again
+            Err(err) => return Err(err),
+        };
+        let msg_offset = offset;
+        offset += FRAME_HEADER_LENGTH + msg.len() as u64;
+        match <T as MsgDecode>::decode(msg.clone()) {
+            Ok(item) => stream.push(item),
+            Err(err) => {
+                tracing::error!(
+                    "Error in decoding message of type {}: {err:?}",
+                    std::any::type_name::<T>()
+                );
+                write_dead_letter(dead_letter, &file, msg_offset, &err, msg.to_vec()).await?;
+            }
+        }
+    }
 
     Ok(FileInfoStream::new(process_name, file, stream))
 }
 
+/// Whether a stream error is caused by the data of a file, from its
+/// compression or framing, rather than by reading it
+fn is_malformed(err: &Error) -> bool {
+    match err {
+        Error::Decode(_) => true,
+        Error::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+async fn write_dead_letter(
+    dead_letter: Option<&FileSinkClient>,
+    file: &FileInfo,
+    offset: u64,
+    err: &Error,
+    data: Vec<u8>,
+) -> Result {
+    metrics::increment_counter!(DEAD_LETTERS_METRIC, "type" => file.prefix.clone());
+    if let Some(dead_letter) = dead_letter {
+        let record = DeadLetter::new(file.key.clone(), offset, err, data);
+        dead_letter.write(DeadLetterV1::from(record), []).await?;
+    }
+    Ok(())
+}
+
 fn create_cache() -> MemoryFileCache {
     Arc::new(Cache::new())
 }
@@ -378,3 +484,267 @@ impl FileInfoPollerState for sqlx::Pool<sqlx::Postgres> {
         .map_err(Error::from)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dead_letter, entropy_report::EntropyReport, file_source, FileType};
+    use chrono::TimeZone;
+    use futures::TryStreamExt;
+    use helium_proto::{EntropyReportV1, Message};
+    use std::{path::Path, str::FromStr, sync::Mutex};
+    use tempfile::TempDir;
+    use tokio::io::AsyncWriteExt;
+
+    /// Poller state that keeps the processed files in memory
+    #[derive(Clone, Default)]
+    struct MemoryState(Arc<Mutex<Vec<FileInfo>>>);
+
+    #[async_trait::async_trait]
+    impl FileInfoPollerState for MemoryState {
+        async fn latest_timestamp(
+            &self,
+            _process_name: &str,
+            _file_type: &str,
+        ) -> Result<Option<DateTime<Utc>>> {
+            Ok(self.0.lock().unwrap().iter().map(|f| f.timestamp).max())
+        }
+
+        async fn exists(&self, _process_name: &str, file_info: &FileInfo) -> Result<bool> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|f| f.key == file_info.key))
+        }
+
+        async fn clean(&self, _process_name: &str, _file_type: &str) -> Result {
+            Ok(())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl FileInfoPollerStateRecorder for &MemoryState {
+        async fn record(self, _process_name: &str, file_info: &FileInfo) -> Result {
+            self.0.lock().unwrap().push(file_info.clone());
+            Ok(())
+        }
+    }
+
+    fn entropy_report(timestamp: u64) -> Vec<u8> {
+        EntropyReportV1 {
+            data: vec![1, 2, 3],
+            timestamp,
+            version: 1,
+        }
+        .encode_to_vec()
+    }
+
+    /// Write a gzip file of the given length delimited frames followed by
+    /// the given raw bytes
+    async fn write_frames(dir: &Path, info: &FileInfo, frames: &[&[u8]], trailer: &[u8]) {
+        let mut data = Vec::new();
+        for frame in frames {
+            data.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+            data.extend_from_slice(frame);
+        }
+        data.extend_from_slice(trailer);
+        let file = tokio::fs::File::create(dir.join(&info.key))
+            .await
+            .expect("create file");
+        let mut encoder = async_compression::tokio::write::GzipEncoder::new(file);
+        encoder.write_all(&data).await.expect("write file");
+        encoder.shutdown().await.expect("close file");
+    }
+
+    #[tokio::test]
+    async fn dead_letters_undecodable_records_and_bad_frames() {
+        let src_dir = TempDir::new().expect("Unable to create temp dir");
+        let store_dir = TempDir::new().expect("Unable to create temp dir");
+        let dead_letter_dir = TempDir::new().expect("Unable to create temp dir");
+        let store = FileStore::new_local(store_dir.path().join("bucket"))
+            .await
+            .expect("local file store");
+
+        let first = entropy_report(1_700_000_000);
+        let garbage: &[u8] = &[0xff, 0xff];
+        let second = entropy_report(1_700_000_060);
+        // A frame header announcing more data than the file holds
+        let truncated: &[u8] = &[0, 0, 0, 100, 1, 2, 3];
+        let info = FileInfo::from((FileType::EntropyReport, Utc::now() - Duration::minutes(1)));
+        write_frames(
+            src_dir.path(),
+            &info,
+            &[&first, garbage, &second],
+            truncated,
+        )
+        .await;
+        store
+            .put(&src_dir.path().join(&info.key))
+            .await
+            .expect("put file");
+
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let (dead_letter_client, dead_letter_sink) =
+            dead_letter::file_sink(&FileType::EntropyReport.to_string(), dead_letter_dir.path())
+                .auto_commit(false)
+                .create()
+                .await
+                .expect("dead letter sink");
+        let sink = tokio::spawn(dead_letter_sink.run(shutdown_listener.clone()));
+
+        let state = MemoryState::default();
+        let (mut receiver, server) = file_source::continuous_source::<EntropyReport, _>()
+            .state(state.clone())
+            .store(store)
+            .prefix(FileType::EntropyReport.to_string())
+            .lookback(LookbackBehavior::StartAfter(
+                Utc.timestamp_opt(0, 0).unwrap(),
+            ))
+            .dead_letter(Some(dead_letter_client.clone()))
+            .create()
+            .await
+            .expect("file info poller");
+        let poller = server.start(shutdown_listener).await.expect("start poller");
+
+        let file = receiver.recv().await.expect("parsed file");
+        assert_eq!(info.key, file.file_info.key);
+        let reports: Vec<EntropyReport> = file
+            .into_stream(&state)
+            .await
+            .expect("record file")
+            .collect()
+            .await;
+        assert_eq!(
+            vec![1_700_000_000, 1_700_000_060],
+            reports
+                .iter()
+                .map(|report| report.timestamp.timestamp())
+                .collect::<Vec<_>>()
+        );
+        assert!(state.exists("default", &info).await.unwrap());
+
+        let manifest = dead_letter_client
+            .commit()
+            .await
+            .expect("commit dead letters")
+            .await
+            .expect("commit didn't complete")
+            .expect("commit failed");
+        assert_eq!(1, manifest.len());
+        let dead_letter_info = FileInfo::from_str(&manifest[0]).expect("dead letter file info");
+        assert_eq!(
+            FileType::DeadLetter,
+            FileType::from_str(&dead_letter_info.prefix).expect("dead letter file type")
+        );
+        let dead_letters: Vec<DeadLetter> =
+            file_source::source([dead_letter_dir.path().join(&manifest[0])])
+                .map_ok(|record| DeadLetter::decode(record).expect("dead letter"))
+                .try_collect()
+                .await
+                .expect("read dead letters");
+        let garbage_offset = FRAME_HEADER_LENGTH + first.len() as u64;
+        let truncated_offset = garbage_offset
+            + FRAME_HEADER_LENGTH
+            + garbage.len() as u64
+            + FRAME_HEADER_LENGTH
+            + second.len() as u64;
+        assert_eq!(
+            vec![
+                (info.key.clone(), garbage_offset, garbage.to_vec()),
+                (info.key.clone(), truncated_offset, vec![]),
+            ],
+            dead_letters
+                .into_iter()
+                .map(|dead_letter| (dead_letter.file_key, dead_letter.offset, dead_letter.data))
+                .collect::<Vec<_>>()
+        );
+
+        shutdown_trigger.trigger();
+        poller.await.expect("poller");
+        sink.await.expect("sink task").expect("sink");
+    }
+
+    #[test]
+    fn only_malformed_data_is_dead_lettered() {
+        let io_error = |kind| Error::from(io::Error::new(kind, "error"));
+        assert!(is_malformed(&io_error(io::ErrorKind::InvalidData)));
+        assert!(is_malformed(&io_error(io::ErrorKind::UnexpectedEof)));
+        assert!(!is_malformed(&io_error(io::ErrorKind::Other)));
+        assert!(!is_malformed(&io_error(io::ErrorKind::ConnectionReset)));
+        assert!(!is_malformed(&io_error(io::ErrorKind::TimedOut)));
+        assert!(!is_malformed(&Error::not_found("file")));
+    }
+
+    #[tokio::test]
+    async fn delivers_and_records_files_in_order_with_workers() {
+        let src_dir = TempDir::new().expect("Unable to create temp dir");
+        let store_dir = TempDir::new().expect("Unable to create temp dir");
+        let store = FileStore::new_local(store_dir.path().join("bucket"))
+            .await
+            .expect("local file store");
+
+        // Earlier files are larger so they take longer to decode than the
+        // files listed after them
+        let start = Utc::now() - Duration::hours(1);
+        let mut infos = Vec::new();
+        for i in 0..6 {
+            let info = FileInfo::from((FileType::EntropyReport, start + Duration::minutes(i)));
+            let reports: Vec<Vec<u8>> = (0..(6 - i) * 500)
+                .map(|n| entropy_report(1_700_000_000 + n as u64))
+                .collect();
+            let frames: Vec<&[u8]> = reports.iter().map(Vec::as_slice).collect();
+            write_frames(src_dir.path(), &info, &frames, &[]).await;
+            store
+                .put(&src_dir.path().join(&info.key))
+                .await
+                .expect("put file");
+            infos.push(info);
+        }
+
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let state = MemoryState::default();
+        let (mut receiver, server) = file_source::continuous_source::<EntropyReport, _>()
+            .state(state.clone())
+            .store(store)
+            .prefix(FileType::EntropyReport.to_string())
+            .lookback(LookbackBehavior::StartAfter(
+                Utc.timestamp_opt(0, 0).unwrap(),
+            ))
+            .workers(4)
+            .queue_size(1)
+            .create()
+            .await
+            .expect("file info poller");
+        let poller = server.start(shutdown_listener).await.expect("start poller");
+
+        for (i, info) in infos.iter().enumerate() {
+            let file = receiver.recv().await.expect("parsed file");
+            assert_eq!(info.key, file.file_info.key);
+            let count = file
+                .into_stream(&state)
+                .await
+                .expect("record file")
+                .count()
+                .await;
+            assert_eq!((6 - i) * 500, count);
+        }
+        assert_eq!(
+            infos
+                .iter()
+                .map(|info| info.key.clone())
+                .collect::<Vec<_>>(),
+            state
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|info| info.key.clone())
+                .collect::<Vec<_>>()
+        );
+
+        shutdown_trigger.trigger();
+        poller.await.expect("poller");
+    }
+}
@@ -0,0 +1,253 @@
+//! Integrity sidecars for files written by a [`FileSink`](crate::FileSink).
+//!
+//! Every rolled file `prefix.timestamp.gz` is accompanied by a json sidecar
+//! `prefix.timestamp.gz.integrity.json` describing the file as written. Readers
+//! use it to reject truncated or corrupted files while decoding them. Files
+//! without a sidecar, such as those written before sidecars were introduced,
+//! are read without verification.
+
+use crate::{BytesMutStream, Error, Result};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::{fs, io::AsyncReadExt};
+
+pub const INTEGRITY_SUFFIX: &str = ".integrity.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileIntegrity {
+    /// Number of records in the file
+    pub record_count: u64,
+    /// Size of the compressed file in bytes
+    pub byte_size: u64,
+    /// Hex encoded sha256 of the compressed file
+    pub sha256: String,
+    /// Earliest timestamp of the records in the file, if they have one
+    pub min_timestamp: Option<DateTime<Utc>>,
+    /// Latest timestamp of the records in the file, if they have one
+    pub max_timestamp: Option<DateTime<Utc>>,
+}
+
+impl FileIntegrity {
+    /// The sidecar key for the given data file key
+    pub fn key(data_key: &str) -> String {
+        format!("{data_key}{INTEGRITY_SUFFIX}")
+    }
+
+    /// The sidecar path for the given data file path
+    pub fn path(data_path: &Path) -> PathBuf {
+        let mut path = data_path.as_os_str().to_owned();
+        path.push(INTEGRITY_SUFFIX);
+        PathBuf::from(path)
+    }
+
+    /// Compute the integrity of a completely written data file
+    pub async fn from_file(
+        data_path: &Path,
+        record_count: u64,
+        min_timestamp: Option<DateTime<Utc>>,
+        max_timestamp: Option<DateTime<Utc>>,
+    ) -> Result<Self> {
+        let mut file = fs::File::open(data_path).await?;
+        let mut hasher = Sha256::new();
+        let mut byte_size = 0;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let read = file.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+            byte_size += read as u64;
+        }
+        Ok(Self {
+            record_count,
+            byte_size,
+            sha256: format!("{:x}", hasher.finalize()),
+            min_timestamp,
+            max_timestamp,
+        })
+    }
+
+    /// Write the sidecar for the given data file path
+    pub async fn write(&self, data_path: &Path) -> Result {
+        fs::write(Self::path(data_path), serde_json::to_vec(self)?).await?;
+        Ok(())
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(buf)?)
+    }
+
+    /// Verify the size and checksum of the compressed contents of the file
+    /// with the given key
+    pub fn verify(&self, key: &str, data: &[u8]) -> Result {
+        self.check(
+            key,
+            data.len() as u64,
+            format!("{:x}", Sha256::digest(data)),
+        )
+    }
+
+    /// Wrap the compressed byte stream of the file with the given key,
+    /// hashing it as it is read. The stream yields an [`Error::Integrity`],
+    /// wrapped in an io error, in place of its end if the size or checksum
+    /// of the file does not match
+    pub fn verify_bytes<S, E>(
+        &self,
+        key: String,
+        bytes: S,
+    ) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static
+    where
+        S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+        E: Into<io::Error>,
+    {
+        let expected = self.clone();
+        let bytes = bytes.map_err(Into::into).boxed();
+        stream::unfold(Some((bytes, Sha256::new(), 0u64)), move |state| {
+            let expected = expected.clone();
+            let key = key.clone();
+            async move {
+                let (mut bytes, mut hasher, mut byte_size) = state?;
+                match bytes.next().await {
+                    Some(Ok(chunk)) => {
+                        hasher.update(&chunk);
+                        byte_size += chunk.len() as u64;
+                        Some((Ok(chunk), Some((bytes, hasher, byte_size))))
+                    }
+                    Some(Err(err)) => Some((Err(err), None)),
+                    None => expected
+                        .check(&key, byte_size, format!("{:x}", hasher.finalize()))
+                        .err()
+                        .map(|err| (Err(io::Error::new(io::ErrorKind::InvalidData, err)), None)),
+                }
+            }
+        })
+    }
+
+    fn check(&self, key: &str, byte_size: u64, sha256: String) -> Result {
+        if byte_size != self.byte_size {
+            return Err(Error::integrity(format!(
+                "{key} has {byte_size} bytes, expected {}",
+                self.byte_size
+            )));
+        }
+        if sha256 != self.sha256 {
+            return Err(Error::integrity(format!(
+                "{key} has sha256 {sha256}, expected {}",
+                self.sha256
+            )));
+        }
+        Ok(())
+    }
+
+    /// Wrap a decoded record stream of the file with the given key, yielding
+    /// an integrity error at the end of the stream if the number of records
+    /// does not match the expected record count
+    pub fn verify_count(&self, key: String, records: BytesMutStream) -> BytesMutStream {
+        let expected = self.record_count;
+        let count = Arc::new(AtomicU64::new(0));
+        let counter = count.clone();
+        records
+            .inspect(move |record| {
+                if record.is_ok() {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+            .chain(
+                stream::once(async move {
+                    let count = count.load(Ordering::Relaxed);
+                    (count != expected).then(|| {
+                        Err(Error::integrity(format!(
+                            "{key} has {count} records, expected {expected}"
+                        )))
+                    })
+                })
+                .filter_map(future::ready),
+            )
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use futures::TryStreamExt;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn verifies_size_checksum_and_count() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let data_path = tmp_dir.path().join("entropy_report.1.gz");
+        fs::write(&data_path, b"some data").await.expect("write");
+
+        let integrity = FileIntegrity::from_file(&data_path, 2, None, None)
+            .await
+            .expect("integrity");
+        integrity.write(&data_path).await.expect("write sidecar");
+        let sidecar = fs::read(tmp_dir.path().join("entropy_report.1.gz.integrity.json"))
+            .await
+            .expect("read sidecar");
+        assert_eq!(integrity, FileIntegrity::decode(&sidecar).expect("decode"));
+
+        assert_eq!(9, integrity.byte_size);
+        assert!(integrity.verify("key", b"some data").is_ok());
+        assert!(matches!(
+            integrity.verify("key", b"some dat"),
+            Err(Error::Integrity(_))
+        ));
+        assert!(matches!(
+            integrity.verify("key", b"same data"),
+            Err(Error::Integrity(_))
+        ));
+
+        let chunks = |data: &'static [&'static [u8]]| {
+            stream::iter(
+                data.iter()
+                    .map(|chunk| Ok::<_, io::Error>(Bytes::from(*chunk))),
+            )
+        };
+        let verified: Vec<Bytes> = integrity
+            .verify_bytes("key".to_string(), chunks(&[b"some ", b"data"]))
+            .try_collect()
+            .await
+            .expect("verified bytes");
+        assert_eq!(vec![Bytes::from("some "), Bytes::from("data")], verified);
+        let err = integrity
+            .verify_bytes("key".to_string(), chunks(&[b"some ", b"dat"]))
+            .try_collect::<Vec<_>>()
+            .await
+            .expect_err("truncated bytes");
+        assert!(matches!(
+            err.into_inner().map(|inner| inner.downcast::<Error>()),
+            Some(Ok(err)) if matches!(*err, Error::Integrity(_))
+        ));
+
+        let records = |n: usize| -> BytesMutStream {
+            stream::iter((0..n).map(|_| Ok(BytesMut::from("record")))).boxed()
+        };
+        assert!(integrity
+            .verify_count("key".to_string(), records(2))
+            .try_collect::<Vec<_>>()
+            .await
+            .is_ok());
+        assert!(matches!(
+            integrity
+                .verify_count("key".to_string(), records(1))
+                .try_collect::<Vec<_>>()
+                .await,
+            Err(Error::Integrity(_))
+        ));
+    }
+}
@@ -1,15 +1,20 @@
 use crate::{
+    compression::{Compression, Encoder},
+    file_integrity::{FileIntegrity, INTEGRITY_SUFFIX},
+    file_source,
     file_upload::{self, FileUpload},
-    Error, Result,
+    key_index::{KeyIndex, INDEX_SUFFIX},
+    registry::{self, RecordKeys, RecordTimestamp},
+    Error, FileType, Result,
 };
-use async_compression::tokio::write::GzipEncoder;
 use bytes::Bytes;
 use chrono::{DateTime, Duration, Utc};
-use futures::{future::LocalBoxFuture, SinkExt, TryFutureExt};
+use futures::{future::LocalBoxFuture, SinkExt, StreamExt, TryFutureExt};
 use metrics::Label;
 use std::{
     io, mem,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 use task_manager::ManagedTask;
 use tokio::{
@@ -31,8 +36,11 @@ pub const SINK_CHECK_MILLIS: i64 = 60_000;
 pub const SINK_CHECK_MILLIS: i64 = 50;
 
 pub const MAX_FRAME_LENGTH: usize = 15_000_000;
+/// Suffix of files left in the tmp dir by an earlier run that could not be
+/// read back completely. They are kept for inspection but never deposited
+pub const PARTIAL_SUFFIX: &str = ".partial";
 
-type Sink = GzipEncoder<BufWriter<File>>;
+type Sink = Encoder<BufWriter<File>>;
 type Transport = FramedWrite<Sink, LengthDelimitedCodec>;
 pub type FileManifest = Vec<String>;
 
@@ -49,6 +57,8 @@ fn transport_sink(transport: &mut Transport) -> &mut Sink {
 #[derive(Debug)]
 pub enum Message {
     Data(oneshot::Sender<Result>, Vec<u8>),
+    /// Data that is acknowledged once the file it is written to is committed
+    CommittedData(oneshot::Sender<Result>, Vec<u8>),
     Commit(oneshot::Sender<Result<FileManifest>>),
     Rollback(oneshot::Sender<Result<FileManifest>>),
 }
@@ -69,6 +79,9 @@ pub struct FileSinkBuilder {
     deposits: Option<file_upload::MessageSender>,
     file_upload: Option<FileUpload>,
     auto_commit: bool,
+    compression: Compression,
+    index: bool,
+    queue_size: usize,
     metric: &'static str,
 }
 
@@ -83,6 +96,9 @@ impl FileSinkBuilder {
             deposits: None,
             file_upload: None,
             auto_commit: true,
+            compression: Compression::default(),
+            index: false,
+            queue_size: 50,
             metric,
         }
     }
@@ -135,8 +151,38 @@ impl FileSinkBuilder {
         }
     }
 
+    /// Compression of the written files, gzip by default
+    pub fn compression(self, compression: Compression) -> Self {
+        Self {
+            compression,
+            ..self
+        }
+    }
+
+    /// Write a [`KeyIndex`] sidecar for every file. Requires the prefix to
+    /// be a [`FileType`] with [`registry::record_keys`]
+    pub fn index(self, index: bool) -> Self {
+        Self { index, ..self }
+    }
+
+    /// Number of messages that can be queued for the sink before writers
+    /// have to wait for room
+    pub fn queue_size(self, queue_size: usize) -> Self {
+        Self { queue_size, ..self }
+    }
+
     pub async fn create(self) -> Result<(FileSinkClient, FileSink)> {
-        let (tx, rx) = message_channel(50);
+        let index = if self.index {
+            let keys = registry::record_keys(FileType::from_str(&self.prefix)?);
+            Some(keys.ok_or_else(|| Error::not_found(format!("record keys of {}", self.prefix)))?)
+        } else {
+            None
+        };
+        // Sinks with a prefix that is not a file type record no timestamps
+        let record_timestamp = FileType::from_str(&self.prefix)
+            .ok()
+            .and_then(registry::record_timestamp);
+        let (tx, rx) = message_channel(self.queue_size);
 
         let client = FileSinkClient {
             sender: tx,
@@ -156,7 +202,11 @@ impl FileSinkBuilder {
             messages: rx,
             staged_files: Vec::new(),
             auto_commit: self.auto_commit,
+            compression: self.compression,
+            index,
+            record_timestamp,
             active_sink: None,
+            committed_acks: Vec::new(),
         };
         sink.init().await?;
         Ok((client, sink))
@@ -171,7 +221,7 @@ pub struct FileSinkClient {
 
 const OK_LABEL: Label = Label::from_static_parts("status", "ok");
 const ERROR_LABEL: Label = Label::from_static_parts("status", "error");
-const SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+pub const SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 impl FileSinkClient {
     pub fn new(sender: MessageSender, metric: &'static str) -> Self {
@@ -182,12 +232,56 @@ impl FileSinkClient {
         &self,
         item: T,
         labels: impl IntoIterator<Item = &(&'static str, &'static str)>,
+    ) -> Result<oneshot::Receiver<Result>> {
+        self.write_with_timeout(item, labels, SEND_TIMEOUT).await
+    }
+
+    /// Write an item, waiting at most `timeout` for room in the sink queue.
+    /// The returned receiver resolves once the item is written to the active
+    /// file
+    pub async fn write_with_timeout<T: prost::Message>(
+        &self,
+        item: T,
+        labels: impl IntoIterator<Item = &(&'static str, &'static str)>,
+        timeout: std::time::Duration,
     ) -> Result<oneshot::Receiver<Result>> {
         let (on_write_tx, on_write_rx) = oneshot::channel();
-        let bytes = item.encode_to_vec();
+        self.send_data(
+            Message::Data(on_write_tx, item.encode_to_vec()),
+            labels,
+            timeout,
+        )
+        .await
+        .map(|_| on_write_rx)
+    }
+
+    /// Like [`Self::write_with_timeout`] but the returned receiver resolves
+    /// once the file the item is written to is committed
+    pub async fn write_committed<T: prost::Message>(
+        &self,
+        item: T,
+        labels: impl IntoIterator<Item = &(&'static str, &'static str)>,
+        timeout: std::time::Duration,
+    ) -> Result<oneshot::Receiver<Result>> {
+        let (on_commit_tx, on_commit_rx) = oneshot::channel();
+        self.send_data(
+            Message::CommittedData(on_commit_tx, item.encode_to_vec()),
+            labels,
+            timeout,
+        )
+        .await
+        .map(|_| on_commit_rx)
+    }
+
+    async fn send_data(
+        &self,
+        message: Message,
+        labels: impl IntoIterator<Item = &(&'static str, &'static str)>,
+        timeout: std::time::Duration,
+    ) -> Result {
         let labels = labels.into_iter().map(Label::from);
         tokio::select! {
-            result = self.sender.send_timeout(Message::Data(on_write_tx, bytes), SEND_TIMEOUT) => match result {
+            result = self.sender.send_timeout(message, timeout) => match result {
                 Ok(_) => {
                     metrics::increment_counter!(
                         self.metric,
@@ -196,7 +290,7 @@ impl FileSinkClient {
                             .collect::<Vec<Label>>()
                     );
                     tracing::debug!("file_sink write succeeded for {:?}", self.metric);
-                    Ok(on_write_rx)
+                    Ok(())
                 }
                 Err(SendTimeoutError::Closed(_)) => {
                     metrics::increment_counter!(
@@ -260,20 +354,44 @@ pub struct FileSink {
     file_upload: Option<FileUpload>,
     staged_files: Vec<PathBuf>,
     auto_commit: bool,
+    compression: Compression,
+    index: Option<RecordKeys>,
+    record_timestamp: Option<RecordTimestamp>,
 
     active_sink: Option<ActiveSink>,
+    /// Acknowledgements of written data waiting for their file to be
+    /// committed
+    committed_acks: Vec<(PathBuf, oneshot::Sender<Result>)>,
 }
 
 #[derive(Debug)]
 struct ActiveSink {
+    path: PathBuf,
     size: usize,
     time: DateTime<Utc>,
     transport: Transport,
+    record_count: u64,
+    min_timestamp: Option<DateTime<Utc>>,
+    max_timestamp: Option<DateTime<Utc>>,
+    index: Option<KeyIndex>,
 }
 
 impl ActiveSink {
-    async fn shutdown(&mut self) -> Result {
+    /// Finish writing the sink and write its sidecars next to it
+    async fn shutdown(mut self) -> Result {
         transport_sink(&mut self.transport).shutdown().await?;
+        FileIntegrity::from_file(
+            &self.path,
+            self.record_count,
+            self.min_timestamp,
+            self.max_timestamp,
+        )
+        .await?
+        .write(&self.path)
+        .await?;
+        if let Some(index) = self.index {
+            index.write(&self.path).await?;
+        }
         Ok(())
     }
 }
@@ -336,20 +454,18 @@ impl FileSink {
             }
         }
 
-        // Move any partial previous sink files to the target
+        // Move any partial previous sink files to the target. Sidecars in tmp
+        // are rebuilt with the file they describe
         let mut dir = fs::read_dir(&self.tmp_path).await?;
         loop {
             match dir.next_entry().await {
-                Ok(Some(entry))
-                    if entry
-                        .file_name()
-                        .to_string_lossy()
-                        .starts_with(&self.prefix) =>
-                {
-                    if self.auto_commit {
-                        let _ = self.deposit_sink(&entry.path()).await;
-                    } else {
-                        let _ = fs::remove_file(&entry.path()).await;
+                Ok(Some(entry)) if self.is_sink_file(&entry.file_name().to_string_lossy()) => {
+                    let path = entry.path();
+                    if let Err(err) = self.recover_sink(&path).await {
+                        tracing::warn!(
+                            "file_sink failed to recover {} with {err:?}",
+                            path.display()
+                        );
                     }
                 }
                 Ok(None) => break,
@@ -360,6 +476,69 @@ impl FileSink {
         Ok(())
     }
 
+    fn is_sink_file(&self, file_name: &str) -> bool {
+        file_name.starts_with(&self.prefix)
+            && !file_name.ends_with(INTEGRITY_SUFFIX)
+            && !file_name.ends_with(INDEX_SUFFIX)
+            && !file_name.ends_with(PARTIAL_SUFFIX)
+    }
+
+    /// Deposit a sink file left in the tmp dir by an earlier run, with its
+    /// sidecars rebuilt from its records. A file that does not read back
+    /// completely is renamed with the [`PARTIAL_SUFFIX`] instead. Without
+    /// auto commit the file was never committed and is removed
+    async fn recover_sink(&mut self, sink_path: &Path) -> Result {
+        remove_sidecars(sink_path).await?;
+        if !self.auto_commit {
+            fs::remove_file(sink_path).await?;
+            return Ok(());
+        }
+        if let Err(err) = self.write_sidecars(sink_path).await {
+            tracing::warn!(
+                "file_sink quarantining partial file {} with {err:?}",
+                sink_path.display()
+            );
+            remove_sidecars(sink_path).await?;
+            let mut partial_path = sink_path.as_os_str().to_owned();
+            partial_path.push(PARTIAL_SUFFIX);
+            fs::rename(sink_path, PathBuf::from(partial_path)).await?;
+            return Ok(());
+        }
+        self.deposit_sink(sink_path).await
+    }
+
+    /// Write the sidecars of a completely written sink file by reading its
+    /// records back
+    async fn write_sidecars(&self, sink_path: &Path) -> Result {
+        let mut records = file_source::source([sink_path]);
+        let mut record_count = 0;
+        let mut min_timestamp: Option<DateTime<Utc>> = None;
+        let mut max_timestamp: Option<DateTime<Utc>> = None;
+        let mut index = self.index.map(|_| KeyIndex::default());
+        while let Some(record) = records.next().await {
+            let record = record?;
+            record_count += 1;
+            if let Some(timestamp) = self
+                .record_timestamp
+                .and_then(|timestamp| timestamp(&record))
+            {
+                min_timestamp = Some(min_timestamp.map_or(timestamp, |min| min.min(timestamp)));
+                max_timestamp = Some(max_timestamp.map_or(timestamp, |max| max.max(timestamp)));
+            }
+            if let (Some(record_keys), Some(index)) = (self.index, index.as_mut()) {
+                index.push(record_keys, &record);
+            }
+        }
+        FileIntegrity::from_file(sink_path, record_count, min_timestamp, max_timestamp)
+            .await?
+            .write(sink_path)
+            .await?;
+        if let Some(index) = index {
+            index.write(sink_path).await?;
+        }
+        Ok(())
+    }
+
     pub async fn run(mut self, shutdown: triggered::Listener) -> Result {
         tracing::info!(
             "starting file sink {} in {}",
@@ -381,15 +560,19 @@ impl FileSink {
                 _ = rollover_timer.tick() => self.maybe_roll().await?,
                 msg = self.messages.recv() => match msg {
                     Some(Message::Data(on_write_tx, bytes)) => {
-                        let res = match self.write(Bytes::from(bytes)).await {
-                            Ok(_) => Ok(()),
-                            Err(err) => {
-                                tracing::error!("failed to store {}: {err:?}", &self.prefix);
-                                Err(err)
-                            }
-                        };
+                        let res = self.write_data(bytes).await;
                         let _ = on_write_tx.send(res);
                     }
+                    Some(Message::CommittedData(on_commit_tx, bytes)) => {
+                        match (self.write_data(bytes).await, self.active_sink.as_ref()) {
+                            (Ok(()), Some(active_sink)) => {
+                                self.committed_acks.push((active_sink.path.clone(), on_commit_tx));
+                            }
+                            (res, _) => {
+                                let _ = on_commit_tx.send(res);
+                            }
+                        }
+                    }
                     Some(Message::Commit(on_commit_tx)) => {
                         let res = self.commit().await;
                         let _ = on_commit_tx.send(res);
@@ -405,18 +588,43 @@ impl FileSink {
             }
         }
         tracing::info!("stopping file sink {}", &self.prefix);
-        if let Some(active_sink) = self.active_sink.as_mut() {
-            let _ = active_sink.shutdown().await;
-            self.active_sink = None;
-        }
+        let _ = self.maybe_close_active_sink().await;
         Ok(())
     }
 
+    async fn write_data(&mut self, bytes: Vec<u8>) -> Result {
+        self.write(Bytes::from(bytes)).await.map_err(|err| {
+            tracing::error!("failed to store {}: {err:?}", &self.prefix);
+            err
+        })
+    }
+
+    /// Resolve the acknowledgements waiting for the given staged file
+    fn ack_committed(&mut self, staged_file: &Path, committed: bool) {
+        let (acks, pending) = mem::take(&mut self.committed_acks)
+            .into_iter()
+            .partition(|(path, _)| path == staged_file);
+        self.committed_acks = pending;
+        for (_, on_commit_tx) in acks {
+            let res = if committed {
+                Ok(())
+            } else {
+                Err(Error::NotCommitted)
+            };
+            let _ = on_commit_tx.send(res);
+        }
+    }
+
     async fn new_sink(&mut self) -> Result {
         let sink_time = Utc::now();
-        let filename = format!("{}.{}.gz", self.prefix, sink_time.timestamp_millis());
+        let filename = format!(
+            "{}.{}.{}",
+            self.prefix,
+            sink_time.timestamp_millis(),
+            self.compression.extension()
+        );
         let new_path = self.tmp_path.join(filename);
-        let writer = GzipEncoder::new(BufWriter::new(
+        let writer = self.compression.encoder(BufWriter::new(
             OpenOptions::new()
                 .write(true)
                 .create(true)
@@ -424,12 +632,17 @@ impl FileSink {
                 .await?,
         ));
 
-        self.staged_files.push(new_path);
+        self.staged_files.push(new_path.clone());
 
         self.active_sink = Some(ActiveSink {
+            path: new_path,
             size: 0,
             time: sink_time,
             transport: new_transport(writer),
+            record_count: 0,
+            min_timestamp: None,
+            max_timestamp: None,
+            index: self.index.as_ref().map(|_| KeyIndex::default()),
         });
 
         Ok(())
@@ -442,7 +655,9 @@ impl FileSink {
         let staged_files = mem::take(&mut self.staged_files);
 
         for staged_file in staged_files.into_iter() {
-            self.deposit_sink(staged_file.as_path()).await?;
+            let res = self.deposit_sink(staged_file.as_path()).await;
+            self.ack_committed(&staged_file, res.is_ok());
+            res?;
             manifest.push(file_name(&staged_file)?);
         }
 
@@ -456,7 +671,9 @@ impl FileSink {
         let staged_files = mem::take(&mut self.staged_files);
 
         for staged_file in staged_files.into_iter() {
+            self.ack_committed(&staged_file, false);
             fs::remove_file(&staged_file).await?;
+            remove_sidecars(&staged_file).await?;
             manifest.push(file_name(&staged_file)?);
         }
 
@@ -477,9 +694,8 @@ impl FileSink {
     }
 
     async fn maybe_close_active_sink(&mut self) -> Result {
-        if let Some(active_sink) = self.active_sink.as_mut() {
+        if let Some(active_sink) = self.active_sink.take() {
             active_sink.shutdown().await?;
-            self.active_sink = None;
         }
 
         Ok(())
@@ -497,13 +713,29 @@ impl FileSink {
         })?;
         let target_path = self.target_path.join(target_filename);
 
+        // Deposit the sidecars, if any, ahead of the file they describe so
+        // readers find them once the file is visible
+        let mut deposit_paths = Vec::with_capacity(3);
+        for (sidecar, target_sidecar) in sidecar_paths(sink_path)
+            .into_iter()
+            .zip(sidecar_paths(&target_path))
+        {
+            if sidecar.exists() {
+                fs::rename(&sidecar, &target_sidecar).await?;
+                deposit_paths.push(target_sidecar);
+            }
+        }
         fs::rename(&sink_path, &target_path).await?;
-        if let Some(deposits) = &self.deposits {
-            file_upload::upload_file(deposits, &target_path).await?;
+        deposit_paths.push(target_path);
+
+        for path in deposit_paths {
+            if let Some(deposits) = &self.deposits {
+                file_upload::upload_file(deposits, &path).await?;
+            }
+            if let Some(file_upload) = &self.file_upload {
+                file_upload.upload_file(&path).await?;
+            };
         }
-        if let Some(file_upload) = &self.file_upload {
-            file_upload.upload_file(&target_path).await?;
-        };
 
         Ok(())
     }
@@ -511,13 +743,13 @@ impl FileSink {
     pub async fn write(&mut self, buf: Bytes) -> Result {
         let buf_len = buf.len();
 
-        match self.active_sink.as_mut() {
+        match self.active_sink.as_ref() {
             // If there is an active sink check if the write would make it too
             // large. if so deposit and make a new sink. Otherwise the current
             // active sink is usable.
             Some(active_sink) => {
                 if active_sink.size + buf_len >= self.max_size {
-                    active_sink.shutdown().await?;
+                    self.maybe_close_active_sink().await?;
                     if self.auto_commit {
                         self.commit().await?;
                     }
@@ -531,8 +763,24 @@ impl FileSink {
         }
 
         if let Some(active_sink) = self.active_sink.as_mut() {
-            active_sink.transport.send(buf).await?;
+            active_sink.transport.send(buf.clone()).await?;
             active_sink.size += buf_len;
+            active_sink.record_count += 1;
+            if let Some(timestamp) = self.record_timestamp.and_then(|timestamp| timestamp(&buf)) {
+                active_sink.min_timestamp = Some(
+                    active_sink
+                        .min_timestamp
+                        .map_or(timestamp, |min| min.min(timestamp)),
+                );
+                active_sink.max_timestamp = Some(
+                    active_sink
+                        .max_timestamp
+                        .map_or(timestamp, |max| max.max(timestamp)),
+                );
+            }
+            if let (Some(record_keys), Some(index)) = (self.index, active_sink.index.as_mut()) {
+                index.push(record_keys, &buf);
+            }
             Ok(())
         } else {
             Err(Error::from(io::Error::new(
@@ -543,6 +791,19 @@ impl FileSink {
     }
 }
 
+fn sidecar_paths(path: &Path) -> [PathBuf; 2] {
+    [FileIntegrity::path(path), KeyIndex::path(path)]
+}
+
+async fn remove_sidecars(path: &Path) -> Result {
+    for sidecar in sidecar_paths(path) {
+        if sidecar.exists() {
+            fs::remove_file(&sidecar).await?;
+        }
+    }
+    Ok(())
+}
+
 pub fn file_name(path_buf: &Path) -> Result<String> {
     path_buf
         .file_name()
@@ -559,6 +820,7 @@ pub fn file_name(path_buf: &Path) -> Result<String> {
 mod tests {
     use super::*;
     use crate::{file_source, FileInfo, FileType};
+    use chrono::TimeZone;
     use futures::stream::StreamExt;
     use std::str::FromStr;
     use tempfile::TempDir;
@@ -604,6 +866,110 @@ mod tests {
         assert_eq!("hello", read_file(&entropy_file).await);
     }
 
+    #[tokio::test]
+    async fn writes_a_framed_zstd_encoded_file() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+
+        let (file_sink_client, file_sink_server) =
+            FileSinkBuilder::new(FileType::EntropyReport, tmp_dir.path(), "fake_metric")
+                .auto_commit(false)
+                .compression(Compression::Zstd)
+                .create()
+                .await
+                .expect("failed to create file sink");
+
+        let sink_thread = tokio::spawn(async move {
+            file_sink_server
+                .run(shutdown_listener.clone())
+                .await
+                .expect("failed to complete file sink");
+        });
+
+        let (on_write_tx, on_write_rx) = oneshot::channel();
+        file_sink_client
+            .sender
+            .send(Message::Data(
+                on_write_tx,
+                String::into_bytes("hello".to_string()),
+            ))
+            .await
+            .expect("failed to send bytes to file sink");
+        on_write_rx
+            .await
+            .expect("write didn't complete")
+            .expect("write failed");
+
+        let receiver = file_sink_client.commit().await.expect("commit failed");
+        let manifest = receiver
+            .await
+            .expect("commit didn't complete completed")
+            .expect("commit failed");
+        assert!(manifest[0].ends_with(".zst"));
+
+        let entropy_file = get_entropy_file(&tmp_dir)
+            .await
+            .expect("no entropy available");
+        assert_eq!("hello", read_file(&entropy_file).await);
+
+        shutdown_trigger.trigger();
+        sink_thread.await.expect("file sink did not complete");
+    }
+
+    #[tokio::test]
+    async fn acks_committed_data_once_committed() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+
+        let (file_sink_client, file_sink_server) =
+            FileSinkBuilder::new(FileType::EntropyReport, tmp_dir.path(), "fake_metric")
+                .auto_commit(false)
+                .create()
+                .await
+                .expect("failed to create file sink");
+
+        let sink_thread = tokio::spawn(async move {
+            file_sink_server
+                .run(shutdown_listener.clone())
+                .await
+                .expect("failed to complete file sink");
+        });
+
+        let (on_commit_tx, mut on_commit_rx) = oneshot::channel();
+        file_sink_client
+            .sender
+            .send(Message::CommittedData(
+                on_commit_tx,
+                String::into_bytes("hello".to_string()),
+            ))
+            .await
+            .expect("failed to send bytes to file sink");
+
+        tokio::time::sleep(time::Duration::from_millis(200)).await;
+        assert!(matches!(
+            on_commit_rx.try_recv(),
+            Err(oneshot::error::TryRecvError::Empty)
+        ));
+
+        let receiver = file_sink_client.commit().await.expect("commit failed");
+        receiver
+            .await
+            .expect("commit didn't complete completed")
+            .expect("commit failed");
+        on_commit_rx
+            .await
+            .expect("ack didn't complete")
+            .expect("data not committed");
+
+        let entropy_file = get_entropy_file(&tmp_dir)
+            .await
+            .expect("no entropy available");
+        assert_eq!("hello", read_file(&entropy_file).await);
+
+        shutdown_trigger.trigger();
+        sink_thread.await.expect("file sink did not complete");
+    }
+
     #[tokio::test]
     async fn only_uploads_after_commit_when_auto_commit_is_false() {
         let tmp_dir = TempDir::new().expect("Unable to create temp dir");
@@ -657,6 +1023,226 @@ mod tests {
         sink_thread.await.expect("file sink did not complete");
     }
 
+    #[tokio::test]
+    async fn writes_sidecars_on_commit() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+
+        let (file_sink_client, file_sink_server) =
+            FileSinkBuilder::new(FileType::EntropyReport, tmp_dir.path(), "fake_metric")
+                .auto_commit(false)
+                .create()
+                .await
+                .expect("failed to create file sink");
+
+        let sink_thread = tokio::spawn(async move {
+            file_sink_server
+                .run(shutdown_listener.clone())
+                .await
+                .expect("failed to complete file sink");
+        });
+
+        // Records are not necessarily written in the order of their timestamps
+        for timestamp in [1_700_000_060, 1_700_000_000, 1_700_000_030] {
+            let report = helium_proto::EntropyReportV1 {
+                data: vec![1, 2, 3],
+                timestamp,
+                version: 1,
+            };
+            file_sink_client
+                .write(report, [])
+                .await
+                .expect("failed to send report to file sink")
+                .await
+                .expect("write didn't complete")
+                .expect("write failed");
+        }
+
+        let receiver = file_sink_client.commit().await.expect("commit failed");
+        let _ = receiver.await.expect("commit didn't complete completed");
+
+        let entropy_file = get_entropy_file(&tmp_dir)
+            .await
+            .expect("no entropy available");
+        let integrity = FileIntegrity::decode(
+            &fs::read(FileIntegrity::path(&entropy_file.path()))
+                .await
+                .expect("no integrity sidecar"),
+        )
+        .expect("invalid integrity sidecar");
+        assert_eq!(3, integrity.record_count);
+        assert_eq!(
+            Some(Utc.timestamp_opt(1_700_000_000, 0).unwrap()),
+            integrity.min_timestamp
+        );
+        assert_eq!(
+            Some(Utc.timestamp_opt(1_700_000_060, 0).unwrap()),
+            integrity.max_timestamp
+        );
+        let data = fs::read(entropy_file.path()).await.expect("read file");
+        integrity
+            .verify(&file_name(&entropy_file.path()).unwrap(), &data)
+            .expect("integrity mismatch");
+
+        shutdown_trigger.trigger();
+        sink_thread.await.expect("file sink did not complete");
+    }
+
+    #[tokio::test]
+    async fn writes_key_index_on_commit() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+
+        assert!(
+            FileSinkBuilder::new(FileType::EntropyReport, tmp_dir.path(), "fake_metric")
+                .index(true)
+                .create()
+                .await
+                .is_err()
+        );
+
+        let (file_sink_client, file_sink_server) =
+            FileSinkBuilder::new(FileType::CbrsHeartbeat, tmp_dir.path(), "fake_metric")
+                .auto_commit(false)
+                .index(true)
+                .create()
+                .await
+                .expect("failed to create file sink");
+
+        let sink_thread = tokio::spawn(async move {
+            file_sink_server
+                .run(shutdown_listener.clone())
+                .await
+                .expect("failed to complete file sink");
+        });
+
+        for cbsd_id in ["cbsd1", "cbsd2", "cbsd1"] {
+            let heartbeat = helium_proto::services::poc_mobile::CellHeartbeatReqV1 {
+                pub_key: vec![1; 33],
+                cbsd_id: cbsd_id.to_string(),
+                timestamp: 1_700_000_000,
+                ..Default::default()
+            };
+            file_sink_client
+                .write(heartbeat, [])
+                .await
+                .expect("failed to send report to file sink")
+                .await
+                .expect("write didn't complete")
+                .expect("write failed");
+        }
+
+        let receiver = file_sink_client.commit().await.expect("commit failed");
+        let _ = receiver.await.expect("commit didn't complete completed");
+
+        let mut entries = fs::read_dir(tmp_dir.path())
+            .await
+            .expect("failed to read tmp dir");
+        let mut data_path = None;
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            if entry.path().extension().map_or(false, |ext| ext == "gz") {
+                data_path = Some(entry.path());
+            }
+        }
+        let data_path = data_path.expect("no heartbeat file");
+        let index = KeyIndex::decode(
+            &fs::read(KeyIndex::path(&data_path))
+                .await
+                .expect("no key index sidecar"),
+        )
+        .expect("invalid key index sidecar");
+        assert_eq!(3, index.record_count);
+        assert_eq!(&[0, 2], index.positions("cbsd1"));
+        assert_eq!(&[1], index.positions("cbsd2"));
+
+        shutdown_trigger.trigger();
+        sink_thread.await.expect("file sink did not complete");
+    }
+
+    #[tokio::test]
+    async fn recovers_files_left_in_tmp() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let target_dir = TempDir::new().expect("Unable to create temp dir");
+
+        // a file as written by a previous run that did not deposit it
+        let (file_sink_client, file_sink_server) =
+            FileSinkBuilder::new(FileType::EntropyReport, tmp_dir.path(), "fake_metric")
+                .auto_commit(false)
+                .create()
+                .await
+                .expect("failed to create file sink");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let sink_thread = tokio::spawn(file_sink_server.run(shutdown_listener));
+        for timestamp in [1_700_000_060, 1_700_000_000] {
+            let report = helium_proto::EntropyReportV1 {
+                data: vec![1, 2, 3],
+                timestamp,
+                version: 1,
+            };
+            file_sink_client
+                .write(report, [])
+                .await
+                .expect("failed to send report to file sink")
+                .await
+                .expect("write didn't complete")
+                .expect("write failed");
+        }
+        let receiver = file_sink_client.commit().await.expect("commit failed");
+        let _ = receiver.await.expect("commit didn't complete completed");
+        shutdown_trigger.trigger();
+        sink_thread
+            .await
+            .expect("file sink did not complete")
+            .expect("file sink failed");
+
+        let written = get_entropy_file(&tmp_dir).await.expect("no entropy file");
+        let data = fs::read(written.path()).await.expect("read file");
+        let target_tmp = target_dir.path().join("tmp");
+        fs::create_dir_all(&target_tmp).await.expect("tmp dir");
+        let complete = target_tmp.join("entropy_report.1700000000000.gz");
+        let partial = target_tmp.join("entropy_report.1700000001000.gz");
+        fs::write(&complete, &data).await.expect("write complete");
+        fs::write(&partial, &data[..data.len() / 2])
+            .await
+            .expect("write partial");
+        // a stale sidecar of the complete file
+        fs::write(FileIntegrity::path(&complete), b"{}")
+            .await
+            .expect("write sidecar");
+
+        let _ = FileSinkBuilder::new(FileType::EntropyReport, target_dir.path(), "fake_metric")
+            .create()
+            .await
+            .expect("failed to create file sink");
+
+        let deposited = target_dir.path().join("entropy_report.1700000000000.gz");
+        assert_eq!(data, fs::read(&deposited).await.expect("deposited file"));
+        let integrity = FileIntegrity::decode(
+            &fs::read(FileIntegrity::path(&deposited))
+                .await
+                .expect("no integrity sidecar"),
+        )
+        .expect("invalid integrity sidecar");
+        assert_eq!(2, integrity.record_count);
+        assert_eq!(
+            Some(Utc.timestamp_opt(1_700_000_000, 0).unwrap()),
+            integrity.min_timestamp
+        );
+        integrity
+            .verify(&file_name(&deposited).unwrap(), &data)
+            .expect("integrity mismatch");
+
+        assert!(!partial.exists());
+        assert!(target_tmp
+            .join("entropy_report.1700000001000.gz.partial")
+            .exists());
+        assert!(!target_dir
+            .path()
+            .join("entropy_report.1700000001000.gz")
+            .exists());
+        assert!(!FileIntegrity::path(&FileIntegrity::path(&deposited)).exists());
+    }
+
     async fn read_file(entry: &DirEntry) -> bytes::BytesMut {
         file_source::source([entry.path()])
             .next()
@@ -1,5 +1,7 @@
-use crate::{file_info_poller::FileInfoPollerConfigBuilder, file_sink, BytesMutStream, Error};
-use async_compression::tokio::bufread::GzipDecoder;
+use crate::{
+    compression::Compression, file_info_poller::FileInfoPollerConfigBuilder, file_sink,
+    BytesMutStream, Error,
+};
 use futures::{
     stream::{self},
     StreamExt, TryFutureExt, TryStreamExt,
@@ -25,16 +27,21 @@ where
         .map(|path| path.as_ref().to_path_buf())
         .collect();
     stream::iter(paths)
-        .map(|path| File::open(path).map_err(Error::from))
+        .map(|path| {
+            let compression = Compression::from_key(&path.to_string_lossy());
+            File::open(path)
+                .map_ok(move |file| (file, compression))
+                .map_err(Error::from)
+        })
         .buffered(2)
         .flat_map(|file| match file {
-            Ok(file) => {
+            Ok((file, compression)) => {
                 let buf_reader = BufReader::new(file);
                 let codec = LengthDelimitedCodec::builder()
                     .max_frame_length(file_sink::MAX_FRAME_LENGTH)
                     .new_codec();
 
-                FramedRead::new(GzipDecoder::new(buf_reader), codec)
+                FramedRead::new(compression.decoder(buf_reader), codec)
                     .map_err(Error::from)
                     .boxed()
             }
@@ -72,6 +79,8 @@ mod test {
             region: "us-east-1".to_string(),
             access_key_id: None,
             secret_access_key: None,
+            local_path: None,
+            verify_integrity: false,
         };
 
         let file_store = FileStore::from_settings(&settings)
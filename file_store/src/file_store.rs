@@ -1,21 +1,37 @@
 use crate::{
+    compression::Compression,
     error::DecodeError,
+    file_integrity::FileIntegrity,
+    key_index::KeyIndex,
+    local_store,
     settings::{self, Settings},
     BytesMutStream, Error, FileInfo, FileInfoStream, Result,
 };
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_s3::{types::ByteStream, Client, Endpoint, Region};
+use bytes::{Bytes, BytesMut};
 use chrono::{DateTime, Utc};
 use futures::FutureExt;
-use futures::{stream, StreamExt, TryFutureExt, TryStreamExt};
+use futures::{stream, Stream, StreamExt, TryFutureExt, TryStreamExt};
 use http::Uri;
-use std::path::Path;
-use std::str::FromStr;
+use std::path::{Path, PathBuf};
+use std::{io, str::FromStr};
+use tokio_util::codec::{Decoder, LengthDelimitedCodec};
 
 #[derive(Debug, Clone)]
 pub struct FileStore {
     pub(crate) bucket: String,
-    client: Client,
+    backend: Backend,
+    verify_integrity: bool,
+}
+
+/// The storage backing a [`FileStore`]. Files are stored under keys of the
+/// form `prefix.timestamp.gz`, or `prefix.timestamp.zst` for zstd
+/// compressed files, in both backends.
+#[derive(Debug, Clone)]
+enum Backend {
+    S3(Client),
+    Local(PathBuf),
 }
 
 pub struct FileData {
@@ -25,6 +41,12 @@ pub struct FileData {
 
 impl FileStore {
     pub async fn from_settings(settings: &Settings) -> Result<Self> {
+        if let Some(local_path) = &settings.local_path {
+            return Self::new_local(local_path.join(&settings.bucket))
+                .await
+                .map(|store| store.verify_integrity(settings.verify_integrity));
+        }
+
         let endpoint: Option<Endpoint> = match &settings.endpoint {
             Some(endpoint) => Uri::from_str(endpoint)
                 .map(Endpoint::immutable)
@@ -54,8 +76,9 @@ impl FileStore {
 
         let client = Client::new(&config);
         Ok(Self {
-            client,
+            backend: Backend::S3(client),
             bucket: settings.bucket.clone(),
+            verify_integrity: settings.verify_integrity,
         })
     }
 
@@ -82,7 +105,32 @@ impl FileStore {
         let config = config.load().await;
 
         let client = Client::new(&config);
-        Ok(Self { client, bucket })
+        Ok(Self {
+            backend: Backend::S3(client),
+            bucket,
+            verify_integrity: false,
+        })
+    }
+
+    /// Create a store backed by a directory on the local filesystem. The
+    /// directory is created if it does not exist.
+    pub async fn new_local(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        local_store::init(&path).await?;
+        Ok(Self {
+            bucket: path.to_string_lossy().to_string(),
+            backend: Backend::Local(path),
+            verify_integrity: false,
+        })
+    }
+
+    /// Verify files streamed with [`FileStore::stream_file`] against their
+    /// integrity sidecars. Off by default
+    pub fn verify_integrity(self, verify_integrity: bool) -> Self {
+        Self {
+            verify_integrity,
+            ..self
+        }
     }
 
     pub async fn list_all<A, B>(
@@ -107,8 +155,14 @@ impl FileStore {
         let before = before.into();
         let after = after.into();
 
-        let request = self
-            .client
+        let client = match &self.backend {
+            Backend::S3(client) => client,
+            Backend::Local(path) => {
+                return local_store::list(path.clone(), file_type, after, before);
+            }
+        };
+
+        let request = client
             .list_objects_v2()
             .bucket(&self.bucket)
             .prefix(file_type.to_string())
@@ -157,35 +211,22 @@ impl FileStore {
     }
 
     pub async fn put(&self, file: &Path) -> Result {
-        let byte_stream = ByteStream::from_path(&file)
-            .await
-            .map_err(|_| Error::not_found(format!("could not open {}", file.display())))?;
         poc_metrics::record_duration!(
             "file_store_put_duration",
-            self.client
-                .put_object()
-                .bucket(&self.bucket)
-                .key(file.file_name().map(|name| name.to_string_lossy()).unwrap())
-                .body(byte_stream)
-                .content_type("application/octet-stream")
-                .send()
-                .map_ok(|_| ())
-                .map_err(Error::s3_error)
-                .await
+            match &self.backend {
+                Backend::S3(client) => put_s3(client, &self.bucket, file).await,
+                Backend::Local(path) => local_store::put(path, file).await,
+            }
         )
     }
 
     pub async fn remove(&self, key: &str) -> Result {
         poc_metrics::record_duration!(
             "file_store_remove_duration",
-            self.client
-                .delete_object()
-                .bucket(&self.bucket)
-                .key(key)
-                .send()
-                .map_ok(|_| ())
-                .map_err(Error::s3_error)
-                .await
+            match &self.backend {
+                Backend::S3(client) => remove_s3(client, &self.bucket, key).await,
+                Backend::Local(path) => local_store::remove(path, key).await,
+            }
         )
     }
 
@@ -193,26 +234,28 @@ impl FileStore {
     where
         K: Into<String>,
     {
-        get_byte_stream(self.client.clone(), self.bucket.clone(), key).await
+        get_byte_stream(self.backend.clone(), self.bucket.clone(), key).await
     }
 
     pub async fn get<K>(&self, key: K) -> Result<BytesMutStream>
     where
         K: Into<String>,
     {
-        Ok(stream_source(self.get_raw(key).await?))
+        let key = key.into();
+        let compression = Compression::from_key(&key);
+        Ok(stream_source(self.get_raw(key).await?, compression))
     }
 
     /// Stream a series of ordered items from the store from remote files with
     /// the given keys.
     pub fn source(&self, infos: FileInfoStream) -> BytesMutStream {
         let bucket = self.bucket.clone();
-        let client = self.client.clone();
+        let backend = self.backend.clone();
         infos
-            .map_ok(move |info| get_byte_stream(client.clone(), bucket.clone(), info.key))
+            .map_ok(move |info| file_byte_stream(backend.clone(), bucket.clone(), info.key))
             .try_buffered(2)
             .flat_map(|stream| match stream {
-                Ok(stream) => stream_source(stream),
+                Ok((stream, compression)) => stream_source(stream, compression),
                 Err(err) => stream::once(async move { Err(err) }).boxed(),
             })
             .fuse()
@@ -225,52 +268,208 @@ impl FileStore {
     /// "worker" number of remote files
     pub fn source_unordered(&self, workers: usize, infos: FileInfoStream) -> BytesMutStream {
         let bucket = self.bucket.clone();
-        let client = self.client.clone();
+        let backend = self.backend.clone();
         infos
-            .map_ok(move |info| get_byte_stream(client.clone(), bucket.clone(), info.key))
+            .map_ok(move |info| file_byte_stream(backend.clone(), bucket.clone(), info.key))
             .try_buffer_unordered(workers)
             .flat_map(|stream| match stream {
-                Ok(stream) => stream_source(stream),
+                Ok((stream, compression)) => stream_source(stream, compression),
                 Err(err) => stream::once(async move { Err(err) }).boxed(),
             })
             .fuse()
             .boxed()
     }
 
+    /// Stream the records of the given file. If integrity verification is
+    /// enabled and the file has an integrity sidecar, the file is verified
+    /// against it while it is streamed. The stream then yields an
+    /// [`Error::Integrity`] for truncated or corrupted files.
     pub async fn stream_file(&self, file_info: FileInfo) -> Result<BytesMutStream> {
-        get_byte_stream(self.client.clone(), self.bucket.clone(), file_info)
-            .await
-            .map(stream_source)
+        let integrity = if self.verify_integrity {
+            self.get_integrity(&file_info.key).await?
+        } else {
+            None
+        };
+        let (stream, compression) = file_byte_stream(
+            self.backend.clone(),
+            self.bucket.clone(),
+            file_info.key.clone(),
+        )
+        .await?;
+        let Some(integrity) = integrity else {
+            return Ok(stream_source(stream, compression));
+        };
+        let records = stream_source(
+            integrity.verify_bytes(file_info.key.clone(), stream),
+            compression,
+        )
+        .map_err(integrity_error)
+        .boxed();
+        Ok(integrity.verify_count(file_info.key, records))
+    }
+
+    /// Get the integrity sidecar for the file with the given key, if any
+    pub async fn get_integrity(&self, key: &str) -> Result<Option<FileIntegrity>> {
+        self.get_sidecar(FileIntegrity::key(key))
+            .await?
+            .map(|data| FileIntegrity::decode(&data))
+            .transpose()
+    }
+
+    /// Get the key index sidecar for the file with the given key, if any
+    pub async fn get_index(&self, key: &str) -> Result<Option<KeyIndex>> {
+        self.get_sidecar(KeyIndex::key(key))
+            .await?
+            .map(|data| KeyIndex::decode(&data))
+            .transpose()
+    }
+
+    async fn get_sidecar(&self, key: String) -> Result<Option<Bytes>> {
+        match self.get_raw(key).await {
+            Ok(stream) => collect_bytes(stream).await.map(Some),
+            Err(Error::NotFound(_)) | Err(Error::Aws(aws_sdk_s3::Error::NoSuchKey(_))) => Ok(None),
+            Err(err) => Err(err),
+        }
     }
 }
 
-fn stream_source(stream: ByteStream) -> BytesMutStream {
-    use async_compression::tokio::bufread::GzipDecoder;
-    use tokio_util::{
-        codec::{length_delimited::LengthDelimitedCodec, FramedRead},
-        io::StreamReader,
-    };
+async fn collect_bytes(stream: ByteStream) -> Result<Bytes> {
+    stream
+        .collect()
+        .await
+        .map(|data| data.into_bytes())
+        .map_err(|err| Error::from(io::Error::new(io::ErrorKind::Other, err)))
+}
+
+/// A file with an integrity sidecar was written completely, so failing to
+/// decode it means it was truncated or corrupted since
+fn integrity_error(err: Error) -> Error {
+    match err {
+        Error::Io(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+            ) =>
+        {
+            let kind = err.kind();
+            match err.into_inner().map(|inner| inner.downcast::<Error>()) {
+                Some(Ok(err)) => *err,
+                Some(Err(inner)) => Error::integrity(inner),
+                None => Error::integrity(kind),
+            }
+        }
+        err => err,
+    }
+}
+
+fn stream_source<S, E>(stream: S, compression: Compression) -> BytesMutStream
+where
+    S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+    E: Into<io::Error>,
+{
+    use tokio_util::{codec::FramedRead, io::StreamReader};
 
     Box::pin(
         FramedRead::new(
-            GzipDecoder::new(StreamReader::new(stream)),
-            LengthDelimitedCodec::new(),
+            compression.decoder(StreamReader::new(stream)),
+            FrameCodec::default(),
         )
         .map_err(Error::from),
     )
 }
 
-async fn get_byte_stream<K>(client: Client, bucket: String, key: K) -> Result<ByteStream>
-where
-    K: Into<String>,
-{
+/// Length delimited frames. Data ending inside a frame fails with
+/// [`io::ErrorKind::UnexpectedEof`] so that, like other malformed data, it
+/// can be told apart from failures to read the file.
+#[derive(Default)]
+struct FrameCodec {
+    codec: LengthDelimitedCodec,
+    // Whether the header of a frame has been read without its data
+    in_frame: bool,
+}
+
+impl Decoder for FrameCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        let len = src.len();
+        let frame = self.codec.decode(src)?;
+        if frame.is_some() {
+            self.in_frame = false;
+        } else if src.len() < len {
+            self.in_frame = true;
+        }
+        Ok(frame)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        match self.decode(src)? {
+            None if self.in_frame || !src.is_empty() => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated frame",
+            )),
+            frame => Ok(frame),
+        }
+    }
+}
+
+async fn put_s3(client: &Client, bucket: &str, file: &Path) -> Result {
+    let byte_stream = ByteStream::from_path(&file)
+        .await
+        .map_err(|_| Error::not_found(format!("could not open {}", file.display())))?;
     client
-        .get_object()
+        .put_object()
+        .bucket(bucket)
+        .key(file.file_name().map(|name| name.to_string_lossy()).unwrap())
+        .body(byte_stream)
+        .content_type("application/octet-stream")
+        .send()
+        .map_ok(|_| ())
+        .map_err(Error::s3_error)
+        .await
+}
+
+async fn remove_s3(client: &Client, bucket: &str, key: &str) -> Result {
+    client
+        .delete_object()
         .bucket(bucket)
         .key(key)
         .send()
-        .map_ok(|output| output.body)
+        .map_ok(|_| ())
         .map_err(Error::s3_error)
-        .fuse()
         .await
 }
+
+/// Get the byte stream of the file with the given key along with its
+/// compression
+async fn file_byte_stream(
+    backend: Backend,
+    bucket: String,
+    key: String,
+) -> Result<(ByteStream, Compression)> {
+    let compression = Compression::from_key(&key);
+    get_byte_stream(backend, bucket, key)
+        .await
+        .map(|stream| (stream, compression))
+}
+
+async fn get_byte_stream<K>(backend: Backend, bucket: String, key: K) -> Result<ByteStream>
+where
+    K: Into<String>,
+{
+    match backend {
+        Backend::S3(client) => {
+            client
+                .get_object()
+                .bucket(bucket)
+                .key(key)
+                .send()
+                .map_ok(|output| output.body)
+                .map_err(Error::s3_error)
+                .fuse()
+                .await
+        }
+        Backend::Local(path) => local_store::get(&path, &key.into()).await,
+    }
+}
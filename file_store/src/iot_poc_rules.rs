@@ -0,0 +1,206 @@
+//! The verification rules an iot PoC was verified with.
+//!
+//! `LoraPocV1` has no field for them, so for every PoC written to an
+//! `iot_poc` file the verifier writes an `iot_verification_rules` record
+//! linking the PoC, by its id, to the rules in effect for it. A set of rules
+//! is identified by its hash, so the PoCs verified with the same rules can be
+//! grouped without comparing the rules themselves.
+
+use crate::{
+    traits::{MsgDecode, MsgTimestamp, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Wire format of a verification rule as it was in effect for a PoC
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IotPocRuleV1 {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub params: String,
+    /// Unix timestamp in milliseconds, 0 if the rule was not scheduled
+    #[prost(uint64, tag = "3")]
+    pub effective_from: u64,
+}
+
+/// Wire format of the rules a PoC was verified with
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IotPocRulesV1 {
+    #[prost(bytes = "vec", tag = "1")]
+    pub poc_id: Vec<u8>,
+    /// Timestamp in millis of the beacon of the PoC, the rules in effect at
+    /// this time are the ones the PoC was verified with
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    /// Hex encoded sha256 of the rules
+    #[prost(string, tag = "3")]
+    pub rules_hash: String,
+    #[prost(message, repeated, tag = "4")]
+    pub rules: Vec<IotPocRuleV1>,
+}
+
+/// A verification rule as it was in effect for a PoC
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct IotPocRule {
+    pub name: String,
+    /// Parameters of the rule, json encoded
+    pub params: String,
+    /// When the rule took effect, if it was scheduled
+    pub effective_from: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct IotPocRules {
+    pub poc_id: Vec<u8>,
+    pub timestamp: DateTime<Utc>,
+    pub rules_hash: String,
+    pub rules: Vec<IotPocRule>,
+}
+
+impl IotPocRules {
+    pub fn new(poc_id: Vec<u8>, timestamp: DateTime<Utc>, rules: Vec<IotPocRule>) -> Self {
+        Self {
+            poc_id,
+            timestamp,
+            rules_hash: rules_hash(&rules),
+            rules,
+        }
+    }
+}
+
+/// The hash identifying a set of rules, the same for the same rules in the
+/// same order
+pub fn rules_hash(rules: &[IotPocRule]) -> String {
+    let mut hasher = Sha256::new();
+    for rule in rules {
+        hasher.update(rule.name.as_bytes());
+        hasher.update([0]);
+        hasher.update(rule.params.as_bytes());
+        hasher.update([0]);
+        hasher.update(
+            rule.effective_from
+                .map_or(0, |effective_from| effective_from.encode_timestamp_millis())
+                .to_le_bytes(),
+        );
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+impl MsgTimestamp<u64> for IotPocRules {
+    fn timestamp(&self) -> u64 {
+        self.timestamp.encode_timestamp_millis()
+    }
+}
+
+impl MsgTimestamp<Result<DateTime<Utc>>> for IotPocRulesV1 {
+    fn timestamp(&self) -> Result<DateTime<Utc>> {
+        self.timestamp.to_timestamp_millis()
+    }
+}
+
+impl MsgDecode for IotPocRules {
+    type Msg = IotPocRulesV1;
+}
+
+impl TryFrom<IotPocRulesV1> for IotPocRules {
+    type Error = Error;
+
+    fn try_from(v: IotPocRulesV1) -> Result<Self> {
+        let timestamp = v.timestamp()?;
+        let rules = v
+            .rules
+            .into_iter()
+            .map(IotPocRule::try_from)
+            .collect::<Result<Vec<IotPocRule>>>()?;
+        Ok(Self {
+            poc_id: v.poc_id,
+            timestamp,
+            rules_hash: v.rules_hash,
+            rules,
+        })
+    }
+}
+
+impl From<IotPocRules> for IotPocRulesV1 {
+    fn from(v: IotPocRules) -> Self {
+        let timestamp = v.timestamp();
+        Self {
+            poc_id: v.poc_id,
+            timestamp,
+            rules_hash: v.rules_hash,
+            rules: v.rules.into_iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<IotPocRuleV1> for IotPocRule {
+    type Error = Error;
+
+    fn try_from(v: IotPocRuleV1) -> Result<Self> {
+        let effective_from = if v.effective_from == 0 {
+            None
+        } else {
+            Some(v.effective_from.to_timestamp_millis()?)
+        };
+        Ok(Self {
+            name: v.name,
+            params: v.params,
+            effective_from,
+        })
+    }
+}
+
+impl From<IotPocRule> for IotPocRuleV1 {
+    fn from(v: IotPocRule) -> Self {
+        Self {
+            name: v.name,
+            params: v.params,
+            effective_from: v
+                .effective_from
+                .map_or(0, |effective_from| effective_from.encode_timestamp_millis()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use prost::Message;
+
+    fn rule(max_distance_km: u32, effective_from: Option<DateTime<Utc>>) -> IotPocRule {
+        IotPocRule {
+            name: "witness_distance".to_string(),
+            params: format!(r#"{{"name":"witness_distance","max_distance_km":{max_distance_km}}}"#),
+            effective_from,
+        }
+    }
+
+    #[test]
+    fn rules_roundtrip_and_are_identified_by_their_hash() {
+        let effective_from = Utc.with_ymd_and_hms(2023, 10, 1, 0, 0, 0).unwrap();
+        let timestamp = Utc.with_ymd_and_hms(2023, 10, 2, 0, 0, 0).unwrap();
+        let rules = IotPocRules::new(
+            vec![1, 2, 3],
+            timestamp,
+            vec![rule(100, None), rule(50, Some(effective_from))],
+        );
+        let encoded = IotPocRulesV1::from(rules.clone()).encode_to_vec();
+        assert_eq!(
+            rules,
+            IotPocRules::decode(encoded.as_slice()).expect("poc rules")
+        );
+
+        let same = IotPocRules::new(vec![4, 5, 6], timestamp, rules.rules.clone());
+        assert_eq!(rules.rules_hash, same.rules_hash);
+        let scheduled_later = IotPocRules::new(
+            vec![1, 2, 3],
+            timestamp,
+            vec![rule(100, None), rule(50, Some(timestamp))],
+        );
+        assert_ne!(rules.rules_hash, scheduled_later.rules_hash);
+    }
+}
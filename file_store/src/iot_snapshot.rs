@@ -0,0 +1,195 @@
+//! Snapshots of the iot config state the iot verifier verifies PoCs against,
+//! so that past epochs can be verified again against the same state.
+//!
+//! Gateway snapshot records are iot config `GatewayInfo` messages, region
+//! params and hex density snapshot records are defined here as there are no
+//! protos for them yet. Each snapshot is completed by a manifest record that
+//! lists the files of all three types it is made of, written once all of
+//! them are committed. The time of a snapshot is the timestamp of the file
+//! of its manifest.
+
+use crate::{
+    error::{DecodeError, EncodeError},
+    traits::MsgDecode,
+    Error, Result, SCALING_PRECISION,
+};
+use helium_proto::{BlockchainRegionParamV1, BlockchainRegionParamsV1, Region};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::Serialize;
+
+/// Wire format of the params of a region
+#[derive(Clone, PartialEq, prost::Message, Serialize)]
+pub struct IotRegionParamsV1 {
+    #[prost(enumeration = "Region", tag = "1")]
+    pub region: i32,
+    #[prost(message, optional, tag = "2")]
+    pub params: Option<BlockchainRegionParamsV1>,
+}
+
+/// Wire format of the transmit scale of a hex
+#[derive(Clone, PartialEq, prost::Message, Serialize)]
+pub struct IotHexDensityV1 {
+    /// The res 11 or coarser h3 index of the hex
+    #[prost(uint64, tag = "1")]
+    pub hex: u64,
+    /// The transmit scale, scaled by 10^SCALING_PRECISION
+    #[prost(uint32, tag = "2")]
+    pub scale: u32,
+}
+
+/// Wire format of the files a snapshot is made of
+#[derive(Clone, PartialEq, prost::Message, Serialize)]
+pub struct IotSnapshotManifestV1 {
+    #[prost(string, repeated, tag = "1")]
+    pub gateway_files: Vec<String>,
+    #[prost(string, repeated, tag = "2")]
+    pub region_params_files: Vec<String>,
+    #[prost(string, repeated, tag = "3")]
+    pub hex_density_files: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct IotRegionParams {
+    pub region: Region,
+    pub params: Vec<BlockchainRegionParamV1>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct IotHexDensity {
+    pub hex: u64,
+    pub scale: Decimal,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct IotSnapshotManifest {
+    pub gateway_files: Vec<String>,
+    pub region_params_files: Vec<String>,
+    pub hex_density_files: Vec<String>,
+}
+
+impl MsgDecode for IotRegionParams {
+    type Msg = IotRegionParamsV1;
+}
+
+impl MsgDecode for IotHexDensity {
+    type Msg = IotHexDensityV1;
+}
+
+impl MsgDecode for IotSnapshotManifest {
+    type Msg = IotSnapshotManifestV1;
+}
+
+impl TryFrom<IotRegionParamsV1> for IotRegionParams {
+    type Error = Error;
+
+    fn try_from(v: IotRegionParamsV1) -> Result<Self> {
+        let region = Region::from_i32(v.region)
+            .ok_or_else(|| DecodeError::unsupported_region("iot_region_params_v1", v.region))?;
+        Ok(Self {
+            region,
+            params: v
+                .params
+                .map(|params| params.region_params)
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl From<IotRegionParams> for IotRegionParamsV1 {
+    fn from(v: IotRegionParams) -> Self {
+        Self {
+            region: v.region.into(),
+            params: Some(BlockchainRegionParamsV1 {
+                region_params: v.params,
+            }),
+        }
+    }
+}
+
+impl TryFrom<IotHexDensityV1> for IotHexDensity {
+    type Error = Error;
+
+    fn try_from(v: IotHexDensityV1) -> Result<Self> {
+        Ok(Self {
+            hex: v.hex,
+            scale: Decimal::new(v.scale as i64, SCALING_PRECISION),
+        })
+    }
+}
+
+impl TryFrom<IotHexDensity> for IotHexDensityV1 {
+    type Error = Error;
+
+    fn try_from(v: IotHexDensity) -> Result<Self> {
+        let mut scale = v.scale;
+        scale.rescale(SCALING_PRECISION);
+        let scale = scale.mantissa().to_u32().ok_or_else(|| {
+            EncodeError::out_of_range(format!("transmit scale {} of hex {}", v.scale, v.hex))
+        })?;
+        Ok(Self { hex: v.hex, scale })
+    }
+}
+
+impl TryFrom<IotSnapshotManifestV1> for IotSnapshotManifest {
+    type Error = Error;
+
+    fn try_from(v: IotSnapshotManifestV1) -> Result<Self> {
+        Ok(Self {
+            gateway_files: v.gateway_files,
+            region_params_files: v.region_params_files,
+            hex_density_files: v.hex_density_files,
+        })
+    }
+}
+
+impl From<IotSnapshotManifest> for IotSnapshotManifestV1 {
+    fn from(v: IotSnapshotManifest) -> Self {
+        Self {
+            gateway_files: v.gateway_files,
+            region_params_files: v.region_params_files,
+            hex_density_files: v.hex_density_files,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn hex_density_roundtrips_at_scaling_precision() {
+        let density = IotHexDensity {
+            hex: 631615575095659519,
+            scale: dec!(0.25),
+        };
+        let encoded = IotHexDensityV1::try_from(density.clone())
+            .expect("encodable hex density")
+            .encode_to_vec();
+        assert_eq!(
+            density,
+            IotHexDensity::decode(encoded.as_slice()).expect("hex density")
+        );
+    }
+
+    #[test]
+    fn hex_density_rejects_unencodable_scales() {
+        let density = |scale| IotHexDensity {
+            hex: 631615575095659519,
+            scale,
+        };
+        assert!(IotHexDensityV1::try_from(density(dec!(-0.25))).is_err());
+        assert!(IotHexDensityV1::try_from(density(dec!(1000000))).is_err());
+    }
+
+    #[test]
+    fn region_params_reject_unknown_regions() {
+        let encoded = IotRegionParamsV1 {
+            region: 1000,
+            params: None,
+        }
+        .encode_to_vec();
+        assert!(IotRegionParams::decode(encoded.as_slice()).is_err());
+    }
+}
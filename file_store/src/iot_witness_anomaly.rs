@@ -0,0 +1,143 @@
+//! Suspicious patterns in the iot witness graph, scored for operators to
+//! review and feed into the denylist.
+
+use crate::{
+    error::DecodeError,
+    traits::{MsgDecode, MsgTimestamp, TimestampDecode, TimestampEncode},
+    Error, Result,
+};
+use chrono::{DateTime, Utc};
+use helium_crypto::PublicKeyBinary;
+use serde::Serialize;
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration, Serialize,
+)]
+#[repr(i32)]
+pub enum IotWitnessAnomalyKind {
+    /// A small group of gateways that only witness each other
+    ClosedCluster = 0,
+    /// A witness whose signal from a beaconer barely varies
+    ConsistentRssi = 1,
+    /// A beaconer that is witnessed by the same gateways time after time
+    StaticWitnessSet = 2,
+}
+
+impl IotWitnessAnomalyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ClosedCluster => "closed_cluster",
+            Self::ConsistentRssi => "consistent_rssi",
+            Self::StaticWitnessSet => "static_witness_set",
+        }
+    }
+}
+
+/// Wire format of a witness graph anomaly
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IotWitnessAnomalyV1 {
+    #[prost(enumeration = "IotWitnessAnomalyKind", tag = "1")]
+    pub kind: i32,
+    /// Between 0 and 1, the higher the more suspicious
+    #[prost(double, tag = "2")]
+    pub score: f64,
+    /// The gateways involved, the beaconer first for anomalies of a beaconer
+    /// and its witnesses
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub gateways: Vec<Vec<u8>>,
+    /// Number of PoCs the anomaly was observed in
+    #[prost(uint64, tag = "4")]
+    pub pocs: u64,
+    /// Timestamp in millis of the start of the window of PoCs analyzed
+    #[prost(uint64, tag = "5")]
+    pub window_start: u64,
+    /// Timestamp in millis of the analysis
+    #[prost(uint64, tag = "6")]
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct IotWitnessAnomaly {
+    pub kind: IotWitnessAnomalyKind,
+    pub score: f64,
+    pub gateways: Vec<PublicKeyBinary>,
+    pub pocs: u64,
+    pub window_start: DateTime<Utc>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl MsgTimestamp<u64> for IotWitnessAnomaly {
+    fn timestamp(&self) -> u64 {
+        self.timestamp.encode_timestamp_millis()
+    }
+}
+
+impl MsgTimestamp<Result<DateTime<Utc>>> for IotWitnessAnomalyV1 {
+    fn timestamp(&self) -> Result<DateTime<Utc>> {
+        self.timestamp.to_timestamp_millis()
+    }
+}
+
+impl MsgDecode for IotWitnessAnomaly {
+    type Msg = IotWitnessAnomalyV1;
+}
+
+impl TryFrom<IotWitnessAnomalyV1> for IotWitnessAnomaly {
+    type Error = Error;
+
+    fn try_from(v: IotWitnessAnomalyV1) -> Result<Self> {
+        let timestamp = v.timestamp()?;
+        let kind = IotWitnessAnomalyKind::from_i32(v.kind).ok_or_else(|| {
+            DecodeError::unsupported_witness_anomaly_kind("iot_witness_anomaly_v1", v.kind)
+        })?;
+        Ok(Self {
+            kind,
+            score: v.score,
+            gateways: v.gateways.into_iter().map(Into::into).collect(),
+            pocs: v.pocs,
+            window_start: v.window_start.to_timestamp_millis()?,
+            timestamp,
+        })
+    }
+}
+
+impl From<IotWitnessAnomaly> for IotWitnessAnomalyV1 {
+    fn from(v: IotWitnessAnomaly) -> Self {
+        let timestamp = v.timestamp();
+        Self {
+            kind: v.kind.into(),
+            score: v.score,
+            gateways: v.gateways.into_iter().map(Into::into).collect(),
+            pocs: v.pocs,
+            window_start: v.window_start.encode_timestamp_millis(),
+            timestamp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use prost::Message;
+
+    #[test]
+    fn anomaly_roundtrips() {
+        let anomaly = IotWitnessAnomaly {
+            kind: IotWitnessAnomalyKind::ConsistentRssi,
+            score: 0.75,
+            gateways: vec![
+                PublicKeyBinary::from(vec![1; 33]),
+                PublicKeyBinary::from(vec![2; 33]),
+            ],
+            pocs: 42,
+            window_start: Utc.with_ymd_and_hms(2023, 10, 1, 0, 0, 0).unwrap(),
+            timestamp: Utc.with_ymd_and_hms(2023, 10, 2, 0, 0, 0).unwrap(),
+        };
+        let encoded = IotWitnessAnomalyV1::from(anomaly.clone()).encode_to_vec();
+        assert_eq!(
+            anomaly,
+            IotWitnessAnomaly::decode(encoded.as_slice()).expect("anomaly")
+        );
+    }
+}
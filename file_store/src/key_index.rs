@@ -0,0 +1,116 @@
+//! Per-file key indexes.
+//!
+//! A file `prefix.timestamp.gz` can be accompanied by a json sidecar
+//! `prefix.timestamp.gz.index.json` that maps the keys, public keys and cbsd
+//! ids, referred to by the records in the file to the positions of those
+//! records in the file. Searches use it to skip files that do not refer to a
+//! key and to decode only the matching records of files that do.
+
+use crate::{registry::RecordKeys, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tokio::fs;
+
+pub const INDEX_SUFFIX: &str = ".index.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyIndex {
+    /// Number of records in the file
+    pub record_count: u64,
+    /// Positions of the records referring to a key, by key
+    pub keys: BTreeMap<String, Vec<u64>>,
+}
+
+impl KeyIndex {
+    /// The sidecar key for the given data file key
+    pub fn key(data_key: &str) -> String {
+        format!("{data_key}{INDEX_SUFFIX}")
+    }
+
+    /// The sidecar path for the given data file path
+    pub fn path(data_path: &Path) -> PathBuf {
+        let mut path = data_path.as_os_str().to_owned();
+        path.push(INDEX_SUFFIX);
+        PathBuf::from(path)
+    }
+
+    /// Add the next record of the file, reading its keys with the given
+    /// [`RecordKeys`]. Records that fail to decode are counted but not
+    /// indexed.
+    pub fn push(&mut self, record_keys: RecordKeys, record: &[u8]) {
+        let position = self.record_count;
+        self.record_count += 1;
+        let Some(keys) = record_keys(record) else {
+            return;
+        };
+        for key in keys {
+            let positions = self.keys.entry(key).or_default();
+            // A record can refer to the same key more than once
+            if positions.last() != Some(&position) {
+                positions.push(position);
+            }
+        }
+    }
+
+    /// Positions of the records referring to the given key
+    pub fn positions(&self, key: &str) -> &[u64] {
+        self.keys.get(key).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Write the sidecar for the given data file path
+    pub async fn write(&self, data_path: &Path) -> Result {
+        fs::write(Self::path(data_path), self.encode()?).await?;
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(buf)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{registry, FileType};
+    use helium_proto::{services::poc_mobile::CellHeartbeatReqV1, Message};
+
+    #[test]
+    fn indexes_keys_by_position() {
+        let heartbeat = |pub_key: Vec<u8>, cbsd_id: &str| {
+            CellHeartbeatReqV1 {
+                pub_key,
+                cbsd_id: cbsd_id.to_string(),
+                timestamp: 1,
+                ..Default::default()
+            }
+            .encode_to_vec()
+        };
+        let key1 = vec![0; 33];
+        let key2 = vec![1; 33];
+        let record_keys =
+            registry::record_keys(FileType::CbrsHeartbeat).expect("cbrs heartbeat keys");
+
+        let mut index = KeyIndex::default();
+        index.push(record_keys, &heartbeat(key1.clone(), "cbsd1"));
+        index.push(record_keys, &heartbeat(key2.clone(), "cbsd2"));
+        index.push(record_keys, b"not a heartbeat");
+        index.push(record_keys, &heartbeat(key1.clone(), "cbsd2"));
+
+        assert_eq!(4, index.record_count);
+        let key1 = helium_crypto::PublicKeyBinary::from(key1).to_string();
+        assert_eq!(&[0, 3], index.positions(&key1));
+        assert_eq!(&[1, 3], index.positions("cbsd2"));
+        assert!(index.positions("cbsd3").is_empty());
+        assert_eq!(
+            index,
+            KeyIndex::decode(&index.encode().expect("encode")).expect("decode")
+        );
+    }
+}
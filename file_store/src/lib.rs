@@ -1,9 +1,12 @@
 pub mod cli;
+pub mod compression;
 pub mod coverage;
+pub mod dead_letter;
 pub mod entropy_report;
 mod error;
 mod file_info;
 pub mod file_info_poller;
+pub mod file_integrity;
 pub mod file_sink;
 pub mod file_source;
 pub mod file_store;
@@ -13,11 +16,17 @@ pub mod hex_boost;
 pub mod iot_beacon_report;
 pub mod iot_invalid_poc;
 pub mod iot_packet;
+pub mod iot_poc_rules;
+pub mod iot_snapshot;
 pub mod iot_valid_poc;
+pub mod iot_witness_anomaly;
 pub mod iot_witness_report;
+pub mod key_index;
+mod local_store;
 pub mod mobile_session;
 pub mod mobile_subscriber;
 pub mod mobile_transfer;
+pub mod registry;
 pub mod reward_manifest;
 mod settings;
 pub mod speedtest;
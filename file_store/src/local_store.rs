@@ -0,0 +1,229 @@
+//! Local filesystem backend for [`FileStore`](crate::FileStore).
+//!
+//! Files are kept flat in a single directory using the same
+//! `prefix.timestamp.gz` keys as the S3 backend, which allows running a
+//! pipeline of daemons against a shared directory without an object store.
+
+use crate::{Error, FileInfo, FileInfoStream, Result};
+use aws_sdk_s3::types::ByteStream;
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt, TryStreamExt};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tokio::fs;
+
+const TMP_DIR: &str = "tmp";
+
+pub(crate) async fn init(root: &Path) -> Result {
+    fs::create_dir_all(root.join(TMP_DIR)).await?;
+    Ok(())
+}
+
+pub(crate) fn list(
+    root: PathBuf,
+    prefix: String,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> FileInfoStream {
+    stream::once(async move { list_dir(&root, &prefix, after, before).await })
+        .map_ok(|infos| stream::iter(infos.into_iter().map(Ok)))
+        .try_flatten()
+        .boxed()
+}
+
+async fn list_dir(
+    root: &Path,
+    prefix: &str,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) -> Result<Vec<FileInfo>> {
+    let mut infos = Vec::new();
+    let mut dir = fs::read_dir(root).await?;
+    while let Some(entry) = dir.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let key = entry.file_name().to_string_lossy().to_string();
+        if !key.starts_with(prefix) || !FileInfo::matches(&key) {
+            continue;
+        }
+        let mut info = FileInfo::from_str(&key)?;
+        info.size = metadata.len() as usize;
+        if after.map_or(true, |v| info.timestamp > v)
+            && before.map_or(true, |v| info.timestamp <= v)
+        {
+            infos.push(info);
+        }
+    }
+    // Match the lexicographic key order of S3 listings
+    infos.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(infos)
+}
+
+pub(crate) async fn put(root: &Path, file: &Path) -> Result {
+    let file_name = file
+        .file_name()
+        .ok_or_else(|| Error::not_found(format!("could not open {}", file.display())))?;
+    // Copy via a temporary file so a listing never sees a partial file
+    let tmp_path = root.join(TMP_DIR).join(file_name);
+    fs::copy(file, &tmp_path).await?;
+    fs::rename(&tmp_path, root.join(file_name)).await?;
+    Ok(())
+}
+
+pub(crate) async fn remove(root: &Path, key: &str) -> Result {
+    fs::remove_file(root.join(key)).await?;
+    Ok(())
+}
+
+pub(crate) async fn get(root: &Path, key: &str) -> Result<ByteStream> {
+    let path = root.join(key);
+    ByteStream::from_path(&path)
+        .await
+        .map_err(|_| Error::not_found(format!("could not open {}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{file_integrity::FileIntegrity, file_sink, Error, FileInfo, FileStore, FileType};
+    use chrono::{Duration, Utc};
+    use futures::{SinkExt, TryStreamExt};
+    use std::path::Path;
+    use tempfile::TempDir;
+    use tokio_util::codec::length_delimited::LengthDelimitedCodec;
+
+    async fn write_file(dir: &Path, info: &FileInfo, records: &[&str]) {
+        let file = tokio::fs::File::create(dir.join(&info.key))
+            .await
+            .expect("create file");
+        let mut transport = LengthDelimitedCodec::builder()
+            .max_frame_length(file_sink::MAX_FRAME_LENGTH)
+            .new_write(async_compression::tokio::write::GzipEncoder::new(file));
+        for record in records {
+            transport
+                .send(bytes::Bytes::from(record.to_string()))
+                .await
+                .expect("write record");
+        }
+        transport.close().await.expect("close file");
+    }
+
+    #[tokio::test]
+    async fn put_list_stream_and_remove() {
+        let src_dir = TempDir::new().expect("Unable to create temp dir");
+        let store_dir = TempDir::new().expect("Unable to create temp dir");
+        let store = FileStore::new_local(store_dir.path().join("bucket"))
+            .await
+            .expect("local file store");
+
+        let now = Utc::now();
+        let old = FileInfo::from((FileType::EntropyReport, now - Duration::hours(2)));
+        let new = FileInfo::from((FileType::EntropyReport, now - Duration::hours(1)));
+        let other = FileInfo::from((FileType::IotPoc, now - Duration::hours(1)));
+        for info in [&old, &new, &other] {
+            write_file(src_dir.path(), info, &["hello", "world"]).await;
+            store
+                .put(&src_dir.path().join(&info.key))
+                .await
+                .expect("put file");
+        }
+
+        let listed = store
+            .list_all(
+                &FileType::EntropyReport.to_string(),
+                now - Duration::minutes(90),
+                now,
+            )
+            .await
+            .expect("list files");
+        assert_eq!(
+            vec![new.key.clone()],
+            listed.iter().map(|i| i.key.clone()).collect::<Vec<_>>()
+        );
+        assert!(listed[0].size > 0);
+
+        let records: Vec<_> = store
+            .stream_file(listed[0].clone())
+            .await
+            .expect("stream file")
+            .try_collect()
+            .await
+            .expect("read records");
+        assert_eq!(vec!["hello", "world"], records);
+
+        store.remove(&new.key).await.expect("remove file");
+        let listed = store
+            .list_all(
+                &FileType::EntropyReport.to_string(),
+                now - Duration::hours(3),
+                now,
+            )
+            .await
+            .expect("list files");
+        assert_eq!(
+            vec![old.key],
+            listed.into_iter().map(|i| i.key).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn verifies_streamed_files_when_enabled() {
+        let src_dir = TempDir::new().expect("Unable to create temp dir");
+        let store_dir = TempDir::new().expect("Unable to create temp dir");
+        let store = FileStore::new_local(store_dir.path().join("bucket"))
+            .await
+            .expect("local file store")
+            .verify_integrity(true);
+
+        let info = FileInfo::from((FileType::EntropyReport, Utc::now()));
+        let path = src_dir.path().join(&info.key);
+        write_file(src_dir.path(), &info, &["hello", "world"]).await;
+        FileIntegrity::from_file(&path, 2, None, None)
+            .await
+            .expect("integrity")
+            .write(&path)
+            .await
+            .expect("write sidecar");
+        store.put(&path).await.expect("put file");
+        store
+            .put(&FileIntegrity::path(&path))
+            .await
+            .expect("put sidecar");
+
+        let records: Vec<_> = store
+            .stream_file(info.clone())
+            .await
+            .expect("stream file")
+            .try_collect()
+            .await
+            .expect("read records");
+        assert_eq!(vec!["hello", "world"], records);
+
+        // Truncate the file, dropping the gzip trailer
+        let data = tokio::fs::read(&path).await.expect("read file");
+        tokio::fs::write(&path, &data[..data.len() - 4])
+            .await
+            .expect("truncate file");
+        store.put(&path).await.expect("put file");
+        let result: Result<Vec<_>, _> = store
+            .stream_file(info.clone())
+            .await
+            .expect("stream file")
+            .try_collect()
+            .await;
+        assert!(matches!(result, Err(Error::Integrity(_))));
+
+        // Without verification the sidecar is not consulted
+        let records: Result<Vec<_>, _> = store
+            .verify_integrity(false)
+            .stream_file(info)
+            .await
+            .expect("stream file")
+            .try_collect()
+            .await;
+        assert!(!matches!(records, Err(Error::Integrity(_))));
+    }
+}
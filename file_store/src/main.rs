@@ -1,6 +1,6 @@
 use clap::Parser;
 use file_store::{
-    cli::{bucket, dump, info},
+    cli::{bucket, dump, export, info, replay, search},
     Result, Settings,
 };
 use std::path;
@@ -29,6 +29,9 @@ pub enum Cmd {
     Info(info::Cmd),
     Dump(dump::Cmd),
     Bucket(Box<bucket::Cmd>),
+    Replay(Box<replay::Cmd>),
+    Export(Box<export::Cmd>),
+    Search(Box<search::Cmd>),
 }
 
 impl Cmd {
@@ -37,6 +40,9 @@ impl Cmd {
             Cmd::Info(cmd) => cmd.run(&settings).await,
             Cmd::Dump(cmd) => cmd.run(&settings).await,
             Cmd::Bucket(cmd) => cmd.run(&settings).await,
+            Cmd::Replay(cmd) => cmd.run(&settings).await,
+            Cmd::Export(cmd) => cmd.run(&settings).await,
+            Cmd::Search(cmd) => cmd.run(&settings).await,
         }
     }
 }
@@ -0,0 +1,743 @@
+//! How to read records of every [`FileType`] without knowing their types.
+//!
+//! Each file type maps to an [`Entry`] that decodes a record into json, and
+//! knows where the keys, public keys and cbsd ids, and the timestamp of a
+//! record live in that json. [`entry`] matches on every file type without a wildcard arm, so a new
+//! file type does not compile until it is wired in here.
+//!
+//! [`record_timestamp`] and [`record_keys`] read the timestamp and the keys
+//! of a record straight from its proto, for writers that need them for every
+//! record and cannot afford the json.
+
+use crate::{
+    coverage::CoverageObjectIngestReport,
+    dead_letter::DeadLetter,
+    entropy_report::EntropyReport,
+    heartbeat::{cli::ValidatedHeartbeat, CbrsHeartbeat, CbrsHeartbeatIngestReport},
+    iot_beacon_report::IotBeaconIngestReport,
+    iot_invalid_poc::{IotInvalidBeaconReport, IotInvalidWitnessReport},
+    iot_packet::{IotValidPacket, PacketRouterPacketReport},
+    iot_poc_rules::IotPocRules,
+    iot_snapshot::{IotHexDensity, IotRegionParams, IotSnapshotManifest},
+    iot_valid_poc::IotPoc,
+    iot_witness_anomaly::IotWitnessAnomaly,
+    iot_witness_report::IotWitnessIngestReport,
+    mobile_session::{DataTransferSessionIngestReport, InvalidDataTransferIngestReport},
+    mobile_subscriber::{
+        SubscriberLocationIngestReport, SubscriberLocationReq,
+        VerifiedSubscriberLocationIngestReport,
+    },
+    mobile_transfer::ValidDataTransferSession,
+    speedtest::{cli::SpeedtestAverage, CellSpeedtest, CellSpeedtestIngestReport},
+    traits::{MsgDecode, MsgTimestamp, TimestampDecode},
+    wifi_heartbeat::{WifiHeartbeat, WifiHeartbeatIngestReport},
+    Error, FileType, Result,
+};
+use base64::Engine;
+use chrono::{DateTime, TimeZone, Utc};
+use helium_crypto::PublicKeyBinary;
+use helium_proto::{
+    services::{
+        iot_config::GatewayInfo,
+        packet_verifier::{self, InvalidPacket},
+        poc_lora::{
+            IotRewardShare, LoraBeaconIngestReportV1, LoraPocV1, LoraWitnessIngestReportV1,
+            NonRewardablePacket,
+        },
+        poc_mobile::{
+            coverage_object_req_v1, CellHeartbeatIngestReportV1, CellHeartbeatReqV1,
+            CoverageObjectIngestReportV1, CoverageObjectV1, DataTransferSessionIngestReportV1,
+            Heartbeat, MobileRewardShare, RadioRewardShare, SeniorityUpdate, SpeedtestAvg,
+            SpeedtestIngestReportV1, SpeedtestReqV1, SubscriberLocationIngestReportV1,
+            VerifiedSpeedtest, WifiHeartbeatIngestReportV1, WifiHeartbeatReqV1,
+        },
+    },
+    BlockchainTxn, BoostedHexUpdateV1, Message, PriceReportV1, RewardManifest, SubnetworkRewards,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Length of a binary helium public key
+const PUBLIC_KEY_LENGTH: usize = 33;
+
+#[derive(Debug)]
+pub struct Entry {
+    /// Decode a record into json
+    pub decode: fn(&[u8]) -> Result<Value>,
+    /// Paths to the public keys and cbsd ids in a decoded record. Path segments are
+    /// separated by `/` and `*` matches every element of an array or object
+    pub keys: &'static [&'static str],
+    /// Where the timestamp of a decoded record lives, if it has one
+    pub timestamp: Option<Timestamp>,
+}
+
+/// Path to a timestamp in a decoded record and how it is encoded
+#[derive(Debug)]
+pub enum Timestamp {
+    DateTime(&'static str),
+    Seconds(&'static str),
+    Millis(&'static str),
+}
+
+impl Entry {
+    /// The b58 public keys and cbsd ids of a decoded record
+    pub fn keys(&self, value: &Value) -> Vec<String> {
+        let mut keys = Vec::new();
+        for path in self.keys {
+            select(value, path, &mut |value| {
+                if let Some(key) = value.as_str() {
+                    keys.push(key.to_string());
+                }
+            });
+        }
+        keys
+    }
+
+    pub fn timestamp(&self, value: &Value) -> Option<DateTime<Utc>> {
+        match self.timestamp.as_ref()? {
+            Timestamp::DateTime(path) => pointer(value, path)?.as_str()?.parse().ok(),
+            Timestamp::Seconds(path) => Utc
+                .timestamp_opt(pointer(value, path)?.as_i64()?, 0)
+                .single(),
+            Timestamp::Millis(path) => Utc
+                .timestamp_millis_opt(pointer(value, path)?.as_i64()?)
+                .single(),
+        }
+    }
+}
+
+pub fn entry(file_type: FileType) -> Entry {
+    use Timestamp::{DateTime, Millis, Seconds};
+    let (decode, keys, timestamp): (fn(&[u8]) -> Result<Value>, &[&str], _) = match file_type {
+        FileType::CbrsHeartbeat => (
+            decode::<CbrsHeartbeat>,
+            &["pubkey", "cbsd_id"],
+            Some(DateTime("timestamp")),
+        ),
+        FileType::CellSpeedtest => (
+            decode::<CellSpeedtest>,
+            &["pubkey"],
+            Some(DateTime("timestamp")),
+        ),
+        FileType::Entropy => (decode::<EntropyReport>, &[], Some(DateTime("timestamp"))),
+        FileType::SubnetworkRewards => (
+            decode_proto::<SubnetworkRewards>,
+            &["rewards/*/account"],
+            None,
+        ),
+        FileType::CbrsHeartbeatIngestReport => (
+            decode::<CbrsHeartbeatIngestReport>,
+            &["report/pubkey", "report/cbsd_id"],
+            Some(DateTime("received_timestamp")),
+        ),
+        FileType::CellSpeedtestIngestReport => (
+            decode::<CellSpeedtestIngestReport>,
+            &["report/pubkey"],
+            Some(DateTime("received_timestamp")),
+        ),
+        FileType::EntropyReport => (decode::<EntropyReport>, &[], Some(DateTime("timestamp"))),
+        FileType::IotBeaconIngestReport => (
+            decode::<IotBeaconIngestReport>,
+            &["report/pub_key"],
+            Some(DateTime("received_timestamp")),
+        ),
+        FileType::IotWitnessIngestReport => (
+            decode::<IotWitnessIngestReport>,
+            &["report/pub_key"],
+            Some(DateTime("received_timestamp")),
+        ),
+        FileType::IotPoc => (
+            decode::<IotPoc>,
+            &[
+                "beacon_report/report/pub_key",
+                "selected_witnesses/*/report/pub_key",
+                "unselected_witnesses/*/report/pub_key",
+            ],
+            Some(DateTime("beacon_report/received_timestamp")),
+        ),
+        FileType::IotInvalidBeaconReport => (
+            decode::<IotInvalidBeaconReport>,
+            &["report/pub_key"],
+            Some(DateTime("received_timestamp")),
+        ),
+        FileType::IotInvalidWitnessReport => (
+            decode::<IotInvalidWitnessReport>,
+            &["report/pub_key"],
+            Some(DateTime("received_timestamp")),
+        ),
+        FileType::SpeedtestAvg => (
+            decode::<SpeedtestAverage>,
+            &["pub_key"],
+            Some(DateTime("timestamp")),
+        ),
+        FileType::ValidatedHeartbeat => (
+            decode::<ValidatedHeartbeat>,
+            &["pub_key", "cbsd_id"],
+            Some(DateTime("timestamp")),
+        ),
+        FileType::SignedPocReceiptTxn => (decode_proto::<BlockchainTxn>, &[], None),
+        FileType::RadioRewardShare => (
+            decode_proto::<RadioRewardShare>,
+            &["owner_key", "hotspot_key", "cbsd_id"],
+            Some(Seconds("start_epoch")),
+        ),
+        FileType::RewardManifest => (
+            decode_proto::<RewardManifest>,
+            &[],
+            Some(Seconds("end_timestamp")),
+        ),
+        FileType::IotPacketReport => (
+            decode::<PacketRouterPacketReport>,
+            &["gateway"],
+            Some(DateTime("received_timestamp")),
+        ),
+        FileType::IotValidPacket => (
+            decode::<IotValidPacket>,
+            &["gateway"],
+            Some(DateTime("packet_timestamp")),
+        ),
+        FileType::InvalidPacket => (decode_proto::<InvalidPacket>, &["gateway"], None),
+        FileType::NonRewardablePacket => (
+            decode_proto::<NonRewardablePacket>,
+            &["packet/gateway"],
+            Some(Millis("timestamp")),
+        ),
+        FileType::IotRewardShare => (
+            decode_proto::<IotRewardShare>,
+            &["reward/*/hotspot_key"],
+            Some(Seconds("start_period")),
+        ),
+        FileType::DataTransferSessionIngestReport => (
+            decode::<DataTransferSessionIngestReport>,
+            &[
+                "report/pub_key",
+                "report/data_transfer_usage/pub_key",
+                "report/data_transfer_usage/payer",
+            ],
+            Some(DateTime("received_timestamp")),
+        ),
+        FileType::InvalidDataTransferSessionIngestReport => (
+            decode::<InvalidDataTransferIngestReport>,
+            &[
+                "report/report/pub_key",
+                "report/report/data_transfer_usage/pub_key",
+                "report/report/data_transfer_usage/payer",
+            ],
+            Some(DateTime("timestamp")),
+        ),
+        FileType::ValidDataTransferSession => (
+            decode::<ValidDataTransferSession>,
+            &["pub_key", "payer"],
+            Some(DateTime("first_timestamp")),
+        ),
+        FileType::PriceReport => (
+            decode_proto::<PriceReportV1>,
+            &[],
+            Some(Seconds("timestamp")),
+        ),
+        FileType::MobileRewardShare => (
+            decode_proto::<MobileRewardShare>,
+            &["reward/*/hotspot_key", "reward/*/cbsd_id"],
+            Some(Seconds("start_period")),
+        ),
+        FileType::SubscriberLocationReq => (
+            decode::<SubscriberLocationReq>,
+            &["carrier_pub_key"],
+            Some(DateTime("timestamp")),
+        ),
+        FileType::SubscriberLocationIngestReport => (
+            decode::<SubscriberLocationIngestReport>,
+            &["report/carrier_pub_key"],
+            Some(DateTime("received_timestamp")),
+        ),
+        FileType::VerifiedSubscriberLocationIngestReport => (
+            decode::<VerifiedSubscriberLocationIngestReport>,
+            &["report/report/carrier_pub_key"],
+            Some(DateTime("timestamp")),
+        ),
+        // There is no proto for mapper messages, they are rendered as is
+        FileType::MapperMsg => (decode_raw, &[], None),
+        FileType::CoverageObject => (
+            decode_proto::<CoverageObjectV1>,
+            &["coverage_object/pub_key", "coverage_object/key_type/*"],
+            Some(Seconds("coverage_object/coverage_claim_time")),
+        ),
+        FileType::CoverageObjectIngestReport => (
+            decode::<CoverageObjectIngestReport>,
+            &["report/pub_key", "report/key_type/*"],
+            Some(DateTime("received_timestamp")),
+        ),
+        FileType::SeniorityUpdate => (
+            decode_proto::<SeniorityUpdate>,
+            &["key_type/*"],
+            Some(Seconds("new_seniority_timestamp")),
+        ),
+        FileType::VerifiedSpeedtest => (
+            decode_proto::<VerifiedSpeedtest>,
+            &["report/report/pub_key"],
+            Some(Millis("timestamp")),
+        ),
+        FileType::WifiHeartbeat => (
+            decode::<WifiHeartbeat>,
+            &["pubkey"],
+            Some(DateTime("timestamp")),
+        ),
+        FileType::WifiHeartbeatIngestReport => (
+            decode::<WifiHeartbeatIngestReport>,
+            &["report/pubkey"],
+            Some(DateTime("received_timestamp")),
+        ),
+        FileType::BoostedHexUpdate => (
+            decode_proto::<BoostedHexUpdateV1>,
+            &[],
+            Some(Seconds("timestamp")),
+        ),
+        FileType::DeadLetter => (decode::<DeadLetter>, &[], Some(DateTime("timestamp"))),
+        // Snapshots are as of the timestamp of their file
+        FileType::IotGatewaySnapshot => (decode_proto::<GatewayInfo>, &["address"], None),
+        FileType::IotRegionParamsSnapshot => (decode::<IotRegionParams>, &[], None),
+        FileType::IotHexDensitySnapshot => (decode::<IotHexDensity>, &[], None),
+        FileType::IotSnapshotManifest => (decode::<IotSnapshotManifest>, &[], None),
+        FileType::IotWitnessAnomaly => (
+            decode::<IotWitnessAnomaly>,
+            &["gateways/*"],
+            Some(DateTime("timestamp")),
+        ),
+        FileType::IotVerificationRules => (decode::<IotPocRules>, &[], Some(DateTime("timestamp"))),
+    };
+    Entry {
+        decode,
+        keys,
+        timestamp,
+    }
+}
+
+/// Reads the timestamp of an encoded record without decoding it to json
+pub type RecordTimestamp = fn(&[u8]) -> Option<DateTime<Utc>>;
+
+/// How to read the timestamp of a record of the given file type, if its
+/// records have one. This is the timestamp [`Entry::timestamp`] reads from the
+/// json of a record
+pub fn record_timestamp(file_type: FileType) -> Option<RecordTimestamp> {
+    let timestamp: RecordTimestamp = match file_type {
+        FileType::CbrsHeartbeat => msg_timestamp::<CbrsHeartbeat>,
+        FileType::CellSpeedtest => msg_timestamp::<CellSpeedtest>,
+        FileType::Entropy | FileType::EntropyReport => msg_timestamp::<EntropyReport>,
+        FileType::CbrsHeartbeatIngestReport => msg_timestamp::<CbrsHeartbeatIngestReport>,
+        FileType::CellSpeedtestIngestReport => msg_timestamp::<CellSpeedtestIngestReport>,
+        FileType::IotBeaconIngestReport => msg_timestamp::<IotBeaconIngestReport>,
+        FileType::IotWitnessIngestReport => msg_timestamp::<IotWitnessIngestReport>,
+        FileType::IotPoc => |buf| LoraPocV1::decode(buf).ok()?.beacon_report?.timestamp().ok(),
+        FileType::IotInvalidBeaconReport => msg_timestamp::<IotInvalidBeaconReport>,
+        FileType::IotInvalidWitnessReport => msg_timestamp::<IotInvalidWitnessReport>,
+        FileType::SpeedtestAvg => |buf| {
+            SpeedtestAvg::decode(buf)
+                .ok()?
+                .timestamp
+                .to_timestamp()
+                .ok()
+        },
+        FileType::ValidatedHeartbeat => {
+            |buf| Heartbeat::decode(buf).ok()?.timestamp.to_timestamp().ok()
+        }
+        FileType::RadioRewardShare => |buf| {
+            RadioRewardShare::decode(buf)
+                .ok()?
+                .start_epoch
+                .to_timestamp()
+                .ok()
+        },
+        FileType::RewardManifest => |buf| {
+            RewardManifest::decode(buf)
+                .ok()?
+                .end_timestamp
+                .to_timestamp()
+                .ok()
+        },
+        FileType::IotPacketReport => msg_timestamp::<PacketRouterPacketReport>,
+        FileType::IotValidPacket => msg_timestamp::<IotValidPacket>,
+        FileType::NonRewardablePacket => |buf| {
+            NonRewardablePacket::decode(buf)
+                .ok()?
+                .timestamp
+                .to_timestamp_millis()
+                .ok()
+        },
+        FileType::IotRewardShare => |buf| {
+            IotRewardShare::decode(buf)
+                .ok()?
+                .start_period
+                .to_timestamp()
+                .ok()
+        },
+        FileType::DataTransferSessionIngestReport => {
+            msg_timestamp::<DataTransferSessionIngestReport>
+        }
+        FileType::InvalidDataTransferSessionIngestReport => {
+            msg_timestamp::<InvalidDataTransferIngestReport>
+        }
+        FileType::ValidDataTransferSession => |buf| {
+            packet_verifier::ValidDataTransferSession::decode(buf)
+                .ok()?
+                .first_timestamp
+                .to_timestamp_millis()
+                .ok()
+        },
+        FileType::PriceReport => |buf| {
+            PriceReportV1::decode(buf)
+                .ok()?
+                .timestamp
+                .to_timestamp()
+                .ok()
+        },
+        FileType::MobileRewardShare => |buf| {
+            MobileRewardShare::decode(buf)
+                .ok()?
+                .start_period
+                .to_timestamp()
+                .ok()
+        },
+        FileType::SubscriberLocationReq => msg_timestamp::<SubscriberLocationReq>,
+        FileType::SubscriberLocationIngestReport => msg_timestamp::<SubscriberLocationIngestReport>,
+        FileType::VerifiedSubscriberLocationIngestReport => {
+            msg_timestamp::<VerifiedSubscriberLocationIngestReport>
+        }
+        FileType::CoverageObject => |buf| {
+            CoverageObjectV1::decode(buf)
+                .ok()?
+                .coverage_object?
+                .coverage_claim_time
+                .to_timestamp()
+                .ok()
+        },
+        FileType::CoverageObjectIngestReport => |buf| {
+            CoverageObjectIngestReportV1::decode(buf)
+                .ok()?
+                .received_timestamp
+                .to_timestamp_millis()
+                .ok()
+        },
+        FileType::SeniorityUpdate => |buf| {
+            SeniorityUpdate::decode(buf)
+                .ok()?
+                .new_seniority_timestamp
+                .to_timestamp()
+                .ok()
+        },
+        FileType::VerifiedSpeedtest => |buf| {
+            VerifiedSpeedtest::decode(buf)
+                .ok()?
+                .timestamp
+                .to_timestamp_millis()
+                .ok()
+        },
+        FileType::WifiHeartbeat => msg_timestamp::<WifiHeartbeat>,
+        FileType::WifiHeartbeatIngestReport => msg_timestamp::<WifiHeartbeatIngestReport>,
+        FileType::BoostedHexUpdate => |buf| {
+            BoostedHexUpdateV1::decode(buf)
+                .ok()?
+                .timestamp
+                .to_timestamp()
+                .ok()
+        },
+        FileType::DeadLetter => msg_timestamp::<DeadLetter>,
+        FileType::IotWitnessAnomaly => msg_timestamp::<IotWitnessAnomaly>,
+        FileType::IotVerificationRules => msg_timestamp::<IotPocRules>,
+        FileType::SubnetworkRewards
+        | FileType::SignedPocReceiptTxn
+        | FileType::InvalidPacket
+        | FileType::MapperMsg
+        | FileType::IotGatewaySnapshot
+        | FileType::IotRegionParamsSnapshot
+        | FileType::IotHexDensitySnapshot
+        | FileType::IotSnapshotManifest => return None,
+    };
+    Some(timestamp)
+}
+
+fn msg_timestamp<T>(buf: &[u8]) -> Option<DateTime<Utc>>
+where
+    T: MsgDecode,
+    T::Msg: MsgTimestamp<Result<DateTime<Utc>>>,
+{
+    T::Msg::decode(buf).ok()?.timestamp().ok()
+}
+
+/// Reads the b58 public keys and cbsd ids of an encoded record without
+/// decoding it to json
+pub type RecordKeys = fn(&[u8]) -> Option<Vec<String>>;
+
+/// How to read the keys of a record of the given file type, for the file
+/// types that can be indexed while they are written. These are the keys
+/// [`Entry::keys`] reads from the json of a record
+pub fn record_keys(file_type: FileType) -> Option<RecordKeys> {
+    let keys: RecordKeys = match file_type {
+        FileType::CbrsHeartbeat => |buf| {
+            let req = CellHeartbeatReqV1::decode(buf).ok()?;
+            Some(vec![public_key(req.pub_key), req.cbsd_id])
+        },
+        FileType::CbrsHeartbeatIngestReport => |buf| {
+            let req = CellHeartbeatIngestReportV1::decode(buf).ok()?.report?;
+            Some(vec![public_key(req.pub_key), req.cbsd_id])
+        },
+        FileType::WifiHeartbeat => |buf| {
+            Some(vec![public_key(
+                WifiHeartbeatReqV1::decode(buf).ok()?.pub_key,
+            )])
+        },
+        FileType::WifiHeartbeatIngestReport => |buf| {
+            let req = WifiHeartbeatIngestReportV1::decode(buf).ok()?.report?;
+            Some(vec![public_key(req.pub_key)])
+        },
+        FileType::CellSpeedtest => {
+            |buf| Some(vec![public_key(SpeedtestReqV1::decode(buf).ok()?.pub_key)])
+        }
+        FileType::CellSpeedtestIngestReport => |buf| {
+            let req = SpeedtestIngestReportV1::decode(buf).ok()?.report?;
+            Some(vec![public_key(req.pub_key)])
+        },
+        FileType::IotBeaconIngestReport => |buf| {
+            let req = LoraBeaconIngestReportV1::decode(buf).ok()?.report?;
+            Some(vec![public_key(req.pub_key)])
+        },
+        FileType::IotWitnessIngestReport => |buf| {
+            let req = LoraWitnessIngestReportV1::decode(buf).ok()?.report?;
+            Some(vec![public_key(req.pub_key)])
+        },
+        FileType::IotPoc => |buf| {
+            let poc = LoraPocV1::decode(buf).ok()?;
+            let mut keys = vec![public_key(poc.beacon_report?.report?.pub_key)];
+            for witness in poc
+                .selected_witnesses
+                .into_iter()
+                .chain(poc.unselected_witnesses)
+            {
+                keys.push(public_key(witness.report?.pub_key));
+            }
+            Some(keys)
+        },
+        FileType::SpeedtestAvg => {
+            |buf| Some(vec![public_key(SpeedtestAvg::decode(buf).ok()?.pub_key)])
+        }
+        FileType::ValidatedHeartbeat => |buf| {
+            let heartbeat = Heartbeat::decode(buf).ok()?;
+            Some(vec![public_key(heartbeat.pub_key), heartbeat.cbsd_id])
+        },
+        FileType::DataTransferSessionIngestReport => |buf| {
+            let req = DataTransferSessionIngestReportV1::decode(buf)
+                .ok()?
+                .report?;
+            let usage = req.data_transfer_usage?;
+            Some(vec![
+                public_key(req.pub_key),
+                public_key(usage.pub_key),
+                public_key(usage.payer),
+            ])
+        },
+        FileType::SubscriberLocationIngestReport => |buf| {
+            let req = SubscriberLocationIngestReportV1::decode(buf).ok()?.report?;
+            Some(vec![public_key(req.carrier_pub_key)])
+        },
+        FileType::CoverageObjectIngestReport => |buf| {
+            let req = CoverageObjectIngestReportV1::decode(buf).ok()?.report?;
+            let key = match req.key_type? {
+                coverage_object_req_v1::KeyType::CbsdId(cbsd_id) => cbsd_id,
+                coverage_object_req_v1::KeyType::HotspotKey(key) => public_key(key),
+            };
+            Some(vec![public_key(req.pub_key), key])
+        },
+        FileType::Entropy
+        | FileType::EntropyReport
+        | FileType::SubnetworkRewards
+        | FileType::IotInvalidBeaconReport
+        | FileType::IotInvalidWitnessReport
+        | FileType::SignedPocReceiptTxn
+        | FileType::RadioRewardShare
+        | FileType::RewardManifest
+        | FileType::IotPacketReport
+        | FileType::IotValidPacket
+        | FileType::InvalidPacket
+        | FileType::NonRewardablePacket
+        | FileType::IotRewardShare
+        | FileType::InvalidDataTransferSessionIngestReport
+        | FileType::ValidDataTransferSession
+        | FileType::PriceReport
+        | FileType::MobileRewardShare
+        | FileType::SubscriberLocationReq
+        | FileType::VerifiedSubscriberLocationIngestReport
+        | FileType::MapperMsg
+        | FileType::CoverageObject
+        | FileType::SeniorityUpdate
+        | FileType::VerifiedSpeedtest
+        | FileType::BoostedHexUpdate
+        | FileType::DeadLetter
+        | FileType::IotGatewaySnapshot
+        | FileType::IotRegionParamsSnapshot
+        | FileType::IotHexDensitySnapshot
+        | FileType::IotSnapshotManifest
+        | FileType::IotWitnessAnomaly
+        | FileType::IotVerificationRules => return None,
+    };
+    Some(keys)
+}
+
+fn public_key(key: Vec<u8>) -> String {
+    PublicKeyBinary::from(key).to_string()
+}
+
+fn decode<T>(buf: &[u8]) -> Result<Value>
+where
+    T: MsgDecode + TryFrom<T::Msg, Error = Error> + Serialize,
+{
+    Ok(serde_json::to_value(T::decode(buf)?)?)
+}
+
+fn decode_proto<T>(buf: &[u8]) -> Result<Value>
+where
+    T: Message + Default + Serialize,
+{
+    let mut value = serde_json::to_value(T::decode(buf)?)?;
+    render_public_keys(None, &mut value);
+    Ok(value)
+}
+
+fn decode_raw(buf: &[u8]) -> Result<Value> {
+    Ok(json!({ "data": base64::engine::general_purpose::STANDARD.encode(buf) }))
+}
+
+/// Protos carry public keys as bytes, render those as b58 like the
+/// file_store types do
+fn render_public_keys(name: Option<&str>, value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (name, value) in map.iter_mut() {
+                render_public_keys(Some(name), value);
+            }
+        }
+        Value::Array(items) => {
+            if let Some(key) = name.filter(|name| is_key_field(name)).and_then(|_| {
+                let bytes = items
+                    .iter()
+                    .map(|v| v.as_u64().and_then(|v| u8::try_from(v).ok()))
+                    .collect::<Option<Vec<u8>>>()?;
+                (bytes.len() == PUBLIC_KEY_LENGTH).then(|| PublicKeyBinary::from(bytes))
+            }) {
+                *value = Value::String(key.to_string());
+            } else {
+                for item in items.iter_mut() {
+                    render_public_keys(None, item);
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+fn is_key_field(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with("key")
+        || matches!(name, "payer" | "account" | "gateway" | "owner" | "address")
+}
+
+fn pointer<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    value.pointer(&format!("/{path}"))
+}
+
+fn select(value: &Value, path: &str, f: &mut impl FnMut(&Value)) {
+    let (segment, rest) = match path.split_once('/') {
+        Some((segment, rest)) => (segment, Some(rest)),
+        None => (path, None),
+    };
+    let mut next = |value: &Value| match rest {
+        Some(rest) => select(value, rest, &mut *f),
+        None => f(value),
+    };
+    match (segment, value) {
+        ("*", Value::Array(items)) => items.iter().for_each(&mut next),
+        ("*", Value::Object(map)) => map.values().for_each(&mut next),
+        (segment, Value::Object(map)) => {
+            if let Some(value) = map.get(segment) {
+                next(value)
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_keys_of_records_without_json() {
+        let file_type = FileType::CbrsHeartbeatIngestReport;
+        let report = CellHeartbeatIngestReportV1 {
+            received_timestamp: 1_696_118_400_000,
+            report: Some(CellHeartbeatReqV1 {
+                pub_key: vec![1; PUBLIC_KEY_LENGTH],
+                cbsd_id: "cbsd".to_string(),
+                timestamp: 1_696_118_400,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let entry = entry(file_type);
+        let keys = record_keys(file_type).expect("record keys")(&report).expect("keys");
+        assert_eq!(entry.keys(&(entry.decode)(&report).expect("decode")), keys);
+        assert!(record_keys(FileType::EntropyReport).is_none());
+    }
+
+    #[test]
+    fn selects_keys_and_timestamps() {
+        let entry = entry(FileType::IotPoc);
+        let value = json!({
+            "beacon_report": {
+                "received_timestamp": "2023-10-01T00:00:00Z",
+                "report": { "pub_key": "beaconer" },
+            },
+            "selected_witnesses": [
+                { "report": { "pub_key": "witness1" } },
+                { "report": { "pub_key": "witness2" } },
+            ],
+            "unselected_witnesses": [],
+        });
+        assert_eq!(vec!["beaconer", "witness1", "witness2"], entry.keys(&value));
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2023, 10, 1, 0, 0, 0).unwrap()),
+            entry.timestamp(&value)
+        );
+
+        let entry = super::entry(FileType::MobileRewardShare);
+        let value = json!({
+            "start_period": 1696118400,
+            "reward": { "RadioReward": { "hotspot_key": "hotspot" } },
+        });
+        assert_eq!(vec!["hotspot"], entry.keys(&value));
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2023, 10, 1, 0, 0, 0).unwrap()),
+            entry.timestamp(&value)
+        );
+    }
+
+    #[test]
+    fn renders_proto_public_keys() {
+        let key: Vec<u8> = (0..PUBLIC_KEY_LENGTH as u8).collect();
+        let solana_key: Vec<u8> = (0..32).collect();
+        let mut value = json!({
+            "reward": { "GatewayReward": { "hotspot_key": key.clone() } },
+            "boosted_hex_pubkey": solana_key.clone(),
+            "payload_hash": key.clone(),
+        });
+        render_public_keys(None, &mut value);
+        assert_eq!(
+            json!({
+                "reward": { "GatewayReward": {
+                    "hotspot_key": PublicKeyBinary::from(key.clone()).to_string(),
+                } },
+                "boosted_hex_pubkey": solana_key,
+                "payload_hash": key,
+            }),
+            value
+        );
+    }
+}
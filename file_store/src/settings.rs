@@ -1,7 +1,7 @@
 use crate::{Error, Result};
 use config::{Config, File};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Settings {
@@ -16,6 +16,17 @@ pub struct Settings {
     /// Should only be used for local testing
     pub access_key_id: Option<String>,
     pub secret_access_key: Option<String>,
+
+    /// Optional local directory to use instead of S3. When set, files are
+    /// stored in `<local_path>/<bucket>` and the aws settings above are
+    /// ignored. Default none
+    pub local_path: Option<PathBuf>,
+
+    /// Verify files read by pollers against their integrity sidecars, when
+    /// they have one. This costs a request for the sidecar of every file.
+    /// Default false
+    #[serde(default)]
+    pub verify_integrity: bool,
 }
 
 pub fn default_region() -> String {
@@ -0,0 +1,304 @@
+use crate::Settings;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use config::{Config, File};
+use futures::future::LocalBoxFuture;
+use serde::Deserialize;
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use task_manager::ManagedTask;
+use tonic::{Request, Status};
+
+const TOKEN_REQUESTS_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "_token_requests");
+
+/// How often the tokens file is reloaded
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Name of the token configured with the legacy `token` setting
+const DEFAULT_TOKEN_NAME: &str = "default";
+
+/// Mobile ingest RPCs a token can be scoped to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    SubmitSpeedtest,
+    SubmitCellHeartbeat,
+    SubmitWifiHeartbeat,
+    SubmitDataTransferSession,
+    SubmitSubscriberLocation,
+    SubmitCoverageObject,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SubmitSpeedtest => "submit_speedtest",
+            Self::SubmitCellHeartbeat => "submit_cell_heartbeat",
+            Self::SubmitWifiHeartbeat => "submit_wifi_heartbeat",
+            Self::SubmitDataTransferSession => "submit_data_transfer_session",
+            Self::SubmitSubscriberLocation => "submit_subscriber_location",
+            Self::SubmitCoverageObject => "submit_coverage_object",
+        }
+    }
+}
+
+/// A named API token, sent as a Bearer authorization header
+#[derive(Clone, Deserialize)]
+pub struct ApiToken {
+    /// Name of the token, recorded in metrics
+    pub name: String,
+    pub token: String,
+    /// RPCs the token is allowed to call. All RPCs if empty
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
+    /// Optional start of the validity of the token
+    #[serde(default)]
+    pub not_before: Option<DateTime<Utc>>,
+    /// Optional end of the validity of the token
+    #[serde(default)]
+    pub not_after: Option<DateTime<Utc>>,
+}
+
+// Settings are logged, so the secret is left out
+impl fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiToken")
+            .field("name", &self.name)
+            .field("token", &"<redacted>")
+            .field("scopes", &self.scopes)
+            .field("not_before", &self.not_before)
+            .field("not_after", &self.not_after)
+            .finish()
+    }
+}
+
+impl ApiToken {
+    fn is_valid_at(&self, now: DateTime<Utc>) -> bool {
+        self.not_before.map_or(true, |not_before| not_before <= now)
+            && self.not_after.map_or(true, |not_after| now < not_after)
+    }
+
+    fn allows(&self, scope: Scope) -> bool {
+        self.scopes.is_empty() || self.scopes.contains(&scope)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokensFile {
+    tokens: Vec<ApiToken>,
+}
+
+/// The API tokens accepted by the mobile ingest server, made up of the
+/// tokens in the settings and those in the optional tokens file
+#[derive(Debug, Clone)]
+pub struct ApiTokens {
+    settings_tokens: Vec<ApiToken>,
+    file: Option<PathBuf>,
+    tokens: Arc<RwLock<Vec<ApiToken>>>,
+}
+
+impl ApiTokens {
+    pub fn new(tokens: Vec<ApiToken>) -> Self {
+        Self {
+            settings_tokens: tokens.clone(),
+            file: None,
+            tokens: Arc::new(RwLock::new(tokens)),
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Result<Self> {
+        let mut tokens = settings.tokens.clone();
+        if let Some(token) = &settings.token {
+            tokens.push(ApiToken {
+                name: DEFAULT_TOKEN_NAME.to_string(),
+                token: token.clone(),
+                scopes: vec![],
+                not_before: None,
+                not_after: None,
+            });
+        }
+        let api_tokens = Self {
+            file: settings.tokens_file.clone(),
+            ..Self::new(tokens)
+        };
+        api_tokens.reload()?;
+        if api_tokens.tokens.read().expect("tokens lock").is_empty() {
+            anyhow::bail!("expected an api token in settings or the tokens file");
+        }
+        Ok(api_tokens)
+    }
+
+    /// Reload the tokens file, if any
+    pub fn reload(&self) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let file_tokens = Config::builder()
+            .add_source(File::from(file.as_path()))
+            .build()
+            .and_then(|config| config.try_deserialize::<TokensFile>())?
+            .tokens;
+        let mut tokens = self.settings_tokens.clone();
+        tokens.extend(file_tokens);
+        *self.tokens.write().expect("tokens lock") = tokens;
+        Ok(())
+    }
+
+    /// Interceptor that authenticates a request by its Bearer token and
+    /// records the matching token in the request extensions for
+    /// [`authorize`]
+    pub fn authenticate<T>(&self, mut req: Request<T>) -> Result<Request<T>, Status> {
+        let token = req
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|value| {
+                let now = Utc::now();
+                self.tokens
+                    .read()
+                    .expect("tokens lock")
+                    .iter()
+                    .find(|token| token.token == value && token.is_valid_at(now))
+                    .cloned()
+            })
+            .ok_or_else(|| Status::unauthenticated("No valid auth token"))?;
+        req.extensions_mut().insert(token);
+        Ok(req)
+    }
+}
+
+/// Check that the token a request was authenticated with is scoped to the
+/// given RPC
+pub fn authorize<T>(request: &Request<T>, scope: Scope) -> Result<(), Status> {
+    let token = request
+        .extensions()
+        .get::<ApiToken>()
+        .ok_or_else(|| Status::unauthenticated("No valid auth token"))?;
+    let allowed = token.allows(scope);
+    metrics::increment_counter!(
+        TOKEN_REQUESTS_METRIC,
+        "token" => token.name.clone(),
+        "rpc" => scope.as_str(),
+        "status" => if allowed { "ok" } else { "denied" }
+    );
+    if allowed {
+        Ok(())
+    } else {
+        Err(Status::permission_denied(format!(
+            "token not allowed to {}",
+            scope.as_str()
+        )))
+    }
+}
+
+impl ManagedTask for ApiTokens {
+    fn start_task(
+        self: Box<Self>,
+        shutdown: triggered::Listener,
+    ) -> LocalBoxFuture<'static, anyhow::Result<()>> {
+        Box::pin(async move {
+            if self.file.is_none() {
+                return Ok(());
+            }
+            let mut reload_timer = tokio::time::interval(RELOAD_INTERVAL);
+            reload_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = shutdown.clone() => return Ok(()),
+                    _ = reload_timer.tick() => {
+                        // Keep the current tokens if the file is broken
+                        if let Err(err) = self.reload() {
+                            tracing::error!(?err, "failed to reload api tokens");
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use tonic::Code;
+
+    fn token(name: &str, scopes: Vec<Scope>) -> ApiToken {
+        ApiToken {
+            name: name.to_string(),
+            token: format!("{name}-secret"),
+            scopes,
+            not_before: None,
+            not_after: None,
+        }
+    }
+
+    fn request(token: &str) -> Request<()> {
+        let mut req = Request::new(());
+        req.metadata_mut().insert(
+            "authorization",
+            format!("Bearer {token}").parse().expect("metadata value"),
+        );
+        req
+    }
+
+    #[test]
+    fn redacts_the_secret_when_debugged() {
+        let debug = format!("{:?}", token("ops", vec![]));
+        assert!(debug.contains("ops"));
+        assert!(!debug.contains("ops-secret"));
+    }
+
+    #[test]
+    fn authorizes_tokens_by_scope() {
+        let tokens = ApiTokens::new(vec![
+            token("carrier", vec![]),
+            token("router", vec![Scope::SubmitDataTransferSession]),
+        ]);
+
+        let req = tokens
+            .authenticate(request("router-secret"))
+            .expect("router");
+        assert!(authorize(&req, Scope::SubmitDataTransferSession).is_ok());
+        let err = authorize(&req, Scope::SubmitSpeedtest).expect_err("denied");
+        assert_eq!(Code::PermissionDenied, err.code());
+
+        let req = tokens
+            .authenticate(request("carrier-secret"))
+            .expect("carrier");
+        assert!(authorize(&req, Scope::SubmitSpeedtest).is_ok());
+
+        let err = tokens
+            .authenticate(request("unknown"))
+            .expect_err("unknown");
+        assert_eq!(Code::Unauthenticated, err.code());
+    }
+
+    #[test]
+    fn only_accepts_tokens_in_their_validity_window() {
+        let now = Utc::now();
+        let expired = ApiToken {
+            not_after: Some(now - Duration::hours(1)),
+            ..token("expired", vec![])
+        };
+        let upcoming = ApiToken {
+            not_before: Some(now + Duration::hours(1)),
+            ..token("upcoming", vec![])
+        };
+        let current = ApiToken {
+            not_before: Some(now - Duration::hours(1)),
+            not_after: Some(now + Duration::hours(1)),
+            ..token("current", vec![])
+        };
+        let tokens = ApiTokens::new(vec![expired, upcoming, current]);
+
+        assert!(tokens.authenticate(request("expired-secret")).is_err());
+        assert!(tokens.authenticate(request("upcoming-secret")).is_err());
+        assert!(tokens.authenticate(request("current-secret")).is_ok());
+    }
+}
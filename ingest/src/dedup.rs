@@ -0,0 +1,199 @@
+use crate::settings::DedupSettings;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const DUPLICATE_REPORTS_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "_duplicate_reports");
+
+/// Hash of a signed request, which includes its signature
+pub type DedupKey = [u8; 32];
+
+pub fn dedup_key<T: prost::Message>(request: &T) -> DedupKey {
+    Sha256::digest(request.encode_to_vec()).into()
+}
+
+/// Bounded cache of the ids accepted requests of one request type were
+/// answered with, used to answer resubmitted requests with the original id
+/// instead of writing them again.
+#[derive(Debug)]
+pub struct DedupCache {
+    report_type: &'static str,
+    window: Duration,
+    max_entries: usize,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Id of each request, and the sequence number of its insertion
+    ids: HashMap<DedupKey, (u64, u64)>,
+    inserted: VecDeque<(Instant, u64, DedupKey)>,
+    sequence: u64,
+}
+
+impl Entries {
+    /// Drop entries older than the window, and the oldest entries beyond
+    /// the given number of entries. An insertion only evicts the id it
+    /// inserted, not one inserted for the same request after it
+    fn evict(&mut self, now: Instant, window: Duration, max_entries: usize) {
+        while let Some((inserted, sequence, key)) = self.inserted.front() {
+            if now.saturating_duration_since(*inserted) < window
+                && self.inserted.len() <= max_entries
+            {
+                break;
+            }
+            if matches!(self.ids.get(key), Some((_, current)) if current == sequence) {
+                self.ids.remove(key);
+            }
+            self.inserted.pop_front();
+        }
+    }
+}
+
+impl DedupCache {
+    pub fn new(report_type: &'static str, settings: &DedupSettings) -> Self {
+        Self {
+            report_type,
+            window: settings.window(),
+            max_entries: settings.max_entries,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// The id the original of a duplicate request was answered with
+    pub fn get(&self, key: &DedupKey) -> Option<u64> {
+        self.get_at(key, Instant::now())
+    }
+
+    /// Remember the id an accepted request is answered with, unless the
+    /// request is already known, in which case the id of the original is
+    /// returned instead
+    pub fn insert(&self, key: DedupKey, id: u64) -> Option<u64> {
+        self.insert_at(key, id, Instant::now())
+    }
+
+    /// Forget a request inserted with the given id, for when it could not be
+    /// written after all
+    pub fn remove(&self, key: &DedupKey, id: u64) {
+        let mut entries = self.entries.lock().expect("dedup cache lock");
+        let Some(&(current, sequence)) = entries.ids.get(key) else {
+            return;
+        };
+        if current != id {
+            return;
+        }
+        entries.ids.remove(key);
+        // The insertion was most likely the last one
+        if let Some(index) = entries
+            .inserted
+            .iter()
+            .rposition(|(_, inserted, _)| *inserted == sequence)
+        {
+            entries.inserted.remove(index);
+        }
+    }
+
+    fn get_at(&self, key: &DedupKey, now: Instant) -> Option<u64> {
+        let mut entries = self.entries.lock().expect("dedup cache lock");
+        entries.evict(now, self.window, self.max_entries);
+        let id = entries.ids.get(key).map(|(id, _)| *id);
+        if id.is_some() {
+            metrics::increment_counter!(DUPLICATE_REPORTS_METRIC, "type" => self.report_type);
+        }
+        id
+    }
+
+    fn insert_at(&self, key: DedupKey, id: u64, now: Instant) -> Option<u64> {
+        let mut entries = self.entries.lock().expect("dedup cache lock");
+        entries.evict(now, self.window, self.max_entries);
+        if let Some((original, _)) = entries.ids.get(&key) {
+            metrics::increment_counter!(DUPLICATE_REPORTS_METRIC, "type" => self.report_type);
+            return Some(*original);
+        }
+        let sequence = entries.sequence;
+        entries.sequence += 1;
+        entries.ids.insert(key, (id, sequence));
+        entries.inserted.push_back((now, sequence, key));
+        entries.evict(now, self.window, self.max_entries);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(window: u64, max_entries: usize) -> DedupCache {
+        DedupCache::new(
+            "test",
+            &DedupSettings {
+                window,
+                max_entries,
+            },
+        )
+    }
+
+    #[test]
+    fn answers_duplicates_within_window_with_original_id() {
+        let cache = cache(60, 10);
+        let now = Instant::now();
+        let key = [1; 32];
+
+        assert_eq!(None, cache.get_at(&key, now));
+        cache.insert_at(key, 42, now);
+        assert_eq!(Some(42), cache.get_at(&key, now + Duration::from_secs(59)));
+        assert_eq!(None, cache.get_at(&key, now + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn evicts_oldest_entries_beyond_max_entries() {
+        let cache = cache(60, 2);
+        let now = Instant::now();
+
+        cache.insert_at([1; 32], 1, now);
+        cache.insert_at([2; 32], 2, now);
+        cache.insert_at([3; 32], 3, now);
+
+        assert_eq!(None, cache.get_at(&[1; 32], now));
+        assert_eq!(Some(2), cache.get_at(&[2; 32], now));
+        assert_eq!(Some(3), cache.get_at(&[3; 32], now));
+    }
+
+    #[test]
+    fn answers_concurrent_duplicates_with_first_id() {
+        let cache = cache(60, 10);
+        let now = Instant::now();
+        let key = [1; 32];
+
+        assert_eq!(None, cache.insert_at(key, 1, now));
+        assert_eq!(Some(1), cache.insert_at(key, 2, now));
+        assert_eq!(Some(1), cache.get_at(&key, now));
+
+        let entries = cache.entries.lock().unwrap();
+        assert_eq!(1, entries.inserted.len());
+    }
+
+    #[test]
+    fn removed_insertions_do_not_count_toward_max_entries() {
+        let cache = cache(60, 2);
+        let now = Instant::now();
+
+        cache.insert_at([1; 32], 1, now);
+        cache.insert_at([2; 32], 2, now);
+        cache.remove(&[2; 32], 2);
+        cache.insert_at([2; 32], 3, now);
+        // a stale id does not remove the newer insertion
+        cache.remove(&[2; 32], 2);
+        assert_eq!(Some(3), cache.get_at(&[2; 32], now));
+        cache.remove(&[2; 32], 3);
+        cache.insert_at([3; 32], 4, now);
+
+        assert_eq!(Some(1), cache.get_at(&[1; 32], now));
+        assert_eq!(None, cache.get_at(&[2; 32], now));
+        assert_eq!(Some(4), cache.get_at(&[3; 32], now));
+        assert_eq!(2, cache.entries.lock().unwrap().inserted.len());
+    }
+}
@@ -0,0 +1,272 @@
+//! HTTP gateway in front of the ingest gRPC services, for clients that cannot
+//! speak gRPC.
+//!
+//! Every unary RPC is exposed as `POST /v1/<service>/<rpc>`, for example
+//! `/v1/poc_mobile/submit_speedtest` or `/v1/poc_lora/submit_lora_beacon`.
+//! Bodies are the same signed request messages, either protobuf encoded
+//! (`application/x-protobuf`) or in their serde JSON encoding
+//! (`application/json`), and are answered in the encoding of the request.
+//! Requests are handed to the gRPC service implementation itself, so they
+//! are verified, authorized, written and counted exactly like gRPC requests.
+
+use anyhow::Error;
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use futures::{
+    future::{LocalBoxFuture, TryFutureExt},
+    Future,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::net::SocketAddr;
+use task_manager::ManagedTask;
+use tonic::{Code, Request, Status};
+
+const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// HTTP server of the gateway routes of a gRPC service. Finishes right away
+/// if no listen address is configured
+pub struct HttpGateway {
+    address: Option<SocketAddr>,
+    router: Router,
+}
+
+impl HttpGateway {
+    pub fn new(address: Option<SocketAddr>, router: Router) -> Self {
+        Self { address, router }
+    }
+}
+
+impl ManagedTask for HttpGateway {
+    fn start_task(
+        self: Box<Self>,
+        shutdown: triggered::Listener,
+    ) -> LocalBoxFuture<'static, anyhow::Result<()>> {
+        Box::pin(async move {
+            let Some(address) = self.address else {
+                return Ok(());
+            };
+            tracing::info!("http gateway listening on {address}");
+            let listener = tokio::net::TcpListener::bind(address).await?;
+            let router = self
+                .router
+                .layer(poc_metrics::request_layer!("ingest_server_http_connection"));
+            axum::serve(listener, router)
+                .with_graceful_shutdown(shutdown)
+                .map_err(Error::from)
+                .await
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Protobuf,
+    Json,
+}
+
+impl Encoding {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+        match content_type.split(';').next()?.trim() {
+            PROTOBUF_CONTENT_TYPE | "application/protobuf" => Some(Self::Protobuf),
+            JSON_CONTENT_TYPE => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    fn decode<T>(self, body: &[u8]) -> Result<T, Status>
+    where
+        T: prost::Message + Default + DeserializeOwned,
+    {
+        match self {
+            Self::Protobuf => T::decode(body)
+                .map_err(|err| Status::invalid_argument(format!("invalid protobuf body: {err}"))),
+            Self::Json => serde_json::from_slice(body)
+                .map_err(|err| Status::invalid_argument(format!("invalid json body: {err}"))),
+        }
+    }
+
+    fn encode<T>(self, message: &T) -> Response
+    where
+        T: prost::Message + Serialize,
+    {
+        match self {
+            Self::Protobuf => (
+                [(header::CONTENT_TYPE, PROTOBUF_CONTENT_TYPE)],
+                message.encode_to_vec(),
+            )
+                .into_response(),
+            Self::Json => Json(message).into_response(),
+        }
+    }
+}
+
+/// Answer an HTTP request with the given gRPC handler. The authorization
+/// header of the request is passed on as gRPC metadata.
+pub async fn handle<Req, Resp, F, Fut>(headers: HeaderMap, body: Bytes, handler: F) -> Response
+where
+    Req: prost::Message + Default + DeserializeOwned,
+    Resp: prost::Message + Serialize,
+    F: FnOnce(Request<Req>) -> Fut,
+    Fut: Future<Output = Result<tonic::Response<Resp>, Status>>,
+{
+    let Some(encoding) = Encoding::from_headers(&headers) else {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("expected {PROTOBUF_CONTENT_TYPE} or {JSON_CONTENT_TYPE} body"),
+        )
+            .into_response();
+    };
+    let request = match encoding.decode(&body).and_then(|message| {
+        let mut request = Request::new(message);
+        if let Some(authorization) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+        {
+            let value = authorization
+                .parse()
+                .map_err(|_| Status::unauthenticated("invalid authorization header"))?;
+            request.metadata_mut().insert("authorization", value);
+        }
+        Ok(request)
+    }) {
+        Ok(request) => request,
+        Err(status) => return status_response(status),
+    };
+    match handler(request).await {
+        Ok(response) => encoding.encode(response.get_ref()),
+        Err(status) => status_response(status),
+    }
+}
+
+fn status_response(status: Status) -> Response {
+    let body = Json(serde_json::json!({
+        "code": status.code() as i32,
+        "message": status.message(),
+    }));
+    (http_status(status.code()), body).into_response()
+}
+
+fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::Cancelled | Code::Unknown | Code::Internal | Code::DataLoss => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helium_proto::services::poc_lora::{LoraBeaconReportReqV1, LoraBeaconReportRespV1};
+    use prost::Message;
+
+    fn headers(content_type: &str, authorization: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+        if let Some(authorization) = authorization {
+            headers.insert(header::AUTHORIZATION, authorization.parse().unwrap());
+        }
+        headers
+    }
+
+    fn beacon() -> LoraBeaconReportReqV1 {
+        LoraBeaconReportReqV1 {
+            pub_key: vec![1, 2, 3],
+            frequency: 868_100_000,
+            signature: vec![4, 5, 6],
+            ..Default::default()
+        }
+    }
+
+    async fn echo(
+        request: Request<LoraBeaconReportReqV1>,
+    ) -> Result<tonic::Response<LoraBeaconReportRespV1>, Status> {
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let beacon = request.into_inner();
+        if beacon.signature.is_empty() {
+            return Err(Status::invalid_argument("invalid signature"));
+        }
+        Ok(tonic::Response::new(LoraBeaconReportRespV1 {
+            id: format!("{}:{authorization}", beacon.frequency),
+        }))
+    }
+
+    async fn body(response: Response) -> Bytes {
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("response body")
+    }
+
+    #[tokio::test]
+    async fn answers_in_the_encoding_of_the_request() {
+        let response = handle(
+            headers(PROTOBUF_CONTENT_TYPE, Some("Bearer secret")),
+            beacon().encode_to_vec().into(),
+            echo,
+        )
+        .await;
+        assert_eq!(StatusCode::OK, response.status());
+        let resp = LoraBeaconReportRespV1::decode(body(response).await).expect("protobuf");
+        assert_eq!("868100000:Bearer secret", resp.id);
+
+        let response = handle(
+            headers("application/json; charset=utf-8", None),
+            serde_json::to_vec(&beacon()).unwrap().into(),
+            echo,
+        )
+        .await;
+        assert_eq!(StatusCode::OK, response.status());
+        let resp: serde_json::Value = serde_json::from_slice(&body(response).await).expect("json");
+        assert_eq!("868100000:", resp["id"]);
+    }
+
+    #[tokio::test]
+    async fn maps_rejections_to_http_statuses() {
+        let unsigned = LoraBeaconReportReqV1 {
+            signature: vec![],
+            ..beacon()
+        };
+        let response = handle(
+            headers(PROTOBUF_CONTENT_TYPE, None),
+            unsigned.encode_to_vec().into(),
+            echo,
+        )
+        .await;
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+        let response = handle(headers("text/plain", None), Bytes::new(), echo).await;
+        assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, response.status());
+
+        assert_eq!(
+            StatusCode::TOO_MANY_REQUESTS,
+            http_status(Code::ResourceExhausted)
+        );
+        assert_eq!(
+            StatusCode::SERVICE_UNAVAILABLE,
+            http_status(Code::Unavailable)
+        );
+    }
+}
@@ -1,5 +1,10 @@
+pub mod api_token;
+pub mod dedup;
+pub mod http_gateway;
+pub mod rate_limit;
+pub mod report_sink;
 pub mod server_iot;
 pub mod server_mobile;
 pub mod settings;
 
-pub use settings::{Mode, Settings};
+pub use settings::{AckPolicy, Mode, Settings};
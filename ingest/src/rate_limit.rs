@@ -0,0 +1,166 @@
+use crate::settings::RateLimit;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tonic::Status;
+
+const THROTTLED_REPORTS_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "_throttled_reports");
+const THROTTLED_KEYS_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "_throttled_keys");
+
+/// How often buckets that have filled up again are dropped
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Token bucket rate limiter of the reports of one request type, keyed by
+/// public key or cbsd id.
+#[derive(Debug)]
+pub struct RateLimiter {
+    report_type: &'static str,
+    limit: RateLimit,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    keys: HashMap<String, Bucket>,
+    pruned: Instant,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    throttled: bool,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.rate).min(limit.burst as f64);
+        self.updated = now;
+    }
+
+    fn is_full(&self, limit: &RateLimit, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * limit.rate >= limit.burst as f64
+    }
+}
+
+impl RateLimiter {
+    pub fn new(report_type: &'static str, limit: RateLimit) -> Self {
+        Self {
+            report_type,
+            limit,
+            buckets: Mutex::new(Buckets {
+                keys: HashMap::new(),
+                pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token for a report from the bucket of every given key. The
+    /// report is rejected, and no tokens are taken, if any of the buckets is
+    /// empty.
+    pub fn check(&self, keys: &[String]) -> Result<(), Status> {
+        self.check_at(keys, Instant::now())
+    }
+
+    fn check_at(&self, keys: &[String], now: Instant) -> Result<(), Status> {
+        let mut buckets = self.buckets.lock().expect("rate limiter lock");
+        if now.saturating_duration_since(buckets.pruned) >= PRUNE_INTERVAL {
+            buckets
+                .keys
+                .retain(|_, bucket| !bucket.is_full(&self.limit, now));
+            buckets.pruned = now;
+        }
+
+        let mut throttled = Vec::new();
+        for key in keys {
+            let bucket = buckets.keys.entry(key.clone()).or_insert_with(|| Bucket {
+                tokens: self.limit.burst as f64,
+                updated: now,
+                throttled: false,
+            });
+            bucket.refill(&self.limit, now);
+            if bucket.tokens < 1.0 {
+                throttled.push(key);
+            }
+        }
+
+        if throttled.is_empty() {
+            for bucket in keys.iter().filter_map(|key| buckets.keys.get_mut(key)) {
+                bucket.tokens -= 1.0;
+                bucket.throttled = false;
+            }
+            return Ok(());
+        }
+
+        metrics::increment_counter!(THROTTLED_REPORTS_METRIC, "type" => self.report_type);
+        for key in throttled {
+            let Some(bucket) = buckets.keys.get_mut(key) else {
+                continue;
+            };
+            // Only count keys as they start getting throttled
+            if !bucket.throttled {
+                bucket.throttled = true;
+                metrics::increment_counter!(THROTTLED_KEYS_METRIC, "type" => self.report_type);
+                tracing::warn!(report_type = self.report_type, key, "throttling reports");
+            }
+        }
+        Err(Status::resource_exhausted("rate limit exceeded"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    #[test]
+    fn throttles_keys_that_exceed_their_limit() {
+        let limiter = RateLimiter::new(
+            "test",
+            RateLimit {
+                rate: 1.0,
+                burst: 2,
+            },
+        );
+        let now = Instant::now();
+        let key1 = vec!["key1".to_string()];
+        let key2 = vec!["key2".to_string()];
+
+        assert!(limiter.check_at(&key1, now).is_ok());
+        assert!(limiter.check_at(&key1, now).is_ok());
+        let err = limiter.check_at(&key1, now).expect_err("throttled");
+        assert_eq!(Code::ResourceExhausted, err.code());
+
+        // Other keys have their own bucket
+        assert!(limiter.check_at(&key2, now).is_ok());
+
+        // Tokens refill at the configured rate
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.check_at(&key1, later).is_ok());
+        assert!(limiter.check_at(&key1, later).is_err());
+    }
+
+    #[test]
+    fn takes_no_tokens_when_any_key_is_throttled() {
+        let limiter = RateLimiter::new(
+            "test",
+            RateLimit {
+                rate: 1.0,
+                burst: 1,
+            },
+        );
+        let now = Instant::now();
+        let pub_key = "pub_key".to_string();
+        let cbsd_id = "cbsd_id".to_string();
+
+        assert!(limiter.check_at(&[pub_key.clone()], now).is_ok());
+        assert!(limiter
+            .check_at(&[pub_key.clone(), cbsd_id.clone()], now)
+            .is_err());
+        assert!(limiter.check_at(&[cbsd_id], now).is_ok());
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{
+    dedup::{DedupCache, DedupKey},
+    rate_limit::RateLimiter,
+    settings::{DedupSettings, RateLimit},
+    AckPolicy,
+};
+use file_store::file_sink::{FileSinkClient, SEND_TIMEOUT};
+use std::{sync::Arc, time::Duration};
+use tonic::Status;
+
+const REPORTS_METRIC: &str = concat!(env!("CARGO_PKG_NAME"), "_reports");
+
+/// A file sink for submitted reports that surfaces sink failures to the
+/// submitter as a retryable status instead of dropping the report.
+#[derive(Debug, Clone)]
+pub struct ReportSink {
+    client: FileSinkClient,
+    report_type: &'static str,
+    ack: AckPolicy,
+    send_timeout: Duration,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    dedup: Option<Arc<DedupCache>>,
+}
+
+impl ReportSink {
+    pub fn new(client: FileSinkClient, report_type: &'static str) -> Self {
+        Self {
+            client,
+            report_type,
+            ack: AckPolicy::default(),
+            send_timeout: SEND_TIMEOUT,
+            rate_limiter: None,
+            dedup: None,
+        }
+    }
+
+    pub fn ack(self, ack: AckPolicy) -> Self {
+        Self { ack, ..self }
+    }
+
+    /// Time to wait for room in a full sink queue before rejecting a report
+    pub fn send_timeout(self, send_timeout: Duration) -> Self {
+        Self {
+            send_timeout,
+            ..self
+        }
+    }
+
+    /// Limit the reports a single key can submit. Unlimited by default
+    pub fn rate_limit(self, rate_limit: Option<RateLimit>) -> Self {
+        Self {
+            rate_limiter: rate_limit
+                .map(|limit| Arc::new(RateLimiter::new(self.report_type, limit))),
+            ..self
+        }
+    }
+
+    /// Check the rate limit of the keys, public keys and cbsd ids, of a
+    /// verified report
+    pub fn check_rate_limit(&self, keys: &[String]) -> Result<(), Status> {
+        self.rate_limiter
+            .as_ref()
+            .map_or(Ok(()), |limiter| limiter.check(keys))
+    }
+
+    /// Answer resubmitted reports with the id of the original instead of
+    /// writing them again. Disabled by default
+    pub fn dedup(self, settings: Option<&DedupSettings>) -> Self {
+        Self {
+            dedup: settings.map(|settings| Arc::new(DedupCache::new(self.report_type, settings))),
+            ..self
+        }
+    }
+
+    /// The id the original of a resubmitted request was answered with
+    pub fn duplicate_id(&self, key: &DedupKey) -> Option<u64> {
+        self.dedup.as_ref().and_then(|dedup| dedup.get(key))
+    }
+
+    /// Write the report for a request that is answered with the given id,
+    /// and remember the request to answer resubmissions of it. Returns the
+    /// id the request is answered with, which is the id of the original if
+    /// the same request was accepted concurrently
+    pub async fn write_once<T: prost::Message>(
+        &self,
+        report: T,
+        key: DedupKey,
+        id: u64,
+    ) -> Result<u64, Status> {
+        let Some(dedup) = &self.dedup else {
+            return self.write(report).await.map(|_| id);
+        };
+        if let Some(original) = dedup.insert(key, id) {
+            return Ok(original);
+        }
+        self.write(report).await.map(|_| id).map_err(|status| {
+            dedup.remove(&key, id);
+            status
+        })
+    }
+
+    /// Write a report, returning once it is acknowledged according to the
+    /// ack policy of the sink
+    pub async fn write<T: prost::Message>(&self, report: T) -> Result<(), Status> {
+        let result = self.submit(report).await;
+        let status = if result.is_ok() {
+            "accepted"
+        } else {
+            "rejected"
+        };
+        metrics::increment_counter!(REPORTS_METRIC, "type" => self.report_type, "status" => status);
+        result
+    }
+
+    async fn submit<T: prost::Message>(&self, report: T) -> Result<(), Status> {
+        let on_ack = match self.ack {
+            AckPolicy::Committed => {
+                self.client
+                    .write_committed(report, [], self.send_timeout)
+                    .await
+            }
+            AckPolicy::Queued | AckPolicy::Written => {
+                self.client
+                    .write_with_timeout(report, [], self.send_timeout)
+                    .await
+            }
+        }
+        .map_err(|err| self.unavailable(err))?;
+
+        if self.ack == AckPolicy::Queued {
+            return Ok(());
+        }
+        on_ack
+            .await
+            .map_err(|_| self.unavailable(file_store::Error::channel()))?
+            .map_err(|err| self.unavailable(err))
+    }
+
+    fn unavailable(&self, err: file_store::Error) -> Status {
+        tracing::warn!(report_type = self.report_type, ?err, "rejecting report");
+        match err {
+            file_store::Error::SendTimeout => Status::unavailable("report sink busy"),
+            _ => Status::unavailable("report sink unavailable"),
+        }
+    }
+}
@@ -1,17 +1,18 @@
-use crate::Settings;
+use crate::{
+    dedup::dedup_key,
+    http_gateway::{self, HttpGateway},
+    report_sink::ReportSink,
+    Settings,
+};
 use anyhow::{Error, Result};
+use axum::{body::Bytes, extract::State, http::HeaderMap, routing::post, Router};
 use chrono::{Duration, Utc};
-use file_store::{
-    file_sink::{self, FileSinkClient},
-    file_upload,
-    traits::MsgVerify,
-    FileType,
-};
+use file_store::{file_sink, file_upload, traits::MsgVerify, FileType};
 use futures::{
     future::{LocalBoxFuture, TryFutureExt},
     Stream, StreamExt,
 };
-use helium_crypto::{Network, PublicKey};
+use helium_crypto::{Network, PublicKey, PublicKeyBinary};
 use helium_proto::services::poc_lora::{
     self, lora_stream_request_v1::Request as StreamRequest,
     lora_stream_response_v1::Response as StreamResponse, LoraBeaconIngestReportV1,
@@ -23,7 +24,7 @@ use std::{convert::TryFrom, net::SocketAddr, path::Path};
 use task_manager::{ManagedTask, TaskManager};
 use tokio::{sync::mpsc::Sender, time::Instant};
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::{transport, Request, Response, Status, Streaming};
+use tonic::{transport, Code, Request, Response, Status, Streaming};
 
 pub type GrpcResult<T> = std::result::Result<Response<T>, Status>;
 pub type GrpcStreamResult<T> = ReceiverStream<Result<T, Status>>;
@@ -33,8 +34,8 @@ type Nonce = [u8; 32];
 
 #[derive(Debug)]
 struct StreamState {
-    beacon_report_sink: FileSinkClient,
-    witness_report_sink: FileSinkClient,
+    beacon_report_sink: ReportSink,
+    witness_report_sink: ReportSink,
     required_network: Network,
     pub_key_bytes: Option<Vec<u8>>,
     session_key: Option<PublicKey>,
@@ -97,26 +98,24 @@ impl StreamState {
     async fn handle_message(&mut self, message: LoraStreamRequestV1) -> Result<(), Status> {
         let timestamp = Utc::now().timestamp_millis() as u64;
         match message.request {
-            Some(StreamRequest::BeaconReport(report)) => {
-                handle_beacon_report(
-                    &self.beacon_report_sink,
-                    timestamp,
-                    report,
-                    self.session_key.as_ref(),
-                    self.pub_key_bytes.as_deref(),
-                )
-                .await
-            }
-            Some(StreamRequest::WitnessReport(report)) => {
-                handle_witness_report(
-                    &self.witness_report_sink,
-                    timestamp,
-                    report,
-                    self.session_key.as_ref(),
-                    self.pub_key_bytes.as_deref(),
-                )
-                .await
-            }
+            Some(StreamRequest::BeaconReport(report)) => handle_beacon_report(
+                &self.beacon_report_sink,
+                timestamp,
+                report,
+                self.session_key.as_ref(),
+                self.pub_key_bytes.as_deref(),
+            )
+            .await
+            .map(|_| ()),
+            Some(StreamRequest::WitnessReport(report)) => handle_witness_report(
+                &self.witness_report_sink,
+                timestamp,
+                report,
+                self.session_key.as_ref(),
+                self.pub_key_bytes.as_deref(),
+            )
+            .await
+            .map(|_| ()),
             Some(StreamRequest::SessionInit(init)) => verify_public_key(&init.pub_key)
                 .and_then(|pk| verify_network(self.required_network, pk))
                 .and_then(|pk| verify_signature(Some(&pk), init))
@@ -149,6 +148,10 @@ impl StreamState {
                             if let Err(err) = self.handle_message(message).await {
                                 let pub_key = self.pub_key_bytes.map(|b| bs58::encode(&b).into_string()).unwrap_or("".to_string());
                                 tracing::info!(?pub_key, ?err, "error while handling message during stream_requests");
+                                // Let the client know to retry reports that were not taken
+                                if matches!(err.code(), Code::Unavailable | Code::ResourceExhausted) {
+                                    _ = tx.send(Err(err)).await;
+                                }
                                 break;
                             }
                         }
@@ -170,8 +173,8 @@ impl StreamState {
 
 #[derive(Clone, Debug)]
 pub struct GrpcServer {
-    pub beacon_report_sink: FileSinkClient,
-    pub witness_report_sink: FileSinkClient,
+    pub beacon_report_sink: ReportSink,
+    pub witness_report_sink: ReportSink,
     pub required_network: Network,
     pub address: SocketAddr,
     pub session_key_offer_timeout: std::time::Duration,
@@ -204,6 +207,34 @@ impl ManagedTask for GrpcServer {
     }
 }
 
+impl GrpcServer {
+    /// Routes of the HTTP gateway, one per unary RPC
+    fn http_router(self) -> Router {
+        Router::new()
+            .route(
+                "/v1/poc_lora/submit_lora_beacon",
+                post(
+                    |State(server): State<Self>, headers: HeaderMap, body: Bytes| {
+                        http_gateway::handle(headers, body, |req| async move {
+                            poc_lora::PocLora::submit_lora_beacon(&server, req).await
+                        })
+                    },
+                ),
+            )
+            .route(
+                "/v1/poc_lora/submit_lora_witness",
+                post(
+                    |State(server): State<Self>, headers: HeaderMap, body: Bytes| {
+                        http_gateway::handle(headers, body, |req| async move {
+                            poc_lora::PocLora::submit_lora_witness(&server, req).await
+                        })
+                    },
+                ),
+            )
+            .with_state(self)
+    }
+}
+
 fn verify_public_key(bytes: &[u8]) -> VerifyResult<PublicKey> {
     PublicKey::try_from(bytes).map_err(|_| Status::invalid_argument("invalid public key"))
 }
@@ -231,53 +262,65 @@ where
 }
 
 async fn handle_beacon_report(
-    file_sink: &FileSinkClient,
+    file_sink: &ReportSink,
     timestamp: u64,
     report: LoraBeaconReportReqV1,
     signing_key: Option<&PublicKey>,
     expected_pubkey_bytes: Option<&[u8]>,
-) -> Result<(), Status> {
-    let ingest_report = verify_signature(signing_key, report)
-        .and_then(|report| {
-            expected_pubkey_bytes
-                .map(|bytes| bytes == report.pub_key)
-                .unwrap_or(true)
-                .then_some(report)
-                .ok_or_else(|| Status::invalid_argument("incorrect pub_key"))
-        })
-        .map(|report| LoraBeaconIngestReportV1 {
+) -> Result<u64, Status> {
+    let report = verify_signature(signing_key, report).and_then(|report| {
+        expected_pubkey_bytes
+            .map(|bytes| bytes == report.pub_key)
+            .unwrap_or(true)
+            .then_some(report)
+            .ok_or_else(|| Status::invalid_argument("incorrect pub_key"))
+    })?;
+    let dedup_key = dedup_key(&report);
+    if let Some(id) = file_sink.duplicate_id(&dedup_key) {
+        return Ok(id);
+    }
+
+    let ingest_report = file_sink
+        .check_rate_limit(&[PublicKeyBinary::from(report.pub_key.clone()).to_string()])
+        .map(|_| LoraBeaconIngestReportV1 {
             received_timestamp: timestamp,
             report: Some(report),
         })?;
 
-    _ = file_sink.write(ingest_report, []).await;
-
-    Ok(())
+    file_sink
+        .write_once(ingest_report, dedup_key, timestamp)
+        .await
 }
 
 async fn handle_witness_report(
-    file_sink: &FileSinkClient,
+    file_sink: &ReportSink,
     timestamp: u64,
     report: LoraWitnessReportReqV1,
     session_key: Option<&PublicKey>,
     expected_pubkey_bytes: Option<&[u8]>,
-) -> Result<(), Status> {
-    let ingest_report = verify_signature(session_key, report)
-        .and_then(|report| {
-            expected_pubkey_bytes
-                .map(|bytes| bytes == report.pub_key)
-                .unwrap_or(true)
-                .then_some(report)
-                .ok_or_else(|| Status::invalid_argument("incorrect pub_key"))
-        })
-        .map(|report| LoraWitnessIngestReportV1 {
+) -> Result<u64, Status> {
+    let report = verify_signature(session_key, report).and_then(|report| {
+        expected_pubkey_bytes
+            .map(|bytes| bytes == report.pub_key)
+            .unwrap_or(true)
+            .then_some(report)
+            .ok_or_else(|| Status::invalid_argument("incorrect pub_key"))
+    })?;
+    let dedup_key = dedup_key(&report);
+    if let Some(id) = file_sink.duplicate_id(&dedup_key) {
+        return Ok(id);
+    }
+
+    let ingest_report = file_sink
+        .check_rate_limit(&[PublicKeyBinary::from(report.pub_key.clone()).to_string()])
+        .map(|_| LoraWitnessIngestReportV1 {
             received_timestamp: timestamp,
             report: Some(report),
         })?;
 
-    _ = file_sink.write(ingest_report, []).await;
-
-    Ok(())
+    file_sink
+        .write_once(ingest_report, dedup_key, timestamp)
+        .await
 }
 
 #[tonic::async_trait]
@@ -292,7 +335,7 @@ impl poc_lora::PocLora for GrpcServer {
         let pub_key = verify_public_key(&event.pub_key)
             .and_then(|pk| verify_network(self.required_network, pk))?;
 
-        handle_beacon_report(
+        let id = handle_beacon_report(
             &self.beacon_report_sink,
             timestamp,
             event,
@@ -301,8 +344,7 @@ impl poc_lora::PocLora for GrpcServer {
         )
         .await?;
 
-        let id = timestamp.to_string();
-        Ok(Response::new(LoraBeaconReportRespV1 { id }))
+        Ok(Response::new(LoraBeaconReportRespV1 { id: id.to_string() }))
     }
 
     async fn submit_lora_witness(
@@ -315,7 +357,7 @@ impl poc_lora::PocLora for GrpcServer {
         let pub_key = verify_public_key(&event.pub_key)
             .and_then(|pk| verify_network(self.required_network, pk))?;
 
-        handle_witness_report(
+        let id = handle_witness_report(
             &self.witness_report_sink,
             timestamp,
             event,
@@ -324,8 +366,9 @@ impl poc_lora::PocLora for GrpcServer {
         )
         .await?;
 
-        let id = timestamp.to_string();
-        Ok(Response::new(LoraWitnessReportRespV1 { id }))
+        Ok(Response::new(LoraWitnessReportRespV1 {
+            id: id.to_string(),
+        }))
     }
 
     type stream_requestsStream = GrpcStreamResult<LoraStreamResponseV1>;
@@ -359,7 +402,9 @@ pub async fn grpc_server(settings: &Settings) -> Result<()> {
         concat!(env!("CARGO_PKG_NAME"), "_beacon_report"),
     )
     .file_upload(Some(file_upload.clone()))
+    .index(true)
     .roll_time(Duration::minutes(5))
+    .queue_size(settings.sink_queue_size)
     .create()
     .await?;
 
@@ -370,19 +415,36 @@ pub async fn grpc_server(settings: &Settings) -> Result<()> {
         concat!(env!("CARGO_PKG_NAME"), "_witness_report"),
     )
     .file_upload(Some(file_upload.clone()))
+    .index(true)
     .roll_time(Duration::minutes(5))
+    .compression(settings.compression.witness)
+    .queue_size(settings.sink_queue_size)
     .create()
     .await?;
 
+    let rate_limits = &settings.rate_limits;
+    let report_sink = |client, report_type, rate_limit| {
+        ReportSink::new(client, report_type)
+            .ack(settings.ack)
+            .send_timeout(settings.sink_send_timeout())
+            .rate_limit(rate_limit)
+            .dedup(settings.dedup.as_ref())
+    };
+
     let grpc_server = GrpcServer {
-        beacon_report_sink,
-        witness_report_sink,
+        beacon_report_sink: report_sink(beacon_report_sink, "beacon", rate_limits.beacon),
+        witness_report_sink: report_sink(witness_report_sink, "witness", rate_limits.witness),
         required_network: settings.network,
         address: grpc_addr,
         session_key_offer_timeout: settings.session_key_offer_timeout(),
         session_key_timeout: settings.session_key_timeout(),
     };
 
+    let http_gateway = HttpGateway::new(
+        settings.http_listen_addr()?,
+        grpc_server.clone().http_router(),
+    );
+
     tracing::info!(
         "grpc listening on {grpc_addr} and server mode {:?}",
         settings.mode
@@ -393,6 +455,7 @@ pub async fn grpc_server(settings: &Settings) -> Result<()> {
         .add_task(beacon_report_sink_server)
         .add_task(witness_report_sink_server)
         .add_task(grpc_server)
+        .add_task(http_gateway)
         .start()
         .await
 }
@@ -1,18 +1,20 @@
-use crate::Settings;
-use anyhow::{bail, Error, Result};
-use chrono::{Duration, Utc};
-use file_store::{
-    file_sink::{self, FileSinkClient},
-    file_upload,
-    traits::MsgVerify,
-    FileType,
+use crate::{
+    api_token::{authorize, ApiTokens, Scope},
+    dedup::dedup_key,
+    http_gateway::{self, HttpGateway},
+    report_sink::ReportSink,
+    Settings,
 };
+use anyhow::{Error, Result};
+use axum::{body::Bytes, extract::State, http::HeaderMap, routing::post, Router};
+use chrono::{Duration, Utc};
+use file_store::{file_sink, file_upload, traits::MsgVerify, FileType};
 use futures::future::LocalBoxFuture;
 use futures_util::TryFutureExt;
 use helium_crypto::{Network, PublicKey};
 use helium_proto::services::poc_mobile::{
-    self, CellHeartbeatIngestReportV1, CellHeartbeatReqV1, CellHeartbeatRespV1,
-    CoverageObjectIngestReportV1, CoverageObjectReqV1, CoverageObjectRespV1,
+    self, coverage_object_req_v1, CellHeartbeatIngestReportV1, CellHeartbeatReqV1,
+    CellHeartbeatRespV1, CoverageObjectIngestReportV1, CoverageObjectReqV1, CoverageObjectRespV1,
     DataTransferSessionIngestReportV1, DataTransferSessionReqV1, DataTransferSessionRespV1,
     SpeedtestIngestReportV1, SpeedtestReqV1, SpeedtestRespV1, SubscriberLocationIngestReportV1,
     SubscriberLocationReqV1, SubscriberLocationRespV1, WifiHeartbeatIngestReportV1,
@@ -20,26 +22,24 @@ use helium_proto::services::poc_mobile::{
 };
 use std::{net::SocketAddr, path::Path};
 use task_manager::{ManagedTask, TaskManager};
-use tonic::{
-    metadata::{Ascii, MetadataValue},
-    transport, Request, Response, Status,
-};
+use tonic::{transport, Request, Response, Status};
 
 const INGEST_WAIT_DURATION_MINUTES: i64 = 15;
 
 pub type GrpcResult<T> = std::result::Result<Response<T>, Status>;
 pub type VerifyResult<T> = std::result::Result<T, Status>;
 
+#[derive(Clone)]
 pub struct GrpcServer {
-    heartbeat_report_sink: FileSinkClient,
-    wifi_heartbeat_report_sink: FileSinkClient,
-    speedtest_report_sink: FileSinkClient,
-    data_transfer_session_sink: FileSinkClient,
-    subscriber_location_report_sink: FileSinkClient,
-    coverage_object_report_sink: FileSinkClient,
+    heartbeat_report_sink: ReportSink,
+    wifi_heartbeat_report_sink: ReportSink,
+    speedtest_report_sink: ReportSink,
+    data_transfer_session_sink: ReportSink,
+    subscriber_location_report_sink: ReportSink,
+    coverage_object_report_sink: ReportSink,
     required_network: Network,
     address: SocketAddr,
-    api_token: MetadataValue<Ascii>,
+    api_tokens: ApiTokens,
 }
 
 impl ManagedTask for GrpcServer {
@@ -47,17 +47,14 @@ impl ManagedTask for GrpcServer {
         self: Box<Self>,
         shutdown: triggered::Listener,
     ) -> LocalBoxFuture<'static, anyhow::Result<()>> {
-        let api_token = self.api_token.clone();
+        let api_tokens = self.api_tokens.clone();
         let address = self.address;
         Box::pin(async move {
             transport::Server::builder()
                 .layer(poc_metrics::request_layer!("ingest_server_grpc_connection"))
                 .add_service(poc_mobile::Server::with_interceptor(
                     *self,
-                    move |req: Request<()>| match req.metadata().get("authorization") {
-                        Some(t) if api_token == t => Ok(req),
-                        _ => Err(Status::unauthenticated("No valid auth token")),
-                    },
+                    move |req: Request<()>| api_tokens.authenticate(req),
                 ))
                 .serve_with_shutdown(address, shutdown)
                 .map_err(Error::from)
@@ -88,6 +85,35 @@ impl GrpcServer {
             .map_err(|_| Status::invalid_argument("invalid signature"))?;
         Ok((public_key, event))
     }
+
+    /// Routes of the HTTP gateway, one per unary RPC, authenticated by the
+    /// same api tokens as the grpc server
+    fn http_router(self) -> Router {
+        macro_rules! route {
+            ($router:expr, $rpc:ident) => {
+                $router.route(
+                    concat!("/v1/poc_mobile/", stringify!($rpc)),
+                    post(
+                        |State(server): State<GrpcServer>, headers: HeaderMap, body: Bytes| {
+                            http_gateway::handle(headers, body, |req| async move {
+                                let req = server.api_tokens.authenticate(req)?;
+                                poc_mobile::PocMobile::$rpc(&server, req).await
+                            })
+                        },
+                    ),
+                )
+            };
+        }
+
+        let router = Router::new();
+        let router = route!(router, submit_speedtest);
+        let router = route!(router, submit_cell_heartbeat);
+        let router = route!(router, submit_wifi_heartbeat);
+        let router = route!(router, submit_data_transfer_session);
+        let router = route!(router, submit_subscriber_location);
+        let router = route!(router, submit_coverage_object);
+        router.with_state(self)
+    }
 }
 
 #[tonic::async_trait]
@@ -96,21 +122,31 @@ impl poc_mobile::PocMobile for GrpcServer {
         &self,
         request: Request<SpeedtestReqV1>,
     ) -> GrpcResult<SpeedtestRespV1> {
+        authorize(&request, Scope::SubmitSpeedtest)?;
         let timestamp: u64 = Utc::now().timestamp_millis() as u64;
         let event = request.into_inner();
-
-        let report = self
+        let (public_key, event) = self
             .verify_public_key(event.pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
-            .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| SpeedtestIngestReportV1 {
+            .and_then(|public_key| self.verify_signature(public_key, event))?;
+        let dedup_key = dedup_key(&event);
+        if let Some(id) = self.speedtest_report_sink.duplicate_id(&dedup_key) {
+            return Ok(Response::new(SpeedtestRespV1 { id: id.to_string() }));
+        }
+
+        let report = self
+            .speedtest_report_sink
+            .check_rate_limit(&[public_key.to_string()])
+            .map(|_| SpeedtestIngestReportV1 {
                 received_timestamp: timestamp,
                 report: Some(event),
             })?;
 
-        _ = self.speedtest_report_sink.write(report, []).await;
-
-        let id = timestamp.to_string();
+        let id = self
+            .speedtest_report_sink
+            .write_once(report, dedup_key, timestamp)
+            .await?
+            .to_string();
         Ok(Response::new(SpeedtestRespV1 { id }))
     }
 
@@ -118,21 +154,35 @@ impl poc_mobile::PocMobile for GrpcServer {
         &self,
         request: Request<CellHeartbeatReqV1>,
     ) -> GrpcResult<CellHeartbeatRespV1> {
+        authorize(&request, Scope::SubmitCellHeartbeat)?;
         let timestamp: u64 = Utc::now().timestamp_millis() as u64;
         let event = request.into_inner();
-
-        let report = self
+        let (public_key, event) = self
             .verify_public_key(event.pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
-            .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| CellHeartbeatIngestReportV1 {
+            .and_then(|public_key| self.verify_signature(public_key, event))?;
+        let dedup_key = dedup_key(&event);
+        if let Some(id) = self.heartbeat_report_sink.duplicate_id(&dedup_key) {
+            return Ok(Response::new(CellHeartbeatRespV1 { id: id.to_string() }));
+        }
+
+        let mut keys = vec![public_key.to_string()];
+        if !event.cbsd_id.is_empty() {
+            keys.push(event.cbsd_id.clone());
+        }
+        let report = self
+            .heartbeat_report_sink
+            .check_rate_limit(&keys)
+            .map(|_| CellHeartbeatIngestReportV1 {
                 received_timestamp: timestamp,
                 report: Some(event),
             })?;
 
-        _ = self.heartbeat_report_sink.write(report, []).await;
-
-        let id = timestamp.to_string();
+        let id = self
+            .heartbeat_report_sink
+            .write_once(report, dedup_key, timestamp)
+            .await?
+            .to_string();
         Ok(Response::new(CellHeartbeatRespV1 { id }))
     }
 
@@ -140,21 +190,31 @@ impl poc_mobile::PocMobile for GrpcServer {
         &self,
         request: Request<WifiHeartbeatReqV1>,
     ) -> GrpcResult<WifiHeartbeatRespV1> {
+        authorize(&request, Scope::SubmitWifiHeartbeat)?;
         let timestamp: u64 = Utc::now().timestamp_millis() as u64;
         let event = request.into_inner();
-
-        let report = self
+        let (public_key, event) = self
             .verify_public_key(event.pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
-            .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| WifiHeartbeatIngestReportV1 {
+            .and_then(|public_key| self.verify_signature(public_key, event))?;
+        let dedup_key = dedup_key(&event);
+        if let Some(id) = self.wifi_heartbeat_report_sink.duplicate_id(&dedup_key) {
+            return Ok(Response::new(WifiHeartbeatRespV1 { id: id.to_string() }));
+        }
+
+        let report = self
+            .wifi_heartbeat_report_sink
+            .check_rate_limit(&[public_key.to_string()])
+            .map(|_| WifiHeartbeatIngestReportV1 {
                 received_timestamp: timestamp,
                 report: Some(event),
             })?;
 
-        _ = self.wifi_heartbeat_report_sink.write(report, []).await;
-
-        let id = timestamp.to_string();
+        let id = self
+            .wifi_heartbeat_report_sink
+            .write_once(report, dedup_key, timestamp)
+            .await?
+            .to_string();
         Ok(Response::new(WifiHeartbeatRespV1 { id }))
     }
 
@@ -162,22 +222,35 @@ impl poc_mobile::PocMobile for GrpcServer {
         &self,
         request: Request<DataTransferSessionReqV1>,
     ) -> GrpcResult<DataTransferSessionRespV1> {
+        authorize(&request, Scope::SubmitDataTransferSession)?;
         let timestamp = Utc::now().timestamp_millis() as u64;
         let event = request.into_inner();
-
-        let report = self
+        let (public_key, event) = self
             .verify_public_key(event.pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
-            .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| DataTransferSessionIngestReportV1 {
+            .and_then(|public_key| self.verify_signature(public_key, event))?;
+        let dedup_key = dedup_key(&event);
+        if let Some(id) = self.data_transfer_session_sink.duplicate_id(&dedup_key) {
+            return Ok(Response::new(DataTransferSessionRespV1 {
+                id: id.to_string(),
+            }));
+        }
+
+        let report = self
+            .data_transfer_session_sink
+            .check_rate_limit(&[public_key.to_string()])
+            .map(|_| DataTransferSessionIngestReportV1 {
                 received_timestamp: timestamp,
                 report: Some(event),
             })?;
 
-        _ = self.data_transfer_session_sink.write(report, []).await;
+        let id = self
+            .data_transfer_session_sink
+            .write_once(report, dedup_key, timestamp)
+            .await?;
 
         Ok(Response::new(DataTransferSessionRespV1 {
-            id: timestamp.to_string(),
+            id: id.to_string(),
         }))
     }
 
@@ -185,32 +258,51 @@ impl poc_mobile::PocMobile for GrpcServer {
         &self,
         request: Request<SubscriberLocationReqV1>,
     ) -> GrpcResult<SubscriberLocationRespV1> {
+        authorize(&request, Scope::SubmitSubscriberLocation)?;
         let timestamp = Utc::now().timestamp_millis() as u64;
         let event = request.into_inner();
         let subscriber_id = event.subscriber_id.clone();
         let timestamp_millis = event.timestamp;
-
-        let report = self
+        let log_status = |status: Status| {
+            tracing::debug!(
+                subscriber_id = ?subscriber_id,
+                timestamp = %timestamp_millis,
+                status = %status
+            );
+            status
+        };
+
+        let (public_key, event) = self
             .verify_public_key(event.carrier_pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
             .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| SubscriberLocationIngestReportV1 {
+            .map_err(log_status)?;
+        let dedup_key = dedup_key(&event);
+        if let Some(id) = self
+            .subscriber_location_report_sink
+            .duplicate_id(&dedup_key)
+        {
+            return Ok(Response::new(SubscriberLocationRespV1 {
+                id: id.to_string(),
+            }));
+        }
+
+        let report = self
+            .subscriber_location_report_sink
+            .check_rate_limit(&[public_key.to_string()])
+            .map(|_| SubscriberLocationIngestReportV1 {
                 received_timestamp: timestamp,
                 report: Some(event),
             })
-            .map_err(|status| {
-                tracing::debug!(
-                    subscriber_id = ?subscriber_id,
-                    timestamp = %timestamp_millis,
-                    status = %status
-                );
-                status
-            })?;
+            .map_err(log_status)?;
 
-        _ = self.subscriber_location_report_sink.write(report, []).await;
+        let id = self
+            .subscriber_location_report_sink
+            .write_once(report, dedup_key, timestamp)
+            .await?;
 
         Ok(Response::new(SubscriberLocationRespV1 {
-            id: timestamp.to_string(),
+            id: id.to_string(),
         }))
     }
 
@@ -218,21 +310,35 @@ impl poc_mobile::PocMobile for GrpcServer {
         &self,
         request: Request<CoverageObjectReqV1>,
     ) -> GrpcResult<CoverageObjectRespV1> {
+        authorize(&request, Scope::SubmitCoverageObject)?;
         let timestamp: u64 = Utc::now().timestamp_millis() as u64;
         let event = request.into_inner();
-
-        let report = self
+        let (public_key, event) = self
             .verify_public_key(event.pub_key.as_ref())
             .and_then(|public_key| self.verify_network(public_key))
-            .and_then(|public_key| self.verify_signature(public_key, event))
-            .map(|(_, event)| CoverageObjectIngestReportV1 {
+            .and_then(|public_key| self.verify_signature(public_key, event))?;
+        let dedup_key = dedup_key(&event);
+        if let Some(id) = self.coverage_object_report_sink.duplicate_id(&dedup_key) {
+            return Ok(Response::new(CoverageObjectRespV1 { id: id.to_string() }));
+        }
+
+        let mut keys = vec![public_key.to_string()];
+        if let Some(coverage_object_req_v1::KeyType::CbsdId(cbsd_id)) = &event.key_type {
+            keys.push(cbsd_id.clone());
+        }
+        let report = self
+            .coverage_object_report_sink
+            .check_rate_limit(&keys)
+            .map(|_| CoverageObjectIngestReportV1 {
                 received_timestamp: timestamp,
                 report: Some(event),
             })?;
 
-        _ = self.coverage_object_report_sink.write(report, []).await;
-
-        let id = timestamp.to_string();
+        let id = self
+            .coverage_object_report_sink
+            .write_once(report, dedup_key, timestamp)
+            .await?
+            .to_string();
         Ok(Response::new(CoverageObjectRespV1 { id }))
     }
 }
@@ -252,7 +358,10 @@ pub async fn grpc_server(settings: &Settings) -> Result<()> {
         concat!(env!("CARGO_PKG_NAME"), "_heartbeat_report"),
     )
     .file_upload(Some(file_upload.clone()))
+    .index(true)
     .roll_time(Duration::minutes(INGEST_WAIT_DURATION_MINUTES))
+    .compression(settings.compression.heartbeat)
+    .queue_size(settings.sink_queue_size)
     .create()
     .await?;
 
@@ -263,7 +372,10 @@ pub async fn grpc_server(settings: &Settings) -> Result<()> {
             concat!(env!("CARGO_PKG_NAME"), "_wifi_heartbeat_report"),
         )
         .file_upload(Some(file_upload.clone()))
+        .index(true)
         .roll_time(Duration::minutes(INGEST_WAIT_DURATION_MINUTES))
+        .compression(settings.compression.heartbeat)
+        .queue_size(settings.sink_queue_size)
         .create()
         .await?;
 
@@ -274,7 +386,9 @@ pub async fn grpc_server(settings: &Settings) -> Result<()> {
         concat!(env!("CARGO_PKG_NAME"), "_speedtest_report"),
     )
     .file_upload(Some(file_upload.clone()))
+    .index(true)
     .roll_time(Duration::minutes(INGEST_WAIT_DURATION_MINUTES))
+    .queue_size(settings.sink_queue_size)
     .create()
     .await?;
 
@@ -288,7 +402,9 @@ pub async fn grpc_server(settings: &Settings) -> Result<()> {
             ),
         )
         .file_upload(Some(file_upload.clone()))
+        .index(true)
         .roll_time(Duration::minutes(INGEST_WAIT_DURATION_MINUTES))
+        .queue_size(settings.sink_queue_size)
         .create()
         .await?;
 
@@ -299,7 +415,9 @@ pub async fn grpc_server(settings: &Settings) -> Result<()> {
             concat!(env!("CARGO_PKG_NAME"), "_subscriber_location_report"),
         )
         .file_upload(Some(file_upload.clone()))
+        .index(true)
         .roll_time(Duration::minutes(INGEST_WAIT_DURATION_MINUTES))
+        .queue_size(settings.sink_queue_size)
         .create()
         .await?;
 
@@ -310,30 +428,64 @@ pub async fn grpc_server(settings: &Settings) -> Result<()> {
             concat!(env!("CARGO_PKG_NAME"), "_coverage_object_report"),
         )
         .file_upload(Some(file_upload.clone()))
+        .index(true)
         .roll_time(Duration::minutes(INGEST_WAIT_DURATION_MINUTES))
+        .queue_size(settings.sink_queue_size)
         .create()
         .await?;
 
-    let Some(api_token) = settings
-        .token
-        .as_ref()
-        .and_then(|token| format!("Bearer {token}").parse::<MetadataValue<_>>().ok())
-    else {
-        bail!("expected valid api token in settings");
+    let api_tokens = ApiTokens::from_settings(settings)?;
+
+    let rate_limits = &settings.rate_limits;
+    let report_sink = |client, report_type, rate_limit| {
+        ReportSink::new(client, report_type)
+            .ack(settings.ack)
+            .send_timeout(settings.sink_send_timeout())
+            .rate_limit(rate_limit)
+            .dedup(settings.dedup.as_ref())
     };
 
     let grpc_server = GrpcServer {
-        heartbeat_report_sink,
-        wifi_heartbeat_report_sink,
-        speedtest_report_sink,
-        data_transfer_session_sink,
-        subscriber_location_report_sink,
-        coverage_object_report_sink,
+        heartbeat_report_sink: report_sink(
+            heartbeat_report_sink,
+            "heartbeat",
+            rate_limits.heartbeat,
+        ),
+        wifi_heartbeat_report_sink: report_sink(
+            wifi_heartbeat_report_sink,
+            "wifi_heartbeat",
+            rate_limits.wifi_heartbeat,
+        ),
+        speedtest_report_sink: report_sink(
+            speedtest_report_sink,
+            "speedtest",
+            rate_limits.speedtest,
+        ),
+        data_transfer_session_sink: report_sink(
+            data_transfer_session_sink,
+            "data_transfer_session",
+            rate_limits.data_transfer_session,
+        ),
+        subscriber_location_report_sink: report_sink(
+            subscriber_location_report_sink,
+            "subscriber_location",
+            rate_limits.subscriber_location,
+        ),
+        coverage_object_report_sink: report_sink(
+            coverage_object_report_sink,
+            "coverage_object",
+            rate_limits.coverage_object,
+        ),
         required_network: settings.network,
         address: grpc_addr,
-        api_token,
+        api_tokens: api_tokens.clone(),
     };
 
+    let http_gateway = HttpGateway::new(
+        settings.http_listen_addr()?,
+        grpc_server.clone().http_router(),
+    );
+
     tracing::info!(
         "grpc listening on {grpc_addr} and server mode {:?}",
         settings.mode
@@ -341,6 +493,7 @@ pub async fn grpc_server(settings: &Settings) -> Result<()> {
 
     TaskManager::builder()
         .add_task(file_upload_server)
+        .add_task(api_tokens)
         .add_task(heartbeat_report_sink_server)
         .add_task(wifi_heartbeat_report_sink_server)
         .add_task(speedtest_report_sink_server)
@@ -348,6 +501,7 @@ pub async fn grpc_server(settings: &Settings) -> Result<()> {
         .add_task(subscriber_location_report_sink_server)
         .add_task(coverage_object_report_sink_server)
         .add_task(grpc_server)
+        .add_task(http_gateway)
         .start()
         .await
 }
@@ -1,9 +1,11 @@
+use crate::api_token::ApiToken;
 use config::{Config, Environment, File};
+use file_store::compression::Compression;
 use helium_crypto::Network;
 use serde::Deserialize;
 use std::{
     net::{AddrParseError, SocketAddr},
-    path::Path,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
@@ -18,6 +20,9 @@ pub struct Settings {
     /// Listen address. Required. Default is 0.0.0.0:9081
     #[serde(default = "default_listen_addr")]
     pub listen: String,
+    /// Optional listen address of the HTTP gateway accepting the same
+    /// requests as the grpc server. Disabled by default
+    pub http_listen: Option<String>,
     /// Local folder for storing intermediate files
     pub cache: String,
     /// Network required in all public keys:  mainnet | testnet
@@ -28,12 +33,41 @@ pub struct Settings {
     /// Timeout of session key session in seconds
     #[serde(default = "default_session_key_timeout")]
     pub session_key_timeout: u64,
+    /// Number of reports queued per report sink before submissions have to
+    /// wait for room. Default 50
+    #[serde(default = "default_sink_queue_size")]
+    pub sink_queue_size: usize,
+    /// Time in milliseconds a submission waits for room in a full report
+    /// sink queue before it is rejected with a retryable status. Default 5000
+    #[serde(default = "default_sink_send_timeout")]
+    pub sink_send_timeout: u64,
+    /// When to acknowledge a submitted report: queued | written | committed.
+    /// Default written
+    #[serde(default)]
+    pub ack: AckPolicy,
+    /// Per key rate limits of reports by request type. Unlimited by default
+    #[serde(default)]
+    pub rate_limits: RateLimits,
+    /// Suppression of resubmitted reports. Disabled by default
+    pub dedup: Option<DedupSettings>,
+    /// Compression of the files of the high volume report sinks. Gzip by
+    /// default
+    #[serde(default)]
+    pub compression: SinkCompression,
     /// Settings for exposed public API
     /// Target bucket for uploads
     pub output: file_store::Settings,
     /// API token required as part of a Bearer authentication GRPC request
-    /// header. Used only by the mobile mode currently
+    /// header. Used only by the mobile mode currently. Accepted as a token
+    /// named "default" that is allowed to call all RPCs
     pub token: Option<String>,
+    /// Named API tokens accepted by the mobile mode, each optionally scoped
+    /// to a subset of RPCs and limited to a validity window
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+    /// Optional file with a `tokens` list of named API tokens, in addition
+    /// to the ones in the settings. Reloaded every minute
+    pub tokens_file: Option<PathBuf>,
     /// Target output bucket details Metrics settings
     pub metrics: poc_metrics::Settings,
 }
@@ -46,6 +80,22 @@ pub fn default_session_key_offer_timeout() -> u64 {
     5
 }
 
+pub fn default_sink_queue_size() -> usize {
+    50
+}
+
+pub fn default_sink_send_timeout() -> u64 {
+    5000
+}
+
+pub fn default_dedup_window() -> u64 {
+    15 * 60
+}
+
+pub fn default_dedup_max_entries() -> usize {
+    1_000_000
+}
+
 pub fn default_listen_addr() -> String {
     "0.0.0.0:9081".to_string()
 }
@@ -67,6 +117,73 @@ pub enum Mode {
     Mobile,
 }
 
+/// When a report is acknowledged to the submitter. Reports that are not yet
+/// acknowledged are rejected with a retryable status if their sink fails.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AckPolicy {
+    /// Once the report is queued for its sink
+    Queued,
+    /// Once the report is written to the active file of its sink
+    #[default]
+    Written,
+    /// Once the file holding the report is committed. Submissions wait for
+    /// up to the roll time of the sink
+    Committed,
+}
+
+/// Token bucket limit of the reports a single public key or cbsd id can
+/// submit
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RateLimit {
+    /// Reports per second a key can submit on average
+    pub rate: f64,
+    /// Number of reports a key can submit in a burst
+    pub burst: u32,
+}
+
+/// Rate limits by request type
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+    pub beacon: Option<RateLimit>,
+    pub witness: Option<RateLimit>,
+    pub heartbeat: Option<RateLimit>,
+    pub wifi_heartbeat: Option<RateLimit>,
+    pub speedtest: Option<RateLimit>,
+    pub data_transfer_session: Option<RateLimit>,
+    pub subscriber_location: Option<RateLimit>,
+    pub coverage_object: Option<RateLimit>,
+}
+
+/// Compression of report sink files by request type: gzip | zstd
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SinkCompression {
+    /// Cbrs and wifi heartbeat reports
+    pub heartbeat: Compression,
+    /// Iot witness reports
+    pub witness: Compression,
+}
+
+/// Settings of the per request type caches of accepted reports used to
+/// answer resubmitted reports with the original id
+#[derive(Debug, Clone, Deserialize)]
+pub struct DedupSettings {
+    /// Time in seconds an accepted report is remembered. Default 900
+    #[serde(default = "default_dedup_window")]
+    pub window: u64,
+    /// Max number of reports remembered per request type. Default 1000000
+    #[serde(default = "default_dedup_max_entries")]
+    pub max_entries: usize,
+}
+
+impl DedupSettings {
+    pub fn window(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.window)
+    }
+}
+
 impl Settings {
     /// Load Settings from a given path. Settings are loaded from a given
     /// optional path and can be overriden with environment variables.
@@ -94,6 +211,13 @@ impl Settings {
         SocketAddr::from_str(&self.listen)
     }
 
+    pub fn http_listen_addr(&self) -> Result<Option<SocketAddr>, AddrParseError> {
+        self.http_listen
+            .as_deref()
+            .map(SocketAddr::from_str)
+            .transpose()
+    }
+
     pub fn session_key_offer_timeout(&self) -> std::time::Duration {
         std::time::Duration::from_secs(self.session_key_offer_timeout)
     }
@@ -101,4 +225,8 @@ impl Settings {
     pub fn session_key_timeout(&self) -> std::time::Duration {
         std::time::Duration::from_secs(self.session_key_timeout)
     }
+
+    pub fn sink_send_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.sink_send_timeout)
+    }
 }
@@ -10,13 +10,13 @@ use helium_proto::services::poc_lora::{
     LoraStreamSessionInitV1, LoraStreamSessionOfferV1, LoraWitnessIngestReportV1,
     LoraWitnessReportReqV1,
 };
-use ingest::server_iot::GrpcServer;
+use ingest::{report_sink::ReportSink, server_iot::GrpcServer};
 use prost::Message;
 use rand::{rngs::OsRng, Rng};
 use task_manager::TaskManager;
 use tokio::{sync::mpsc::error::TryRecvError, task::LocalSet, time::timeout};
 use tokio_stream::{wrappers::ReceiverStream, StreamExt};
-use tonic::{transport::Channel, Streaming};
+use tonic::{transport::Channel, Code, Streaming};
 
 #[tokio::test]
 async fn initialize_session_and_send_beacon_and_witness() {
@@ -369,6 +369,42 @@ async fn stream_stops_on_session_timeout() {
         .await;
 }
 
+#[tokio::test]
+async fn stream_returns_unavailable_when_report_sink_is_closed() {
+    let (beacon_client, beacons) = create_file_sink();
+    let (witness_client, _) = create_file_sink();
+    let port = get_port();
+    drop(beacons);
+
+    LocalSet::new()
+        .run_until(async move {
+            tokio::task::spawn_local(async move {
+                let server = create_test_server(port, beacon_client, witness_client, None, None);
+                TaskManager::builder().add_task(server).start().await
+            });
+
+            let pub_key = generate_keypair();
+            let session_key = generate_keypair();
+
+            let mut client = connect_and_stream(port).await;
+            let offer = client.receive_offer().await;
+
+            client
+                .send_init(
+                    offer,
+                    pub_key.public_key(),
+                    session_key.public_key(),
+                    &pub_key,
+                )
+                .await;
+
+            client.send_beacon(pub_key.public_key(), &session_key).await;
+
+            client.assert_unavailable().await;
+        })
+        .await;
+}
+
 struct MockFileSinkReceiver {
     receiver: tokio::sync::mpsc::Receiver<SinkMessage>,
 }
@@ -390,16 +426,22 @@ impl MockFileSinkReceiver {
 
     async fn receive_beacon(&mut self) -> LoraBeaconIngestReportV1 {
         match self.receive().await {
-            SinkMessage::Data(_, bytes) => LoraBeaconIngestReportV1::decode(bytes.as_slice())
-                .expect("decode beacon ingest report"),
+            SinkMessage::Data(on_write_tx, bytes) => {
+                let _ = on_write_tx.send(Ok(()));
+                LoraBeaconIngestReportV1::decode(bytes.as_slice())
+                    .expect("decode beacon ingest report")
+            }
             _ => panic!("invalid beacon message"),
         }
     }
 
     async fn receive_witness(&mut self) -> LoraWitnessIngestReportV1 {
         match self.receive().await {
-            SinkMessage::Data(_, bytes) => LoraWitnessIngestReportV1::decode(bytes.as_slice())
-                .expect("decode witness ingest report"),
+            SinkMessage::Data(on_write_tx, bytes) => {
+                let _ = on_write_tx.send(Ok(()));
+                LoraWitnessIngestReportV1::decode(bytes.as_slice())
+                    .expect("decode witness ingest report")
+            }
             _ => panic!("invalid witness message"),
         }
     }
@@ -456,6 +498,13 @@ impl TestClient {
         };
     }
 
+    async fn assert_unavailable(mut self) {
+        let Ok(Some(Err(status))) = timeout(seconds(1), self.in_stream.next()).await else {
+            panic!("Should have received an error status from the server")
+        };
+        assert_eq!(Code::Unavailable, status.code());
+    }
+
     async fn send_init(
         &mut self,
         offer: LoraStreamSessionOfferV1,
@@ -545,8 +594,8 @@ fn create_test_server(
     let offer_timeout = offer_timeout.unwrap_or(5000);
     let timeout = timeout.unwrap_or(30 * 60000);
     GrpcServer {
-        beacon_report_sink: beacon_file_sink,
-        witness_report_sink: witness_file_sink,
+        beacon_report_sink: ReportSink::new(beacon_file_sink, "beacon"),
+        witness_report_sink: ReportSink::new(witness_file_sink, "witness"),
         required_network: Network::MainNet,
         address: SocketAddr::from_str(&format!("127.0.0.1:{port}")).expect("socket address"),
         session_key_offer_timeout: std::time::Duration::from_millis(offer_timeout),
@@ -7,6 +7,7 @@ use crate::{
 };
 use anyhow::{bail, Result};
 use file_store::{
+    dead_letter,
     file_info_poller::{FileInfoStream, LookbackBehavior},
     file_sink::FileSinkBuilder,
     file_sink::FileSinkClient,
@@ -157,6 +158,12 @@ impl Cmd {
         .create()
         .await?;
 
+        let (packet_dead_letters, packet_dead_letters_server) =
+            dead_letter::file_sink(&FileType::IotPacketReport.to_string(), store_base_path)
+                .file_upload(Some(file_upload.clone()))
+                .create()
+                .await?;
+
         let org_client = Arc::new(Mutex::new(CachedOrgClient::new(OrgClient::from_settings(
             &settings.iot_config_client,
         )?)));
@@ -169,6 +176,7 @@ impl Cmd {
                 .store(file_store)
                 .lookback(LookbackBehavior::StartAfter(settings.start_after()))
                 .prefix(FileType::IotPacketReport.to_string())
+                .dead_letter(Some(packet_dead_letters))
                 .create()
                 .await?;
 
@@ -193,6 +201,7 @@ impl Cmd {
             .add_task(file_upload_server)
             .add_task(valid_packets_server)
             .add_task(invalid_packets_server)
+            .add_task(packet_dead_letters_server)
             .add_task(move |shutdown| {
                 org_client
                     .monitor_funds(
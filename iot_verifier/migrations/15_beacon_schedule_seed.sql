@@ -0,0 +1,5 @@
+create table beacon_schedule_seed (
+    epoch_start timestamptz primary key not null,
+    entropy bytea not null,
+    entropy_timestamp timestamptz not null
+);
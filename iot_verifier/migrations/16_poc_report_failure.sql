@@ -0,0 +1,31 @@
+create type failurereason AS enum (
+    'entropy_missing',
+    'beacon_missing',
+    'not_processed',
+    'database',
+    'decode',
+    'gateway_resolution',
+    'region_params',
+    'file_sink',
+    'other'
+);
+
+alter table poc_report add column last_failure failurereason;
+
+create table purged_poc_report (
+    id bytea primary key not null,
+    remote_entropy bytea,
+    packet_data bytea not null,
+    report_data bytea not null,
+    report_type reporttype,
+    report_timestamp timestamptz not null,
+    attempts integer default 0 not null,
+    reason failurereason not null,
+    purged_at timestamptz default now() not null
+);
+
+CREATE INDEX idx_purged_poc_report_report_timestamp
+ON purged_poc_report(report_timestamp);
+
+CREATE INDEX idx_purged_poc_report_purged_at
+ON purged_poc_report(purged_at);
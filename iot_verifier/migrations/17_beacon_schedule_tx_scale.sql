@@ -0,0 +1,11 @@
+create table beacon_schedule_tx_scales (
+    epoch_start timestamptz primary key not null,
+    refreshed_at timestamptz not null
+);
+
+create table beacon_schedule_tx_scale (
+    epoch_start timestamptz not null references beacon_schedule_tx_scales (epoch_start) on delete cascade,
+    hex bigint not null,
+    tx_scale decimal not null,
+    primary key (epoch_start, hex)
+);
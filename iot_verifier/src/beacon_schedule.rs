@@ -0,0 +1,408 @@
+//! Beacon schedules scaled by hex density.
+//!
+//! By default a gateway may beacon once per bucket of the global beacon
+//! interval. With the `beacon_schedule` rule's `density_scaled` set, each
+//! gateway instead gets a number of beacon slots per epoch scaled by the
+//! transmit scale of its hex, so gateways in dense hexes beacon less and
+//! gateways in sparse hexes more. The epoch is split into that many windows
+//! and each window holds one slot, placed by hashing the epoch's seed with
+//! the gateway's public key.
+//!
+//! The transmit scales of an epoch are those of the last refresh of the hex
+//! density map before the epoch starts, so a refresh during the epoch does
+//! not change the schedules of the epoch.
+//!
+//! The seed of an epoch is the earliest entropy of the epoch before it,
+//! leaving out entropy from within the [`seed_margin`] of the epoch start, so
+//! it does not depend on the order entropy is loaded in. Seeds are no longer
+//! proposed once their epoch has started, so schedules are fixed before the
+//! epoch starts and can be computed offline from the public key, the epoch,
+//! the seed and the transmit scale. Gateways are not scheduled in an epoch
+//! without a seed or transmit scales.
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use helium_crypto::PublicKeyBinary;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+/// default min number of beacons per epoch of a density scaled schedule
+pub const MIN_BEACONS_PER_EPOCH: u32 = 1;
+/// default max number of beacons per epoch of a density scaled schedule
+pub const MAX_BEACONS_PER_EPOCH: u32 = 6;
+/// default length of a beacon slot in minutes
+pub const BEACON_SLOT_MINUTES: u32 = 60;
+/// number of past epochs whose transmit scales are kept
+pub const TX_SCALE_RETENTION_EPOCHS: i32 = 30;
+
+/// length of an epoch of beacon schedules
+pub fn epoch() -> Duration {
+    Duration::hours(24)
+}
+
+/// start of the epoch the given time is in
+pub fn epoch_start(timestamp: DateTime<Utc>) -> DateTime<Utc> {
+    timestamp
+        .duration_trunc(epoch())
+        .expect("epoch is a valid truncation")
+}
+
+/// entropy from within this margin of the start of an epoch does not seed
+/// it, so late entropy cannot change a seed around the epoch boundary
+pub fn seed_margin() -> Duration {
+    Duration::hours(1)
+}
+
+/// The epoch the given entropy may seed, none if it is within the seed
+/// margin of that epoch's start
+pub fn seeded_epoch(entropy_timestamp: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let seeded_epoch = epoch_start(entropy_timestamp) + epoch();
+    (entropy_timestamp <= seeded_epoch - seed_margin()).then_some(seeded_epoch)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BeaconScheduleError {
+    #[error("no beacon schedule seed for epoch {0}")]
+    MissingSeed(DateTime<Utc>),
+    #[error("no beacon schedule transmit scales for epoch {0}")]
+    MissingTxScales(DateTime<Utc>),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Parameters of a density scaled beacon schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ScheduleParams {
+    pub min_beacons: u32,
+    pub max_beacons: u32,
+    pub slot_minutes: u32,
+}
+
+impl Default for ScheduleParams {
+    fn default() -> Self {
+        Self {
+            min_beacons: MIN_BEACONS_PER_EPOCH,
+            max_beacons: MAX_BEACONS_PER_EPOCH,
+            slot_minutes: BEACON_SLOT_MINUTES,
+        }
+    }
+}
+
+impl ScheduleParams {
+    /// Number of beacons per epoch of a gateway in a hex of the given
+    /// transmit scale: the max scaled by the transmit scale, rounded up, and
+    /// at least the min
+    pub fn beacons(&self, tx_scale: Decimal) -> u32 {
+        let max = self.max_beacons.max(1);
+        let scaled = (Decimal::from(max) * tx_scale)
+            .ceil()
+            .to_u32()
+            .unwrap_or(max);
+        scaled.min(max).max(self.min_beacons.min(max)).max(1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BeaconSlot {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// The beacon slots of a gateway in an epoch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BeaconSchedule {
+    pub epoch_start: DateTime<Utc>,
+    pub tx_scale: Decimal,
+    pub slots: Vec<BeaconSlot>,
+}
+
+impl BeaconSchedule {
+    pub fn new(
+        seed: &[u8],
+        pub_key: &PublicKeyBinary,
+        epoch_start: DateTime<Utc>,
+        tx_scale: Decimal,
+        params: &ScheduleParams,
+    ) -> Self {
+        let beacons = params.beacons(tx_scale);
+        let window = epoch().num_seconds() / i64::from(beacons);
+        let slot = (i64::from(params.slot_minutes) * 60).clamp(1, window);
+        let slots = (0..beacons)
+            .map(|index| {
+                let offset = slot_offset(seed, pub_key, epoch_start, index) % (window - slot + 1);
+                let start = epoch_start + Duration::seconds(i64::from(index) * window + offset);
+                BeaconSlot {
+                    start,
+                    end: start + Duration::seconds(slot),
+                }
+            })
+            .collect();
+        Self {
+            epoch_start,
+            tx_scale,
+            slots,
+        }
+    }
+
+    /// The schedule of a gateway in the given epoch, seeded from the db and
+    /// scaled by the transmit scale of the gateway's location fixed for the
+    /// epoch. Fails if the epoch has no seed or transmit scales
+    pub async fn resolve<'c, E>(
+        executor: E,
+        pub_key: &PublicKeyBinary,
+        location: u64,
+        epoch_start: DateTime<Utc>,
+        default_tx_scale: Decimal,
+        params: &ScheduleParams,
+    ) -> Result<Self, BeaconScheduleError>
+    where
+        E: sqlx::Executor<'c, Database = sqlx::Postgres> + Copy,
+    {
+        let seed = ScheduleSeed::get(executor, epoch_start)
+            .await?
+            .ok_or(BeaconScheduleError::MissingSeed(epoch_start))?;
+        let tx_scale = ScheduleTxScales::get(executor, epoch_start, location)
+            .await?
+            .ok_or(BeaconScheduleError::MissingTxScales(epoch_start))?
+            .unwrap_or(default_tx_scale);
+        Ok(Self::new(&seed, pub_key, epoch_start, tx_scale, params))
+    }
+
+    /// Index of the slot the given time is in, none if it is in no slot
+    pub fn slot_index(&self, timestamp: DateTime<Utc>) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| slot.start <= timestamp && timestamp < slot.end)
+    }
+}
+
+fn slot_offset(
+    seed: &[u8],
+    pub_key: &PublicKeyBinary,
+    epoch_start: DateTime<Utc>,
+    index: u32,
+) -> i64 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(seed);
+    hasher.update(pub_key.as_ref());
+    hasher.update(&epoch_start.timestamp().to_le_bytes());
+    hasher.update(&index.to_le_bytes());
+    let hash = hasher.finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash.as_bytes()[..8]);
+    (u64::from_le_bytes(bytes) >> 1) as i64
+}
+
+/// How often a gateway may beacon
+#[derive(Debug, Clone)]
+pub enum BeaconCadence {
+    /// once per bucket of the beacon interval
+    Interval(Duration),
+    /// once per slot of a density scaled schedule
+    Scheduled(BeaconSchedule),
+}
+
+/// The entropy the beacon schedules of an epoch are seeded with
+pub struct ScheduleSeed;
+
+impl ScheduleSeed {
+    /// Make the given entropy the seed of the epoch after the one it is in if
+    /// it is earlier than the current seed, ties broken by the lowest entropy.
+    /// Entropy within the seed margin of that epoch, or proposed once the
+    /// epoch has started, seeds nothing
+    pub async fn propose<'c, E>(
+        executor: E,
+        entropy: &[u8],
+        entropy_timestamp: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error>
+    where
+        E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+    {
+        let Some(seeded_epoch) = seeded_epoch(entropy_timestamp) else {
+            return Ok(());
+        };
+        if now >= seeded_epoch {
+            return Ok(());
+        }
+        sqlx::query(
+            r#"
+            insert into beacon_schedule_seed (epoch_start, entropy, entropy_timestamp)
+            values ($1, $2, $3)
+            on conflict (epoch_start) do update set
+                entropy = excluded.entropy,
+                entropy_timestamp = excluded.entropy_timestamp
+            where excluded.entropy_timestamp < beacon_schedule_seed.entropy_timestamp
+                or (excluded.entropy_timestamp = beacon_schedule_seed.entropy_timestamp
+                    and excluded.entropy < beacon_schedule_seed.entropy)
+            "#,
+        )
+        .bind(seeded_epoch)
+        .bind(entropy)
+        .bind(entropy_timestamp)
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get<'c, E>(
+        executor: E,
+        epoch_start: DateTime<Utc>,
+    ) -> Result<Option<Vec<u8>>, sqlx::Error>
+    where
+        E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+    {
+        sqlx::query_scalar::<_, Vec<u8>>(
+            r#"
+            select entropy from beacon_schedule_seed
+            where epoch_start = $1
+            "#,
+        )
+        .bind(epoch_start)
+        .fetch_optional(executor)
+        .await
+    }
+}
+
+/// The transmit scales of hexes the beacon schedules of an epoch are scaled
+/// by
+pub struct ScheduleTxScales;
+
+impl ScheduleTxScales {
+    /// Fix the transmit scales of a refresh of the hex density map for the
+    /// epoch after the one it was refreshed in, replacing those of an earlier
+    /// refresh, and drop the scales of epochs past retention
+    pub async fn save(
+        pool: &PgPool,
+        tx_scales: &HashMap<u64, Decimal>,
+        refreshed_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        const NUMBER_OF_FIELDS_IN_QUERY: u16 = 3;
+        const MAX_BATCH_ENTRIES: usize = (u16::MAX / NUMBER_OF_FIELDS_IN_QUERY) as usize;
+        let scaled_epoch = epoch_start(refreshed_at) + epoch();
+        let tx_scales: Vec<_> = tx_scales.iter().collect();
+        let mut transaction = pool.begin().await?;
+        sqlx::query(
+            r#"
+            delete from beacon_schedule_tx_scales
+            where epoch_start = $1 or epoch_start < $2
+            "#,
+        )
+        .bind(scaled_epoch)
+        .bind(scaled_epoch - epoch() * TX_SCALE_RETENTION_EPOCHS)
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query(
+            r#"
+            insert into beacon_schedule_tx_scales (epoch_start, refreshed_at)
+            values ($1, $2)
+            "#,
+        )
+        .bind(scaled_epoch)
+        .bind(refreshed_at)
+        .execute(&mut *transaction)
+        .await?;
+        for tx_scales in tx_scales.chunks(MAX_BATCH_ENTRIES) {
+            let mut query_builder: sqlx::QueryBuilder<sqlx::Postgres> = sqlx::QueryBuilder::new(
+                " insert into beacon_schedule_tx_scale (epoch_start, hex, tx_scale) ",
+            );
+            query_builder.push_values(tx_scales, |mut builder, (hex, tx_scale)| {
+                builder
+                    .push_bind(scaled_epoch)
+                    .push_bind(**hex as i64)
+                    .push_bind(**tx_scale);
+            });
+            query_builder.build().execute(&mut *transaction).await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// The transmit scale of a hex fixed for the given epoch, none if the
+    /// hex had no scale, and none at all if the epoch has no transmit scales
+    pub async fn get<'c, E>(
+        executor: E,
+        epoch_start: DateTime<Utc>,
+        hex: u64,
+    ) -> Result<Option<Option<Decimal>>, sqlx::Error>
+    where
+        E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+    {
+        sqlx::query_scalar::<_, Option<Decimal>>(
+            r#"
+            select tx_scale.tx_scale from beacon_schedule_tx_scales tx_scales
+            left join beacon_schedule_tx_scale tx_scale
+                on tx_scale.epoch_start = tx_scales.epoch_start and tx_scale.hex = $2
+            where tx_scales.epoch_start = $1
+            "#,
+        )
+        .bind(epoch_start)
+        .bind(hex as i64)
+        .fetch_optional(executor)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+    use std::str::FromStr;
+
+    const PUBKEY1: &str = "112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf";
+    const PUBKEY2: &str = "11z69eJ3czc92k6snrfR9ek7g2uRWXosFbnG9v4bXgwhfUCivUo";
+
+    #[test]
+    fn entropy_near_the_epoch_start_seeds_nothing() {
+        let epoch_start = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+        let previous_epoch = epoch_start - epoch();
+        assert_eq!(Some(epoch_start), seeded_epoch(previous_epoch));
+        assert_eq!(Some(epoch_start), seeded_epoch(epoch_start - seed_margin()));
+        assert_eq!(
+            None,
+            seeded_epoch(epoch_start - seed_margin() + Duration::seconds(1))
+        );
+        assert_eq!(Some(epoch_start + epoch()), seeded_epoch(epoch_start));
+    }
+
+    #[test]
+    fn dense_hexes_beacon_less() {
+        let params = ScheduleParams::default();
+        assert_eq!(6, params.beacons(dec!(1.0)));
+        assert_eq!(2, params.beacons(dec!(0.2)));
+        assert_eq!(1, params.beacons(dec!(0.0060)));
+        let params = ScheduleParams {
+            min_beacons: 2,
+            ..params
+        };
+        assert_eq!(2, params.beacons(dec!(0.0060)));
+    }
+
+    #[test]
+    fn slots_are_one_per_window_and_differ_by_gateway() {
+        let epoch_start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let params = ScheduleParams::default();
+        let pubkey1 = PublicKeyBinary::from_str(PUBKEY1).unwrap();
+        let pubkey2 = PublicKeyBinary::from_str(PUBKEY2).unwrap();
+        let schedule1 = BeaconSchedule::new(b"seed", &pubkey1, epoch_start, dec!(0.5), &params);
+        let schedule2 = BeaconSchedule::new(b"seed", &pubkey2, epoch_start, dec!(0.5), &params);
+
+        assert_eq!(3, schedule1.slots.len());
+        for (index, slot) in schedule1.slots.iter().enumerate() {
+            let window_start = epoch_start + Duration::hours(8 * index as i64);
+            assert!(slot.start >= window_start);
+            assert!(slot.end <= window_start + Duration::hours(8));
+            assert_eq!(Duration::minutes(60), slot.end - slot.start);
+            assert_eq!(Some(index), schedule1.slot_index(slot.start));
+            assert_eq!(None, schedule1.slot_index(slot.end));
+        }
+        assert_ne!(schedule1.slots, schedule2.slots);
+        // the same inputs give the same schedule
+        assert_eq!(
+            schedule1,
+            BeaconSchedule::new(b"seed", &pubkey1, epoch_start, dec!(0.5), &params)
+        );
+    }
+}
@@ -0,0 +1,48 @@
+pub mod requeue_purged;
+pub mod reward_diff;
+pub mod reward_dry_run;
+pub mod reward_from_db;
+pub mod simulate;
+pub mod verify_poc;
+
+use crate::Settings;
+use anyhow::Result;
+use file_store::{traits::MsgDecode, BytesMutStream};
+use futures::stream::{Stream, TryStreamExt};
+use helium_proto::BlockchainTokenTypeV1;
+use price::PriceTracker;
+use rust_decimal::Decimal;
+
+/// Decode every message of a stream of files
+async fn decode_all<T>(stream: BytesMutStream) -> Result<Vec<T>>
+where
+    T: MsgDecode + TryFrom<T::Msg, Error = file_store::Error>,
+{
+    decode_stream(stream).try_collect().await
+}
+
+/// Decode the messages of a stream of files as they are read
+fn decode_stream<T>(stream: BytesMutStream) -> impl Stream<Item = Result<T>>
+where
+    T: MsgDecode + TryFrom<T::Msg, Error = file_store::Error>,
+{
+    stream
+        .map_err(anyhow::Error::from)
+        .and_then(|buf| async move { T::decode(buf).map_err(anyhow::Error::from) })
+}
+
+/// The given IOT price, or the latest price of the price oracle if none is
+/// given
+async fn iot_price(settings: &Settings, iot_price: Option<Decimal>) -> Result<Decimal> {
+    if let Some(iot_price) = iot_price {
+        return Ok(iot_price);
+    }
+    let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+    let (price_tracker, price_daemon) =
+        PriceTracker::start(&settings.price_tracker, shutdown_listener).await?;
+    let iot_price = price_tracker.price(&BlockchainTokenTypeV1::Iot).await;
+    shutdown_trigger.trigger();
+    // the daemon fails if the price is not available, which is reported below
+    let _ = price_daemon.await;
+    Ok(Decimal::from(iot_price?))
+}
@@ -0,0 +1,64 @@
+//! Re-queue of reports the purger removed as stale.
+//!
+//! The purger keeps a copy of every report it purges, with the reason the
+//! report was never verified, for a week. Once the cause is fixed, for example
+//! a gap in the entropy or a database outage, the reports received in a window
+//! can be put back in the queue with their attempts reset. They are then
+//! verified as if they had just been loaded.
+//!
+//! A stale invalid report was already written for every purged report. A
+//! re-queued report which is verified is output a second time, as a valid or
+//! invalid PoC. A beacon is only verified again if its entropy is in the
+//! database, so beacons purged for missing entropy need the entropy to be
+//! loaded first.
+
+use crate::{
+    poc_report::{FailureReason, Report, ReportType},
+    Settings,
+};
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use serde_json::json;
+
+/// Put reports purged as stale back in the verification queue
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Re-queue reports received at or after this time
+    #[clap(long)]
+    after: NaiveDateTime,
+    /// Re-queue reports received before this time
+    #[clap(long)]
+    before: NaiveDateTime,
+    /// Only re-queue reports of this type
+    #[clap(long, value_enum)]
+    report_type: Option<ReportType>,
+    /// Only re-queue reports purged for this reason
+    #[clap(long, value_enum)]
+    reason: Option<FailureReason>,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let pool = settings.database.connect(env!("CARGO_PKG_NAME")).await?;
+        let requeued = Report::requeue_purged(
+            &pool,
+            self.after.and_utc(),
+            self.before.and_utc(),
+            self.report_type,
+            self.reason,
+        )
+        .await?;
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({
+                "after": self.after.and_utc(),
+                "before": self.before.and_utc(),
+                "report_type": self.report_type,
+                "reason": self.reason,
+                "requeued": requeued,
+            }))?
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+//! Per gateway diff of two reward outputs, for example a dry run of an epoch
+//! and the rewards production wrote for it.
+//!
+//! Each output is a directory of iot_reward_share files or a single one. A
+//! directory can be the output of `reward-dry-run`, or hold the files listed
+//! in a reward manifest after downloading them from the rewards bucket.
+
+use anyhow::Result;
+use file_store::{file_source, FileInfo, FileType};
+use futures::stream::TryStreamExt;
+use helium_crypto::PublicKeyBinary;
+use helium_proto::{
+    services::poc_lora::{iot_reward_share::Reward as ProtoReward, IotRewardShare},
+    Message,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Compare two reward outputs per gateway
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Directory or file of the old rewards
+    old: PathBuf,
+    /// Directory or file of the new rewards
+    new: PathBuf,
+    /// Also list the gateways whose rewards did not change
+    #[clap(long)]
+    all: bool,
+}
+
+/// The gateway rewards of a reward output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct Rewards {
+    pub(super) beacon: u64,
+    pub(super) witness: u64,
+    pub(super) dc_transfer: u64,
+}
+
+impl Rewards {
+    pub(super) fn total(&self) -> u64 {
+        self.beacon + self.witness + self.dc_transfer
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct GatewayDiff {
+    pub(super) gateway: PublicKeyBinary,
+    pub(super) old_beacon: u64,
+    pub(super) old_witness: u64,
+    pub(super) old_dc_transfer: u64,
+    pub(super) old_total: u64,
+    pub(super) new_beacon: u64,
+    pub(super) new_witness: u64,
+    pub(super) new_dc_transfer: u64,
+    pub(super) new_total: u64,
+    pub(super) diff: i64,
+}
+
+impl GatewayDiff {
+    fn changed(&self) -> bool {
+        self.old_beacon != self.new_beacon
+            || self.old_witness != self.new_witness
+            || self.old_dc_transfer != self.new_dc_transfer
+    }
+}
+
+/// Totals per reward type of a reward output
+#[derive(Debug, Default, Serialize)]
+struct RewardTotals {
+    /// start and end in seconds of the reward periods in the output
+    periods: BTreeSet<(u64, u64)>,
+    gateways: usize,
+    beacon: u64,
+    witness: u64,
+    dc_transfer: u64,
+    operational: u64,
+    unallocated: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct RewardDiff {
+    old: RewardTotals,
+    new: RewardTotals,
+    changed: usize,
+    gateways: Vec<GatewayDiff>,
+}
+
+impl Cmd {
+    pub async fn run(&self) -> Result<()> {
+        let (old_rewards, old) = read_rewards(&self.old).await?;
+        let (new_rewards, new) = read_rewards(&self.new).await?;
+        if old.periods != new.periods {
+            eprintln!("warning: the outputs are of different reward periods");
+        }
+        let mut gateways = diff(old_rewards, new_rewards);
+        let changed = gateways.iter().filter(|gateway| gateway.changed()).count();
+        if !self.all {
+            gateways.retain(GatewayDiff::changed);
+        }
+        let reward_diff = RewardDiff {
+            old,
+            new,
+            changed,
+            gateways,
+        };
+        println!("{}", serde_json::to_string_pretty(&reward_diff)?);
+        Ok(())
+    }
+}
+
+async fn read_rewards(path: &Path) -> Result<(HashMap<PublicKeyBinary, Rewards>, RewardTotals)> {
+    let mut gateways: HashMap<PublicKeyBinary, Rewards> = HashMap::new();
+    let mut totals = RewardTotals::default();
+    let mut shares = file_source::source(reward_files(path)?);
+    while let Some(buf) = shares.try_next().await? {
+        let share = IotRewardShare::decode(buf)?;
+        totals
+            .periods
+            .insert((share.start_period, share.end_period));
+        match share.reward {
+            Some(ProtoReward::GatewayReward(reward)) => {
+                totals.beacon += reward.beacon_amount;
+                totals.witness += reward.witness_amount;
+                totals.dc_transfer += reward.dc_transfer_amount;
+                let rewards = gateways
+                    .entry(PublicKeyBinary::from(reward.hotspot_key))
+                    .or_default();
+                rewards.beacon += reward.beacon_amount;
+                rewards.witness += reward.witness_amount;
+                rewards.dc_transfer += reward.dc_transfer_amount;
+            }
+            Some(ProtoReward::OperationalReward(reward)) => totals.operational += reward.amount,
+            Some(ProtoReward::UnallocatedReward(reward)) => {
+                *totals
+                    .unallocated
+                    .entry(reward.reward_type().as_str_name().to_string())
+                    .or_default() += reward.amount;
+            }
+            None => (),
+        }
+    }
+    totals.gateways = gateways.len();
+    Ok((gateways, totals))
+}
+
+fn reward_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = vec![];
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        let is_reward_share = path
+            .file_name()
+            .and_then(|name| FileInfo::from_str(&name.to_string_lossy()).ok())
+            .is_some_and(|info| info.prefix == FileType::IotRewardShare.to_str());
+        if is_reward_share {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The old and new rewards of every gateway in either, largest changes first
+pub(super) fn diff(
+    old: HashMap<PublicKeyBinary, Rewards>,
+    mut new: HashMap<PublicKeyBinary, Rewards>,
+) -> Vec<GatewayDiff> {
+    let mut gateways: Vec<_> = old
+        .into_iter()
+        .map(|(gateway, old)| {
+            let new = new.remove(&gateway).unwrap_or_default();
+            (gateway, old, new)
+        })
+        .collect();
+    gateways.extend(
+        new.into_iter()
+            .map(|(gateway, new)| (gateway, Rewards::default(), new)),
+    );
+    let mut gateways: Vec<_> = gateways
+        .into_iter()
+        .map(|(gateway, old, new)| GatewayDiff {
+            gateway,
+            old_beacon: old.beacon,
+            old_witness: old.witness,
+            old_dc_transfer: old.dc_transfer,
+            old_total: old.total(),
+            new_beacon: new.beacon,
+            new_witness: new.witness,
+            new_dc_transfer: new.dc_transfer,
+            new_total: new.total(),
+            diff: new.total() as i64 - old.total() as i64,
+        })
+        .collect();
+    gateways.sort_by(|a, b| {
+        b.diff
+            .abs()
+            .cmp(&a.diff.abs())
+            .then_with(|| a.gateway.to_string().cmp(&b.gateway.to_string()))
+    });
+    gateways
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewards(beacon: u64, witness: u64, dc_transfer: u64) -> Rewards {
+        Rewards {
+            beacon,
+            witness,
+            dc_transfer,
+        }
+    }
+
+    #[test]
+    fn diff_covers_gateways_of_either_output() {
+        let gw1 = PublicKeyBinary::from(vec![1; 33]);
+        let gw2 = PublicKeyBinary::from(vec![2; 33]);
+        let gw3 = PublicKeyBinary::from(vec![3; 33]);
+        let old = HashMap::from([
+            (gw1.clone(), rewards(10, 20, 0)),
+            (gw2.clone(), rewards(5, 5, 5)),
+        ]);
+        let new = HashMap::from([
+            (gw1.clone(), rewards(10, 20, 0)),
+            (gw3.clone(), rewards(0, 100, 0)),
+        ]);
+
+        let gateways = diff(old, new);
+        let diffs: Vec<_> = gateways
+            .iter()
+            .map(|gateway| (gateway.gateway.clone(), gateway.diff))
+            .collect();
+        assert_eq!(vec![(gw3, 100), (gw2, -15), (gw1, 0)], diffs);
+    }
+}
@@ -0,0 +1,104 @@
+//! Dry run of the rewards of an epoch.
+//!
+//! The rewards of a period are computed from the shares in the database the
+//! way the rewarder computes them: PoC and data transfer, operational,
+//! oracle and unallocated. They are written with their manifest to a local
+//! directory. Nothing is uploaded, the shares are not cleared and the
+//! rewarded timestamps are left as they are. An epoch can be previewed
+//! before it is paid out, and compared to other rewards with `reward-diff`.
+
+use crate::{cli::iot_price, rewarder, Settings};
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime};
+use file_store::{file_sink, FileType};
+use reward_scheduler::Scheduler;
+use rust_decimal::Decimal;
+use serde_json::json;
+use std::path::PathBuf;
+
+/// Write the rewards of a period to a local directory without paying them out
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Directory to write the rewards and their manifest to
+    #[clap(long, short)]
+    out: PathBuf,
+    /// Start of the reward period. The next period to be rewarded if not
+    /// given
+    #[clap(long, requires = "end")]
+    start: Option<NaiveDateTime>,
+    /// End of the reward period
+    #[clap(long, requires = "start")]
+    end: Option<NaiveDateTime>,
+    /// Price of IOT in millionths of a dollar. The latest price of the price
+    /// oracle if not given
+    #[clap(long)]
+    iot_price: Option<Decimal>,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let pool = settings.database.connect(env!("CARGO_PKG_NAME")).await?;
+        let reward_period = match (self.start, self.end) {
+            (Some(start), Some(end)) => start.and_utc()..end.and_utc(),
+            _ => {
+                Scheduler::new(
+                    Duration::hours(settings.rewards),
+                    rewarder::fetch_rewarded_timestamp("last_rewarded_end_time", &pool).await?,
+                    rewarder::fetch_rewarded_timestamp("next_rewarded_end_time", &pool).await?,
+                    settings.reward_offset_duration(),
+                )
+                .reward_period
+            }
+        };
+        let iot_price = iot_price(settings, self.iot_price).await?;
+
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let (rewards_sink, rewards_sink_server) = file_sink::FileSinkBuilder::new(
+            FileType::IotRewardShare,
+            &self.out,
+            concat!(env!("CARGO_PKG_NAME"), "_dry_run_reward_shares"),
+        )
+        .auto_commit(false)
+        .create()
+        .await?;
+        let (reward_manifests_sink, reward_manifests_sink_server) =
+            file_sink::FileSinkBuilder::new(
+                FileType::RewardManifest,
+                &self.out,
+                concat!(env!("CARGO_PKG_NAME"), "_dry_run_reward_manifest"),
+            )
+            .auto_commit(false)
+            .create()
+            .await?;
+        let rewards_sink_handle = tokio::spawn(rewards_sink_server.run(shutdown_listener.clone()));
+        let reward_manifests_sink_handle =
+            tokio::spawn(reward_manifests_sink_server.run(shutdown_listener));
+
+        let written_files = async {
+            let written_files =
+                rewarder::write_rewards(&pool, &rewards_sink, &reward_period, iot_price).await?;
+            rewarder::write_manifest(
+                &reward_manifests_sink,
+                &reward_period,
+                written_files.clone(),
+            )
+            .await?;
+            anyhow::Ok(written_files)
+        }
+        .await;
+        shutdown_trigger.trigger();
+        rewards_sink_handle.await??;
+        reward_manifests_sink_handle.await??;
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({
+                "start": reward_period.start,
+                "end": reward_period.end,
+                "iot_price": iot_price,
+                "written_files": written_files?,
+            }))?
+        );
+        Ok(())
+    }
+}
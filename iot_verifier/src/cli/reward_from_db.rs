@@ -0,0 +1,96 @@
+use crate::{
+    cli::iot_price,
+    reward_share::{self, GatewayShares},
+    Settings,
+};
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use helium_crypto::PublicKeyBinary;
+use helium_proto::services::poc_lora::iot_reward_share::Reward as ProtoReward;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+/// Reward a period from the shares in the database
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    #[clap(long)]
+    start: NaiveDateTime,
+    #[clap(long)]
+    end: NaiveDateTime,
+    /// Price of IOT in millionths of a dollar. The latest price of the price
+    /// oracle if not given
+    #[clap(long)]
+    iot_price: Option<Decimal>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Rewards {
+    beacon: u64,
+    witness: u64,
+    dc_transfer: u64,
+    total: u64,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let start = self.start.and_utc();
+        let end = self.end.and_utc();
+
+        tracing::info!("Rewarding shares from the following time range: {start} to {end}");
+        let reward_period = start..end;
+        let iot_price = iot_price(settings, self.iot_price).await?;
+        let pool = settings.database.connect(env!("CARGO_PKG_NAME")).await?;
+
+        let reward_shares = reward_share::aggregate_reward_shares(&pool, &reward_period).await?;
+        let gateway_shares = GatewayShares::new(reward_shares)?;
+        let (beacon_rewards_per_share, witness_rewards_per_share, dc_transfer_rewards_per_share) =
+            gateway_shares
+                .calculate_rewards_per_share(&reward_period, iot_price)
+                .await?;
+        let (expected_beacon_rewards, expected_witness_rewards) =
+            reward_share::get_scheduled_poc_tokens(end - start, Decimal::ZERO);
+        let expected_dc_transfer_rewards = reward_share::get_scheduled_dc_tokens(end - start);
+
+        let mut total_rewards = Rewards::default();
+        let mut gateway_rewards = HashMap::<PublicKeyBinary, Rewards>::new();
+        for (_reward_amount, reward) in gateway_shares.into_iot_reward_shares(
+            &reward_period,
+            beacon_rewards_per_share,
+            witness_rewards_per_share,
+            dc_transfer_rewards_per_share,
+        ) {
+            if let Some(ProtoReward::GatewayReward(reward)) = reward.reward {
+                for rewards in [
+                    &mut total_rewards,
+                    gateway_rewards
+                        .entry(PublicKeyBinary::from(reward.hotspot_key))
+                        .or_default(),
+                ] {
+                    rewards.beacon += reward.beacon_amount;
+                    rewards.witness += reward.witness_amount;
+                    rewards.dc_transfer += reward.dc_transfer_amount;
+                    rewards.total +=
+                        reward.beacon_amount + reward.witness_amount + reward.dc_transfer_amount;
+                }
+            }
+        }
+        let rewards: Vec<_> = gateway_rewards.into_iter().collect();
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({
+                "iot_price": iot_price,
+                "rewards": rewards,
+                "total_rewards": total_rewards,
+                "expected_rewards": {
+                    "beacon": expected_beacon_rewards,
+                    "witness": expected_witness_rewards,
+                    "dc_transfer": expected_dc_transfer_rewards,
+                },
+            }))?
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,516 @@
+//! Simulation of changes to the iot reward parameters over historical PoCs
+//! and packets.
+//!
+//! The PoCs and packets of a reward period are rewarded twice. The first run
+//! uses the reward units and hex scales they were verified with and the
+//! current split of the rewards. The second run recomputes the reward units
+//! and splits the rewards with the alternative parameters read from a JSON
+//! file:
+//!
+//! ```json
+//! {
+//!   "reward_units": {
+//!     "witness_redundancy": 4,
+//!     "decay_rate": "0.8",
+//!     "tx_reward_unit_cap": "2"
+//!   },
+//!   "split": {
+//!     "beacon_percent": "0.06",
+//!     "witness_percent": "0.24",
+//!     "data_transfer_percent": "0.50",
+//!     "beacon_dc_remainder_percent": "0.20",
+//!     "witness_dc_remainder_percent": "0.80"
+//!   }
+//! }
+//! ```
+//!
+//! Parameters left out keep their current value. The witnesses selected for a
+//! PoC are kept as verified, so changes to `max_witnesses_per_poc` are not
+//! simulated.
+//!
+//! The old and new rewards of every gateway are written as CSV or JSON. The
+//! JSON output also summarizes both distributions with percentiles and the
+//! Gini coefficient. The CSV output only has the gateway rows, and its
+//! summary is printed to stderr.
+
+use crate::{
+    cli::{
+        decode_all,
+        reward_diff::{diff, GatewayDiff, Rewards},
+    },
+    reward_share::{GatewayDCShare, GatewayPocShare, GatewayShares, RewardShares, RewardSplit},
+    runner::{poc_beaconer_reward_unit, poc_per_witness_reward_unit, RewardUnitParams},
+    Settings,
+};
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use file_store::{file_source, iot_packet::IotValidPacket, iot_valid_poc::IotPoc};
+use helium_crypto::PublicKeyBinary;
+use helium_proto::services::poc_lora::{
+    iot_reward_share::Reward as ProtoReward, VerificationStatus,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    ops::Range,
+    path::PathBuf,
+};
+
+const PERCENTILES: [u8; 6] = [10, 25, 50, 75, 90, 99];
+
+/// Simulate the rewards of a period under alternative reward parameters
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// IotPoc files of the reward period
+    #[clap(long = "pocs")]
+    poc_files: Vec<PathBuf>,
+    /// IotValidPacket files of the reward period
+    #[clap(long = "packets")]
+    packet_files: Vec<PathBuf>,
+    /// JSON file of the alternative reward parameters
+    #[clap(long)]
+    params: PathBuf,
+    /// Start of the reward period
+    #[clap(long)]
+    start: NaiveDateTime,
+    /// End of the reward period
+    #[clap(long)]
+    end: NaiveDateTime,
+    /// Price of IOT in millionths of a dollar, as reported by the price oracle
+    #[clap(long)]
+    iot_price: Decimal,
+    /// Output format
+    #[clap(long, value_enum, default_value = "json")]
+    format: Format,
+    /// Path to write the output to, stdout if not given
+    #[clap(long, short)]
+    out: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+/// Reward parameters to simulate
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SimulationParams {
+    pub reward_units: RewardUnitParams,
+    pub split: RewardSplit,
+}
+
+#[derive(Debug, Serialize)]
+struct Distribution {
+    total: u64,
+    percentiles: BTreeMap<String, u64>,
+    gini: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    gateways: usize,
+    old: Distribution,
+    new: Distribution,
+}
+
+#[derive(Debug, Serialize)]
+struct Simulation {
+    params: SimulationParams,
+    summary: Summary,
+    gateways: Vec<GatewayDiff>,
+}
+
+impl Cmd {
+    pub async fn run(&self, _settings: &Settings) -> Result<()> {
+        let params: SimulationParams =
+            serde_json::from_slice(&tokio::fs::read(&self.params).await?)?;
+        let pocs: Vec<IotPoc> = decode_all(file_source::source(&self.poc_files)).await?;
+        let packets: Vec<IotValidPacket> =
+            decode_all(file_source::source(&self.packet_files)).await?;
+        let reward_period = self.start.and_utc()..self.end.and_utc();
+
+        let old = reward_gateways(
+            pocs.iter().flat_map(GatewayPocShare::shares_from_poc),
+            &packets,
+            &reward_period,
+            self.iot_price,
+            &RewardSplit::default(),
+        );
+        let new_pocs = pocs
+            .into_iter()
+            .map(|poc| with_reward_units(poc, &params.reward_units))
+            .collect::<Result<Vec<_>>>()?;
+        let new = reward_gateways(
+            new_pocs.iter().flat_map(GatewayPocShare::shares_from_poc),
+            &packets,
+            &reward_period,
+            self.iot_price,
+            &params.split,
+        );
+
+        let gateways = diff(old, new);
+        let summary = Summary {
+            gateways: gateways.len(),
+            old: distribution(gateways.iter().map(|gateway| gateway.old_total)),
+            new: distribution(gateways.iter().map(|gateway| gateway.new_total)),
+        };
+
+        let out: Box<dyn Write> = match &self.out {
+            Some(path) => Box::new(std::fs::File::create(path)?),
+            None => Box::new(std::io::stdout()),
+        };
+        match self.format {
+            Format::Json => {
+                let simulation = Simulation {
+                    params,
+                    summary,
+                    gateways,
+                };
+                serde_json::to_writer_pretty(out, &simulation)?;
+            }
+            Format::Csv => {
+                let mut wtr = csv::Writer::from_writer(out);
+                for gateway in gateways {
+                    wtr.serialize(gateway)?;
+                }
+                wtr.flush()?;
+                eprintln!("{}", serde_json::to_string_pretty(&summary)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The PoC with the reward units of its beaconer and selected witnesses
+/// recomputed the way the runner does, under the given parameters
+fn with_reward_units(mut poc: IotPoc, params: &RewardUnitParams) -> Result<IotPoc> {
+    let num_witnesses = poc.selected_witnesses.len() as u32;
+    let witness_reward_unit = poc_per_witness_reward_unit(num_witnesses, params)?;
+    poc.beacon_report.reward_unit = poc_beaconer_reward_unit(num_witnesses, params)?;
+    for witness in poc.selected_witnesses.iter_mut() {
+        witness.reward_unit = match witness.status {
+            VerificationStatus::Valid => witness_reward_unit,
+            VerificationStatus::Invalid => Decimal::ZERO,
+        };
+    }
+    Ok(poc)
+}
+
+/// The rewards of every gateway in the period, from its PoC shares and the
+/// data transfer shares of the packets
+fn reward_gateways(
+    poc_shares: impl Iterator<Item = GatewayPocShare>,
+    packets: &[IotValidPacket],
+    reward_period: &Range<DateTime<Utc>>,
+    iot_price: Decimal,
+    split: &RewardSplit,
+) -> HashMap<PublicKeyBinary, Rewards> {
+    let in_period = |timestamp: DateTime<Utc>| {
+        timestamp > reward_period.start && timestamp <= reward_period.end
+    };
+    // shares are unique by gateway and PoC in the db, and by packet for data
+    // transfer, the last one written wins
+    let poc_shares: HashMap<_, _> = poc_shares
+        .filter(|share| in_period(share.reward_timestamp))
+        .map(|share| ((share.hotspot_key.clone(), share.poc_id.clone()), share))
+        .collect();
+    let dc_shares: HashMap<_, _> = packets
+        .iter()
+        .filter(|packet| packet.num_dcs > 0)
+        .map(GatewayDCShare::share_from_packet)
+        .filter(|share| in_period(share.reward_timestamp))
+        .map(|share| (share.id.clone(), share))
+        .collect();
+
+    let mut shares: HashMap<PublicKeyBinary, RewardShares> = HashMap::new();
+    for share in poc_shares.values() {
+        shares
+            .entry(share.hotspot_key.clone())
+            .or_default()
+            .add_poc_reward(share);
+    }
+    for share in dc_shares.values() {
+        shares
+            .entry(share.hotspot_key.clone())
+            .or_default()
+            .add_dc_reward(share);
+    }
+
+    let gateway_shares = GatewayShares { shares };
+    let (beacon_rewards_per_share, witness_rewards_per_share, dc_transfer_rewards_per_share) =
+        gateway_shares.rewards_per_share_with_split(reward_period, iot_price, split);
+    gateway_shares
+        .into_iot_reward_shares(
+            reward_period,
+            beacon_rewards_per_share,
+            witness_rewards_per_share,
+            dc_transfer_rewards_per_share,
+        )
+        .filter_map(|(_, reward_share)| match reward_share.reward {
+            Some(ProtoReward::GatewayReward(reward)) => Some((
+                PublicKeyBinary::from(reward.hotspot_key),
+                Rewards {
+                    beacon: reward.beacon_amount,
+                    witness: reward.witness_amount,
+                    dc_transfer: reward.dc_transfer_amount,
+                },
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+fn distribution(rewards: impl Iterator<Item = u64>) -> Distribution {
+    let mut rewards: Vec<u64> = rewards.collect();
+    rewards.sort_unstable();
+    Distribution {
+        total: rewards.iter().sum(),
+        percentiles: PERCENTILES
+            .iter()
+            .map(|p| (format!("p{p}"), percentile(&rewards, *p)))
+            .collect(),
+        gini: gini(&rewards),
+    }
+}
+
+/// Nearest rank percentile of sorted values
+fn percentile(sorted: &[u64], p: u8) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (usize::from(p) * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Gini coefficient of sorted values, 0 if they are all equal and close to 1
+/// if a single one has all of the total
+fn gini(sorted: &[u64]) -> f64 {
+    let total: u128 = sorted.iter().map(|value| u128::from(*value)).sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let n = sorted.len() as f64;
+    let weighted: u128 = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, value)| (i as u128 + 1) * u128::from(*value))
+        .sum();
+    (2.0 * weighted as f64) / (n * total as f64) - (n + 1.0) / n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use file_store::{
+        iot_beacon_report::IotBeaconReport,
+        iot_valid_poc::{IotValidBeaconReport, IotVerifiedWitnessReport},
+        iot_witness_report::IotWitnessReport,
+    };
+    use helium_proto::{
+        services::poc_lora::{InvalidParticipantSide, InvalidReason},
+        DataRate,
+    };
+    use rust_decimal_macros::dec;
+    use std::str::FromStr;
+
+    const PUBKEY1: &str = "112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf";
+    const PUBKEY2: &str = "11z69eJ3czc92k6snrfR9ek7g2uRWXosFbnG9v4bXgwhfUCivUo";
+    const PUBKEY3: &str = "112bUGwooPd1dCDd3h3yZwskjxCzBsQNKeaJTuUF4hSgYedcsFa9";
+    const PUBKEY4: &str = "13ABbtvMrRK8jgYrT3h6Y9Zu44nS6829kzsamiQn9Eefeu3VAZs";
+
+    fn reward_period() -> Range<DateTime<Utc>> {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        start..start + Duration::hours(24)
+    }
+
+    fn pub_key(pubkey: &str) -> PublicKeyBinary {
+        PublicKeyBinary::from_str(pubkey).unwrap()
+    }
+
+    fn witness(
+        pubkey: &str,
+        status: VerificationStatus,
+        received_timestamp: DateTime<Utc>,
+    ) -> IotVerifiedWitnessReport {
+        IotVerifiedWitnessReport {
+            received_timestamp,
+            status,
+            report: IotWitnessReport {
+                pub_key: pub_key(pubkey),
+                data: vec![],
+                timestamp: received_timestamp,
+                tmst: 0,
+                signal: -1080,
+                snr: 35,
+                frequency: 867900032,
+                datarate: DataRate::Sf12bw125,
+                signature: vec![],
+            },
+            location: Some(631615575095659519),
+            gain: 12,
+            elevation: 10,
+            hex_scale: Decimal::ONE,
+            reward_unit: Decimal::ONE,
+            invalid_reason: InvalidReason::ReasonNone,
+            participant_side: InvalidParticipantSide::SideNone,
+            invalid_details: None,
+        }
+    }
+
+    fn poc(
+        poc_id: &[u8],
+        beaconer: &str,
+        received_timestamp: DateTime<Utc>,
+        witnesses: Vec<IotVerifiedWitnessReport>,
+    ) -> IotPoc {
+        IotPoc {
+            poc_id: poc_id.to_vec(),
+            beacon_report: IotValidBeaconReport {
+                received_timestamp,
+                location: Some(631615575095659519),
+                gain: 12,
+                elevation: 10,
+                hex_scale: Decimal::ONE,
+                report: IotBeaconReport {
+                    pub_key: pub_key(beaconer),
+                    local_entropy: vec![],
+                    remote_entropy: vec![],
+                    data: poc_id.to_vec(),
+                    frequency: 867900000,
+                    channel: 0,
+                    datarate: DataRate::Sf12bw125,
+                    tx_power: 8,
+                    timestamp: received_timestamp,
+                    signature: vec![],
+                    tmst: 0,
+                },
+                reward_unit: Decimal::ONE,
+            },
+            selected_witnesses: witnesses,
+            unselected_witnesses: vec![],
+        }
+    }
+
+    fn packet(gateway: &str, num_dcs: u32, packet_timestamp: DateTime<Utc>) -> IotValidPacket {
+        IotValidPacket {
+            payload_size: 24,
+            gateway: pub_key(gateway),
+            payload_hash: vec![num_dcs as u8],
+            num_dcs,
+            packet_timestamp,
+        }
+    }
+
+    #[test]
+    fn recomputes_reward_units_of_valid_witnesses() {
+        let received = reward_period().start + Duration::hours(1);
+        let mut witnesses: Vec<_> = (0..4)
+            .map(|_| witness(PUBKEY2, VerificationStatus::Valid, received))
+            .collect();
+        witnesses.push(witness(PUBKEY3, VerificationStatus::Invalid, received));
+        let poc = poc(b"poc", PUBKEY1, received, witnesses);
+
+        // more witnesses than the redundancy decay the reward units
+        let params = RewardUnitParams {
+            witness_redundancy: 4,
+            decay_rate: dec!(0.8),
+            tx_reward_unit_cap: dec!(2),
+        };
+        let decayed = with_reward_units(poc.clone(), &params).unwrap();
+        assert_eq!(dec!(1.2), decayed.beacon_report.reward_unit);
+        let witness_units: Vec<_> = decayed
+            .selected_witnesses
+            .iter()
+            .map(|witness| witness.reward_unit)
+            .collect();
+        assert_eq!(vec![dec!(0.76); 4], witness_units[..4]);
+        assert_eq!(Decimal::ZERO, witness_units[4]);
+
+        // fewer witnesses than the redundancy scale the beaconer's reward unit
+        let params = RewardUnitParams {
+            witness_redundancy: 8,
+            ..params
+        };
+        let scaled = with_reward_units(poc, &params).unwrap();
+        assert_eq!(dec!(0.625), scaled.beacon_report.reward_unit);
+        assert_eq!(Decimal::ONE, scaled.selected_witnesses[0].reward_unit);
+        assert_eq!(Decimal::ZERO, scaled.selected_witnesses[4].reward_unit);
+    }
+
+    #[test]
+    fn rewards_shares_of_the_period_once() {
+        let period = reward_period();
+        let received = period.start + Duration::hours(1);
+        let pocs = vec![
+            poc(
+                b"poc1",
+                PUBKEY1,
+                received,
+                vec![witness(PUBKEY2, VerificationStatus::Valid, received)],
+            ),
+            // before the period
+            poc(b"poc2", PUBKEY3, period.start, vec![]),
+        ];
+        let packets = vec![
+            packet(PUBKEY4, 1000, received),
+            packet(PUBKEY3, 0, received),
+            packet(PUBKEY3, 1000, period.end + Duration::seconds(1)),
+        ];
+        let rewards = |pocs: &[IotPoc], split: &RewardSplit| {
+            reward_gateways(
+                pocs.iter().flat_map(GatewayPocShare::shares_from_poc),
+                &packets,
+                &period,
+                dec!(1.0),
+                split,
+            )
+        };
+
+        let old = rewards(&pocs, &RewardSplit::default());
+        assert_eq!(3, old.len());
+        assert!(!old.contains_key(&pub_key(PUBKEY3)));
+        let beaconer = old[&pub_key(PUBKEY1)];
+        assert!(beaconer.beacon > 0);
+        assert_eq!(0, beaconer.witness);
+        let witness = old[&pub_key(PUBKEY2)];
+        assert_eq!(0, witness.beacon);
+        assert!(witness.witness > 0);
+        let data_transfer = old[&pub_key(PUBKEY4)];
+        assert!(data_transfer.dc_transfer > 0);
+        assert_eq!(0, data_transfer.beacon + data_transfer.witness);
+
+        // shares written twice are rewarded once
+        let duplicated: Vec<_> = pocs.iter().chain(pocs.iter()).cloned().collect();
+        assert_eq!(old, rewards(&duplicated, &RewardSplit::default()));
+
+        let split = RewardSplit {
+            witness_percent: RewardSplit::default().witness_percent * dec!(2),
+            ..RewardSplit::default()
+        };
+        let new = rewards(&pocs, &split);
+        assert_eq!(beaconer, new[&pub_key(PUBKEY1)]);
+        assert!(new[&pub_key(PUBKEY2)].witness > witness.witness);
+    }
+
+    #[test]
+    fn percentiles_are_nearest_rank() {
+        let rewards: Vec<u64> = (1..=20).collect();
+        assert_eq!(2, percentile(&rewards, 10));
+        assert_eq!(10, percentile(&rewards, 50));
+        assert_eq!(20, percentile(&rewards, 99));
+        assert_eq!(0, percentile(&[], 50));
+    }
+
+    #[test]
+    fn gini_measures_inequality() {
+        assert_eq!(0.0, gini(&[5, 5, 5, 5]));
+        assert_eq!(0.0, gini(&[0, 0]));
+        assert!((gini(&[0, 0, 0, 100]) - 0.75).abs() < 1e-9);
+        assert!((gini(&[1, 2, 3, 4]) - 0.25).abs() < 1e-9);
+    }
+}
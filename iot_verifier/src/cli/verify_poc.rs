@@ -0,0 +1,764 @@
+//! Offline re-verification of PoCs, to explain why a beacon or witness was
+//! found valid or invalid.
+//!
+//! Beacons, witnesses and entropy are read from local files or streamed from
+//! the ingest and entropy buckets for a time range. Gateways and region
+//! params are loaded from the latest snapshot written by the verifier at or
+//! before the given time, from the output bucket or a local directory,
+//! so PoCs are verified against the state they were verified against.
+//!
+//! The last beacon and witness times the beacon schedule and reciprocity
+//! checks depend on, and the seeds and per hex transmit scales of density
+//! scaled beacon schedules, are not part of the snapshot and are optionally
+//! read from a JSON file:
+//!
+//! ```json
+//! {
+//!   "last_beacons": {"112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf": "2024-03-01T00:00:00Z"},
+//!   "last_witnesses": {},
+//!   "schedule_seeds": {"2024-03-01T00:00:00Z": "c2VlZA=="},
+//!   "schedule_tx_scales": {"2024-03-01T00:00:00Z": {"631181359475650047": "0.5"}}
+//! }
+//! ```
+//!
+//! Hexes not in the transmit scales of an epoch are scaled by the default
+//! transmit scale.
+//!
+//! Every rule in effect is evaluated for the beacon and each of its
+//! witnesses, and the result is printed as json.
+
+use crate::{
+    beacon_schedule::{self, BeaconCadence, BeaconSchedule},
+    cli::decode_stream,
+    entropy::ENTROPY_LIFESPAN,
+    last_beacon::LastBeacon,
+    poc::{
+        do_beacon_verifications, do_witness_verifications, explain_beacon_verifications,
+        explain_witness_verifications, verify_reciprocity, RuleOutcome, WitnessMeasurements,
+        DEFAULT_TX_SCALE,
+    },
+    poc_rules::PocRules,
+    snapshot::Snapshot,
+    Settings,
+};
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use denylist::DenyList;
+use file_store::{
+    entropy_report::EntropyReport, file_source, iot_beacon_report::IotBeaconIngestReport,
+    iot_witness_report::IotWitnessIngestReport, BytesMutStream, FileStore, FileType,
+};
+use futures::{future, TryStreamExt};
+use helium_crypto::PublicKeyBinary;
+use helium_proto::services::poc_lora::InvalidReason;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+/// Re-verify PoCs and explain the outcome of every verification rule
+#[derive(Debug, clap::Args)]
+pub struct Cmd {
+    /// Load the latest snapshot written at or before this time. Defaults to
+    /// the start of the time range
+    #[clap(long)]
+    snapshot_at: Option<NaiveDateTime>,
+    /// Local directory to load the snapshot from instead of the output bucket
+    #[clap(long)]
+    snapshot_dir: Option<PathBuf>,
+    /// JSON file of the last beacon and witness times and beacon schedule
+    /// seeds to verify against
+    #[clap(long)]
+    state: Option<PathBuf>,
+    /// Beacon ingest report files. If none are given, beacons are read from
+    /// the ingest bucket for the given time range
+    #[clap(long = "beacons")]
+    beacon_files: Vec<PathBuf>,
+    /// Witness ingest report files
+    #[clap(long = "witnesses")]
+    witness_files: Vec<PathBuf>,
+    /// Entropy report files
+    #[clap(long = "entropy")]
+    entropy_files: Vec<PathBuf>,
+    /// Start of the time range to read reports for from the buckets
+    #[clap(long)]
+    after: Option<NaiveDateTime>,
+    /// End of the time range to read reports for from the buckets
+    #[clap(long)]
+    before: Option<NaiveDateTime>,
+    /// Only verify the beacons of this gateway
+    #[clap(long)]
+    beaconer: Option<PublicKeyBinary>,
+}
+
+/// Report files, or bucket files listed in a time range, streamed as they
+/// are read
+struct Sources {
+    beacons: BytesMutStream,
+    witnesses: BytesMutStream,
+    entropy: BytesMutStream,
+}
+
+impl Cmd {
+    pub async fn run(&self, settings: &Settings) -> Result<()> {
+        let snapshot_store = match &self.snapshot_dir {
+            Some(dir) => FileStore::new_local(dir).await?,
+            None => FileStore::from_settings(&settings.output).await?,
+        };
+
+        let mut deny_list = DenyList::new(&settings.denylist)?;
+        if let Err(err) = deny_list
+            .update_to_latest(&settings.denylist.denylist_url)
+            .await
+        {
+            tracing::warn!("failed to update denylist, verifying with local filter: {err:?}");
+        }
+
+        let verifier = Verifier {
+            snapshot: self.snapshot(&snapshot_store).await?,
+            state: self.state().await?,
+            deny_list,
+            rules: PocRules::new(settings.poc_rules.clone()),
+            beacon_interval: settings.beacon_interval()?,
+        };
+        let sources = self.sources(settings).await?;
+        let explanations = self.explain(&verifier, sources).await?;
+
+        println!("{}", serde_json::to_string_pretty(&explanations)?);
+        Ok(())
+    }
+
+    /// Explain the beacons of the sources, of the given beaconer if any. Only
+    /// the witnesses and entropy of those beacons are kept while the sources
+    /// are streamed
+    async fn explain(
+        &self,
+        verifier: &Verifier,
+        sources: Sources,
+    ) -> Result<Vec<BeaconExplanation>> {
+        let beacons: Vec<IotBeaconIngestReport> = decode_stream(sources.beacons)
+            .try_filter(|beacon: &IotBeaconIngestReport| {
+                future::ready(
+                    self.beaconer
+                        .as_ref()
+                        .map_or(true, |beaconer| beacon.report.pub_key == *beaconer),
+                )
+            })
+            .try_collect()
+            .await?;
+        let beacon_data: HashSet<Vec<u8>> = beacons
+            .iter()
+            .map(|beacon| beacon.report.data.clone())
+            .collect();
+        let remote_entropy: HashSet<Vec<u8>> = beacons
+            .iter()
+            .map(|beacon| beacon.report.remote_entropy.clone())
+            .collect();
+
+        let mut witnesses: HashMap<Vec<u8>, Vec<IotWitnessIngestReport>> = HashMap::new();
+        decode_stream(sources.witnesses)
+            .try_for_each(|witness: IotWitnessIngestReport| {
+                if beacon_data.contains(&witness.report.data) {
+                    witnesses
+                        .entry(witness.report.data.clone())
+                        .or_default()
+                        .push(witness);
+                }
+                future::ready(Ok(()))
+            })
+            .await?;
+        let mut entropy: HashMap<Vec<u8>, EntropyReport> = HashMap::new();
+        decode_stream(sources.entropy)
+            .try_for_each(|report: EntropyReport| {
+                if remote_entropy.contains(&report.data) {
+                    entropy.insert(report.data.clone(), report);
+                }
+                future::ready(Ok(()))
+            })
+            .await?;
+
+        Ok(beacons
+            .into_iter()
+            .map(|beacon| {
+                let entropy = entropy.get(&beacon.report.remote_entropy);
+                let mut beacon_witnesses =
+                    witnesses.remove(&beacon.report.data).unwrap_or_default();
+                beacon_witnesses.sort_by_key(|witness| witness.received_timestamp);
+                verifier.explain(beacon, entropy, beacon_witnesses)
+            })
+            .collect())
+    }
+
+    async fn snapshot(&self, store: &FileStore) -> Result<Snapshot> {
+        let at = self
+            .snapshot_at
+            .or(self.after)
+            .ok_or_else(|| anyhow!("either a snapshot time or an after time is required"))?
+            .and_utc();
+        Ok(Snapshot::load(store, at).await?)
+    }
+
+    async fn state(&self) -> Result<State> {
+        let Some(path) = &self.state else {
+            return Ok(State::default());
+        };
+        let state: StateFile = serde_json::from_slice(&tokio::fs::read(path).await?)?;
+        state.try_into()
+    }
+
+    async fn sources(&self, settings: &Settings) -> Result<Sources> {
+        if !self.beacon_files.is_empty() {
+            return Ok(self.file_sources());
+        }
+        let (Some(after), Some(before)) = (self.after, self.before) else {
+            return Err(anyhow!(
+                "either beacon files or an after and before time range are required"
+            ));
+        };
+        let (after, before) = (after.and_utc(), before.and_utc());
+        let ingest = FileStore::from_settings(&settings.ingest).await?;
+        let entropy = FileStore::from_settings(&settings.entropy).await?;
+        // witnesses can be written out up to a rollup period after their
+        // beacon, and entropy is valid up to its lifespan after its timestamp
+        let witness_before = before + settings.ingestor_rollup_time();
+        let entropy_after = after - Duration::seconds(ENTROPY_LIFESPAN);
+        Ok(Sources {
+            beacons: ingest.source(ingest.list(
+                FileType::IotBeaconIngestReport.to_str(),
+                after,
+                before,
+            )),
+            witnesses: ingest.source(ingest.list(
+                FileType::IotWitnessIngestReport.to_str(),
+                after,
+                witness_before,
+            )),
+            entropy: entropy.source(entropy.list(
+                FileType::EntropyReport.to_str(),
+                entropy_after,
+                before,
+            )),
+        })
+    }
+
+    fn file_sources(&self) -> Sources {
+        Sources {
+            beacons: file_source::source(&self.beacon_files),
+            witnesses: file_source::source(&self.witness_files),
+            entropy: file_source::source(&self.entropy_files),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StateFile {
+    /// time of the last valid beacon of a gateway, before the PoCs verified
+    #[serde(default)]
+    last_beacons: HashMap<PublicKeyBinary, DateTime<Utc>>,
+    /// time of the last valid witness of a gateway, before the PoCs verified
+    #[serde(default)]
+    last_witnesses: HashMap<PublicKeyBinary, DateTime<Utc>>,
+    /// base64 seed of the beacon schedules of an epoch, by epoch start
+    #[serde(default)]
+    schedule_seeds: HashMap<DateTime<Utc>, String>,
+    /// transmit scale of a hex fixed for the beacon schedules of an epoch, by
+    /// epoch start
+    #[serde(default)]
+    schedule_tx_scales: HashMap<DateTime<Utc>, HashMap<u64, Decimal>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    last_beacons: HashMap<PublicKeyBinary, DateTime<Utc>>,
+    last_witnesses: HashMap<PublicKeyBinary, DateTime<Utc>>,
+    schedule_seeds: HashMap<DateTime<Utc>, Vec<u8>>,
+    schedule_tx_scales: HashMap<DateTime<Utc>, HashMap<u64, Decimal>>,
+}
+
+impl TryFrom<StateFile> for State {
+    type Error = anyhow::Error;
+
+    fn try_from(state: StateFile) -> Result<Self> {
+        let schedule_seeds = state
+            .schedule_seeds
+            .into_iter()
+            .map(|(epoch_start, seed)| {
+                let seed = base64::engine::general_purpose::STANDARD
+                    .decode(seed)
+                    .map_err(|err| anyhow!("invalid schedule seed of {epoch_start}: {err}"))?;
+                Ok((epoch_start, seed))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            last_beacons: state.last_beacons,
+            last_witnesses: state.last_witnesses,
+            schedule_seeds,
+            schedule_tx_scales: state.schedule_tx_scales,
+        })
+    }
+}
+
+struct Verifier {
+    snapshot: Snapshot,
+    state: State,
+    deny_list: DenyList,
+    rules: PocRules,
+    beacon_interval: Duration,
+}
+
+#[derive(Debug, Serialize)]
+struct BeaconExplanation {
+    pub_key: PublicKeyBinary,
+    received_timestamp: DateTime<Utc>,
+    entropy_timestamp: Option<DateTime<Utc>>,
+    /// the reason the beacon is invalid, none if valid
+    invalid_reason: Option<&'static str>,
+    rules: Vec<RuleOutcome>,
+    witnesses: Vec<WitnessExplanation>,
+}
+
+#[derive(Debug, Serialize)]
+struct WitnessExplanation {
+    pub_key: PublicKeyBinary,
+    received_timestamp: DateTime<Utc>,
+    /// the reason the witness is invalid, none if valid
+    invalid_reason: Option<&'static str>,
+    rules: Vec<RuleOutcome>,
+    measurements: Option<WitnessMeasurements>,
+}
+
+impl Verifier {
+    /// Verify a PoC the way the runner does, explaining the outcome of every
+    /// rule in effect when its beacon was received
+    fn explain(
+        &self,
+        beacon: IotBeaconIngestReport,
+        entropy: Option<&EntropyReport>,
+        witnesses: Vec<IotWitnessIngestReport>,
+    ) -> BeaconExplanation {
+        let mut explanation = BeaconExplanation {
+            pub_key: beacon.report.pub_key.clone(),
+            received_timestamp: beacon.received_timestamp,
+            entropy_timestamp: entropy.map(|entropy| entropy.timestamp),
+            invalid_reason: None,
+            rules: vec![],
+            witnesses: vec![],
+        };
+        let Some(entropy) = entropy else {
+            // the runner does not verify a beacon until its entropy is known
+            explanation.invalid_reason = Some("missing_entropy");
+            return explanation;
+        };
+        let Some(beaconer_info) = self.snapshot.gateway(&beacon.report.pub_key) else {
+            explanation.invalid_reason = Some(InvalidReason::GatewayNotFound.as_str_name());
+            return explanation;
+        };
+        let Some(ref beaconer_metadata) = beaconer_info.metadata else {
+            explanation.invalid_reason = Some(InvalidReason::NotAsserted.as_str_name());
+            return explanation;
+        };
+        let Some(region_params) = self.snapshot.region_params(beaconer_metadata.region) else {
+            explanation.invalid_reason = Some("missing_region_params");
+            return explanation;
+        };
+
+        let rules = self.rules.active_at(beacon.received_timestamp);
+        let entropy_start = entropy.timestamp;
+        let entropy_end = entropy_start + Duration::seconds(ENTROPY_LIFESPAN);
+        let entropy_version = entropy.version as i32;
+        let beacon_cadence = match rules.beacon_schedule() {
+            Some(params) => {
+                let epoch_start = beacon_schedule::epoch_start(beacon.received_timestamp);
+                let Some(seed) = self.state.schedule_seeds.get(&epoch_start) else {
+                    // the runner does not verify a beacon until its epoch is seeded
+                    explanation.invalid_reason = Some("missing_schedule_seed");
+                    return explanation;
+                };
+                let Some(tx_scales) = self.state.schedule_tx_scales.get(&epoch_start) else {
+                    // nor until the transmit scales of its epoch are fixed
+                    explanation.invalid_reason = Some("missing_schedule_tx_scales");
+                    return explanation;
+                };
+                let tx_scale = tx_scales
+                    .get(&beaconer_metadata.location)
+                    .copied()
+                    .unwrap_or(*DEFAULT_TX_SCALE);
+                BeaconCadence::Scheduled(BeaconSchedule::new(
+                    seed,
+                    &beacon.report.pub_key,
+                    epoch_start,
+                    tx_scale,
+                    &params,
+                ))
+            }
+            None => BeaconCadence::Interval(self.beacon_interval),
+        };
+        let last_beacon = || {
+            self.state
+                .last_beacons
+                .get(&beacon.report.pub_key)
+                .map(|timestamp| LastBeacon {
+                    id: beacon.report.pub_key.clone(),
+                    timestamp: *timestamp,
+                })
+        };
+        explanation.rules = explain_beacon_verifications(
+            &rules,
+            &self.deny_list,
+            entropy_start,
+            entropy_end,
+            entropy_version,
+            last_beacon(),
+            &beacon,
+            beaconer_info,
+            region_params,
+            &beacon_cadence,
+        )
+        .unwrap_or_default();
+        explanation.invalid_reason = match do_beacon_verifications(
+            &rules,
+            &self.deny_list,
+            entropy_start,
+            entropy_end,
+            entropy_version,
+            last_beacon(),
+            &beacon,
+            beaconer_info,
+            region_params,
+            &beacon_cadence,
+        ) {
+            Err(invalid) => Some(invalid.reason().as_str_name()),
+            Ok(())
+                if !verify_reciprocity(
+                    beacon.received_timestamp,
+                    self.state
+                        .last_witnesses
+                        .get(&beacon.report.pub_key)
+                        .copied(),
+                ) || witnesses.is_empty() =>
+            {
+                Some(InvalidReason::GatewayNoValidWitnesses.as_str_name())
+            }
+            Ok(()) => None,
+        };
+        // witnesses of an invalid beacon are not verified
+        if explanation.invalid_reason.is_some() {
+            return explanation;
+        }
+
+        let Some(witness_first_ts) = witnesses.first().map(|w| w.received_timestamp) else {
+            return explanation;
+        };
+        let mut seen = Vec::new();
+        for witness in witnesses {
+            let mut witness_explanation = WitnessExplanation {
+                pub_key: witness.report.pub_key.clone(),
+                received_timestamp: witness.received_timestamp,
+                invalid_reason: None,
+                rules: vec![],
+                measurements: None,
+            };
+            if seen.contains(&witness.report.pub_key) {
+                witness_explanation.invalid_reason = Some(InvalidReason::Duplicate.as_str_name());
+                explanation.witnesses.push(witness_explanation);
+                continue;
+            }
+            seen.push(witness.report.pub_key.clone());
+            witness_explanation.invalid_reason =
+                match self.snapshot.gateway(&witness.report.pub_key) {
+                    None => Some(InvalidReason::GatewayNotFound.as_str_name()),
+                    Some(witness_info) => {
+                        if let Ok(witness) = explain_witness_verifications(
+                            &rules,
+                            &self.deny_list,
+                            entropy_start,
+                            entropy_end,
+                            &witness,
+                            witness_info,
+                            &beacon,
+                            beaconer_metadata,
+                            witness_first_ts,
+                        ) {
+                            witness_explanation.rules = witness.rules;
+                            witness_explanation.measurements = Some(witness.measurements);
+                        }
+                        match do_witness_verifications(
+                            &rules,
+                            &self.deny_list,
+                            entropy_start,
+                            entropy_end,
+                            &witness,
+                            witness_info,
+                            &beacon,
+                            beaconer_metadata,
+                            witness_first_ts,
+                        ) {
+                            Err(invalid) => Some(invalid.reason().as_str_name()),
+                            Ok(())
+                                if !verify_reciprocity(
+                                    witness.received_timestamp,
+                                    self.state
+                                        .last_beacons
+                                        .get(&witness.report.pub_key)
+                                        .copied(),
+                                ) =>
+                            {
+                                Some(InvalidReason::GatewayNoValidBeacons.as_str_name())
+                            }
+                            Ok(()) => None,
+                        }
+                    }
+                };
+            explanation.witnesses.push(witness_explanation);
+        }
+        explanation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use file_store::{
+        file_sink::FileSinkBuilder,
+        iot_beacon_report::IotBeaconReport,
+        iot_snapshot::{
+            IotRegionParams, IotRegionParamsV1, IotSnapshotManifest, IotSnapshotManifestV1,
+        },
+        iot_witness_report::IotWitnessReport,
+    };
+    use helium_proto::{
+        services::{
+            iot_config::{
+                GatewayInfo as GatewayInfoProto, GatewayMetadata as GatewayMetadataProto,
+            },
+            poc_lora::{
+                LoraBeaconIngestReportV1, LoraBeaconReportReqV1, LoraWitnessIngestReportV1,
+                LoraWitnessReportReqV1,
+            },
+        },
+        BlockchainRegionParamV1, DataRate, EntropyReportV1, Region as ProtoRegion,
+    };
+    use std::{path::Path, str::FromStr};
+    use tempfile::TempDir;
+
+    const PUBKEY1: &str = "112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf";
+    const PUBKEY2: &str = "112bUGwooPd1dCDd3h3yZwskjxCzBsQNKeaJTuUF4hSgYedcsFa9";
+
+    #[derive(Debug, Parser)]
+    struct Cli {
+        #[clap(flatten)]
+        cmd: Cmd,
+    }
+
+    /// Write the messages to a committed file of the given type, returning
+    /// the names of the files written
+    async fn write_file<T: prost::Message>(
+        dir: &Path,
+        file_type: FileType,
+        msgs: Vec<T>,
+    ) -> Vec<String> {
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let (sink, server) = FileSinkBuilder::new(file_type, dir, "fake_metric")
+            .auto_commit(false)
+            .create()
+            .await
+            .expect("failed to create file sink");
+        let server = tokio::spawn(server.run(shutdown_listener));
+        for msg in msgs {
+            sink.write(msg, []).await.expect("failed to write");
+        }
+        let files = sink.commit().await.unwrap().await.unwrap().unwrap();
+        shutdown_trigger.trigger();
+        server.await.unwrap().unwrap();
+        files
+    }
+
+    fn beacon(pubkey: &str, data: &[u8], remote_entropy: &[u8]) -> LoraBeaconIngestReportV1 {
+        let received_timestamp = Utc::now();
+        let report = IotBeaconIngestReport {
+            received_timestamp,
+            report: IotBeaconReport {
+                pub_key: PublicKeyBinary::from_str(pubkey).unwrap(),
+                local_entropy: vec![1, 2, 3],
+                remote_entropy: remote_entropy.to_vec(),
+                data: data.to_vec(),
+                frequency: 867900000,
+                channel: 0,
+                datarate: DataRate::Sf12bw125,
+                tx_power: 8,
+                timestamp: received_timestamp,
+                signature: vec![],
+                tmst: 0,
+            },
+        };
+        LoraBeaconIngestReportV1 {
+            received_timestamp: received_timestamp.timestamp_millis() as u64,
+            report: Some(LoraBeaconReportReqV1::from(report)),
+        }
+    }
+
+    fn witness(pubkey: &str, data: &[u8]) -> LoraWitnessIngestReportV1 {
+        let received_timestamp = Utc::now();
+        let report = IotWitnessReport {
+            pub_key: PublicKeyBinary::from_str(pubkey).unwrap(),
+            data: data.to_vec(),
+            timestamp: received_timestamp,
+            tmst: 0,
+            signal: -1080,
+            snr: 35,
+            frequency: 867900032,
+            datarate: DataRate::Sf12bw125,
+            signature: vec![],
+        };
+        LoraWitnessIngestReportV1 {
+            received_timestamp: received_timestamp.timestamp_millis() as u64,
+            report: Some(LoraWitnessReportReqV1::from(report)),
+        }
+    }
+
+    fn entropy(data: &[u8]) -> EntropyReportV1 {
+        EntropyReportV1 {
+            data: data.to_vec(),
+            timestamp: Utc::now().timestamp() as u64,
+            version: 0,
+        }
+    }
+
+    /// Write a snapshot of a single gateway asserted in a region whose
+    /// params are not in the snapshot
+    async fn write_snapshot(dir: &Path) {
+        let gateway = GatewayInfoProto {
+            address: PublicKeyBinary::from_str(PUBKEY1).unwrap().into(),
+            metadata: Some(GatewayMetadataProto {
+                location: "8c2681a3064edff".to_string(),
+                elevation: 10,
+                gain: 12,
+                region: ProtoRegion::Us915.into(),
+            }),
+            is_full_hotspot: true,
+        };
+        let region_params = IotRegionParamsV1::from(IotRegionParams {
+            region: ProtoRegion::Eu868,
+            params: vec![BlockchainRegionParamV1 {
+                channel_frequency: 867_100_000,
+                bandwidth: 125_000,
+                max_eirp: 160,
+                ..Default::default()
+            }],
+        });
+        let manifest = IotSnapshotManifest {
+            gateway_files: write_file(dir, FileType::IotGatewaySnapshot, vec![gateway]).await,
+            region_params_files: write_file(
+                dir,
+                FileType::IotRegionParamsSnapshot,
+                vec![region_params],
+            )
+            .await,
+            hex_density_files: vec![],
+        };
+        write_file(
+            dir,
+            FileType::IotSnapshotManifest,
+            vec![IotSnapshotManifestV1::from(manifest)],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn explains_pocs_from_files_against_a_snapshot() {
+        let snapshot_dir = TempDir::new().expect("Unable to create temp dir");
+        let reports_dir = TempDir::new().expect("Unable to create temp dir");
+        write_snapshot(snapshot_dir.path()).await;
+
+        let beacon_files = write_file(
+            reports_dir.path(),
+            FileType::IotBeaconIngestReport,
+            vec![
+                beacon(PUBKEY1, b"poc1", b"entropy1"),
+                beacon(PUBKEY2, b"poc2", b"entropy2"),
+                beacon(PUBKEY1, b"poc3", b"unknown"),
+            ],
+        )
+        .await;
+        let witness_files = write_file(
+            reports_dir.path(),
+            FileType::IotWitnessIngestReport,
+            vec![witness(PUBKEY2, b"poc1"), witness(PUBKEY1, b"other")],
+        )
+        .await;
+        let entropy_files = write_file(
+            reports_dir.path(),
+            FileType::EntropyReport,
+            vec![entropy(b"entropy1"), entropy(b"entropy2")],
+        )
+        .await;
+
+        let path = |files: &[String]| reports_dir.path().join(&files[0]);
+        let snapshot_at = (Utc::now() + Duration::seconds(1))
+            .naive_utc()
+            .format("%Y-%m-%dT%H:%M:%S")
+            .to_string();
+        let args = |beaconer: Option<&str>| {
+            let mut args = vec![
+                "verify-poc".to_string(),
+                "--snapshot-at".to_string(),
+                snapshot_at.clone(),
+                "--snapshot-dir".to_string(),
+                snapshot_dir.path().display().to_string(),
+                "--beacons".to_string(),
+                path(&beacon_files).display().to_string(),
+                "--witnesses".to_string(),
+                path(&witness_files).display().to_string(),
+                "--entropy".to_string(),
+                path(&entropy_files).display().to_string(),
+            ];
+            if let Some(beaconer) = beaconer {
+                args.extend(["--beaconer".to_string(), beaconer.to_string()]);
+            }
+            Cli::try_parse_from(args).expect("invalid args").cmd
+        };
+
+        let cmd = args(None);
+        let store = FileStore::new_local(snapshot_dir.path()).await.unwrap();
+        let verifier = Verifier {
+            snapshot: cmd.snapshot(&store).await.expect("no snapshot"),
+            state: cmd.state().await.unwrap(),
+            deny_list: DenyList::try_from(Vec::<PublicKeyBinary>::new()).unwrap(),
+            rules: PocRules::new(vec![]),
+            beacon_interval: Duration::hours(6),
+        };
+
+        let explanations = cmd.explain(&verifier, cmd.file_sources()).await.unwrap();
+        let outcomes: Vec<_> = explanations
+            .iter()
+            .map(|explanation| {
+                (
+                    explanation.pub_key.to_string(),
+                    explanation.invalid_reason,
+                    explanation.entropy_timestamp.is_some(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (PUBKEY1.to_string(), Some("missing_region_params"), true),
+                (
+                    PUBKEY2.to_string(),
+                    Some(InvalidReason::GatewayNotFound.as_str_name()),
+                    true
+                ),
+                (PUBKEY1.to_string(), Some("missing_entropy"), false),
+            ],
+            outcomes
+        );
+
+        let cmd = args(Some(PUBKEY2));
+        let explanations = cmd.explain(&verifier, cmd.file_sources()).await.unwrap();
+        assert_eq!(1, explanations.len());
+        assert_eq!(PUBKEY2, explanations[0].pub_key.to_string());
+    }
+}
@@ -0,0 +1,398 @@
+//! Read-only HTTP API over the hex density map, to explain the transmit scale
+//! of a hex or gateway.
+//!
+//! * `GET /v1/hex_scale` the time of the last refresh of the map and the
+//!   number of hexes in it
+//! * `GET /v1/hex_scale/hex/:hex` the scale of an asserted hex, given as an
+//!   h3 string or integer, and the occupancy of its parents it was computed
+//!   from
+//! * `GET /v1/hex_scale/gateway/:address` the same for the asserted hex of a
+//!   gateway
+//! * `GET /v1/beacon_schedule/gateway/:address?epoch=` the beacon slots of a
+//!   gateway in the epoch of the given time, the current epoch by default,
+//!   scaled by the transmit scales fixed for that epoch. Not found unless the
+//!   beacon schedule is density scaled at that time and the epoch is seeded
+//!   and has transmit scales
+//!
+//! Hexes not in the map are scaled by the default transmit scale, and are
+//! answered with `"default": true`.
+
+use crate::{
+    beacon_schedule::{self, BeaconSchedule, BeaconScheduleError},
+    gateway_cache::GatewayCache,
+    hex_density::{HexDensityMap, ResolutionOccupancy},
+    poc::DEFAULT_TX_SCALE,
+    poc_rules::PocRules,
+};
+use anyhow::Error;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use futures::future::{LocalBoxFuture, TryFutureExt};
+use h3o::CellIndex;
+use helium_crypto::PublicKeyBinary;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::{net::SocketAddr, str::FromStr};
+use task_manager::ManagedTask;
+
+/// HTTP server of the hex density API. Finishes right away if no listen
+/// address is configured
+pub struct DensityApi {
+    address: Option<SocketAddr>,
+    hex_density_map: HexDensityMap,
+    gateway_cache: GatewayCache,
+    pool: PgPool,
+    rules: PocRules,
+}
+
+#[derive(Clone)]
+struct ApiState {
+    hex_density_map: HexDensityMap,
+    gateway_cache: GatewayCache,
+    pool: PgPool,
+    rules: PocRules,
+}
+
+#[derive(Debug, Serialize)]
+struct StatusResponse {
+    refreshed_at: Option<DateTime<Utc>>,
+    hexes: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct HexScaleResponse {
+    hex: String,
+    scale: Decimal,
+    /// Whether the hex is not in the map and scaled by the default scale
+    default: bool,
+    refreshed_at: Option<DateTime<Utc>>,
+    /// Not known if the map was not computed by this verifier
+    resolutions: Option<Vec<ResolutionOccupancy>>,
+}
+
+#[derive(Debug, Serialize)]
+struct GatewayScaleResponse {
+    address: PublicKeyBinary,
+    /// None if the gateway has not asserted a location
+    hex_scale: Option<HexScaleResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleQuery {
+    epoch: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+struct BeaconScheduleResponse {
+    address: PublicKeyBinary,
+    #[serde(flatten)]
+    schedule: BeaconSchedule,
+}
+
+impl DensityApi {
+    pub fn new(
+        address: Option<SocketAddr>,
+        hex_density_map: HexDensityMap,
+        gateway_cache: GatewayCache,
+        pool: PgPool,
+        rules: PocRules,
+    ) -> Self {
+        Self {
+            address,
+            hex_density_map,
+            gateway_cache,
+            pool,
+            rules,
+        }
+    }
+
+    fn router(self) -> Router {
+        Router::new()
+            .route("/v1/hex_scale", get(status))
+            .route("/v1/hex_scale/hex/:hex", get(hex_scale))
+            .route("/v1/hex_scale/gateway/:address", get(gateway_scale))
+            .route(
+                "/v1/beacon_schedule/gateway/:address",
+                get(gateway_beacon_schedule),
+            )
+            .with_state(ApiState {
+                hex_density_map: self.hex_density_map,
+                gateway_cache: self.gateway_cache,
+                pool: self.pool,
+                rules: self.rules,
+            })
+    }
+}
+
+impl ManagedTask for DensityApi {
+    fn start_task(
+        self: Box<Self>,
+        shutdown: triggered::Listener,
+    ) -> LocalBoxFuture<'static, anyhow::Result<()>> {
+        Box::pin(async move {
+            let Some(address) = self.address else {
+                return Ok(());
+            };
+            tracing::info!("density api listening on {address}");
+            let listener = tokio::net::TcpListener::bind(address).await?;
+            let router = self.router().layer(poc_metrics::request_layer!(
+                "iot_verifier_density_api_connection"
+            ));
+            axum::serve(listener, router)
+                .with_graceful_shutdown(shutdown)
+                .map_err(Error::from)
+                .await
+        })
+    }
+}
+
+async fn status(State(state): State<ApiState>) -> Json<StatusResponse> {
+    Json(StatusResponse {
+        refreshed_at: state.hex_density_map.refreshed_at().await,
+        hexes: state.hex_density_map.hex_count().await,
+    })
+}
+
+async fn hex_scale(State(state): State<ApiState>, Path(hex): Path<String>) -> Response {
+    let hex = match CellIndex::from_str(&hex).ok().or_else(|| {
+        u64::from_str(&hex)
+            .ok()
+            .and_then(|hex| CellIndex::try_from(hex).ok())
+    }) {
+        Some(hex) => hex,
+        None => return (StatusCode::BAD_REQUEST, "invalid h3 index").into_response(),
+    };
+    Json(explain(&state.hex_density_map, hex).await).into_response()
+}
+
+async fn gateway_scale(State(state): State<ApiState>, Path(address): Path<String>) -> Response {
+    let Ok(address) = PublicKeyBinary::from_str(&address) else {
+        return (StatusCode::BAD_REQUEST, "invalid gateway address").into_response();
+    };
+    let Ok(gateway_info) = state.gateway_cache.resolve_gateway_info(&address).await else {
+        return (StatusCode::NOT_FOUND, "unknown gateway").into_response();
+    };
+    let hex_scale = match gateway_info
+        .metadata
+        .and_then(|metadata| CellIndex::try_from(metadata.location).ok())
+    {
+        Some(hex) => Some(explain(&state.hex_density_map, hex).await),
+        None => None,
+    };
+    Json(GatewayScaleResponse { address, hex_scale }).into_response()
+}
+
+async fn gateway_beacon_schedule(
+    State(state): State<ApiState>,
+    Path(address): Path<String>,
+    Query(query): Query<ScheduleQuery>,
+) -> Response {
+    let Ok(address) = PublicKeyBinary::from_str(&address) else {
+        return (StatusCode::BAD_REQUEST, "invalid gateway address").into_response();
+    };
+    let epoch_start = beacon_schedule::epoch_start(query.epoch.unwrap_or_else(Utc::now));
+    let Some(params) = state.rules.active_at(epoch_start).beacon_schedule() else {
+        return (
+            StatusCode::NOT_FOUND,
+            "beacon schedule is not density scaled",
+        )
+            .into_response();
+    };
+    let Ok(gateway_info) = state.gateway_cache.resolve_gateway_info(&address).await else {
+        return (StatusCode::NOT_FOUND, "unknown gateway").into_response();
+    };
+    let Some(metadata) = gateway_info.metadata else {
+        return (StatusCode::NOT_FOUND, "gateway not asserted").into_response();
+    };
+    match BeaconSchedule::resolve(
+        &state.pool,
+        &address,
+        metadata.location,
+        epoch_start,
+        *DEFAULT_TX_SCALE,
+        &params,
+    )
+    .await
+    {
+        Ok(schedule) => Json(BeaconScheduleResponse { address, schedule }).into_response(),
+        Err(BeaconScheduleError::MissingSeed(_)) => {
+            (StatusCode::NOT_FOUND, "no beacon schedule seed for epoch").into_response()
+        }
+        Err(BeaconScheduleError::MissingTxScales(_)) => (
+            StatusCode::NOT_FOUND,
+            "no beacon schedule transmit scales for epoch",
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::warn!(%address, "failed to resolve beacon schedule: {err:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn explain(hex_density_map: &HexDensityMap, hex: CellIndex) -> HexScaleResponse {
+    let scale = hex_density_map.get(u64::from(hex)).await;
+    HexScaleResponse {
+        hex: hex.to_string(),
+        scale: scale.unwrap_or(*DEFAULT_TX_SCALE),
+        default: scale.is_none(),
+        refreshed_at: hex_density_map.refreshed_at().await,
+        resolutions: hex_density_map.occupancy(hex).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway_updater::GatewayMap;
+    use axum::body::Bytes;
+    use iot_config::gateway_info::{GatewayInfo, GatewayMetadata};
+    use rust_decimal_macros::dec;
+    use std::collections::HashMap;
+    use tokio::sync::watch;
+
+    const PUBKEY1: &str = "112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf";
+    const PUBKEY2: &str = "112bUGwooPd1dCDd3h3yZwskjxCzBsQNKeaJTuUF4hSgYedcsFa9";
+    const HEX: u64 = 0x8c2681a3064edff;
+
+    /// Api state with a hex density map of a single hex, and a cache of an
+    /// asserted and an unasserted gateway
+    async fn state(refreshed_at: DateTime<Utc>) -> ApiState {
+        let hex_density_map = HexDensityMap::new();
+        hex_density_map
+            .swap(HashMap::from([(HEX, dec!(0.5))]), refreshed_at)
+            .await;
+        let gateways: GatewayMap = [
+            GatewayInfo {
+                address: PublicKeyBinary::from_str(PUBKEY1).unwrap(),
+                metadata: Some(GatewayMetadata {
+                    location: HEX,
+                    elevation: 10,
+                    gain: 12,
+                    region: helium_proto::Region::Eu868,
+                }),
+                is_full_hotspot: true,
+            },
+            GatewayInfo {
+                address: PublicKeyBinary::from_str(PUBKEY2).unwrap(),
+                metadata: None,
+                is_full_hotspot: true,
+            },
+        ]
+        .into_iter()
+        .map(|gateway| (gateway.address.clone(), gateway))
+        .collect();
+        let (_, gateway_cache_receiver) = watch::channel(gateways);
+        ApiState {
+            hex_density_map,
+            gateway_cache: GatewayCache::new(gateway_cache_receiver),
+            // never connected, none of the requests below reach the database
+            pool: PgPool::connect_lazy("postgres://localhost/iot_verifier").unwrap(),
+            rules: PocRules::new(vec![]),
+        }
+    }
+
+    async fn body(response: Response) -> Bytes {
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("response body")
+    }
+
+    async fn json(response: Response) -> serde_json::Value {
+        serde_json::from_slice(&body(response).await).expect("json")
+    }
+
+    #[tokio::test]
+    async fn answers_the_status_of_the_map() {
+        let refreshed_at = Utc::now() - chrono::Duration::hours(1);
+        let Json(status) = status(State(state(refreshed_at).await)).await;
+        assert_eq!(Some(refreshed_at), status.refreshed_at);
+        assert_eq!(1, status.hexes);
+    }
+
+    #[tokio::test]
+    async fn answers_the_scale_of_a_hex() {
+        let refreshed_at = Utc::now();
+        let state = state(refreshed_at).await;
+        let hex = CellIndex::try_from(HEX).unwrap();
+
+        for path in [hex.to_string(), HEX.to_string()] {
+            let response = hex_scale(State(state.clone()), Path(path)).await;
+            assert_eq!(StatusCode::OK, response.status());
+            let resp = json(response).await;
+            assert_eq!(hex.to_string(), resp["hex"]);
+            assert_eq!("0.5", resp["scale"]);
+            assert_eq!(Some(false), resp["default"].as_bool());
+            assert_eq!(
+                serde_json::to_value(refreshed_at).unwrap(),
+                resp["refreshed_at"]
+            );
+            // the map was loaded, not computed
+            assert!(resp["resolutions"].is_null());
+        }
+
+        let parent = hex.parent(h3o::Resolution::Eight).unwrap();
+        let response = hex_scale(State(state.clone()), Path(parent.to_string())).await;
+        assert_eq!(StatusCode::OK, response.status());
+        let resp = json(response).await;
+        assert_eq!(DEFAULT_TX_SCALE.to_string(), resp["scale"]);
+        assert_eq!(Some(true), resp["default"].as_bool());
+
+        let response = hex_scale(State(state), Path("not a hex".to_string())).await;
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[tokio::test]
+    async fn answers_the_scale_of_a_gateway() {
+        let state = state(Utc::now()).await;
+
+        let response = gateway_scale(State(state.clone()), Path(PUBKEY1.to_string())).await;
+        assert_eq!(StatusCode::OK, response.status());
+        let resp = json(response).await;
+        assert_eq!(PUBKEY1, resp["address"]);
+        assert_eq!("0.5", resp["hex_scale"]["scale"]);
+
+        let response = gateway_scale(State(state.clone()), Path(PUBKEY2.to_string())).await;
+        assert_eq!(StatusCode::OK, response.status());
+        assert!(json(response).await["hex_scale"].is_null());
+
+        let unknown = "11z69eJ3czc92k6snrfR9ek7g2uRWXosFbnG9v4bXgwhfUCivUo";
+        let response = gateway_scale(State(state.clone()), Path(unknown.to_string())).await;
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+        let response = gateway_scale(State(state), Path("not an address".to_string())).await;
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+
+    #[tokio::test]
+    async fn does_not_answer_schedules_unless_density_scaled() {
+        let state = state(Utc::now()).await;
+
+        let response = gateway_beacon_schedule(
+            State(state.clone()),
+            Path(PUBKEY1.to_string()),
+            Query(ScheduleQuery { epoch: None }),
+        )
+        .await;
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+        assert_eq!(
+            "beacon schedule is not density scaled",
+            String::from_utf8_lossy(&body(response).await)
+        );
+
+        let response = gateway_beacon_schedule(
+            State(state),
+            Path("not an address".to_string()),
+            Query(ScheduleQuery { epoch: None }),
+        )
+        .await;
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    }
+}
@@ -1,5 +1,6 @@
-use crate::entropy::Entropy;
+use crate::{beacon_schedule::ScheduleSeed, entropy::Entropy};
 use blake3::hash;
+use chrono::Utc;
 use file_store::{entropy_report::EntropyReport, file_info_poller::FileInfoStream};
 use futures::{future::LocalBoxFuture, StreamExt, TryStreamExt};
 use sqlx::PgPool;
@@ -63,6 +64,8 @@ impl EntropyLoader {
                     report.version as i32,
                 )
                 .await?;
+                ScheduleSeed::propose(&mut transaction, &report.data, report.timestamp, Utc::now())
+                    .await?;
                 metrics::increment_counter!("oracles_iot_verifier_loader_entropy");
                 Ok(transaction)
             })
@@ -1,9 +1,11 @@
+use chrono::{DateTime, Utc};
 use file_store::SCALING_PRECISION;
 use h3o::{CellIndex, Resolution};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use serde::Serialize;
 use std::{cmp, collections::HashMap, sync::Arc};
 use tokio::sync::RwLock;
 
@@ -65,7 +67,25 @@ lazy_static! {
 }
 
 #[derive(Debug, Clone)]
-pub struct HexDensityMap(Arc<RwLock<HashMap<u64, Decimal>>>);
+pub struct HexDensityMap(Arc<RwLock<HexDensity>>);
+
+#[derive(Debug, Default)]
+struct HexDensity {
+    scales: HashMap<u64, Decimal>,
+    // the global map the scales were computed from, if they were not loaded
+    global_map: Option<GlobalHexMap>,
+    refreshed_at: Option<DateTime<Utc>>,
+}
+
+/// The occupancy of the parent of a hex at one of the resolutions the
+/// transmit scale of the hex is the product of
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResolutionOccupancy {
+    pub resolution: u8,
+    pub hex: String,
+    pub unclipped: u64,
+    pub clipped: u64,
+}
 
 impl Default for HexDensityMap {
     fn default() -> Self {
@@ -75,15 +95,58 @@ impl Default for HexDensityMap {
 
 impl HexDensityMap {
     pub fn new() -> Self {
-        Self(Arc::new(RwLock::new(HashMap::new())))
+        Self(Arc::new(RwLock::new(HexDensity::default())))
     }
 
     pub async fn get(&self, hex: u64) -> Option<Decimal> {
-        self.0.read().await.get(&hex).cloned()
+        self.0.read().await.scales.get(&hex).cloned()
+    }
+
+    /// Swap in the given scales, as of the time they were computed at
+    pub async fn swap(&self, new_map: HashMap<u64, Decimal>, refreshed_at: DateTime<Utc>) {
+        *self.0.write().await = HexDensity {
+            scales: new_map,
+            global_map: None,
+            refreshed_at: Some(refreshed_at),
+        };
+    }
+
+    /// Swap in the scales computed from the given global map at the given
+    /// time, keeping the global map to explain them with
+    pub async fn swap_computed(
+        &self,
+        new_map: HashMap<u64, Decimal>,
+        global_map: GlobalHexMap,
+        refreshed_at: DateTime<Utc>,
+    ) {
+        *self.0.write().await = HexDensity {
+            scales: new_map,
+            global_map: Some(global_map),
+            refreshed_at: Some(refreshed_at),
+        };
+    }
+
+    pub async fn to_map(&self) -> HashMap<u64, Decimal> {
+        self.0.read().await.scales.clone()
+    }
+
+    pub async fn hex_count(&self) -> usize {
+        self.0.read().await.scales.len()
+    }
+
+    pub async fn refreshed_at(&self) -> Option<DateTime<Utc>> {
+        self.0.read().await.refreshed_at
     }
 
-    pub async fn swap(&self, new_map: HashMap<u64, Decimal>) {
-        *self.0.write().await = new_map;
+    /// The occupancies the scale of the given hex was computed from. None if
+    /// the scales were not computed here, as when loaded from a snapshot
+    pub async fn occupancy(&self, hex: CellIndex) -> Option<Vec<ResolutionOccupancy>> {
+        self.0
+            .read()
+            .await
+            .global_map
+            .as_ref()
+            .map(|global_map| global_map.occupancy(hex))
     }
 }
 
@@ -125,6 +188,29 @@ impl GlobalHexMap {
         }
     }
 
+    /// The unclipped and clipped counts of the parents of a hex that
+    /// [`compute_hex_density_map`] multiplies into its scale
+    pub fn occupancy(&self, hex: CellIndex) -> Vec<ResolutionOccupancy> {
+        SCALING_RES
+            .iter()
+            .filter_map(|res| {
+                let parent = hex.parent(*res)?;
+                match (
+                    self.unclipped_hexes.get(&parent),
+                    self.clipped_hexes.get(&parent),
+                ) {
+                    (Some(unclipped), Some(clipped)) => Some(ResolutionOccupancy {
+                        resolution: u8::from(*res),
+                        hex: parent.to_string(),
+                        unclipped: *unclipped,
+                        clipped: *clipped,
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
     pub fn reduce_global(&mut self) {
         // At the point this reduce is triggered the only keys present in the unclipped
         // hexmap are the res 11 parent keys
@@ -326,4 +412,33 @@ mod tests {
         ]);
         assert_eq!(hex_density_map, expected_map);
     }
+
+    #[test]
+    fn occupancy_explains_scale() {
+        let indexes: Vec<u64> = vec![
+            631210990515722239,
+            631210990515722239,
+            631210990515727359,
+            631210990515728895,
+            631210990516363775,
+        ];
+        let mut gw_map = GlobalHexMap::new();
+        for index in &indexes {
+            gw_map.increment_unclipped(*index);
+        }
+        gw_map.reduce_global();
+        let hex_density_map = compute_hex_density_map(&gw_map);
+
+        for index in indexes {
+            let occupancy = gw_map.occupancy(CellIndex::try_from(index).unwrap());
+            assert!(!occupancy.is_empty());
+            let scale = occupancy
+                .iter()
+                .fold(dec!(1.0), |scale, occupancy| {
+                    scale * Decimal::from(occupancy.clipped) / Decimal::from(occupancy.unclipped)
+                })
+                .round_dp(SCALING_PRECISION);
+            assert_eq!(hex_density_map[&index], scale);
+        }
+    }
 }
@@ -1,3 +1,6 @@
+pub mod beacon_schedule;
+pub mod cli;
+pub mod density_api;
 pub mod entropy;
 pub mod entropy_loader;
 pub mod gateway_cache;
@@ -10,13 +13,16 @@ pub mod meta;
 pub mod packet_loader;
 pub mod poc;
 pub mod poc_report;
+pub mod poc_rules;
 pub mod purger;
 pub mod region_cache;
 pub mod reward_share;
 pub mod rewarder;
 pub mod runner;
 mod settings;
+pub mod snapshot;
 pub mod telemetry;
 pub mod tx_scaler;
+pub mod witness_graph;
 pub mod witness_updater;
 pub use settings::Settings;
@@ -3,14 +3,28 @@ use anyhow::Result;
 use chrono::Duration as ChronoDuration;
 use clap::Parser;
 use file_store::{
-    entropy_report::EntropyReport, file_info_poller::LookbackBehavior, file_sink, file_source,
-    file_upload, iot_packet::IotValidPacket, FileStore, FileType,
+    dead_letter, entropy_report::EntropyReport, file_info_poller::LookbackBehavior, file_sink,
+    file_source, file_upload, iot_packet::IotValidPacket, iot_valid_poc::IotPoc, FileStore,
+    FileType,
 };
 use iot_config::client::Client as IotConfigClient;
 use iot_verifier::{
-    entropy_loader, gateway_cache::GatewayCache, gateway_updater::GatewayUpdater, loader,
-    packet_loader, purger, rewarder::Rewarder, runner, telemetry,
-    tx_scaler::Server as DensityScaler, witness_updater::WitnessUpdater, Settings,
+    cli::{requeue_purged, reward_diff, reward_dry_run, reward_from_db, simulate, verify_poc},
+    density_api::DensityApi,
+    entropy_loader,
+    gateway_cache::GatewayCache,
+    gateway_updater::GatewayUpdater,
+    loader, packet_loader,
+    poc_rules::PocRules,
+    purger,
+    rewarder::Rewarder,
+    runner,
+    snapshot::{GatewaysSource, Snapshot, SnapshotWriter, SNAPSHOT_MAX_FILE_SIZE},
+    telemetry,
+    tx_scaler::Server as DensityScaler,
+    witness_graph::WitnessGraphDaemon,
+    witness_updater::WitnessUpdater,
+    Settings,
 };
 use price::PriceTracker;
 use std::path;
@@ -42,12 +56,24 @@ impl Cli {
 #[derive(Debug, clap::Subcommand)]
 pub enum Cmd {
     Server(Server),
+    VerifyPoc(verify_poc::Cmd),
+    Simulate(simulate::Cmd),
+    RewardDryRun(reward_dry_run::Cmd),
+    RewardDiff(reward_diff::Cmd),
+    RewardFromDb(reward_from_db::Cmd),
+    RequeuePurged(requeue_purged::Cmd),
 }
 
 impl Cmd {
     pub async fn run(&self, settings: Settings) -> Result<()> {
         match self {
             Self::Server(cmd) => cmd.run(&settings).await,
+            Self::VerifyPoc(cmd) => cmd.run(&settings).await,
+            Self::Simulate(cmd) => cmd.run(&settings).await,
+            Self::RewardDryRun(cmd) => cmd.run(&settings).await,
+            Self::RewardDiff(cmd) => cmd.run().await,
+            Self::RewardFromDb(cmd) => cmd.run(&settings).await,
+            Self::RequeuePurged(cmd) => cmd.run(&settings).await,
         }
     }
 }
@@ -75,7 +101,17 @@ impl Server {
             file_upload::FileUpload::from_settings_tm(&settings.output).await?;
         let store_base_path = path::Path::new(&settings.cache);
 
-        let iot_config_client = IotConfigClient::from_settings(&settings.iot_config_client)?;
+        // gateways and region params are resolved from iot config unless
+        // replaying against a snapshot
+        let gateways = match settings.snapshot_at {
+            Some(at) => {
+                let output_store = FileStore::from_settings(&settings.output).await?;
+                GatewaysSource::Snapshot(Snapshot::load(&output_store, at).await?)
+            }
+            None => GatewaysSource::IotConfig(IotConfigClient::from_settings(
+                &settings.iot_config_client,
+            )?),
+        };
 
         // create the witness updater to handle serialization of last witness updates to db
         // also exposes a cache of the last witness updates
@@ -84,18 +120,10 @@ impl Server {
         // *
         // setup caches
         // *
-        let (gateway_updater_receiver, gateway_updater_server) = GatewayUpdater::new(
-            settings.gateway_refresh_interval(),
-            iot_config_client.clone(),
-        )
-        .await?;
+        let (gateway_updater_receiver, gateway_updater_server) =
+            GatewayUpdater::new(settings.gateway_refresh_interval(), gateways.clone()).await?;
         let gateway_cache = GatewayCache::new(gateway_updater_receiver.clone());
 
-        // *
-        // setup the price tracker requirements
-        // *
-        let (price_tracker, price_daemon) = PriceTracker::new_tm(&settings.price_tracker).await?;
-
         // *
         // setup the loader requirements
         // *
@@ -105,47 +133,168 @@ impl Server {
         // *
         // setup the density scaler requirements
         // *
-        let density_scaler = DensityScaler::new(
-            settings.loader_window_max_lookback_age(),
+        let density_scaler = match &gateways {
+            GatewaysSource::Snapshot(snapshot) => {
+                DensityScaler::from_snapshot(
+                    snapshot.hex_density(),
+                    snapshot.taken_at(),
+                    settings.loader_window_max_lookback_age(),
+                    pool.clone(),
+                    gateway_updater_receiver.clone(),
+                )
+                .await
+            }
+            GatewaysSource::IotConfig(_) => {
+                DensityScaler::new(
+                    settings.loader_window_max_lookback_age(),
+                    pool.clone(),
+                    gateway_updater_receiver.clone(),
+                )
+                .await?
+            }
+        };
+
+        let density_api = DensityApi::new(
+            settings.density_api_listen_addr()?,
+            density_scaler.hex_density_map.clone(),
+            gateway_cache.clone(),
             pool.clone(),
-            gateway_updater_receiver,
-        )
-        .await?;
+            PocRules::new(settings.poc_rules.clone()),
+        );
+
+        // a replay against a snapshot only loads and verifies reports, it
+        // neither writes snapshots nor rewards or purges
+        let replay = settings.snapshot_at.is_some();
 
         // *
-        // setup the rewarder requirements
+        // setup the snapshot writer requirements
         // *
+        let snapshot_writer = if replay {
+            None
+        } else {
+            let snapshot_interval = settings.snapshot_interval();
+            let snapshot_roll_time = snapshot_interval.unwrap_or(ChronoDuration::minutes(30));
+
+            let (gateway_snapshot_sink, gateway_snapshot_sink_server) =
+                file_sink::FileSinkBuilder::new(
+                    FileType::IotGatewaySnapshot,
+                    store_base_path,
+                    concat!(env!("CARGO_PKG_NAME"), "_gateway_snapshot"),
+                )
+                .file_upload(Some(file_upload.clone()))
+                .auto_commit(false)
+                .max_size(SNAPSHOT_MAX_FILE_SIZE)
+                .roll_time(snapshot_roll_time)
+                .create()
+                .await?;
 
-        // Gateway reward shares sink
-        let (rewards_sink, gateway_rewards_sink_server) = file_sink::FileSinkBuilder::new(
-            FileType::IotRewardShare,
-            store_base_path,
-            concat!(env!("CARGO_PKG_NAME"), "_gateway_reward_shares"),
-        )
-        .file_upload(Some(file_upload.clone()))
-        .auto_commit(false)
-        .create()
-        .await?;
+            let (region_params_snapshot_sink, region_params_snapshot_sink_server) =
+                file_sink::FileSinkBuilder::new(
+                    FileType::IotRegionParamsSnapshot,
+                    store_base_path,
+                    concat!(env!("CARGO_PKG_NAME"), "_region_params_snapshot"),
+                )
+                .file_upload(Some(file_upload.clone()))
+                .auto_commit(false)
+                .max_size(SNAPSHOT_MAX_FILE_SIZE)
+                .roll_time(snapshot_roll_time)
+                .create()
+                .await?;
 
-        // Reward manifest
-        let (reward_manifests_sink, reward_manifests_sink_server) =
-            file_sink::FileSinkBuilder::new(
-                FileType::RewardManifest,
+            let (hex_density_snapshot_sink, hex_density_snapshot_sink_server) =
+                file_sink::FileSinkBuilder::new(
+                    FileType::IotHexDensitySnapshot,
+                    store_base_path,
+                    concat!(env!("CARGO_PKG_NAME"), "_hex_density_snapshot"),
+                )
+                .file_upload(Some(file_upload.clone()))
+                .auto_commit(false)
+                .max_size(SNAPSHOT_MAX_FILE_SIZE)
+                .roll_time(snapshot_roll_time)
+                .create()
+                .await?;
+
+            let (manifest_snapshot_sink, manifest_snapshot_sink_server) =
+                file_sink::FileSinkBuilder::new(
+                    FileType::IotSnapshotManifest,
+                    store_base_path,
+                    concat!(env!("CARGO_PKG_NAME"), "_snapshot_manifest"),
+                )
+                .file_upload(Some(file_upload.clone()))
+                .auto_commit(false)
+                .roll_time(snapshot_roll_time)
+                .create()
+                .await?;
+
+            let snapshot_writer = SnapshotWriter::new(
+                snapshot_interval,
+                gateways.clone(),
+                gateway_updater_receiver,
+                density_scaler.hex_density_map.clone(),
+                gateway_snapshot_sink,
+                region_params_snapshot_sink,
+                hex_density_snapshot_sink,
+                manifest_snapshot_sink,
+            );
+            Some((
+                snapshot_writer,
+                gateway_snapshot_sink_server,
+                region_params_snapshot_sink_server,
+                hex_density_snapshot_sink_server,
+                manifest_snapshot_sink_server,
+            ))
+        };
+
+        // *
+        // setup the rewarder requirements
+        // *
+
+        let rewarder = if replay {
+            None
+        } else {
+            // *
+            // setup the price tracker requirements
+            // *
+            let (price_tracker, price_daemon) =
+                PriceTracker::new_tm(&settings.price_tracker).await?;
+
+            // Gateway reward shares sink
+            let (rewards_sink, gateway_rewards_sink_server) = file_sink::FileSinkBuilder::new(
+                FileType::IotRewardShare,
                 store_base_path,
-                concat!(env!("CARGO_PKG_NAME"), "_iot_reward_manifest"),
+                concat!(env!("CARGO_PKG_NAME"), "_gateway_reward_shares"),
             )
             .file_upload(Some(file_upload.clone()))
             .auto_commit(false)
             .create()
             .await?;
 
-        let rewarder = Rewarder {
-            pool: pool.clone(),
-            rewards_sink,
-            reward_manifests_sink,
-            reward_period_hours: settings.rewards,
-            reward_offset: settings.reward_offset_duration(),
-            price_tracker,
+            // Reward manifest
+            let (reward_manifests_sink, reward_manifests_sink_server) =
+                file_sink::FileSinkBuilder::new(
+                    FileType::RewardManifest,
+                    store_base_path,
+                    concat!(env!("CARGO_PKG_NAME"), "_iot_reward_manifest"),
+                )
+                .file_upload(Some(file_upload.clone()))
+                .auto_commit(false)
+                .create()
+                .await?;
+
+            let rewarder = Rewarder {
+                pool: pool.clone(),
+                rewards_sink,
+                reward_manifests_sink,
+                reward_period_hours: settings.rewards,
+                reward_offset: settings.reward_offset_duration(),
+                price_tracker,
+            };
+            Some((
+                rewarder,
+                price_daemon,
+                gateway_rewards_sink_server,
+                reward_manifests_sink_server,
+            ))
         };
 
         // *
@@ -154,11 +303,18 @@ impl Server {
         let max_lookback_age = settings.loader_window_max_lookback_age();
         let entropy_store = FileStore::from_settings(&settings.entropy).await?;
         let entropy_interval = settings.entropy_interval();
+        let (entropy_dead_letters, entropy_dead_letters_server) =
+            dead_letter::file_sink(&FileType::EntropyReport.to_string(), store_base_path)
+                .file_upload(Some(file_upload.clone()))
+                .create()
+                .await?;
         let (entropy_loader_receiver, entropy_loader_server) =
             file_source::continuous_source::<EntropyReport, _>()
                 .state(pool.clone())
                 .store(entropy_store)
                 .prefix(FileType::EntropyReport.to_string())
+                .dead_letter(Some(entropy_dead_letters))
+                .workers(settings.file_poller_workers)
                 .lookback(LookbackBehavior::Max(max_lookback_age))
                 .poll_duration(entropy_interval)
                 .offset(entropy_interval * 2)
@@ -187,11 +343,18 @@ impl Server {
 
         let packet_store = FileStore::from_settings(&settings.packet_ingest).await?;
         let packet_interval = settings.packet_interval();
+        let (packet_dead_letters, packet_dead_letters_server) =
+            dead_letter::file_sink(&FileType::IotValidPacket.to_string(), store_base_path)
+                .file_upload(Some(file_upload.clone()))
+                .create()
+                .await?;
         let (pk_loader_receiver, pk_loader_server) =
             file_source::continuous_source::<IotValidPacket, _>()
                 .state(pool.clone())
                 .store(packet_store.clone())
                 .prefix(FileType::IotValidPacket.to_string())
+                .dead_letter(Some(packet_dead_letters))
+                .workers(settings.file_poller_workers)
                 .lookback(LookbackBehavior::Max(max_lookback_age))
                 .poll_duration(packet_interval)
                 .offset(packet_interval * 2)
@@ -210,42 +373,51 @@ impl Server {
         // setup the purger requirements
         // *
 
-        let (purger_invalid_beacon_sink, purger_invalid_beacon_sink_server) =
-            file_sink::FileSinkBuilder::new(
-                FileType::IotInvalidBeaconReport,
-                store_base_path,
-                concat!(env!("CARGO_PKG_NAME"), "_invalid_beacon"),
-            )
-            .file_upload(Some(file_upload.clone()))
-            .auto_commit(false)
-            .create()
-            .await?;
+        let purger = if replay {
+            None
+        } else {
+            let (purger_invalid_beacon_sink, purger_invalid_beacon_sink_server) =
+                file_sink::FileSinkBuilder::new(
+                    FileType::IotInvalidBeaconReport,
+                    store_base_path,
+                    concat!(env!("CARGO_PKG_NAME"), "_invalid_beacon"),
+                )
+                .file_upload(Some(file_upload.clone()))
+                .auto_commit(false)
+                .create()
+                .await?;
 
-        let (purger_invalid_witness_sink, purger_invalid_witness_sink_server) =
-            file_sink::FileSinkBuilder::new(
-                FileType::IotInvalidWitnessReport,
-                store_base_path,
-                concat!(env!("CARGO_PKG_NAME"), "_invalid_witness_report"),
+            let (purger_invalid_witness_sink, purger_invalid_witness_sink_server) =
+                file_sink::FileSinkBuilder::new(
+                    FileType::IotInvalidWitnessReport,
+                    store_base_path,
+                    concat!(env!("CARGO_PKG_NAME"), "_invalid_witness_report"),
+                )
+                .file_upload(Some(file_upload.clone()))
+                .auto_commit(false)
+                .create()
+                .await?;
+
+            let base_stale_period = settings.base_stale_period();
+            let beacon_stale_period = settings.beacon_stale_period();
+            let witness_stale_period = settings.witness_stale_period();
+            let entropy_stale_period = settings.entropy_stale_period();
+            let purger = purger::Purger::new(
+                base_stale_period,
+                beacon_stale_period,
+                witness_stale_period,
+                entropy_stale_period,
+                pool.clone(),
+                purger_invalid_beacon_sink,
+                purger_invalid_witness_sink,
             )
-            .file_upload(Some(file_upload.clone()))
-            .auto_commit(false)
-            .create()
             .await?;
-
-        let base_stale_period = settings.base_stale_period();
-        let beacon_stale_period = settings.beacon_stale_period();
-        let witness_stale_period = settings.witness_stale_period();
-        let entropy_stale_period = settings.entropy_stale_period();
-        let purger = purger::Purger::new(
-            base_stale_period,
-            beacon_stale_period,
-            witness_stale_period,
-            entropy_stale_period,
-            pool.clone(),
-            purger_invalid_beacon_sink,
-            purger_invalid_witness_sink,
-        )
-        .await?;
+            Some((
+                purger,
+                purger_invalid_beacon_sink_server,
+                purger_invalid_witness_sink_server,
+            ))
+        };
 
         // *
         // setup the runner requirements
@@ -283,43 +455,131 @@ impl Server {
         .create()
         .await?;
 
+        let (runner_poc_rules_sink, runner_poc_rules_sink_server) =
+            file_sink::FileSinkBuilder::new(
+                FileType::IotVerificationRules,
+                store_base_path,
+                concat!(env!("CARGO_PKG_NAME"), "_poc_rules"),
+            )
+            .file_upload(Some(file_upload.clone()))
+            .roll_time(ChronoDuration::minutes(2))
+            .create()
+            .await?;
+
         let runner = runner::Runner::from_settings(
             settings,
-            iot_config_client.clone(),
+            gateways,
             pool.clone(),
             gateway_cache.clone(),
             runner_invalid_beacon_sink,
             runner_invalid_witness_sink,
             runner_poc_sink,
+            runner_poc_rules_sink,
             density_scaler.hex_density_map.clone(),
             witness_updater,
         )
         .await?;
 
-        TaskManager::builder()
+        // *
+        // setup the witness graph requirements
+        // *
+        let witness_graph = if settings.witness_graph.enabled {
+            let (anomaly_sink, anomaly_sink_server) = file_sink::FileSinkBuilder::new(
+                FileType::IotWitnessAnomaly,
+                store_base_path,
+                concat!(env!("CARGO_PKG_NAME"), "_witness_anomaly"),
+            )
+            .file_upload(Some(file_upload.clone()))
+            .auto_commit(false)
+            .create()
+            .await?;
+
+            let output_store = FileStore::from_settings(&settings.output).await?;
+            let (poc_receiver, poc_source_server) = file_source::continuous_source::<IotPoc, _>()
+                .state(pool.clone())
+                .store(output_store)
+                .prefix(FileType::IotPoc.to_string())
+                .process_name("witness_graph".to_string())
+                .lookback(LookbackBehavior::Max(settings.witness_graph.window()))
+                .create()
+                .await?;
+
+            let witness_graph = WitnessGraphDaemon::new(
+                pool.clone(),
+                settings.witness_graph.clone(),
+                poc_receiver,
+                anomaly_sink,
+            );
+            Some((witness_graph, anomaly_sink_server, poc_source_server))
+        } else {
+            None
+        };
+
+        let mut task_manager = TaskManager::builder()
             .add_task(file_upload_server)
-            .add_task(gateway_rewards_sink_server)
-            .add_task(reward_manifests_sink_server)
+            .add_task(entropy_dead_letters_server)
+            .add_task(packet_dead_letters_server)
             .add_task(non_rewardable_packet_sink_server)
-            .add_task(purger_invalid_beacon_sink_server)
-            .add_task(purger_invalid_witness_sink_server)
             .add_task(runner_invalid_beacon_sink_server)
             .add_task(runner_invalid_witness_sink_server)
             .add_task(witness_updater_server)
             .add_task(runner_poc_sink_server)
-            .add_task(price_daemon)
+            .add_task(runner_poc_rules_sink_server)
             .add_task(density_scaler)
+            .add_task(density_api)
             .add_task(gateway_updater_server)
-            .add_task(purger)
             .add_task(runner)
             .add_task(entropy_loader)
             .add_task(packet_loader)
             .add_task(loader)
             .add_task(pk_loader_server)
-            .add_task(entropy_loader_server)
-            .add_task(rewarder)
-            .start()
-            .await
+            .add_task(entropy_loader_server);
+        if let Some((
+            snapshot_writer,
+            gateway_snapshot_sink_server,
+            region_params_snapshot_sink_server,
+            hex_density_snapshot_sink_server,
+            manifest_snapshot_sink_server,
+        )) = snapshot_writer
+        {
+            task_manager = task_manager
+                .add_task(gateway_snapshot_sink_server)
+                .add_task(region_params_snapshot_sink_server)
+                .add_task(hex_density_snapshot_sink_server)
+                .add_task(manifest_snapshot_sink_server)
+                .add_task(snapshot_writer);
+        }
+        if let Some((
+            purger,
+            purger_invalid_beacon_sink_server,
+            purger_invalid_witness_sink_server,
+        )) = purger
+        {
+            task_manager = task_manager
+                .add_task(purger_invalid_beacon_sink_server)
+                .add_task(purger_invalid_witness_sink_server)
+                .add_task(purger);
+        }
+        if let Some((
+            rewarder,
+            price_daemon,
+            gateway_rewards_sink_server,
+            reward_manifests_sink_server,
+        )) = rewarder
+        {
+            task_manager = task_manager
+                .add_task(gateway_rewards_sink_server)
+                .add_task(reward_manifests_sink_server)
+                .add_task(price_daemon)
+                .add_task(rewarder);
+        }
+        if let Some((witness_graph, anomaly_sink_server, poc_source_server)) = witness_graph {
+            task_manager = task_manager
+                .add_task(anomaly_sink_server)
+                .add_task(poc_source_server)
+                .add_task(witness_graph);
+        }
+        task_manager.start().await
     }
 }
 
@@ -1,9 +1,12 @@
 use crate::{
+    beacon_schedule::{self, BeaconCadence, BeaconSchedule},
     entropy::ENTROPY_LIFESPAN,
     gateway_cache::{GatewayCache, GatewayCacheError},
     hex_density::HexDensityMap,
     last_beacon::LastBeacon,
     last_witness::LastWitness,
+    poc_report::FailureReason,
+    poc_rules::{ActiveRules, PocRules, Rule},
     region_cache::RegionCache,
     witness_updater::WitnessUpdater,
 };
@@ -29,6 +32,7 @@ use iot_config::{
 };
 use lazy_static::lazy_static;
 use rust_decimal::Decimal;
+use serde::Serialize;
 use sqlx::PgPool;
 use std::f64::consts::PI;
 
@@ -39,10 +43,6 @@ pub const C: f64 = 2.998e8;
 /// R is the (average) radius of the earth
 pub const R: f64 = 6.371e6;
 
-/// max permitted distance of a witness from a beaconer measured in KM
-const POC_DISTANCE_LIMIT: u32 = 100;
-/// the minimum distance in cells between a beaconer and witness
-const POC_CELL_DISTANCE_MINIMUM: u32 = 8;
 /// the resolution at which parent cell distance is derived
 const POC_CELL_PARENT_RES: Resolution = Resolution::Eleven;
 
@@ -52,11 +52,7 @@ lazy_static! {
     /// again when an inactive hotspot's h3 index would otherwise be garbage-collected
     /// from density scaling calculations and not finding a value on subsequent lookups
     /// would disqualify the hotspot from validating further beacons
-    static ref DEFAULT_TX_SCALE: Decimal = Decimal::new(2000, 4);
-    /// max permitted lag between the first witness and all subsequent witnesses
-    static ref MAX_WITNESS_LAG: Duration = Duration::milliseconds(1500);
-    /// max permitted lag between the beaconer and a witness
-    static ref MAX_BEACON_TO_WITNESS_LAG: Duration = Duration::milliseconds(4000);
+    pub static ref DEFAULT_TX_SCALE: Decimal = Decimal::new(2000, 4);
     /// the duration in which a beaconer or witness must have a valid opposite report from
     static ref RECIPROCITY_WINDOW: Duration = Duration::hours(48);
 
@@ -67,6 +63,12 @@ pub struct InvalidResponse {
     details: Option<InvalidDetails>,
 }
 
+impl InvalidResponse {
+    pub fn reason(&self) -> InvalidReason {
+        self.reason
+    }
+}
+
 pub struct Poc {
     pool: PgPool,
     beacon_interval: Duration,
@@ -75,6 +77,7 @@ pub struct Poc {
     entropy_start: DateTime<Utc>,
     entropy_end: DateTime<Utc>,
     entropy_version: i32,
+    rules: ActiveRules,
 }
 #[derive(Clone, Debug)]
 pub struct VerifyBeaconResult {
@@ -88,10 +91,11 @@ pub struct VerifyBeaconResult {
 #[derive(Clone, Debug)]
 pub struct VerifyWitnessesResult {
     pub verified_witnesses: Vec<IotVerifiedWitnessReport>,
-    pub failed_witnesses: Vec<IotWitnessIngestReport>,
+    pub failed_witnesses: Vec<(IotWitnessIngestReport, FailureReason)>,
 }
 
 impl Poc {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         pool: PgPool,
         beacon_interval: Duration,
@@ -99,8 +103,12 @@ impl Poc {
         witness_reports: Vec<IotWitnessIngestReport>,
         entropy_start: DateTime<Utc>,
         entropy_version: i32,
+        rules: &PocRules,
     ) -> Self {
         let entropy_end = entropy_start + Duration::seconds(ENTROPY_LIFESPAN);
+        // the beacon and all its witnesses are verified by the rules in effect
+        // when the beacon was received
+        let rules = rules.active_at(beacon_report.received_timestamp);
         Self {
             pool,
             beacon_interval,
@@ -109,9 +117,15 @@ impl Poc {
             entropy_start,
             entropy_end,
             entropy_version,
+            rules,
         }
     }
 
+    /// The rules the PoC is verified with
+    pub fn rules(&self) -> &ActiveRules {
+        &self.rules
+    }
+
     pub async fn verify_beacon<G>(
         &mut self,
         hex_density_map: &HexDensityMap,
@@ -148,11 +162,26 @@ impl Poc {
             .await
         {
             Ok(res) => res,
-            Err(err) => return Err(anyhow::Error::from(err)),
+            Err(err) => return Err(anyhow::Error::from(err).context(FailureReason::RegionParams)),
         };
         // we have beaconer info, proceed to verifications
         let last_beacon = LastBeacon::get(&self.pool, &beaconer_pub_key).await?;
+        let beacon_cadence = match self.rules.beacon_schedule() {
+            Some(params) => BeaconCadence::Scheduled(
+                BeaconSchedule::resolve(
+                    &self.pool,
+                    &beaconer_pub_key,
+                    beaconer_metadata.location,
+                    beacon_schedule::epoch_start(self.beacon_report.received_timestamp),
+                    *DEFAULT_TX_SCALE,
+                    &params,
+                )
+                .await?,
+            ),
+            None => BeaconCadence::Interval(self.beacon_interval),
+        };
         match do_beacon_verifications(
+            &self.rules,
             deny_list,
             self.entropy_start,
             self.entropy_end,
@@ -161,7 +190,7 @@ impl Poc {
             &self.beacon_report,
             &beaconer_info,
             &beaconer_region_info.region_params,
-            self.beacon_interval,
+            &beacon_cadence,
         ) {
             Ok(()) => {
                 let tx_scale = hex_density_map
@@ -210,7 +239,7 @@ impl Poc {
     ) -> anyhow::Result<VerifyWitnessesResult> {
         let mut witnesses_to_update: Vec<LastWitness> = Vec::new();
         let mut verified_witnesses: Vec<IotVerifiedWitnessReport> = Vec::new();
-        let mut failed_witnesses: Vec<IotWitnessIngestReport> = Vec::new();
+        let mut failed_witnesses: Vec<(IotWitnessIngestReport, FailureReason)> = Vec::new();
         let mut existing_gateways: Vec<PublicKeyBinary> = Vec::new();
         let witnesses = self.witness_reports.clone();
 
@@ -256,7 +285,9 @@ impl Poc {
                             };
                             verified_witnesses.push(verified_witness);
                         }
-                        Err(_) => failed_witnesses.push(witness_report),
+                        Err(err) => {
+                            failed_witnesses.push((witness_report, FailureReason::from_error(&err)))
+                        }
                     }
                 } else {
                     // the report is a dup
@@ -348,6 +379,7 @@ impl Poc {
         };
         // run the witness verifications
         match do_witness_verifications(
+            &self.rules,
             deny_list,
             self.entropy_start,
             self.entropy_end,
@@ -392,9 +424,10 @@ impl Poc {
             let last_witness = witness_updater
                 .get_last_witness(&self.beacon_report.report.pub_key)
                 .await?;
-            return Ok(last_witness.map_or(false, |lw| {
-                self.beacon_report.received_timestamp - lw.timestamp < *RECIPROCITY_WINDOW
-            }));
+            return Ok(verify_reciprocity(
+                self.beacon_report.received_timestamp,
+                last_witness.map(|lw| lw.timestamp),
+            ));
         }
         Ok(false)
     }
@@ -404,14 +437,16 @@ impl Poc {
         report: &IotWitnessIngestReport,
     ) -> anyhow::Result<bool> {
         let last_beacon = LastBeacon::get(&self.pool, &report.report.pub_key).await?;
-        Ok(last_beacon.map_or(false, |lw| {
-            report.received_timestamp - lw.timestamp < *RECIPROCITY_WINDOW
-        }))
+        Ok(verify_reciprocity(
+            report.received_timestamp,
+            last_beacon.map(|lb| lb.timestamp),
+        ))
     }
 }
 
 #[allow(clippy::too_many_arguments)]
 pub fn do_beacon_verifications(
+    rules: &ActiveRules,
     deny_list: &DenyList,
     entropy_start: DateTime<Utc>,
     entropy_end: DateTime<Utc>,
@@ -420,13 +455,12 @@ pub fn do_beacon_verifications(
     beacon_report: &IotBeaconIngestReport,
     beaconer_info: &GatewayInfo,
     beaconer_region_params: &[BlockchainRegionParamV1],
-    beacon_interval: Duration,
+    beacon_cadence: &BeaconCadence,
 ) -> GenericVerifyResult {
     tracing::debug!(
         "verifying beacon from beaconer: {:?}",
         beaconer_info.address.clone()
     );
-    let beacon_received_ts = beacon_report.received_timestamp;
     let beaconer_metadata = match beaconer_info.metadata {
         Some(ref metadata) => metadata,
         None => {
@@ -436,18 +470,21 @@ pub fn do_beacon_verifications(
             })
         }
     };
-    verify_denylist(&beacon_report.report.pub_key, deny_list)?;
-    verify_entropy(entropy_start, entropy_end, beacon_received_ts)?;
-    verify_gw_capability(beaconer_info.is_full_hotspot)?;
-    verify_beacon_schedule(&last_beacon, beacon_received_ts, beacon_interval)?;
-    verify_beacon_payload(
-        &beacon_report.report,
-        beaconer_metadata.region,
-        beaconer_region_params,
-        beaconer_metadata.gain,
-        entropy_start,
-        entropy_version as u32,
-    )?;
+    for rule in rules.iter() {
+        verify_beacon_rule(
+            *rule,
+            deny_list,
+            entropy_start,
+            entropy_end,
+            entropy_version,
+            &last_beacon,
+            beacon_report,
+            beaconer_info,
+            beaconer_metadata,
+            beaconer_region_params,
+            beacon_cadence,
+        )?;
+    }
     tracing::debug!(
         "valid beacon from beaconer: {:?}",
         beaconer_info.address.clone()
@@ -457,6 +494,7 @@ pub fn do_beacon_verifications(
 
 #[allow(clippy::too_many_arguments)]
 pub fn do_witness_verifications(
+    rules: &ActiveRules,
     deny_list: &DenyList,
     entropy_start: DateTime<Utc>,
     entropy_end: DateTime<Utc>,
@@ -470,7 +508,6 @@ pub fn do_witness_verifications(
         "verifying witness from gateway: {:?}",
         witness_info.address.clone()
     );
-    let beacon_report = &beacon_report;
     let witness_metadata = match witness_info.metadata {
         Some(ref metadata) => metadata,
         None => {
@@ -480,44 +517,20 @@ pub fn do_witness_verifications(
             })
         }
     };
-    verify_denylist(&witness_report.report.pub_key, deny_list)?;
-    verify_edge_denylist(
-        &beacon_report.report.pub_key,
-        &witness_report.report.pub_key,
-        deny_list,
-    )?;
-    verify_self_witness(
-        &beacon_report.report.pub_key,
-        &witness_report.report.pub_key,
-    )?;
-    verify_entropy(
-        entropy_start,
-        entropy_end,
-        witness_report.received_timestamp,
-    )?;
-    verify_witness_lag(
-        beacon_report.received_timestamp,
-        witness_first_ts,
-        witness_report.received_timestamp,
-    )?;
-    verify_witness_data(&beacon_report.report.data, &witness_report.report.data)?;
-    verify_gw_capability(witness_info.is_full_hotspot)?;
-    verify_witness_freq(
-        beacon_report.report.frequency,
-        witness_report.report.frequency,
-    )?;
-    verify_witness_region(beaconer_metadata.region, witness_metadata.region)?;
-    verify_witness_cell_distance(beaconer_metadata.location, witness_metadata.location)?;
-    verify_witness_distance(beaconer_metadata.location, witness_metadata.location)?;
-    verify_witness_rssi(
-        witness_report.report.signal,
-        witness_report.report.frequency,
-        beacon_report.report.tx_power,
-        beaconer_metadata.gain,
-        witness_metadata.gain,
-        beaconer_metadata.location,
-        witness_metadata.location,
-    )?;
+    for rule in rules.iter() {
+        verify_witness_rule(
+            *rule,
+            deny_list,
+            entropy_start,
+            entropy_end,
+            witness_report,
+            witness_info,
+            witness_metadata,
+            beacon_report,
+            beaconer_metadata,
+            witness_first_ts,
+        )?;
+    }
     tracing::debug!(
         "valid witness from gateway: {:?}",
         witness_info.address.clone()
@@ -525,6 +538,296 @@ pub fn do_witness_verifications(
     Ok(())
 }
 
+/// Evaluate every beacon rule independently of the others, for explaining
+/// the verification of a beacon. Unlike `do_beacon_verifications` evaluation
+/// does not stop at the first rule the beacon fails
+#[allow(clippy::too_many_arguments)]
+pub fn explain_beacon_verifications(
+    rules: &ActiveRules,
+    deny_list: &DenyList,
+    entropy_start: DateTime<Utc>,
+    entropy_end: DateTime<Utc>,
+    entropy_version: i32,
+    last_beacon: Option<LastBeacon>,
+    beacon_report: &IotBeaconIngestReport,
+    beaconer_info: &GatewayInfo,
+    beaconer_region_params: &[BlockchainRegionParamV1],
+    beacon_cadence: &BeaconCadence,
+) -> GenericVerifyResult<Vec<RuleOutcome>> {
+    let Some(ref beaconer_metadata) = beaconer_info.metadata else {
+        return Err(InvalidResponse {
+            reason: InvalidReason::NotAsserted,
+            details: None,
+        });
+    };
+    Ok(rules
+        .iter()
+        .filter(|rule| rule.applies_to_beacon())
+        .map(|rule| {
+            RuleOutcome::new(
+                *rule,
+                verify_beacon_rule(
+                    *rule,
+                    deny_list,
+                    entropy_start,
+                    entropy_end,
+                    entropy_version,
+                    &last_beacon,
+                    beacon_report,
+                    beaconer_info,
+                    beaconer_metadata,
+                    beaconer_region_params,
+                    beacon_cadence,
+                ),
+            )
+        })
+        .collect())
+}
+
+/// Evaluate every witness rule independently of the others, for explaining
+/// the verification of a witness, along with the values the rules compare
+/// against their thresholds
+#[allow(clippy::too_many_arguments)]
+pub fn explain_witness_verifications(
+    rules: &ActiveRules,
+    deny_list: &DenyList,
+    entropy_start: DateTime<Utc>,
+    entropy_end: DateTime<Utc>,
+    witness_report: &IotWitnessIngestReport,
+    witness_info: &GatewayInfo,
+    beacon_report: &IotBeaconIngestReport,
+    beaconer_metadata: &GatewayMetadata,
+    witness_first_ts: DateTime<Utc>,
+) -> GenericVerifyResult<WitnessExplanation> {
+    let Some(ref witness_metadata) = witness_info.metadata else {
+        return Err(InvalidResponse {
+            reason: InvalidReason::NotAsserted,
+            details: None,
+        });
+    };
+    let rules = rules
+        .iter()
+        .filter(|rule| rule.applies_to_witness())
+        .map(|rule| {
+            RuleOutcome::new(
+                *rule,
+                verify_witness_rule(
+                    *rule,
+                    deny_list,
+                    entropy_start,
+                    entropy_end,
+                    witness_report,
+                    witness_info,
+                    witness_metadata,
+                    beacon_report,
+                    beaconer_metadata,
+                    witness_first_ts,
+                ),
+            )
+        })
+        .collect();
+    let distance_m = calc_distance(beaconer_metadata.location, witness_metadata.location).ok();
+    let measurements = WitnessMeasurements {
+        distance_m,
+        cell_distance: calc_cell_distance(beaconer_metadata.location, witness_metadata.location)
+            .ok(),
+        expected_rssi_dbm: distance_m.map(|distance| {
+            calc_expected_rssi(
+                beacon_report.report.tx_power,
+                witness_report.report.frequency,
+                distance,
+                beaconer_metadata.gain,
+                witness_metadata.gain,
+            )
+        }),
+        // signal is submitted as DBM * 10
+        signal_dbm: witness_report.report.signal as f64 / 10.0,
+        beacon_lag_ms: (witness_report.received_timestamp - beacon_report.received_timestamp)
+            .num_milliseconds(),
+        first_witness_lag_ms: (witness_report.received_timestamp - witness_first_ts)
+            .num_milliseconds(),
+        frequency_diff_hz: beacon_report
+            .report
+            .frequency
+            .abs_diff(witness_report.report.frequency),
+    };
+    Ok(WitnessExplanation {
+        rules,
+        measurements,
+    })
+}
+
+/// The outcome of a single rule
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleOutcome {
+    pub rule: Rule,
+    /// the reason the report failed the rule, none if it passed
+    pub invalid_reason: Option<&'static str>,
+}
+
+impl RuleOutcome {
+    fn new(rule: Rule, result: GenericVerifyResult) -> Self {
+        Self {
+            rule,
+            invalid_reason: result.err().map(|err| err.reason.as_str_name()),
+        }
+    }
+}
+
+/// The values a witness is verified on
+#[derive(Debug, Clone, Serialize)]
+pub struct WitnessMeasurements {
+    /// distance between the beaconer and the witness in meters
+    pub distance_m: Option<u32>,
+    /// distance between the beaconer and the witness in res 11 cells
+    pub cell_distance: Option<u32>,
+    /// max plausible signal of the witness at its distance, in dBm
+    pub expected_rssi_dbm: Option<f64>,
+    /// signal reported by the witness, in dBm
+    pub signal_dbm: f64,
+    /// time between receiving the beacon and the witness
+    pub beacon_lag_ms: i64,
+    /// time between receiving the first witness and the witness
+    pub first_witness_lag_ms: i64,
+    /// difference between the beacon and the witness frequency
+    pub frequency_diff_hz: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WitnessExplanation {
+    pub rules: Vec<RuleOutcome>,
+    pub measurements: WitnessMeasurements,
+}
+
+/// whether a gateway had a valid report of the opposite kind recently enough
+/// for its beacon or witness received at the given time to be valid
+pub fn verify_reciprocity(received_ts: DateTime<Utc>, last_ts: Option<DateTime<Utc>>) -> bool {
+    last_ts.map_or(false, |last_ts| received_ts - last_ts < *RECIPROCITY_WINDOW)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify_beacon_rule(
+    rule: Rule,
+    deny_list: &DenyList,
+    entropy_start: DateTime<Utc>,
+    entropy_end: DateTime<Utc>,
+    entropy_version: i32,
+    last_beacon: &Option<LastBeacon>,
+    beacon_report: &IotBeaconIngestReport,
+    beaconer_info: &GatewayInfo,
+    beaconer_metadata: &GatewayMetadata,
+    beaconer_region_params: &[BlockchainRegionParamV1],
+    beacon_cadence: &BeaconCadence,
+) -> GenericVerifyResult {
+    let beacon_received_ts = beacon_report.received_timestamp;
+    match rule {
+        Rule::Denylist => verify_denylist(&beacon_report.report.pub_key, deny_list),
+        Rule::Entropy => verify_entropy(entropy_start, entropy_end, beacon_received_ts),
+        Rule::GatewayCapability => verify_gw_capability(beaconer_info.is_full_hotspot),
+        Rule::BeaconSchedule { .. } => match beacon_cadence {
+            BeaconCadence::Interval(beacon_interval) => {
+                verify_beacon_schedule(last_beacon, beacon_received_ts, *beacon_interval)
+            }
+            BeaconCadence::Scheduled(schedule) => {
+                verify_beacon_slot(last_beacon, beacon_received_ts, schedule)
+            }
+        },
+        Rule::BeaconPayload => verify_beacon_payload(
+            &beacon_report.report,
+            beaconer_metadata.region,
+            beaconer_region_params,
+            beaconer_metadata.gain,
+            entropy_start,
+            entropy_version as u32,
+        ),
+        // witness only rules
+        Rule::EdgeDenylist
+        | Rule::SelfWitness
+        | Rule::WitnessLag { .. }
+        | Rule::WitnessData
+        | Rule::WitnessFrequency { .. }
+        | Rule::WitnessRegion
+        | Rule::WitnessCellDistance { .. }
+        | Rule::WitnessDistance { .. }
+        | Rule::WitnessRssi => Ok(()),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify_witness_rule(
+    rule: Rule,
+    deny_list: &DenyList,
+    entropy_start: DateTime<Utc>,
+    entropy_end: DateTime<Utc>,
+    witness_report: &IotWitnessIngestReport,
+    witness_info: &GatewayInfo,
+    witness_metadata: &GatewayMetadata,
+    beacon_report: &IotBeaconIngestReport,
+    beaconer_metadata: &GatewayMetadata,
+    witness_first_ts: DateTime<Utc>,
+) -> GenericVerifyResult {
+    match rule {
+        Rule::Denylist => verify_denylist(&witness_report.report.pub_key, deny_list),
+        Rule::EdgeDenylist => verify_edge_denylist(
+            &beacon_report.report.pub_key,
+            &witness_report.report.pub_key,
+            deny_list,
+        ),
+        Rule::SelfWitness => verify_self_witness(
+            &beacon_report.report.pub_key,
+            &witness_report.report.pub_key,
+        ),
+        Rule::Entropy => verify_entropy(
+            entropy_start,
+            entropy_end,
+            witness_report.received_timestamp,
+        ),
+        Rule::WitnessLag {
+            max_beacon_to_witness_lag_ms,
+            max_witness_lag_ms,
+        } => verify_witness_lag(
+            beacon_report.received_timestamp,
+            witness_first_ts,
+            witness_report.received_timestamp,
+            Duration::milliseconds(max_beacon_to_witness_lag_ms),
+            Duration::milliseconds(max_witness_lag_ms),
+        ),
+        Rule::WitnessData => {
+            verify_witness_data(&beacon_report.report.data, &witness_report.report.data)
+        }
+        Rule::GatewayCapability => verify_gw_capability(witness_info.is_full_hotspot),
+        Rule::WitnessFrequency { max_frequency_diff } => verify_witness_freq(
+            beacon_report.report.frequency,
+            witness_report.report.frequency,
+            max_frequency_diff,
+        ),
+        Rule::WitnessRegion => {
+            verify_witness_region(beaconer_metadata.region, witness_metadata.region)
+        }
+        Rule::WitnessCellDistance { min_cell_distance } => verify_witness_cell_distance(
+            beaconer_metadata.location,
+            witness_metadata.location,
+            min_cell_distance,
+        ),
+        Rule::WitnessDistance { max_distance_km } => verify_witness_distance(
+            beaconer_metadata.location,
+            witness_metadata.location,
+            max_distance_km,
+        ),
+        Rule::WitnessRssi => verify_witness_rssi(
+            witness_report.report.signal,
+            witness_report.report.frequency,
+            beacon_report.report.tx_power,
+            beaconer_metadata.gain,
+            witness_metadata.gain,
+            beaconer_metadata.location,
+            witness_metadata.location,
+        ),
+        // beacon only rules
+        Rule::BeaconSchedule { .. } | Rule::BeaconPayload => Ok(()),
+    }
+}
+
 /// verify beaconer is permitted to beacon at this time
 fn verify_beacon_schedule(
     last_beacon: &Option<LastBeacon>,
@@ -574,6 +877,35 @@ fn verify_beacon_schedule(
     Ok(())
 }
 
+/// verify beaconer is beaconing in one of its slots, and only once per slot
+fn verify_beacon_slot(
+    last_beacon: &Option<LastBeacon>,
+    beacon_received_ts: DateTime<Utc>,
+    schedule: &BeaconSchedule,
+) -> GenericVerifyResult {
+    let irregular = || {
+        tracing::debug!(
+            "beacon verification failed, reason:
+                IrregularInterval. beacon_received_ts: {}, schedule: {:?}",
+            beacon_received_ts,
+            schedule
+        );
+        Err(InvalidResponse {
+            reason: InvalidReason::IrregularInterval,
+            details: None,
+        })
+    };
+    let Some(slot) = schedule.slot_index(beacon_received_ts) else {
+        return irregular();
+    };
+    if let Some(last_beacon) = last_beacon {
+        if schedule.slot_index(last_beacon.timestamp) == Some(slot) {
+            return irregular();
+        }
+    }
+    Ok(())
+}
+
 /// verify if gateway is on the deny list
 fn verify_denylist(pub_key: &PublicKeyBinary, deny_list: &DenyList) -> GenericVerifyResult {
     if deny_list.contains_key(pub_key) {
@@ -744,18 +1076,20 @@ fn verify_gw_capability(is_full_hotspot: bool) -> GenericVerifyResult {
 
 /// verify witness lag
 /// if the first received event is the beacon then,
-/// all witnesses must be received within MAX_BEACON_TO_WITNESS_LAG of the beacon
+/// all witnesses must be received within max_beacon_to_witness_lag of the beacon
 /// if the first received event is a witness then,
-/// all subsequent witnesses must be received within MAX_WITNESS_LAG of that first witness
+/// all subsequent witnesses must be received within max_witness_lag of that first witness
 fn verify_witness_lag(
     beacon_received_ts: DateTime<Utc>,
     first_witness_ts: DateTime<Utc>,
     received_ts: DateTime<Utc>,
+    max_beacon_to_witness_lag: Duration,
+    max_witness_lag: Duration,
 ) -> GenericVerifyResult {
     let (first_event_ts, max_permitted_lag) = if beacon_received_ts <= first_witness_ts {
-        (beacon_received_ts, *MAX_BEACON_TO_WITNESS_LAG)
+        (beacon_received_ts, max_beacon_to_witness_lag)
     } else {
-        (first_witness_ts, *MAX_WITNESS_LAG)
+        (first_witness_ts, max_witness_lag)
     };
     let this_witness_lag = received_ts - first_event_ts;
     if this_witness_lag > max_permitted_lag {
@@ -793,9 +1127,13 @@ fn verify_self_witness(
 }
 
 /// verify witness is utilizing same freq and that of the beaconer
-/// tolerance is 100Khz
-fn verify_witness_freq(beacon_freq: u64, witness_freq: u64) -> GenericVerifyResult {
-    if (beacon_freq.abs_diff(witness_freq) as i32) > 1000 * 100 {
+/// within the given tolerance in Hz
+fn verify_witness_freq(
+    beacon_freq: u64,
+    witness_freq: u64,
+    max_frequency_diff: u64,
+) -> GenericVerifyResult {
+    if beacon_freq.abs_diff(witness_freq) > max_frequency_diff {
         tracing::debug!(
             "witness verification failed, reason: {:?}. beaconer freq: {beacon_freq}, witness freq: {witness_freq}",
             InvalidReason::InvalidFrequency
@@ -827,7 +1165,11 @@ fn verify_witness_region(
 }
 
 /// verify witness does not exceed max distance from beaconer
-fn verify_witness_distance(beacon_loc: u64, witness_loc: u64) -> GenericVerifyResult {
+fn verify_witness_distance(
+    beacon_loc: u64,
+    witness_loc: u64,
+    max_distance_km: u32,
+) -> GenericVerifyResult {
     let witness_distance = match calc_distance(beacon_loc, witness_loc) {
         Ok(d) => d,
         Err(_) => {
@@ -837,7 +1179,7 @@ fn verify_witness_distance(beacon_loc: u64, witness_loc: u64) -> GenericVerifyRe
             })
         }
     };
-    if witness_distance / 1000 > POC_DISTANCE_LIMIT {
+    if witness_distance / 1000 > max_distance_km {
         tracing::debug!(
             "witness verification failed, reason: {:?}. distance {witness_distance}",
             InvalidReason::MaxDistanceExceeded
@@ -851,7 +1193,11 @@ fn verify_witness_distance(beacon_loc: u64, witness_loc: u64) -> GenericVerifyRe
 }
 
 /// verify min hex distance between beaconer and witness
-fn verify_witness_cell_distance(beacon_loc: u64, witness_loc: u64) -> GenericVerifyResult {
+fn verify_witness_cell_distance(
+    beacon_loc: u64,
+    witness_loc: u64,
+    min_cell_distance: u32,
+) -> GenericVerifyResult {
     let cell_distance = match calc_cell_distance(beacon_loc, witness_loc) {
         Ok(d) => d,
         Err(_) => {
@@ -861,7 +1207,7 @@ fn verify_witness_cell_distance(beacon_loc: u64, witness_loc: u64) -> GenericVer
             })
         }
     };
-    if cell_distance < POC_CELL_DISTANCE_MINIMUM {
+    if cell_distance < min_cell_distance {
         tracing::debug!(
             "witness verification failed, reason: {:?}. cell distance {cell_distance}",
             InvalidReason::BelowMinDistance
@@ -1061,7 +1407,13 @@ impl VerifyBeaconResult {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::last_beacon::LastBeacon;
+    use crate::{
+        last_beacon::LastBeacon,
+        poc_rules::{
+            RuleSettings, MAX_BEACON_TO_WITNESS_LAG_MS, MAX_FREQUENCY_DIFF, MAX_WITNESS_LAG_MS,
+            POC_CELL_DISTANCE_MINIMUM, POC_DISTANCE_LIMIT,
+        },
+    };
     use chrono::{Duration, TimeZone};
     use denylist::DenyList;
     use file_store::iot_beacon_report::IotBeaconReport;
@@ -1265,6 +1617,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_verify_beacon_slot() {
+        let epoch_start = beacon_schedule::epoch_start(
+            DateTime::parse_from_str("2023 Jan 02 00:00:01 +0000", "%Y %b %d %H:%M:%S %z")
+                .unwrap()
+                .into(),
+        );
+        let pub_key = PublicKeyBinary::from_str(PUBKEY1).unwrap();
+        let schedule = BeaconSchedule::new(
+            b"seed",
+            &pub_key,
+            epoch_start,
+            Decimal::ONE,
+            &beacon_schedule::ScheduleParams::default(),
+        );
+        let first_slot = schedule.slots[0];
+        let second_slot = schedule.slots[1];
+        let last_beacon = Some(LastBeacon {
+            id: pub_key,
+            timestamp: first_slot.start,
+        });
+        // beacon is in a later slot than last beacon, expectation pass
+        assert!(verify_beacon_slot(&last_beacon, second_slot.start, &schedule).is_ok());
+        // first beacon of the gateway in a slot, expectation pass
+        assert!(verify_beacon_slot(&None, first_slot.start, &schedule).is_ok());
+
+        // beacon is in the same slot as last beacon, expectation fail
+        assert_eq!(
+            Err(InvalidResponse {
+                reason: InvalidReason::IrregularInterval,
+                details: None
+            }),
+            verify_beacon_slot(
+                &last_beacon,
+                first_slot.end - Duration::seconds(1),
+                &schedule
+            )
+        );
+
+        // beacon is outside of any slot, expectation fail
+        assert_eq!(
+            Err(InvalidResponse {
+                reason: InvalidReason::IrregularInterval,
+                details: None
+            }),
+            verify_beacon_slot(&None, first_slot.end, &schedule)
+        );
+    }
+
     #[test]
     fn test_verify_entropy() {
         let now = Utc::now();
@@ -1377,6 +1778,15 @@ mod tests {
     #[test]
     fn test_verify_witness_lag() {
         let now = Utc::now();
+        let verify_witness_lag = |beacon_received_ts, first_witness_ts, received_ts| {
+            super::verify_witness_lag(
+                beacon_received_ts,
+                first_witness_ts,
+                received_ts,
+                Duration::milliseconds(MAX_BEACON_TO_WITNESS_LAG_MS),
+                Duration::milliseconds(MAX_WITNESS_LAG_MS),
+            )
+        };
         // a beacon is received first and our test witness is within the acceptable lag from that beacon
         assert!(verify_witness_lag(
             now - Duration::seconds(60),
@@ -1450,14 +1860,14 @@ mod tests {
         // over the tolerance level
         let witness3_freq = beacon_freq + (1000 * 110);
 
-        assert!(verify_witness_freq(beacon_freq, witness1_freq).is_ok());
-        assert!(verify_witness_freq(beacon_freq, witness2_freq).is_ok());
+        assert!(verify_witness_freq(beacon_freq, witness1_freq, MAX_FREQUENCY_DIFF).is_ok());
+        assert!(verify_witness_freq(beacon_freq, witness2_freq, MAX_FREQUENCY_DIFF).is_ok());
         assert_eq!(
             Err(InvalidResponse {
                 reason: InvalidReason::InvalidFrequency,
                 details: None
             }),
-            verify_witness_freq(beacon_freq, witness3_freq)
+            verify_witness_freq(beacon_freq, witness3_freq, MAX_FREQUENCY_DIFF)
         );
     }
 
@@ -1481,13 +1891,13 @@ mod tests {
         let beacon_loc = LOC0;
         let witness1_loc = LOC1;
         let witness2_loc = LOC2;
-        assert!(verify_witness_distance(beacon_loc, witness1_loc).is_ok());
+        assert!(verify_witness_distance(beacon_loc, witness1_loc, POC_DISTANCE_LIMIT).is_ok());
         assert_eq!(
             Err(InvalidResponse {
                 reason: InvalidReason::MaxDistanceExceeded,
                 details: None
             }),
-            verify_witness_distance(beacon_loc, witness2_loc)
+            verify_witness_distance(beacon_loc, witness2_loc, POC_DISTANCE_LIMIT)
         );
     }
 
@@ -1503,10 +1913,13 @@ mod tests {
                 reason: InvalidReason::BelowMinDistance,
                 details: None
             }),
-            verify_witness_cell_distance(beacon_loc, witness1_loc)
+            verify_witness_cell_distance(beacon_loc, witness1_loc, POC_CELL_DISTANCE_MINIMUM)
         );
         // witness 2's location is 28 cells from the beaconer and thus valid
-        assert!(verify_witness_cell_distance(beacon_loc, witness2_loc).is_ok());
+        assert!(
+            verify_witness_cell_distance(beacon_loc, witness2_loc, POC_CELL_DISTANCE_MINIMUM)
+                .is_ok()
+        );
     }
 
     #[test]
@@ -1581,7 +1994,7 @@ mod tests {
         let beaconer_info = beaconer_gateway_info(Some(LOC0), ProtoRegion::Eu868, true);
         let entropy_start = Utc.timestamp_millis_opt(ENTROPY_TIMESTAMP).unwrap();
         let entropy_end = entropy_start + Duration::minutes(3);
-        let beacon_interval = Duration::seconds(21600); // 6 hours in secs
+        let beacon_cadence = BeaconCadence::Interval(Duration::seconds(21600)); // 6 hours in secs
         let deny_list: DenyList = vec![PublicKeyBinary::from_str(DENIED_PUBKEY1).unwrap()]
             .try_into()
             .unwrap();
@@ -1590,6 +2003,7 @@ mod tests {
         let beacon_report1 =
             valid_beacon_report(DENIED_PUBKEY1, entropy_start + Duration::minutes(4));
         let resp1 = do_beacon_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1598,7 +2012,7 @@ mod tests {
             &beacon_report1,
             &beaconer_info,
             &default_region_params(),
-            beacon_interval,
+            &beacon_cadence,
         );
         assert_eq!(
             Err(InvalidResponse {
@@ -1613,6 +2027,7 @@ mod tests {
         // test entropy lifespan verification is active in the beacon validation list
         let beacon_report1 = valid_beacon_report(PUBKEY1, entropy_start + Duration::minutes(4));
         let resp1 = do_beacon_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1621,7 +2036,7 @@ mod tests {
             &beacon_report1,
             &beaconer_info,
             &default_region_params(),
-            beacon_interval,
+            &beacon_cadence,
         );
         assert_eq!(
             Err(InvalidResponse {
@@ -1635,6 +2050,7 @@ mod tests {
         let beacon_report2 = valid_beacon_report(PUBKEY1, entropy_start + Duration::minutes(2));
         let beacon_info2 = beaconer_gateway_info(None, ProtoRegion::Eu868, true);
         let resp2 = do_beacon_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1643,7 +2059,7 @@ mod tests {
             &beacon_report2,
             &beacon_info2,
             &default_region_params(),
-            beacon_interval,
+            &beacon_cadence,
         );
         assert_eq!(
             Err(InvalidResponse {
@@ -1660,6 +2076,7 @@ mod tests {
             timestamp: Utc::now() - Duration::hours(5),
         };
         let resp3 = do_beacon_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1668,7 +2085,7 @@ mod tests {
             &beacon_report3,
             &beaconer_info,
             &default_region_params(),
-            beacon_interval,
+            &beacon_cadence,
         );
         assert_eq!(
             Err(InvalidResponse {
@@ -1682,6 +2099,7 @@ mod tests {
         let beacon_report4 = valid_beacon_report(PUBKEY1, entropy_start + Duration::minutes(2));
         let beacon_info4 = beaconer_gateway_info(Some(LOC0), ProtoRegion::Eu868, false);
         let resp4 = do_beacon_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1690,7 +2108,7 @@ mod tests {
             &beacon_report4,
             &beacon_info4,
             &default_region_params(),
-            beacon_interval,
+            &beacon_cadence,
         );
         assert_eq!(
             Err(InvalidResponse {
@@ -1703,6 +2121,7 @@ mod tests {
         // test beacon construction verification is active in the beacon validation list
         let beacon_report5 = invalid_beacon_bad_payload(entropy_start + Duration::minutes(2));
         let resp5 = do_beacon_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1711,7 +2130,7 @@ mod tests {
             &beacon_report5,
             &beaconer_info,
             &default_region_params(),
-            beacon_interval,
+            &beacon_cadence,
         );
         assert_eq!(
             Err(InvalidResponse {
@@ -1724,6 +2143,7 @@ mod tests {
         // for completeness, confirm our valid beacon report is sane
         let beacon_report6 = valid_beacon_report(PUBKEY1, entropy_start + Duration::minutes(2));
         let resp6 = do_beacon_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1732,7 +2152,7 @@ mod tests {
             &beacon_report6,
             &beaconer_info,
             &default_region_params(),
-            beacon_interval,
+            &beacon_cadence,
         );
         assert_eq!(Ok(()), resp6);
     }
@@ -1763,6 +2183,7 @@ mod tests {
         // test self witness verification is active in the witness validation list
         let witness_report1 = invalid_witness_self_witness(entropy_start + Duration::minutes(2));
         let resp1 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1783,6 +2204,7 @@ mod tests {
         // test entropy lifespan verification is active in the witness validation list
         let witness_report2 = valid_witness_report(PUBKEY2, entropy_start + Duration::minutes(5));
         let resp2 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1803,6 +2225,7 @@ mod tests {
         // test witness packet data verification is active in the witness validation list
         let witness_report3 = invalid_witness_bad_data(entropy_start + Duration::minutes(2));
         let resp3 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1824,6 +2247,7 @@ mod tests {
         let witness_report4 = valid_witness_report(PUBKEY2, entropy_start + Duration::minutes(2));
         let witness_info4 = witness_gateway_info(None, ProtoRegion::Eu868, true);
         let resp4 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1844,6 +2268,7 @@ mod tests {
         // test witness frequency verification is active in the witness validation list
         let witness_report5 = invalid_witness_bad_freq(entropy_start + Duration::minutes(2));
         let resp5 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1865,6 +2290,7 @@ mod tests {
         let witness_report6 = valid_witness_report(PUBKEY2, entropy_start + Duration::minutes(2));
         let witness_info6 = witness_gateway_info(Some(LOC1), ProtoRegion::Us915, true);
         let resp6 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1886,6 +2312,7 @@ mod tests {
         let witness_report7 = valid_witness_report(PUBKEY2, entropy_start + Duration::minutes(2));
         let witness_info7 = witness_gateway_info(Some(LOC3), ProtoRegion::Eu868, true);
         let resp7 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1907,6 +2334,7 @@ mod tests {
         let witness_report8 = valid_witness_report(PUBKEY2, entropy_start + Duration::minutes(2));
         let witness_info8 = witness_gateway_info(Some(LOC2), ProtoRegion::Eu868, true);
         let resp8 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1927,6 +2355,7 @@ mod tests {
         // test witness rssi verification is active in the witness validation list
         let witness_report9 = invalid_witness_bad_rssi(entropy_start + Duration::minutes(2));
         let resp9 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1948,6 +2377,7 @@ mod tests {
         let witness_report10 = valid_witness_report(PUBKEY2, entropy_start + Duration::minutes(2));
         let witness_info10 = witness_gateway_info(Some(LOC4), ProtoRegion::Eu868, false);
         let resp10 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1970,6 +2400,7 @@ mod tests {
         let witness_info11 = witness_gateway_info(Some(LOC4), ProtoRegion::Eu868, true);
 
         let resp11 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -1991,6 +2422,7 @@ mod tests {
         let witness_report12 = valid_witness_report(PUBKEY2, entropy_start + Duration::minutes(2));
         let witness_info12 = witness_gateway_info(Some(LOC4), ProtoRegion::Eu868, true);
         let resp12 = do_witness_verifications(
+            &ActiveRules::default(),
             &deny_list,
             entropy_start,
             entropy_end,
@@ -2003,6 +2435,122 @@ mod tests {
         assert_eq!(Ok(()), resp12);
     }
 
+    #[test]
+    fn test_witness_verifications_follow_configured_rules() {
+        let beacon_report = valid_beacon_report(PUBKEY1, Utc::now() - Duration::minutes(2));
+        let beaconer_metadata = beaconer_gateway_info(Some(LOC0), ProtoRegion::Eu868, true)
+            .metadata
+            .expect("beaconer should have metadata");
+        let entropy_start = Utc.timestamp_millis_opt(1676381847900).unwrap();
+        let entropy_end = entropy_start + Duration::minutes(3);
+        let deny_list: DenyList = vec![PublicKeyBinary::from_str(DENIED_PUBKEY1).unwrap()]
+            .try_into()
+            .unwrap();
+        // witness is a couple thousand km away from the beaconer
+        let witness_report = valid_witness_report(PUBKEY2, entropy_start + Duration::minutes(2));
+        let witness_info = witness_gateway_info(Some(LOC2), ProtoRegion::Eu868, true);
+        let verify = |rules: &ActiveRules| {
+            do_witness_verifications(
+                rules,
+                &deny_list,
+                entropy_start,
+                entropy_end,
+                &witness_report,
+                &witness_info,
+                &beacon_report,
+                &beaconer_metadata,
+                witness_report.received_timestamp,
+            )
+        };
+
+        let rules = PocRules::new(vec![
+            RuleSettings {
+                rule: Rule::WitnessDistance {
+                    max_distance_km: 5000,
+                },
+                enabled: true,
+                effective_from: Some(entropy_start),
+            },
+            RuleSettings {
+                rule: Rule::WitnessRssi,
+                enabled: false,
+                effective_from: Some(entropy_start),
+            },
+        ]);
+
+        // before the rules take effect the witness is too far from the beaconer
+        assert_eq!(
+            Err(InvalidResponse {
+                reason: InvalidReason::MaxDistanceExceeded,
+                details: None
+            }),
+            verify(&rules.active_at(entropy_start - Duration::seconds(1)))
+        );
+        // once they do it is within the raised limit and its rssi goes unchecked
+        assert_eq!(Ok(()), verify(&rules.active_at(entropy_start)));
+    }
+
+    #[test]
+    fn test_explain_witness_verifications_evaluates_every_rule() {
+        let beacon_report = valid_beacon_report(PUBKEY1, Utc::now() - Duration::minutes(2));
+        let beaconer_metadata = beaconer_gateway_info(Some(LOC0), ProtoRegion::Eu868, true)
+            .metadata
+            .expect("beaconer should have metadata");
+        let entropy_start = Utc.timestamp_millis_opt(1676381847900).unwrap();
+        let entropy_end = entropy_start + Duration::minutes(3);
+        let deny_list: DenyList = vec![PublicKeyBinary::from_str(DENIED_PUBKEY1).unwrap()]
+            .try_into()
+            .unwrap();
+        // witness is too far away, too loud for its distance and on the wrong
+        // frequency
+        let witness_report = invalid_witness_bad_freq(entropy_start + Duration::minutes(2));
+        let witness_info = witness_gateway_info(Some(LOC2), ProtoRegion::Eu868, true);
+
+        let explanation = explain_witness_verifications(
+            &ActiveRules::default(),
+            &deny_list,
+            entropy_start,
+            entropy_end,
+            &witness_report,
+            &witness_info,
+            &beacon_report,
+            &beaconer_metadata,
+            witness_report.received_timestamp,
+        )
+        .expect("witness is asserted");
+
+        let failed: Vec<_> = explanation
+            .rules
+            .iter()
+            .filter_map(|outcome| {
+                outcome
+                    .invalid_reason
+                    .map(|reason| (outcome.rule.name(), reason))
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "witness_frequency",
+                    InvalidReason::InvalidFrequency.as_str_name()
+                ),
+                (
+                    "witness_distance",
+                    InvalidReason::MaxDistanceExceeded.as_str_name()
+                ),
+                ("witness_rssi", InvalidReason::BadRssi.as_str_name()),
+            ],
+            failed
+        );
+        assert!(!explanation
+            .rules
+            .iter()
+            .any(|outcome| !outcome.rule.applies_to_witness()));
+        assert!(explanation.measurements.distance_m.unwrap() > POC_DISTANCE_LIMIT * 1000);
+        assert!(explanation.measurements.expected_rssi_dbm.is_some());
+        assert_eq!(0, explanation.measurements.first_witness_lag_ms);
+    }
+
     fn beaconer_gateway_info(
         location: Option<u64>,
         region: ProtoRegion,
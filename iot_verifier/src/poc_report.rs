@@ -1,7 +1,8 @@
-use crate::entropy::ENTROPY_LIFESPAN;
+use crate::{entropy::ENTROPY_LIFESPAN, gateway_cache::GatewayCacheError};
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{Postgres, Transaction};
+use std::fmt;
 
 const REPORT_INSERT_SQL: &str = "insert into poc_report (
     id,
@@ -13,7 +14,7 @@ const REPORT_INSERT_SQL: &str = "insert into poc_report (
     status
 ) ";
 
-#[derive(sqlx::Type, Serialize, Deserialize, Debug)]
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, clap::ValueEnum)]
 #[sqlx(type_name = "reporttype", rename_all = "lowercase")]
 pub enum ReportType {
     Witness,
@@ -29,6 +30,97 @@ pub enum IotStatus {
     Invalid,
 }
 
+/// Why a report could not be verified. The last failure of a report is
+/// recorded on each failed attempt, and the purger reports it when the report
+/// goes stale
+#[derive(
+    sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum,
+)]
+#[sqlx(type_name = "failurereason", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum FailureReason {
+    /// the entropy of the beacon was never received
+    EntropyMissing,
+    /// the witnessed beacon was never received or verified
+    BeaconMissing,
+    /// the report was never attempted
+    NotProcessed,
+    Database,
+    Decode,
+    GatewayResolution,
+    RegionParams,
+    FileSink,
+    Other,
+}
+
+impl FailureReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::EntropyMissing => "entropy_missing",
+            Self::BeaconMissing => "beacon_missing",
+            Self::NotProcessed => "not_processed",
+            Self::Database => "database",
+            Self::Decode => "decode",
+            Self::GatewayResolution => "gateway_resolution",
+            Self::RegionParams => "region_params",
+            Self::FileSink => "file_sink",
+            Self::Other => "other",
+        }
+    }
+
+    /// Categorize the error of a failed attempt. A reason attached to the
+    /// error as context takes precedence over the errors of its chain
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        if let Some(reason) = err.downcast_ref::<Self>() {
+            return *reason;
+        }
+        err.chain()
+            .find_map(|cause| {
+                if cause.is::<sqlx::Error>() {
+                    Some(Self::Database)
+                } else if let Some(err) = cause.downcast_ref::<file_store::Error>() {
+                    Some(Self::from_file_store_error(err))
+                } else if cause.is::<GatewayCacheError>() {
+                    Some(Self::GatewayResolution)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(Self::Other)
+    }
+
+    fn from_file_store_error(err: &file_store::Error) -> Self {
+        use file_store::Error;
+        match err {
+            Error::Decode(_) | Error::NotFound(_) | Error::Integrity(_) | Error::Crypto(_) => {
+                Self::Decode
+            }
+            Error::Io(_)
+            | Error::Encode(_)
+            | Error::Channel
+            | Error::NoManifest
+            | Error::SendTimeout
+            | Error::Shutdown
+            | Error::NotCommitted
+            | Error::SendError(_) => Self::FileSink,
+            Error::DbError(_) => Self::Database,
+            Error::Csv(_)
+            | Error::Parquet(_)
+            | Error::Arrow(_)
+            | Error::Aws(_)
+            | Error::Config(_)
+            | Error::JoinError(_)
+            | Error::FileInfoPollerError(_) => Self::Other,
+        }
+    }
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 pub struct InsertBindings {
     pub id: Vec<u8>,
     pub remote_entropy: Vec<u8>,
@@ -56,6 +148,8 @@ pub struct Report {
     pub timestamp: Option<DateTime<Utc>>,
     #[sqlx(default)]
     pub version: Option<i32>,
+    #[sqlx(default)]
+    pub last_failure: Option<FailureReason>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -334,6 +428,7 @@ impl Report {
         executor: E,
         id: &Vec<u8>,
         timestamp: DateTime<Utc>,
+        reason: FailureReason,
     ) -> Result<(), ReportError>
     where
         E: sqlx::Executor<'c, Database = sqlx::Postgres>,
@@ -342,11 +437,13 @@ impl Report {
             r#"
             update poc_report set
                 attempts = attempts + 1,
-                last_processed = $1
-            where id = $2;
+                last_processed = $1,
+                last_failure = $2
+            where id = $3;
             "#,
         )
         .bind(timestamp)
+        .bind(reason)
         .bind(id)
         .execute(executor)
         .await?;
@@ -368,12 +465,18 @@ impl Report {
         // if the entropy is not there the beacon will never be processed
         // Such beacons will eventually be handled by the purger and failed there
         // stale beacon reports, for this reason, are determined solely based on time
+        // the entropy, if any, is joined to tell beacons which never had
+        // entropy apart from those which failed verification
         let stale_time = Utc::now() - stale_period;
         Ok(sqlx::query_as::<_, Self>(
             r#"
-            select * from poc_report
-            where report_type = 'beacon' and status = 'ready'
-            and created_at < $1
+            select poc_report.*,
+                entropy.timestamp,
+                entropy.version
+            from poc_report
+            left join entropy on poc_report.remote_entropy=entropy.data
+            where poc_report.report_type = 'beacon' and poc_report.status = 'ready'
+            and poc_report.created_at < $1
             "#,
         )
         .bind(stale_time)
@@ -421,4 +524,160 @@ impl Report {
         .fetch_all(executor)
         .await?)
     }
+
+    /// Why a stale report was never verified
+    pub fn stale_reason(&self) -> FailureReason {
+        match self.report_type {
+            ReportType::Beacon if self.timestamp.is_none() => FailureReason::EntropyMissing,
+            ReportType::Beacon => self.last_failure.unwrap_or(FailureReason::NotProcessed),
+            ReportType::Witness => self.last_failure.unwrap_or(FailureReason::BeaconMissing),
+        }
+    }
+
+    /// Keep a copy of a purged report so that it can be re-queued
+    pub async fn archive_purged(
+        executor: impl sqlx::PgExecutor<'_>,
+        report: &Report,
+        reason: FailureReason,
+    ) -> Result<(), ReportError> {
+        sqlx::query(
+            r#"
+            insert into purged_poc_report (
+                id,
+                remote_entropy,
+                packet_data,
+                report_data,
+                report_type,
+                report_timestamp,
+                attempts,
+                reason
+            ) values ($1, $2, $3, $4, $5, $6, $7, $8)
+            on conflict (id) do update set
+                attempts = excluded.attempts,
+                reason = excluded.reason,
+                purged_at = now()
+            "#,
+        )
+        .bind(&report.id)
+        .bind(&report.remote_entropy)
+        .bind(&report.packet_data)
+        .bind(&report.report_data)
+        .bind(&report.report_type)
+        .bind(report.report_timestamp)
+        .bind(report.attempts)
+        .bind(reason)
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+
+    /// Move the purged reports received in the given window back to the
+    /// queue, optionally only those of a type or purged for a reason.
+    /// Returns the number of re-queued reports
+    pub async fn requeue_purged(
+        executor: impl sqlx::PgExecutor<'_>,
+        after: DateTime<Utc>,
+        before: DateTime<Utc>,
+        report_type: Option<ReportType>,
+        reason: Option<FailureReason>,
+    ) -> Result<u64, ReportError> {
+        Ok(sqlx::query(
+            r#"
+            with requeued as (
+                delete from purged_poc_report
+                where report_timestamp >= $1 and report_timestamp < $2
+                and ($3::reporttype is null or report_type = $3)
+                and ($4::failurereason is null or reason = $4)
+                returning id, remote_entropy, packet_data, report_data, report_type, report_timestamp
+            )
+            insert into poc_report (
+                id,
+                remote_entropy,
+                packet_data,
+                report_data,
+                report_timestamp,
+                report_type,
+                status
+            )
+            select id, remote_entropy, packet_data, report_data, report_timestamp, report_type, 'ready'
+            from requeued
+            on conflict (id) do nothing
+            "#,
+        )
+        .bind(after)
+        .bind(before)
+        .bind(report_type)
+        .bind(reason)
+        .execute(executor)
+        .await?
+        .rows_affected())
+    }
+
+    /// Drop the copies of reports purged before the retention period
+    pub async fn prune_purged(
+        executor: impl sqlx::PgExecutor<'_>,
+        retention_period: Duration,
+    ) -> Result<u64, ReportError> {
+        let purged_before = Utc::now() - retention_period;
+        Ok(sqlx::query(
+            r#"
+            delete from purged_poc_report
+            where purged_at < $1
+            "#,
+        )
+        .bind(purged_before)
+        .execute(executor)
+        .await?
+        .rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn categorizes_file_store_errors_by_variant() {
+        let reason = |err: file_store::Error| FailureReason::from_error(&anyhow::Error::from(err));
+        assert_eq!(
+            FailureReason::Decode,
+            reason("not a file".parse::<file_store::FileInfo>().unwrap_err())
+        );
+        assert_eq!(
+            FailureReason::Decode,
+            reason(file_store::Error::NotFound("report".to_string()))
+        );
+        assert_eq!(
+            FailureReason::FileSink,
+            reason(file_store::Error::SendTimeout)
+        );
+        assert_eq!(FailureReason::FileSink, reason(file_store::Error::Shutdown));
+        assert_eq!(
+            FailureReason::FileSink,
+            reason(file_store::Error::Io(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "disk full"
+            )))
+        );
+        assert_eq!(
+            FailureReason::Database,
+            reason(file_store::Error::DbError(sqlx::Error::PoolTimedOut))
+        );
+    }
+
+    #[test]
+    fn prefers_the_reason_attached_as_context() {
+        let err =
+            anyhow::Error::from(file_store::Error::Shutdown).context(FailureReason::RegionParams);
+        assert_eq!(FailureReason::RegionParams, FailureReason::from_error(&err));
+        let err = Err::<(), _>(file_store::Error::Channel)
+            .context("writing invalid beacon")
+            .unwrap_err();
+        assert_eq!(FailureReason::FileSink, FailureReason::from_error(&err));
+        assert_eq!(
+            FailureReason::Other,
+            FailureReason::from_error(&anyhow::anyhow!("unexpected"))
+        );
+    }
 }
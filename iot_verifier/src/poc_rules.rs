@@ -0,0 +1,419 @@
+//! Named PoC verification rules.
+//!
+//! Every check of a beacon or witness is a rule that can be disabled or have
+//! its thresholds changed from settings. A rule can be listed several times
+//! with different `effective_from` timestamps, so that a parameter change can
+//! be scheduled ahead of time. The rules in effect for a PoC are the ones in
+//! effect when its beacon was received, and are recorded next to the PoC
+//! output as `iot_verification_rules` records.
+
+use crate::beacon_schedule::{
+    ScheduleParams, BEACON_SLOT_MINUTES, MAX_BEACONS_PER_EPOCH, MIN_BEACONS_PER_EPOCH,
+};
+use chrono::{DateTime, Utc};
+use file_store::iot_poc_rules::IotPocRule;
+use serde::{Deserialize, Serialize};
+
+/// max permitted distance of a witness from a beaconer measured in KM
+pub const POC_DISTANCE_LIMIT: u32 = 100;
+/// the minimum distance in cells between a beaconer and witness
+pub const POC_CELL_DISTANCE_MINIMUM: u32 = 8;
+/// max permitted lag between the first witness and all subsequent witnesses
+pub const MAX_WITNESS_LAG_MS: i64 = 1500;
+/// max permitted lag between the beaconer and a witness
+pub const MAX_BEACON_TO_WITNESS_LAG_MS: i64 = 4000;
+/// max permitted difference between the beacon and witness frequency, in Hz
+pub const MAX_FREQUENCY_DIFF: u64 = 100 * 1000;
+
+/// A verification rule and its parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum Rule {
+    /// gateway is not on the deny list
+    Denylist,
+    /// beaconer-witness edge is not on the deny list
+    EdgeDenylist,
+    /// witness is not witnessing its own beacon
+    SelfWitness,
+    /// report was received within the lifespan of its entropy
+    Entropy,
+    /// witness was received in time after the beacon and the first witness
+    WitnessLag {
+        #[serde(default = "default_max_beacon_to_witness_lag_ms")]
+        max_beacon_to_witness_lag_ms: i64,
+        #[serde(default = "default_max_witness_lag_ms")]
+        max_witness_lag_ms: i64,
+    },
+    /// witness reports the data of the beacon
+    WitnessData,
+    /// gateway is permitted to participate in PoC
+    GatewayCapability,
+    /// beaconer is permitted to beacon at this time, once per beacon interval
+    /// or, if density scaled, once per slot of its
+    /// [`BeaconSchedule`](crate::beacon_schedule::BeaconSchedule)
+    BeaconSchedule {
+        #[serde(default)]
+        density_scaled: bool,
+        #[serde(default = "default_min_beacons_per_epoch")]
+        min_beacons_per_epoch: u32,
+        #[serde(default = "default_max_beacons_per_epoch")]
+        max_beacons_per_epoch: u32,
+        #[serde(default = "default_beacon_slot_minutes")]
+        slot_minutes: u32,
+    },
+    /// beacon was constructed from the entropy and the beaconer region
+    BeaconPayload,
+    /// witness is on the frequency of the beacon
+    WitnessFrequency {
+        #[serde(default = "default_max_frequency_diff")]
+        max_frequency_diff: u64,
+    },
+    /// witness is in the region of the beaconer
+    WitnessRegion,
+    /// witness is not too close to the beaconer
+    WitnessCellDistance {
+        #[serde(default = "default_min_cell_distance")]
+        min_cell_distance: u32,
+    },
+    /// witness is not too far from the beaconer
+    WitnessDistance {
+        #[serde(default = "default_max_distance_km")]
+        max_distance_km: u32,
+    },
+    /// witness signal is plausible for its distance from the beaconer
+    WitnessRssi,
+}
+
+fn default_max_beacon_to_witness_lag_ms() -> i64 {
+    MAX_BEACON_TO_WITNESS_LAG_MS
+}
+
+fn default_max_witness_lag_ms() -> i64 {
+    MAX_WITNESS_LAG_MS
+}
+
+fn default_max_frequency_diff() -> u64 {
+    MAX_FREQUENCY_DIFF
+}
+
+fn default_min_cell_distance() -> u32 {
+    POC_CELL_DISTANCE_MINIMUM
+}
+
+fn default_max_distance_km() -> u32 {
+    POC_DISTANCE_LIMIT
+}
+
+fn default_min_beacons_per_epoch() -> u32 {
+    MIN_BEACONS_PER_EPOCH
+}
+
+fn default_max_beacons_per_epoch() -> u32 {
+    MAX_BEACONS_PER_EPOCH
+}
+
+fn default_beacon_slot_minutes() -> u32 {
+    BEACON_SLOT_MINUTES
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Rule {
+    /// Every rule with its default parameters, in the order rules are
+    /// applied
+    pub fn defaults() -> [Self; 14] {
+        [
+            Self::Denylist,
+            Self::EdgeDenylist,
+            Self::SelfWitness,
+            Self::Entropy,
+            Self::WitnessLag {
+                max_beacon_to_witness_lag_ms: MAX_BEACON_TO_WITNESS_LAG_MS,
+                max_witness_lag_ms: MAX_WITNESS_LAG_MS,
+            },
+            Self::WitnessData,
+            Self::GatewayCapability,
+            Self::BeaconSchedule {
+                density_scaled: false,
+                min_beacons_per_epoch: MIN_BEACONS_PER_EPOCH,
+                max_beacons_per_epoch: MAX_BEACONS_PER_EPOCH,
+                slot_minutes: BEACON_SLOT_MINUTES,
+            },
+            Self::BeaconPayload,
+            Self::WitnessFrequency {
+                max_frequency_diff: MAX_FREQUENCY_DIFF,
+            },
+            Self::WitnessRegion,
+            Self::WitnessCellDistance {
+                min_cell_distance: POC_CELL_DISTANCE_MINIMUM,
+            },
+            Self::WitnessDistance {
+                max_distance_km: POC_DISTANCE_LIMIT,
+            },
+            Self::WitnessRssi,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Denylist => "denylist",
+            Self::EdgeDenylist => "edge_denylist",
+            Self::SelfWitness => "self_witness",
+            Self::Entropy => "entropy",
+            Self::WitnessLag { .. } => "witness_lag",
+            Self::WitnessData => "witness_data",
+            Self::GatewayCapability => "gateway_capability",
+            Self::BeaconSchedule { .. } => "beacon_schedule",
+            Self::BeaconPayload => "beacon_payload",
+            Self::WitnessFrequency { .. } => "witness_frequency",
+            Self::WitnessRegion => "witness_region",
+            Self::WitnessCellDistance { .. } => "witness_cell_distance",
+            Self::WitnessDistance { .. } => "witness_distance",
+            Self::WitnessRssi => "witness_rssi",
+        }
+    }
+
+    /// Whether the rule is applied to beacons
+    pub fn applies_to_beacon(&self) -> bool {
+        matches!(
+            self,
+            Self::Denylist
+                | Self::Entropy
+                | Self::GatewayCapability
+                | Self::BeaconSchedule { .. }
+                | Self::BeaconPayload
+        )
+    }
+
+    /// Whether the rule is applied to witnesses
+    pub fn applies_to_witness(&self) -> bool {
+        !matches!(self, Self::BeaconSchedule { .. } | Self::BeaconPayload)
+    }
+}
+
+/// A rule as configured in settings
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuleSettings {
+    #[serde(flatten)]
+    pub rule: Rule,
+    /// Whether the rule is applied. Default true
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// When the rule takes effect, by the received time of beacons. Always
+    /// in effect if not set
+    #[serde(default)]
+    pub effective_from: Option<DateTime<Utc>>,
+}
+
+/// The configured rules, by the time they take effect. Rules that are not
+/// configured are applied with their default parameters
+#[derive(Debug, Clone, Default)]
+pub struct PocRules {
+    settings: Vec<RuleSettings>,
+}
+
+impl PocRules {
+    pub fn new(settings: Vec<RuleSettings>) -> Self {
+        Self { settings }
+    }
+
+    /// The rules in effect for a PoC whose beacon was received at the given
+    /// time
+    pub fn active_at(&self, timestamp: DateTime<Utc>) -> ActiveRules {
+        let rules = Rule::defaults()
+            .into_iter()
+            .filter_map(|default| {
+                // of the settings of a rule in effect, the latest to take effect wins
+                let settings = self
+                    .settings
+                    .iter()
+                    .filter(|settings| {
+                        settings.rule.name() == default.name()
+                            && settings
+                                .effective_from
+                                .map_or(true, |effective_from| effective_from <= timestamp)
+                    })
+                    .max_by_key(|settings| settings.effective_from);
+                match settings {
+                    Some(settings) if !settings.enabled => None,
+                    Some(settings) => Some(ActiveRule {
+                        rule: settings.rule,
+                        effective_from: settings.effective_from,
+                    }),
+                    None => Some(ActiveRule {
+                        rule: default,
+                        effective_from: None,
+                    }),
+                }
+            })
+            .collect();
+        ActiveRules { rules }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ActiveRule {
+    rule: Rule,
+    effective_from: Option<DateTime<Utc>>,
+}
+
+/// The enabled rules in effect for a PoC, in the order they are applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveRules {
+    rules: Vec<ActiveRule>,
+}
+
+impl Default for ActiveRules {
+    fn default() -> Self {
+        PocRules::default().active_at(Utc::now())
+    }
+}
+
+impl ActiveRules {
+    pub fn iter(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter().map(|active| &active.rule)
+    }
+
+    /// The params of the beacon schedule if it is density scaled
+    pub fn beacon_schedule(&self) -> Option<ScheduleParams> {
+        self.iter().find_map(|rule| match *rule {
+            Rule::BeaconSchedule {
+                density_scaled: true,
+                min_beacons_per_epoch,
+                max_beacons_per_epoch,
+                slot_minutes,
+            } => Some(ScheduleParams {
+                min_beacons: min_beacons_per_epoch,
+                max_beacons: max_beacons_per_epoch,
+                slot_minutes,
+            }),
+            _ => None,
+        })
+    }
+
+    /// The rules as recorded next to the PoC output
+    pub fn to_records(&self) -> Vec<IotPocRule> {
+        self.rules
+            .iter()
+            .map(|active| IotPocRule {
+                name: active.rule.name().to_string(),
+                params: serde_json::to_string(&active.rule).expect("serializable rule"),
+                effective_from: active.effective_from,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn settings(rule: Rule, enabled: bool, effective_from: Option<DateTime<Utc>>) -> RuleSettings {
+        RuleSettings {
+            rule,
+            enabled,
+            effective_from,
+        }
+    }
+
+    #[test]
+    fn applies_all_rules_with_defaults_if_unconfigured() {
+        let rules = PocRules::default().active_at(Utc::now());
+        assert_eq!(
+            Rule::defaults().to_vec(),
+            rules.iter().copied().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn applies_the_latest_rule_in_effect() {
+        let hip_epoch = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let rules = PocRules::new(vec![
+            settings(
+                Rule::WitnessDistance {
+                    max_distance_km: 50,
+                },
+                true,
+                Some(hip_epoch),
+            ),
+            settings(Rule::WitnessRssi, false, None),
+        ]);
+
+        let before = rules.active_at(hip_epoch - Duration::seconds(1));
+        assert!(before.iter().any(|rule| *rule
+            == Rule::WitnessDistance {
+                max_distance_km: POC_DISTANCE_LIMIT
+            }));
+        assert!(!before.iter().any(|rule| *rule == Rule::WitnessRssi));
+
+        let after = rules.active_at(hip_epoch);
+        assert!(after.iter().any(|rule| *rule
+            == Rule::WitnessDistance {
+                max_distance_km: 50
+            }));
+        assert_eq!(
+            Some(IotPocRule {
+                name: "witness_distance".to_string(),
+                params: r#"{"name":"witness_distance","max_distance_km":50}"#.to_string(),
+                effective_from: Some(hip_epoch),
+            }),
+            after
+                .to_records()
+                .into_iter()
+                .find(|rule| rule.name == "witness_distance")
+        );
+    }
+
+    #[test]
+    fn deserializes_rules_from_settings() {
+        let settings: Vec<RuleSettings> = serde_json::from_str(
+            r#"[
+                {"name": "witness_cell_distance", "min_cell_distance": 10},
+                {"name": "witness_distance", "effective_from": "2024-03-01T00:00:00Z"},
+                {"name": "beacon_schedule", "enabled": false}
+            ]"#,
+        )
+        .expect("rule settings");
+
+        assert_eq!(
+            Rule::WitnessCellDistance {
+                min_cell_distance: 10
+            },
+            settings[0].rule
+        );
+        assert_eq!(
+            Rule::WitnessDistance {
+                max_distance_km: POC_DISTANCE_LIMIT
+            },
+            settings[1].rule
+        );
+        assert!(settings[1].effective_from.is_some());
+        assert!(!settings[2].enabled);
+    }
+
+    #[test]
+    fn density_scales_the_beacon_schedule_if_configured() {
+        assert_eq!(
+            None,
+            PocRules::default().active_at(Utc::now()).beacon_schedule()
+        );
+
+        let settings: Vec<RuleSettings> = serde_json::from_str(
+            r#"[{"name": "beacon_schedule", "density_scaled": true, "max_beacons_per_epoch": 8}]"#,
+        )
+        .expect("rule settings");
+        assert_eq!(
+            Some(ScheduleParams {
+                min_beacons: MIN_BEACONS_PER_EPOCH,
+                max_beacons: 8,
+                slot_minutes: BEACON_SLOT_MINUTES,
+            }),
+            PocRules::new(settings)
+                .active_at(Utc::now())
+                .beacon_schedule()
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{entropy::Entropy, poc_report::Report, telemetry};
+use crate::{
+    entropy::Entropy,
+    poc_report::{FailureReason, Report},
+    telemetry,
+};
 use chrono::Duration;
 use file_store::{
     file_sink::FileSinkClient,
@@ -17,7 +21,7 @@ use helium_proto::services::poc_lora::{
 };
 use lazy_static::lazy_static;
 use sqlx::{PgPool, Postgres};
-use std::ops::DerefMut;
+use std::{collections::BTreeMap, ops::DerefMut};
 use task_manager::ManagedTask;
 use tokio::{
     sync::Mutex,
@@ -34,6 +38,8 @@ lazy_static! {
     static ref WITNESS_STALE_PERIOD: Duration = Duration::minutes(45);
     /// the period after which an entropy entry in the DB will be deemed stale
     static ref ENTROPY_STALE_PERIOD: Duration = Duration::minutes(60);
+    /// the period for which purged reports are kept in the DB for re-queueing
+    static ref PURGED_REPORT_RETENTION_PERIOD: Duration = Duration::days(7);
 }
 
 pub struct Purger {
@@ -117,10 +123,11 @@ impl Purger {
         tracing::info!("purging {:?} stale beacons", stale_beacons.len());
 
         let tx = Mutex::new(self.pool.begin().await?);
+        let purged_beacons = Breakdown::default();
         stream::iter(stale_beacons)
             .for_each_concurrent(PURGER_WORKERS, |report| async {
                 match self.handle_purged_beacon(&tx, report).await {
-                    Ok(()) => (),
+                    Ok(reason) => purged_beacons.add(reason).await,
                     Err(err) => {
                         tracing::warn!("failed to purge beacon: {err:?}")
                     }
@@ -129,6 +136,7 @@ impl Purger {
             .await;
         self.invalid_beacon_sink.commit().await?;
         tx.into_inner().commit().await?;
+        purged_beacons.report("beacon").await;
 
         let witness_stale_period = self.base_stale_period + self.witness_stale_period;
         tracing::info!(
@@ -140,10 +148,11 @@ impl Purger {
         tracing::info!("purging {num_stale_witnesses} stale witnesses");
 
         let tx = Mutex::new(self.pool.begin().await?);
+        let purged_witnesses = Breakdown::default();
         stream::iter(stale_witnesses)
             .for_each_concurrent(PURGER_WORKERS, |report| async {
                 match self.handle_purged_witness(&tx, report).await {
-                    Ok(()) => (),
+                    Ok(reason) => purged_witnesses.add(reason).await,
                     Err(err) => {
                         tracing::warn!("failed to purge witness: {err:?}")
                     }
@@ -152,8 +161,12 @@ impl Purger {
             .await;
         self.invalid_witness_sink.commit().await?;
         tx.into_inner().commit().await?;
+        purged_witnesses.report("witness").await;
         tracing::info!("completed purging {num_stale_witnesses} stale witnesses");
 
+        // drop purged reports which are past re-queueing
+        _ = Report::prune_purged(&self.pool, *PURGED_REPORT_RETENTION_PERIOD).await;
+
         // purge any stale entropy, no need to output anything to s3 here
         _ = Entropy::purge(
             &self.pool,
@@ -167,7 +180,8 @@ impl Purger {
         &self,
         tx: &Mutex<sqlx::Transaction<'_, Postgres>>,
         db_beacon: Report,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<FailureReason> {
+        let reason = db_beacon.stale_reason();
         let beacon_buf: &[u8] = &db_beacon.report_data;
         let beacon_report = IotBeaconIngestReport::decode(beacon_buf)?;
         let beacon_id = beacon_report.ingest_id();
@@ -187,20 +201,26 @@ impl Purger {
         self.invalid_beacon_sink
             .write(
                 invalid_beacon_proto,
-                &[("reason", InvalidReason::Stale.as_str_name())],
+                &[
+                    ("reason", InvalidReason::Stale.as_str_name()),
+                    ("failure", reason.as_str()),
+                ],
             )
             .await?;
-        // delete the report from the DB
-        Report::delete_report(tx.lock().await.deref_mut(), &beacon_id).await?;
+        // keep a copy for re-queueing and delete the report from the DB
+        let mut tx = tx.lock().await;
+        Report::archive_purged(tx.deref_mut(), &db_beacon, reason).await?;
+        Report::delete_report(tx.deref_mut(), &beacon_id).await?;
         telemetry::decrement_num_beacons();
-        Ok(())
+        Ok(reason)
     }
 
     async fn handle_purged_witness(
         &self,
         tx: &Mutex<sqlx::Transaction<'_, Postgres>>,
         db_witness: Report,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<FailureReason> {
+        let reason = db_witness.stale_reason();
         let witness_buf: &[u8] = &db_witness.report_data;
         let witness_report = IotWitnessIngestReport::decode(witness_buf)?;
         let witness_id = witness_report.ingest_id();
@@ -217,12 +237,35 @@ impl Purger {
         self.invalid_witness_sink
             .write(
                 invalid_witness_report_proto,
-                &[("reason", InvalidReason::Stale.as_str_name())],
+                &[
+                    ("reason", InvalidReason::Stale.as_str_name()),
+                    ("failure", reason.as_str()),
+                ],
             )
             .await?;
 
-        // delete the report from the DB
-        Report::delete_report(tx.lock().await.deref_mut(), &witness_id).await?;
-        Ok(())
+        // keep a copy for re-queueing and delete the report from the DB
+        let mut tx = tx.lock().await;
+        Report::archive_purged(tx.deref_mut(), &db_witness, reason).await?;
+        Report::delete_report(tx.deref_mut(), &witness_id).await?;
+        Ok(reason)
+    }
+}
+
+/// Count of the reports purged in a tick per failure reason
+#[derive(Default)]
+struct Breakdown(Mutex<BTreeMap<&'static str, u64>>);
+
+impl Breakdown {
+    async fn add(&self, reason: FailureReason) {
+        *self.0.lock().await.entry(reason.as_str()).or_default() += 1;
+    }
+
+    async fn report(self, report_type: &'static str) {
+        let breakdown = self.0.into_inner();
+        for (reason, count) in &breakdown {
+            telemetry::count_purged_reports(report_type, *reason, *count);
+        }
+        tracing::info!("purged stale {report_type} reports by failure: {breakdown:?}");
     }
 }
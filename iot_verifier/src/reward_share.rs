@@ -8,6 +8,7 @@ use helium_proto::services::poc_lora::iot_reward_share::Reward as ProtoReward;
 use lazy_static::lazy_static;
 use rust_decimal::prelude::*;
 use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
 use sqlx::{Postgres, Transaction};
 use std::{collections::HashMap, ops::Range};
 
@@ -44,21 +45,11 @@ pub fn get_scheduled_poc_tokens(
     duration: Duration,
     dc_transfer_remainder: Decimal,
 ) -> (Decimal, Decimal) {
-    (
-        get_tokens_by_duration(*REWARDS_PER_DAY * *BEACON_REWARDS_PER_DAY_PERCENT, duration)
-            + (dc_transfer_remainder * *BEACON_DC_REMAINER_PERCENT),
-        get_tokens_by_duration(
-            *REWARDS_PER_DAY * *WITNESS_REWARDS_PER_DAY_PERCENT,
-            duration,
-        ) + (dc_transfer_remainder * *WITNESS_DC_REMAINER_PERCENT),
-    )
+    RewardSplit::default().scheduled_poc_tokens(duration, dc_transfer_remainder)
 }
 
 pub fn get_scheduled_dc_tokens(duration: Duration) -> Decimal {
-    get_tokens_by_duration(
-        *REWARDS_PER_DAY * *DATA_TRANSFER_REWARDS_PER_DAY_PERCENT,
-        duration,
-    )
+    RewardSplit::default().scheduled_dc_tokens(duration)
 }
 
 pub fn get_scheduled_ops_fund_tokens(duration: Duration) -> Decimal {
@@ -75,6 +66,50 @@ pub fn get_scheduled_oracle_tokens(duration: Duration) -> Decimal {
     )
 }
 
+/// The split of the daily rewards between beacons, witnesses and data
+/// transfer, and of the unused data transfer rewards between beacons and
+/// witnesses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RewardSplit {
+    pub beacon_percent: Decimal,
+    pub witness_percent: Decimal,
+    pub data_transfer_percent: Decimal,
+    pub beacon_dc_remainder_percent: Decimal,
+    pub witness_dc_remainder_percent: Decimal,
+}
+
+impl Default for RewardSplit {
+    fn default() -> Self {
+        Self {
+            beacon_percent: *BEACON_REWARDS_PER_DAY_PERCENT,
+            witness_percent: *WITNESS_REWARDS_PER_DAY_PERCENT,
+            data_transfer_percent: *DATA_TRANSFER_REWARDS_PER_DAY_PERCENT,
+            beacon_dc_remainder_percent: *BEACON_DC_REMAINER_PERCENT,
+            witness_dc_remainder_percent: *WITNESS_DC_REMAINER_PERCENT,
+        }
+    }
+}
+
+impl RewardSplit {
+    pub fn scheduled_poc_tokens(
+        &self,
+        duration: Duration,
+        dc_transfer_remainder: Decimal,
+    ) -> (Decimal, Decimal) {
+        (
+            get_tokens_by_duration(*REWARDS_PER_DAY * self.beacon_percent, duration)
+                + (dc_transfer_remainder * self.beacon_dc_remainder_percent),
+            get_tokens_by_duration(*REWARDS_PER_DAY * self.witness_percent, duration)
+                + (dc_transfer_remainder * self.witness_dc_remainder_percent),
+        )
+    }
+
+    pub fn scheduled_dc_tokens(&self, duration: Duration) -> Decimal {
+        get_tokens_by_duration(*REWARDS_PER_DAY * self.data_transfer_percent, duration)
+    }
+}
+
 #[derive(sqlx::FromRow)]
 pub struct GatewayPocShare {
     pub hotspot_key: PublicKeyBinary,
@@ -296,13 +331,24 @@ impl GatewayShares {
         reward_period: &'_ Range<DateTime<Utc>>,
         iot_price: Decimal,
     ) -> anyhow::Result<(Decimal, Decimal, Decimal)> {
+        Ok(self.rewards_per_share_with_split(reward_period, iot_price, &RewardSplit::default()))
+    }
+
+    /// The rewards per beacon, witness and data transfer share under the
+    /// given split of the rewards
+    pub fn rewards_per_share_with_split(
+        &self,
+        reward_period: &'_ Range<DateTime<Utc>>,
+        iot_price: Decimal,
+        split: &RewardSplit,
+    ) -> (Decimal, Decimal, Decimal) {
         // the total number of shares for beacons, witnesses and data transfer
         // dc shares here is the sum of all spent data transfer DC this epoch
         let (total_beacon_shares, total_witness_shares, total_dc_shares) = self.total_shares();
 
         // the total number of iot rewards for dc transfer this epoch
         let total_dc_transfer_rewards =
-            get_scheduled_dc_tokens(reward_period.end - reward_period.start);
+            split.scheduled_dc_tokens(reward_period.end - reward_period.start);
 
         // convert the total spent data transfer DC to it equiv iot bone value
         // the rewards distributed to gateways will be equal to this
@@ -317,7 +363,7 @@ impl GatewayShares {
             );
         // the total amounts of iot rewards this epoch for beacons, witnesses
         // taking into account any remaining dc transfer rewards
-        let (total_beacon_rewards, total_witness_rewards) = get_scheduled_poc_tokens(
+        let (total_beacon_rewards, total_witness_rewards) = split.scheduled_poc_tokens(
             reward_period.end - reward_period.start,
             dc_transfer_rewards_unused,
         );
@@ -335,11 +381,11 @@ impl GatewayShares {
             %dc_transfer_rewards_per_share,
             "data transfer rewards"
         );
-        Ok((
+        (
             beacon_rewards_per_share,
             witness_rewards_per_share,
             dc_transfer_rewards_per_share,
-        ))
+        )
     }
 
     pub fn total_shares(&self) -> (Decimal, Decimal, Decimal) {
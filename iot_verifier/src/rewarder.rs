@@ -111,17 +111,13 @@ impl Rewarder {
         scheduler: &Scheduler,
         iot_price: Decimal,
     ) -> anyhow::Result<()> {
-        let reward_period = &scheduler.reward_period;
-
-        // process rewards for poc and dc
-        reward_poc_and_dc(&self.pool, &self.rewards_sink, reward_period, iot_price).await?;
-        // process rewards for the operational fund
-        reward_operational(&self.rewards_sink, reward_period).await?;
-        // process rewards for the oracle
-        reward_oracles(&self.rewards_sink, reward_period).await?;
-
-        // commit the filesink
-        let written_files = self.rewards_sink.commit().await?.await??;
+        let written_files = write_rewards(
+            &self.pool,
+            &self.rewards_sink,
+            &scheduler.reward_period,
+            iot_price,
+        )
+        .await?;
 
         // purge db
         let mut transaction = self.pool.begin().await?;
@@ -143,18 +139,12 @@ impl Rewarder {
         transaction.commit().await?;
 
         // now that the db has been purged, safe to write out the manifest
-        self.reward_manifests_sink
-            .write(
-                RewardManifest {
-                    start_timestamp: scheduler.reward_period.start.encode_timestamp(),
-                    end_timestamp: scheduler.reward_period.end.encode_timestamp(),
-                    written_files,
-                },
-                [],
-            )
-            .await?
-            .await??;
-        self.reward_manifests_sink.commit().await?;
+        write_manifest(
+            &self.reward_manifests_sink,
+            &scheduler.reward_period,
+            written_files,
+        )
+        .await?;
         telemetry::last_rewarded_end_time(scheduler.reward_period.end);
         Ok(())
     }
@@ -204,6 +194,45 @@ impl Rewarder {
     }
 }
 
+/// Write and commit the rewards of all reward types for the period, returning
+/// the files written. Only reads from the db
+pub async fn write_rewards(
+    pool: &Pool<Postgres>,
+    rewards_sink: &file_sink::FileSinkClient,
+    reward_period: &Range<DateTime<Utc>>,
+    iot_price: Decimal,
+) -> anyhow::Result<Vec<String>> {
+    // process rewards for poc and dc
+    reward_poc_and_dc(pool, rewards_sink, reward_period, iot_price).await?;
+    // process rewards for the operational fund
+    reward_operational(rewards_sink, reward_period).await?;
+    // process rewards for the oracle
+    reward_oracles(rewards_sink, reward_period).await?;
+
+    // commit the filesink
+    Ok(rewards_sink.commit().await?.await??)
+}
+
+pub async fn write_manifest(
+    reward_manifests_sink: &file_sink::FileSinkClient,
+    reward_period: &Range<DateTime<Utc>>,
+    written_files: Vec<String>,
+) -> anyhow::Result<()> {
+    reward_manifests_sink
+        .write(
+            RewardManifest {
+                start_timestamp: reward_period.start.encode_timestamp(),
+                end_timestamp: reward_period.end.encode_timestamp(),
+                written_files,
+            },
+            [],
+        )
+        .await?
+        .await??;
+    reward_manifests_sink.commit().await?;
+    Ok(())
+}
+
 pub async fn reward_poc_and_dc(
     pool: &Pool<Postgres>,
     rewards_sink: &file_sink::FileSinkClient,
@@ -2,7 +2,8 @@ use crate::{
     gateway_cache::GatewayCache,
     hex_density::HexDensityMap,
     poc::{Poc, VerifyBeaconResult},
-    poc_report::Report,
+    poc_report::{FailureReason, Report},
+    poc_rules::PocRules,
     region_cache::RegionCache,
     reward_share::GatewayPocShare,
     telemetry,
@@ -15,6 +16,7 @@ use file_store::{
     file_sink::FileSinkClient,
     iot_beacon_report::IotBeaconIngestReport,
     iot_invalid_poc::{IotInvalidBeaconReport, IotInvalidWitnessReport},
+    iot_poc_rules::{IotPocRule, IotPocRules, IotPocRulesV1},
     iot_valid_poc::{IotPoc, IotValidBeaconReport, IotVerifiedWitnessReport},
     iot_witness_report::IotWitnessIngestReport,
     traits::{IngestId, MsgDecode, ReportId},
@@ -28,6 +30,7 @@ use helium_proto::services::poc_lora::{
 use iot_config::client::Gateways;
 use rust_decimal::{Decimal, MathematicalOps};
 use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::time::Duration;
 use task_manager::ManagedTask;
@@ -41,6 +44,25 @@ const WITNESS_REDUNDANCY: u32 = 4;
 const POC_REWARD_DECAY_RATE: Decimal = dec!(0.8);
 const HIP15_TX_REWARD_UNIT_CAP: Decimal = Decimal::TWO;
 
+/// Parameters of the HIP 15 reward unit curve of beaconers and witnesses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RewardUnitParams {
+    pub witness_redundancy: u32,
+    pub decay_rate: Decimal,
+    pub tx_reward_unit_cap: Decimal,
+}
+
+impl Default for RewardUnitParams {
+    fn default() -> Self {
+        Self {
+            witness_redundancy: WITNESS_REDUNDANCY,
+            decay_rate: POC_REWARD_DECAY_RATE,
+            tx_reward_unit_cap: HIP15_TX_REWARD_UNIT_CAP,
+        }
+    }
+}
+
 pub struct Runner<G> {
     pub pool: PgPool,
     pub beacon_interval: ChronoDuration,
@@ -55,8 +77,10 @@ pub struct Runner<G> {
     pub invalid_beacon_sink: FileSinkClient,
     pub invalid_witness_sink: FileSinkClient,
     pub poc_sink: FileSinkClient,
+    pub poc_rules_sink: FileSinkClient,
     pub hex_density_map: HexDensityMap,
     pub witness_updater: WitnessUpdater,
+    pub poc_rules: PocRules,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -100,6 +124,7 @@ where
         invalid_beacon_sink: FileSinkClient,
         invalid_witness_sink: FileSinkClient,
         poc_sink: FileSinkClient,
+        poc_rules_sink: FileSinkClient,
         hex_density_map: HexDensityMap,
         witness_updater: WitnessUpdater,
     ) -> anyhow::Result<Self> {
@@ -137,8 +162,10 @@ where
             invalid_beacon_sink,
             invalid_witness_sink,
             poc_sink,
+            poc_rules_sink,
             hex_density_map,
             witness_updater,
+            poc_rules: PocRules::new(settings.poc_rules.clone()),
         })
     }
 
@@ -216,7 +243,13 @@ where
                     Ok(()) => (),
                     Err(err) => {
                         tracing::warn!("failed to handle beacon: {err:?}");
-                        _ = Report::update_attempts(&self.pool, &beacon_id, Utc::now()).await;
+                        _ = Report::update_attempts(
+                            &self.pool,
+                            &beacon_id,
+                            Utc::now(),
+                            FailureReason::from_error(&err),
+                        )
+                        .await;
                     }
                 }
             })
@@ -263,6 +296,7 @@ where
             witnesses.clone(),
             entropy_start_time,
             entropy_version,
+            &self.poc_rules,
         )
         .await;
 
@@ -298,11 +332,13 @@ where
                     // thus one or more failing witnesses will not block the overall POC
                     if !verified_witnesses_result.failed_witnesses.is_empty() {
                         tracing::warn!("failed to handle witness");
-                        for failed_witness_report in verified_witnesses_result.failed_witnesses {
+                        for (failed_witness_report, reason) in
+                            verified_witnesses_result.failed_witnesses
+                        {
                             let failed_witness = failed_witness_report.report;
                             let id =
                                 failed_witness.report_id(failed_witness_report.received_timestamp);
-                            Report::update_attempts(&self.pool, &id, Utc::now()).await?;
+                            Report::update_attempts(&self.pool, &id, Utc::now(), reason).await?;
                         }
                         return Ok(());
                     };
@@ -334,10 +370,15 @@ where
                     let num_valid_selected_witnesses = selected_witnesses.len();
 
                     // get reward units based on the count of valid selected witnesses
-                    let beaconer_reward_units =
-                        poc_beaconer_reward_unit(num_valid_selected_witnesses as u32)?;
-                    let witness_reward_units =
-                        poc_per_witness_reward_unit(num_valid_selected_witnesses as u32)?;
+                    let reward_unit_params = RewardUnitParams::default();
+                    let beaconer_reward_units = poc_beaconer_reward_unit(
+                        num_valid_selected_witnesses as u32,
+                        &reward_unit_params,
+                    )?;
+                    let witness_reward_units = poc_per_witness_reward_unit(
+                        num_valid_selected_witnesses as u32,
+                        &reward_unit_params,
+                    )?;
                     // update the reward units for those valid witnesses within our selected list
                     selected_witnesses
                         .iter_mut()
@@ -369,6 +410,7 @@ where
                         valid_beacon_report,
                         selected_witnesses,
                         unselected_witnesses,
+                        poc.rules().to_records(),
                     )
                     .await?;
                 }
@@ -426,7 +468,13 @@ where
             Ok(_) => (),
             Err(err) => {
                 tracing::error!("failed to save invalid_poc to s3, {err}");
-                Report::update_attempts(&self.pool, &beacon_report_id, Utc::now()).await?;
+                Report::update_attempts(
+                    &self.pool,
+                    &beacon_report_id,
+                    Utc::now(),
+                    FailureReason::FileSink,
+                )
+                .await?;
                 return Ok(());
             }
         }
@@ -470,6 +518,7 @@ where
         valid_beacon_report: IotValidBeaconReport,
         selected_witnesses: Vec<IotVerifiedWitnessReport>,
         unselected_witnesses: Vec<IotVerifiedWitnessReport>,
+        rules: Vec<IotPocRule>,
     ) -> anyhow::Result<()> {
         let received_timestamp = valid_beacon_report.received_timestamp;
         let beacon_id = valid_beacon_report.report.report_id(received_timestamp);
@@ -489,6 +538,25 @@ where
         // TODO: expand this transaction to cover all of the database access below?
         transaction.commit().await?;
 
+        // save the rules the poc was verified with ahead of the poc, so that
+        // every poc written has its rules recorded
+        let poc_rules = IotPocRules::new(iot_poc.poc_id.clone(), received_timestamp, rules);
+        if let Err(err) = self
+            .poc_rules_sink
+            .write(IotPocRulesV1::from(poc_rules), [])
+            .await
+        {
+            tracing::error!("failed to save poc rules to s3, {err}");
+            Report::update_attempts(
+                &self.pool,
+                &beacon_report_id,
+                Utc::now(),
+                FailureReason::FileSink,
+            )
+            .await?;
+            return Ok(());
+        }
+
         let poc_proto: LoraPocV1 = iot_poc.into();
         // save the poc to s3, if write fails update attempts and go no further
         // allow the poc to be reprocessed next tick
@@ -496,7 +564,13 @@ where
             Ok(_) => (),
             Err(err) => {
                 tracing::error!("failed to save invalid_witness_report to s3, {err}");
-                Report::update_attempts(&self.pool, &beacon_report_id, Utc::now()).await?;
+                Report::update_attempts(
+                    &self.pool,
+                    &beacon_report_id,
+                    Utc::now(),
+                    FailureReason::FileSink,
+                )
+                .await?;
                 return Ok(());
             }
         }
@@ -514,22 +588,26 @@ where
     }
 }
 
-fn poc_beaconer_reward_unit(num_witnesses: u32) -> anyhow::Result<Decimal> {
+pub fn poc_beaconer_reward_unit(
+    num_witnesses: u32,
+    params: &RewardUnitParams,
+) -> anyhow::Result<Decimal> {
+    let witness_redundancy = params.witness_redundancy;
     let reward_units = if num_witnesses == 0 {
         Decimal::ZERO
-    } else if num_witnesses <= WITNESS_REDUNDANCY {
+    } else if num_witnesses <= witness_redundancy {
         if let Some(sub_redundancy_units) =
-            Decimal::from_f32_retain(num_witnesses as f32 / WITNESS_REDUNDANCY as f32)
+            Decimal::from_f32_retain(num_witnesses as f32 / witness_redundancy as f32)
         {
             sub_redundancy_units
         } else {
-            anyhow::bail!("invalid fractional division: {num_witnesses} / {WITNESS_REDUNDANCY}");
+            anyhow::bail!("invalid fractional division: {num_witnesses} / {witness_redundancy}");
         }
     } else {
-        let exp = num_witnesses - WITNESS_REDUNDANCY;
-        if let Some(to_sub) = POC_REWARD_DECAY_RATE.checked_powu(exp as u64) {
+        let exp = num_witnesses - witness_redundancy;
+        if let Some(to_sub) = params.decay_rate.checked_powu(exp as u64) {
             let unnormalized = Decimal::TWO - to_sub;
-            std::cmp::min(HIP15_TX_REWARD_UNIT_CAP, unnormalized)
+            std::cmp::min(params.tx_reward_unit_cap, unnormalized)
         } else {
             anyhow::bail!("invalid exponent: {exp}");
         }
@@ -537,17 +615,21 @@ fn poc_beaconer_reward_unit(num_witnesses: u32) -> anyhow::Result<Decimal> {
     Ok(reward_units.round_dp(SCALING_PRECISION))
 }
 
-fn poc_per_witness_reward_unit(num_witnesses: u32) -> anyhow::Result<Decimal> {
+pub fn poc_per_witness_reward_unit(
+    num_witnesses: u32,
+    params: &RewardUnitParams,
+) -> anyhow::Result<Decimal> {
+    let witness_redundancy = params.witness_redundancy;
     let reward_units = if num_witnesses == 0 {
         Decimal::ZERO
-    } else if num_witnesses <= WITNESS_REDUNDANCY {
+    } else if num_witnesses <= witness_redundancy {
         Decimal::ONE
     } else {
-        let exp = num_witnesses - WITNESS_REDUNDANCY;
-        if let Some(to_sub) = POC_REWARD_DECAY_RATE.checked_powu(exp as u64) {
-            let unnormalized = (Decimal::from(WITNESS_REDUNDANCY) - (Decimal::ONE - to_sub))
+        let exp = num_witnesses - witness_redundancy;
+        if let Some(to_sub) = params.decay_rate.checked_powu(exp as u64) {
+            let unnormalized = (Decimal::from(witness_redundancy) - (Decimal::ONE - to_sub))
                 / Decimal::from(num_witnesses);
-            std::cmp::min(HIP15_TX_REWARD_UNIT_CAP, unnormalized)
+            std::cmp::min(params.tx_reward_unit_cap, unnormalized)
         } else {
             anyhow::bail!("invalid exponent: {exp}");
         }
@@ -787,12 +869,13 @@ mod tests {
     fn reward_unit_calculations() {
         let mut witness_rewards = vec![];
         let mut beacon_rewards = vec![];
+        let params = RewardUnitParams::default();
         for witnesses in 1..=15 {
-            let witness_reward =
-                poc_per_witness_reward_unit(witnesses).expect("failed witness reward calculation");
+            let witness_reward = poc_per_witness_reward_unit(witnesses, &params)
+                .expect("failed witness reward calculation");
             witness_rewards.push(witness_reward);
-            let beacon_reward =
-                poc_beaconer_reward_unit(witnesses).expect("failed beacon reward calculation");
+            let beacon_reward = poc_beaconer_reward_unit(witnesses, &params)
+                .expect("failed beacon reward calculation");
             beacon_rewards.push(beacon_reward);
         }
 
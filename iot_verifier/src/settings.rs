@@ -1,8 +1,13 @@
+use crate::{poc_rules::RuleSettings, witness_graph::WitnessGraphSettings};
 use anyhow::bail;
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use config::{Config, Environment, File};
 use serde::Deserialize;
-use std::path::Path;
+use std::{
+    net::{AddrParseError, SocketAddr},
+    path::Path,
+    str::FromStr,
+};
 use tokio::time;
 
 #[derive(Debug, Deserialize, Clone)]
@@ -83,6 +88,11 @@ pub struct Settings {
     /// File store poll interval for incoming packets, in seconds. (Default is 900; 15 minutes)
     #[serde(default = "default_packet_interval")]
     pub packet_interval: i64,
+    /// Number of entropy and packet files downloaded and decoded
+    /// concurrently, to catch up faster after an outage. Files are still
+    /// processed in order. (Default is 1)
+    #[serde(default = "default_file_poller_workers")]
+    pub file_poller_workers: usize,
     /// the max number of times a beacon report will be retried
     /// after this the report will be ignored and eventually be purged
     #[serde(default = "default_beacon_max_retries")]
@@ -97,6 +107,30 @@ pub struct Settings {
     /// interval at which region params in the cache are refreshed
     #[serde(default = "default_region_params_refresh_interval")]
     pub region_params_refresh_interval: u64,
+    /// PoC verification rules overriding the defaults, each optionally
+    /// scheduled to take effect at a given time. Rules that are not listed
+    /// are applied with their default parameters
+    #[serde(default)]
+    pub poc_rules: Vec<RuleSettings>,
+    /// Interval in seconds at which snapshots of the gateways, region params
+    /// and hex density map are written to the output bucket. Disabled if not
+    /// set
+    #[serde(default)]
+    pub snapshot_interval: Option<i64>,
+    /// Resolve gateways, region params and the hex density map from the latest
+    /// snapshot in the output bucket taken in the `MAX_SNAPSHOT_AGE_HOURS` up
+    /// to this time instead of from iot config, to verify past epochs again.
+    /// Neither snapshots nor rewards are written and nothing is purged while
+    /// set
+    #[serde(default)]
+    pub snapshot_at: Option<DateTime<Utc>>,
+    /// Witness graph anomaly detection. Disabled by default
+    #[serde(default)]
+    pub witness_graph: WitnessGraphSettings,
+    /// Optional listen address of the read-only hex density API. Disabled by
+    /// default
+    #[serde(default)]
+    pub density_api_listen: Option<String>,
 }
 
 // Default: 30 minutes
@@ -191,6 +225,10 @@ fn default_packet_interval() -> i64 {
     900
 }
 
+fn default_file_poller_workers() -> usize {
+    1
+}
+
 // runner runs at 30 sec intervals
 // 60 permits retries for up to 30 mins
 fn default_beacon_max_retries() -> u64 {
@@ -278,6 +316,19 @@ impl Settings {
     pub fn gateway_refresh_interval(&self) -> Duration {
         Duration::seconds(self.gateway_refresh_interval)
     }
+    pub fn density_api_listen_addr(&self) -> Result<Option<SocketAddr>, AddrParseError> {
+        self.density_api_listen
+            .as_deref()
+            .map(SocketAddr::from_str)
+            .transpose()
+    }
+    pub fn snapshot_interval(&self) -> Option<Duration> {
+        self.snapshot_at
+            .is_none()
+            .then_some(self.snapshot_interval)
+            .flatten()
+            .map(Duration::seconds)
+    }
     pub fn region_params_refresh_interval(&self) -> time::Duration {
         time::Duration::from_secs(self.region_params_refresh_interval)
     }
@@ -0,0 +1,619 @@
+//! Snapshots of the gateways, region params and hex density map PoCs are
+//! verified against.
+//!
+//! The gateway cache, region cache and hex density map are views of iot
+//! config at the time of processing. The [`SnapshotWriter`] periodically
+//! writes them to the output bucket, followed by a manifest of the files of
+//! the snapshot, and a [`Snapshot`] loaded from there resolves gateways and
+//! region params in their place, so that a past epoch can be verified again
+//! against the state it was verified against.
+
+use crate::{
+    gateway_updater::{GatewayMap, MessageReceiver},
+    hex_density::HexDensityMap,
+};
+use chrono::{DateTime, Duration, Utc};
+use file_store::{
+    file_sink::FileSinkClient,
+    iot_snapshot::{
+        IotHexDensity, IotHexDensityV1, IotRegionParams, IotRegionParamsV1, IotSnapshotManifest,
+        IotSnapshotManifestV1,
+    },
+    traits::MsgDecode,
+    FileInfo, FileStore, FileType,
+};
+use futures::{
+    future::LocalBoxFuture,
+    stream::{self, StreamExt, TryStreamExt},
+    TryFutureExt,
+};
+use helium_crypto::PublicKeyBinary;
+use helium_proto::{
+    services::iot_config::GatewayInfo as GatewayInfoProto, BlockchainRegionParamV1, Message,
+    Region as ProtoRegion,
+};
+use iot_config::{
+    client::{Client as IotConfigClient, ClientError, Gateways, RegionParamsInfo},
+    gateway_info::{GatewayInfo, GatewayInfoStream},
+};
+use rust_decimal::Decimal;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+use task_manager::ManagedTask;
+use tokio::time;
+
+/// Snapshot sinks roll over at this size rather than the default so that
+/// each snapshot usually lands in a single file per type
+pub const SNAPSHOT_MAX_FILE_SIZE: usize = 2_000_000_000;
+
+/// How far back from the time a snapshot is loaded at its manifest is looked
+/// for. Snapshots have to be written at least this often to be loaded
+pub const MAX_SNAPSHOT_AGE_HOURS: i64 = 24;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("no snapshot in the {MAX_SNAPSHOT_AGE_HOURS} hours up to {0}")]
+    NotFound(DateTime<Utc>),
+    #[error("empty snapshot manifest: {0}")]
+    EmptyManifest(String),
+    #[error("region params not in snapshot: {0}")]
+    RegionNotFound(ProtoRegion),
+    #[error("file store error: {0}")]
+    FileStore(#[from] file_store::Error),
+    #[error("decode error: {0}")]
+    Decode(#[from] helium_proto::DecodeError),
+}
+
+/// Gateways, region params and hex density map as of a point in time
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    /// time of the manifest of the snapshot
+    taken_at: DateTime<Utc>,
+    gateways: Arc<GatewayMap>,
+    region_params: Arc<HashMap<ProtoRegion, Vec<BlockchainRegionParamV1>>>,
+    hex_density: Arc<HashMap<u64, Decimal>>,
+}
+
+impl Snapshot {
+    /// Load the latest snapshot taken at or before the given time, made up
+    /// of the files listed in its manifest
+    pub async fn load(store: &FileStore, at: DateTime<Utc>) -> Result<Self, SnapshotError> {
+        let (taken_at, manifest) = latest_manifest(store, at).await?;
+        let gateways = stream_files(store, manifest.gateway_files)
+            .await?
+            .map_err(SnapshotError::from)
+            .and_then(|buf| async move {
+                let info = GatewayInfo::from(GatewayInfoProto::decode(buf)?);
+                Ok::<_, SnapshotError>((info.address.clone(), info))
+            })
+            .try_collect()
+            .await?;
+        let region_params = stream_files(store, manifest.region_params_files)
+            .await?
+            .map_err(SnapshotError::from)
+            .and_then(|buf| async move {
+                let region_params = IotRegionParams::decode(buf)?;
+                Ok::<_, SnapshotError>((region_params.region, region_params.params))
+            })
+            .try_collect()
+            .await?;
+        let hex_density = stream_files(store, manifest.hex_density_files)
+            .await?
+            .map_err(SnapshotError::from)
+            .and_then(|buf| async move {
+                let density = IotHexDensity::decode(buf)?;
+                Ok::<_, SnapshotError>((density.hex, density.scale))
+            })
+            .try_collect()
+            .await?;
+        Ok(Self {
+            taken_at,
+            gateways: Arc::new(gateways),
+            region_params: Arc::new(region_params),
+            hex_density: Arc::new(hex_density),
+        })
+    }
+
+    pub fn taken_at(&self) -> DateTime<Utc> {
+        self.taken_at
+    }
+
+    pub fn hex_density(&self) -> HashMap<u64, Decimal> {
+        self.hex_density.as_ref().clone()
+    }
+
+    pub fn gateway(&self, address: &PublicKeyBinary) -> Option<&GatewayInfo> {
+        self.gateways.get(address)
+    }
+
+    pub fn region_params(&self, region: ProtoRegion) -> Option<&Vec<BlockchainRegionParamV1>> {
+        self.region_params.get(&region)
+    }
+}
+
+/// The time and manifest of the latest snapshot written at or before the
+/// given time
+async fn latest_manifest(
+    store: &FileStore,
+    at: DateTime<Utc>,
+) -> Result<(DateTime<Utc>, IotSnapshotManifest), SnapshotError> {
+    let after = at - Duration::hours(MAX_SNAPSHOT_AGE_HOURS);
+    let file_info = store
+        .list_all(FileType::IotSnapshotManifest.to_str(), after, at)
+        .await?
+        .into_iter()
+        .max_by_key(|file_info| file_info.timestamp)
+        .ok_or(SnapshotError::NotFound(at))?;
+    tracing::info!("loading snapshot {}", file_info.key);
+    let (key, taken_at) = (file_info.key.clone(), file_info.timestamp);
+    let buf = store
+        .stream_file(file_info)
+        .await?
+        .next()
+        .await
+        .ok_or(SnapshotError::EmptyManifest(key))??;
+    Ok((taken_at, IotSnapshotManifest::decode(buf)?))
+}
+
+/// The records of the given files of a snapshot, in order
+async fn stream_files(
+    store: &FileStore,
+    files: Vec<String>,
+) -> Result<file_store::BytesMutStream, SnapshotError> {
+    let mut streams = Vec::with_capacity(files.len());
+    for file in files {
+        let file_info: FileInfo = file.parse()?;
+        streams.push(store.stream_file(file_info).await?);
+    }
+    Ok(stream::iter(streams).flatten().boxed())
+}
+
+#[async_trait::async_trait]
+impl Gateways for Snapshot {
+    type Error = SnapshotError;
+
+    async fn resolve_gateway_info(
+        &mut self,
+        address: &PublicKeyBinary,
+    ) -> Result<Option<GatewayInfo>, Self::Error> {
+        Ok(self.gateways.get(address).cloned())
+    }
+
+    async fn stream_gateways_info(&mut self) -> Result<GatewayInfoStream, Self::Error> {
+        let gateways: Vec<GatewayInfo> = self.gateways.values().cloned().collect();
+        Ok(stream::iter(gateways).boxed())
+    }
+
+    async fn resolve_region_params(
+        &mut self,
+        region: ProtoRegion,
+    ) -> Result<RegionParamsInfo, Self::Error> {
+        let region_params = self
+            .region_params
+            .get(&region)
+            .ok_or(SnapshotError::RegionNotFound(region))?;
+        Ok(RegionParamsInfo {
+            region,
+            region_params: region_params.clone(),
+        })
+    }
+}
+
+/// Where gateways and region params are resolved from
+#[derive(Clone, Debug)]
+pub enum GatewaysSource {
+    IotConfig(IotConfigClient),
+    Snapshot(Snapshot),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GatewaysSourceError {
+    #[error("iot config error: {0}")]
+    IotConfig(#[from] ClientError),
+    #[error("snapshot error: {0}")]
+    Snapshot(#[from] SnapshotError),
+}
+
+#[async_trait::async_trait]
+impl Gateways for GatewaysSource {
+    type Error = GatewaysSourceError;
+
+    async fn resolve_gateway_info(
+        &mut self,
+        address: &PublicKeyBinary,
+    ) -> Result<Option<GatewayInfo>, Self::Error> {
+        match self {
+            Self::IotConfig(client) => Ok(client.resolve_gateway_info(address).await?),
+            Self::Snapshot(snapshot) => Ok(snapshot.resolve_gateway_info(address).await?),
+        }
+    }
+
+    async fn stream_gateways_info(&mut self) -> Result<GatewayInfoStream, Self::Error> {
+        match self {
+            Self::IotConfig(client) => Ok(client.stream_gateways_info().await?),
+            Self::Snapshot(snapshot) => Ok(snapshot.stream_gateways_info().await?),
+        }
+    }
+
+    async fn resolve_region_params(
+        &mut self,
+        region: ProtoRegion,
+    ) -> Result<RegionParamsInfo, Self::Error> {
+        match self {
+            Self::IotConfig(client) => Ok(client.resolve_region_params(region).await?),
+            Self::Snapshot(snapshot) => Ok(snapshot.resolve_region_params(region).await?),
+        }
+    }
+}
+
+/// Periodically writes snapshots of the gateway cache, the region params of
+/// the regions of its gateways and the hex density map. Once the files of
+/// all three are committed, a manifest listing them is written, so that a
+/// snapshot is only loaded once it is complete. Finishes right away if no
+/// interval is configured
+pub struct SnapshotWriter<G> {
+    interval: Option<Duration>,
+    gateways: G,
+    gateway_cache_receiver: MessageReceiver,
+    hex_density_map: HexDensityMap,
+    gateway_sink: FileSinkClient,
+    region_params_sink: FileSinkClient,
+    hex_density_sink: FileSinkClient,
+    manifest_sink: FileSinkClient,
+}
+
+impl<G> ManagedTask for SnapshotWriter<G>
+where
+    G: Gateways,
+{
+    fn start_task(
+        self: Box<Self>,
+        shutdown: triggered::Listener,
+    ) -> LocalBoxFuture<'static, anyhow::Result<()>> {
+        let handle = tokio::spawn(self.run(shutdown));
+        Box::pin(
+            handle
+                .map_err(anyhow::Error::from)
+                .and_then(|result| async move { result.map_err(anyhow::Error::from) }),
+        )
+    }
+}
+
+impl<G> SnapshotWriter<G>
+where
+    G: Gateways,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        interval: Option<Duration>,
+        gateways: G,
+        gateway_cache_receiver: MessageReceiver,
+        hex_density_map: HexDensityMap,
+        gateway_sink: FileSinkClient,
+        region_params_sink: FileSinkClient,
+        hex_density_sink: FileSinkClient,
+        manifest_sink: FileSinkClient,
+    ) -> Self {
+        Self {
+            interval,
+            gateways,
+            gateway_cache_receiver,
+            hex_density_map,
+            gateway_sink,
+            region_params_sink,
+            hex_density_sink,
+            manifest_sink,
+        }
+    }
+
+    pub async fn run(mut self, shutdown: triggered::Listener) -> anyhow::Result<()> {
+        let Some(interval) = self.interval else {
+            return Ok(());
+        };
+        tracing::info!("starting snapshot writer");
+        let mut trigger_timer =
+            time::interval(interval.to_std().expect("valid interval in seconds"));
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.clone() => break,
+                _ = trigger_timer.tick() => self.write_snapshots().await?,
+            }
+        }
+        tracing::info!("stopping snapshot writer");
+        Ok(())
+    }
+
+    async fn write_snapshots(&mut self) -> anyhow::Result<()> {
+        let hex_density = self
+            .hex_density_map
+            .to_map()
+            .await
+            .into_iter()
+            .map(|(hex, scale)| IotHexDensityV1::try_from(IotHexDensity { hex, scale }))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let gateways: Vec<GatewayInfo> = self
+            .gateway_cache_receiver
+            .borrow()
+            .values()
+            .cloned()
+            .collect();
+        let regions: BTreeSet<ProtoRegion> = gateways
+            .iter()
+            .filter_map(|gateway| gateway.metadata.as_ref())
+            .map(|metadata| metadata.region)
+            .collect();
+
+        tracing::info!("writing gateway snapshot of {} gateways", gateways.len());
+        for gateway in gateways {
+            let address = gateway.address.clone();
+            match GatewayInfoProto::try_from(gateway) {
+                Ok(proto) => {
+                    self.gateway_sink.write(proto, []).await?;
+                }
+                Err(err) => tracing::warn!(%address, "skipping gateway in snapshot: {err:?}"),
+            }
+        }
+        let gateway_files = self.gateway_sink.commit().await?.await??;
+
+        for region in regions {
+            let region_params = self
+                .gateways
+                .resolve_region_params(region)
+                .await
+                .map_err(|err| anyhow::anyhow!("failed to resolve {region} params: {err:?}"))?;
+            self.region_params_sink
+                .write(
+                    IotRegionParamsV1::from(IotRegionParams {
+                        region,
+                        params: region_params.region_params,
+                    }),
+                    [],
+                )
+                .await?;
+        }
+        let region_params_files = self.region_params_sink.commit().await?.await??;
+
+        tracing::info!(
+            "writing hex density snapshot of {} hexes",
+            hex_density.len()
+        );
+        for density in hex_density {
+            self.hex_density_sink.write(density, []).await?;
+        }
+        let hex_density_files = self.hex_density_sink.commit().await?.await??;
+
+        let manifest = IotSnapshotManifest {
+            gateway_files,
+            region_params_files,
+            hex_density_files,
+        };
+        self.manifest_sink
+            .write(IotSnapshotManifestV1::from(manifest), [])
+            .await?;
+        self.manifest_sink.commit().await?.await??;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use file_store::file_sink::FileSinkBuilder;
+    use iot_config::gateway_info::GatewayMetadata;
+    use rust_decimal_macros::dec;
+    use std::{path::Path, str::FromStr};
+    use tempfile::TempDir;
+    use tokio::sync::watch;
+
+    const PUBKEY1: &str = "112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf";
+    const PUBKEY2: &str = "112bUGwooPd1dCDd3h3yZwskjxCzBsQNKeaJTuUF4hSgYedcsFa9";
+    const LOCATION: u64 = 0x8c2681a3064edff;
+
+    fn gateway(address: &str, region: ProtoRegion) -> GatewayInfo {
+        GatewayInfo {
+            address: PublicKeyBinary::from_str(address).unwrap(),
+            metadata: Some(GatewayMetadata {
+                location: LOCATION,
+                elevation: 10,
+                gain: 12,
+                region,
+            }),
+            is_full_hotspot: true,
+        }
+    }
+
+    fn region_params() -> Vec<BlockchainRegionParamV1> {
+        vec![BlockchainRegionParamV1 {
+            channel_frequency: 867_100_000,
+            bandwidth: 125_000,
+            max_eirp: 160,
+            ..Default::default()
+        }]
+    }
+
+    async fn file_sink(
+        dir: &Path,
+        file_type: FileType,
+        shutdown: &triggered::Listener,
+    ) -> FileSinkClient {
+        let (client, server) = FileSinkBuilder::new(file_type, dir, "fake_metric")
+            .auto_commit(false)
+            .create()
+            .await
+            .expect("failed to create file sink");
+        tokio::spawn(server.run(shutdown.clone()));
+        client
+    }
+
+    async fn snapshot_writer(
+        dir: &Path,
+        gateways: Vec<GatewayInfo>,
+        hex_density: HashMap<u64, Decimal>,
+        shutdown: &triggered::Listener,
+    ) -> SnapshotWriter<Snapshot> {
+        let gateways: GatewayMap = gateways
+            .into_iter()
+            .map(|gateway| (gateway.address.clone(), gateway))
+            .collect();
+        let (_, gateway_cache_receiver) = watch::channel(gateways);
+        let hex_density_map = HexDensityMap::new();
+        hex_density_map.swap(hex_density, Utc::now()).await;
+        let source = Snapshot {
+            taken_at: Utc::now(),
+            gateways: Arc::new(GatewayMap::new()),
+            region_params: Arc::new(HashMap::from([(ProtoRegion::Eu868, region_params())])),
+            hex_density: Arc::new(HashMap::new()),
+        };
+        SnapshotWriter::new(
+            Some(Duration::hours(1)),
+            source,
+            gateway_cache_receiver,
+            hex_density_map,
+            file_sink(dir, FileType::IotGatewaySnapshot, shutdown).await,
+            file_sink(dir, FileType::IotRegionParamsSnapshot, shutdown).await,
+            file_sink(dir, FileType::IotHexDensitySnapshot, shutdown).await,
+            file_sink(dir, FileType::IotSnapshotManifest, shutdown).await,
+        )
+    }
+
+    #[tokio::test]
+    async fn loads_the_written_snapshot() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let hex_density = HashMap::from([(LOCATION, dec!(0.5))]);
+        let mut writer = snapshot_writer(
+            tmp_dir.path(),
+            vec![gateway(PUBKEY1, ProtoRegion::Eu868)],
+            hex_density.clone(),
+            &shutdown_listener,
+        )
+        .await;
+        writer.write_snapshots().await.expect("failed to write");
+
+        let store = FileStore::new_local(tmp_dir.path()).await.unwrap();
+        let mut snapshot = Snapshot::load(&store, Utc::now())
+            .await
+            .expect("no snapshot");
+
+        let address = PublicKeyBinary::from_str(PUBKEY1).unwrap();
+        let loaded = snapshot
+            .resolve_gateway_info(&address)
+            .await
+            .unwrap()
+            .expect("gateway not in snapshot");
+        let metadata = loaded.metadata.expect("no gateway metadata");
+        assert_eq!(LOCATION, metadata.location);
+        assert_eq!(ProtoRegion::Eu868, metadata.region);
+        assert_eq!(1, snapshot.gateways.len());
+        assert_eq!(
+            region_params(),
+            snapshot
+                .resolve_region_params(ProtoRegion::Eu868)
+                .await
+                .unwrap()
+                .region_params
+        );
+        assert!(matches!(
+            snapshot.resolve_region_params(ProtoRegion::Us915).await,
+            Err(SnapshotError::RegionNotFound(ProtoRegion::Us915))
+        ));
+        assert_eq!(hex_density, snapshot.hex_density());
+
+        shutdown_trigger.trigger();
+    }
+
+    #[tokio::test]
+    async fn ignores_files_without_a_manifest() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let mut writer = snapshot_writer(
+            tmp_dir.path(),
+            vec![gateway(PUBKEY1, ProtoRegion::Eu868)],
+            HashMap::new(),
+            &shutdown_listener,
+        )
+        .await;
+        writer.write_snapshots().await.expect("failed to write");
+
+        // a later gateway snapshot whose manifest was never written
+        let gateway_sink = file_sink(
+            tmp_dir.path(),
+            FileType::IotGatewaySnapshot,
+            &shutdown_listener,
+        )
+        .await;
+        gateway_sink
+            .write(
+                GatewayInfoProto::try_from(gateway(PUBKEY2, ProtoRegion::Eu868)).unwrap(),
+                [],
+            )
+            .await
+            .unwrap();
+        gateway_sink.commit().await.unwrap().await.unwrap().unwrap();
+
+        let store = FileStore::new_local(tmp_dir.path()).await.unwrap();
+        let snapshot = Snapshot::load(&store, Utc::now())
+            .await
+            .expect("no snapshot");
+        let addresses: Vec<PublicKeyBinary> = snapshot.gateways.keys().cloned().collect();
+        assert_eq!(vec![PublicKeyBinary::from_str(PUBKEY1).unwrap()], addresses);
+
+        shutdown_trigger.trigger();
+    }
+
+    #[tokio::test]
+    async fn does_not_load_snapshots_out_of_range() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let before = Utc::now() - Duration::seconds(1);
+        let mut writer = snapshot_writer(
+            tmp_dir.path(),
+            vec![gateway(PUBKEY1, ProtoRegion::Eu868)],
+            HashMap::new(),
+            &shutdown_listener,
+        )
+        .await;
+        writer.write_snapshots().await.expect("failed to write");
+
+        let store = FileStore::new_local(tmp_dir.path()).await.unwrap();
+        assert!(matches!(
+            Snapshot::load(&store, before).await,
+            Err(SnapshotError::NotFound(_))
+        ));
+        let too_late = Utc::now() + Duration::hours(MAX_SNAPSHOT_AGE_HOURS + 1);
+        assert!(matches!(
+            Snapshot::load(&store, too_late).await,
+            Err(SnapshotError::NotFound(_))
+        ));
+        assert!(Snapshot::load(&store, Utc::now()).await.is_ok());
+
+        shutdown_trigger.trigger();
+    }
+
+    #[tokio::test]
+    async fn does_not_write_unencodable_hex_density() {
+        let tmp_dir = TempDir::new().expect("Unable to create temp dir");
+        let (shutdown_trigger, shutdown_listener) = triggered::trigger();
+        let mut writer = snapshot_writer(
+            tmp_dir.path(),
+            vec![gateway(PUBKEY1, ProtoRegion::Eu868)],
+            HashMap::from([(LOCATION, dec!(-0.5))]),
+            &shutdown_listener,
+        )
+        .await;
+        assert!(writer.write_snapshots().await.is_err());
+
+        let store = FileStore::new_local(tmp_dir.path()).await.unwrap();
+        assert!(store
+            .list_all(FileType::IotGatewaySnapshot.to_str(), None, Utc::now())
+            .await
+            .unwrap()
+            .is_empty());
+
+        shutdown_trigger.trigger();
+    }
+}
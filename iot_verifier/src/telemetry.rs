@@ -16,6 +16,11 @@ const LOADER_DROPPED_WITNESS_COUNTER: &str =
 const BEACON_GUAGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "num_beacons");
 const INVALID_WITNESS_COUNTER: &str =
     concat!(env!("CARGO_PKG_NAME"), "_", "invalid_witness_report");
+const WITNESS_GRAPH_GATEWAYS_GAUGE: &str =
+    concat!(env!("CARGO_PKG_NAME"), "_", "witness_graph_gateways");
+const WITNESS_GRAPH_EDGES_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "witness_graph_edges");
+const WITNESS_ANOMALIES_GAUGE: &str = concat!(env!("CARGO_PKG_NAME"), "_", "witness_anomalies");
+const PURGED_REPORT_COUNTER: &str = concat!(env!("CARGO_PKG_NAME"), "_", "purged_report");
 const LAST_REWARDED_END_TIME: &str = "last_rewarded_end_time";
 
 pub async fn initialize(db: &Pool<Postgres>) -> anyhow::Result<()> {
@@ -65,6 +70,19 @@ pub fn increment_invalid_witnesses(labels: &[(&'static str, &'static str)]) {
     metrics::increment_counter!(INVALID_WITNESS_COUNTER, labels);
 }
 
+pub fn count_purged_reports(report_type: &'static str, reason: &'static str, count: u64) {
+    metrics::counter!(PURGED_REPORT_COUNTER, count, "report_type" => report_type, "reason" => reason);
+}
+
+pub fn witness_graph_size(gateways: usize, edges: usize) {
+    metrics::gauge!(WITNESS_GRAPH_GATEWAYS_GAUGE, gateways as f64);
+    metrics::gauge!(WITNESS_GRAPH_EDGES_GAUGE, edges as f64);
+}
+
+pub fn witness_anomalies(kind: &'static str, count: usize) {
+    metrics::gauge!(WITNESS_ANOMALIES_GAUGE, count as f64, "kind" => kind);
+}
+
 pub fn last_rewarded_end_time(datetime: DateTime<Utc>) {
     metrics::gauge!(LAST_REWARDED_END_TIME, datetime.timestamp() as f64);
 }
@@ -1,4 +1,5 @@
 use crate::{
+    beacon_schedule::ScheduleTxScales,
     gateway_updater::MessageReceiver,
     hex_density::{compute_hex_density_map, GlobalHexMap, HexDensityMap},
     last_beacon::LastBeacon,
@@ -6,6 +7,7 @@ use crate::{
 use chrono::{DateTime, Duration, Utc};
 use futures::future::LocalBoxFuture;
 use helium_crypto::PublicKeyBinary;
+use rust_decimal::Decimal;
 use sqlx::PgPool;
 use std::collections::HashMap;
 use task_manager::ManagedTask;
@@ -19,6 +21,8 @@ pub struct Server {
     pool: PgPool,
     refresh_offset: Duration,
     gateway_cache_receiver: MessageReceiver,
+    // the map is not refreshed when served from a snapshot
+    refresh: bool,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -49,6 +53,7 @@ impl Server {
             pool,
             refresh_offset,
             gateway_cache_receiver,
+            refresh: true,
         };
 
         server.refresh_scaling_map().await?;
@@ -56,6 +61,26 @@ impl Server {
         Ok(server)
     }
 
+    /// Serve the given hex density map, as of the time of its snapshot,
+    /// instead of computing it from recent activity
+    pub async fn from_snapshot(
+        hex_density: HashMap<u64, Decimal>,
+        taken_at: DateTime<Utc>,
+        refresh_offset: Duration,
+        pool: PgPool,
+        gateway_cache_receiver: MessageReceiver,
+    ) -> Self {
+        let hex_density_map = HexDensityMap::new();
+        hex_density_map.swap(hex_density, taken_at).await;
+        Self {
+            hex_density_map,
+            pool,
+            refresh_offset,
+            gateway_cache_receiver,
+            refresh: false,
+        }
+    }
+
     pub async fn run(mut self, shutdown: triggered::Listener) -> anyhow::Result<()> {
         tracing::info!("starting tx scaler process");
 
@@ -63,7 +88,7 @@ impl Server {
             tokio::select! {
                 biased;
                 _ = shutdown.clone() => break,
-                _ = self.gateway_cache_receiver.changed() => self.refresh_scaling_map().await?,
+                _ = self.gateway_cache_receiver.changed(), if self.refresh => self.refresh_scaling_map().await?,
             }
         }
 
@@ -72,7 +97,8 @@ impl Server {
     }
 
     pub async fn refresh_scaling_map(&mut self) -> anyhow::Result<()> {
-        let refresh_start = Utc::now() - self.refresh_offset;
+        let refreshed_at = Utc::now();
+        let refresh_start = refreshed_at - self.refresh_offset;
         tracing::info!("density_scaler: generating hex scaling map, starting at {refresh_start:?}");
         let mut global_map = GlobalHexMap::new();
         let active_gateways = self.gateways_recent_activity(refresh_start).await?;
@@ -89,7 +115,12 @@ impl Server {
             "density_scaler: scaling factor map entries: {}",
             new_map.len()
         );
-        self.hex_density_map.swap(new_map).await;
+        // the scales of the last refresh before an epoch starts are fixed
+        // for the beacon schedules of that epoch
+        ScheduleTxScales::save(&self.pool, &new_map, refreshed_at).await?;
+        self.hex_density_map
+            .swap_computed(new_map, global_map, refreshed_at)
+            .await;
         tracing::info!(
             "density_scaler: generating hex scaling map, completed at {:?}",
             Utc::now()
@@ -0,0 +1,571 @@
+//! Anomaly detection over the graph of which gateways witness which.
+//!
+//! The denylist and the per report checks catch known colluders and
+//! physically implausible witnesses, but not groups of gateways that game PoC
+//! together. The [`WitnessGraphDaemon`] builds a rolling beaconer to witness
+//! graph from the verified PoCs in the output bucket and periodically scores
+//! it for
+//!
+//! * closed clusters: small groups of gateways that only witness each other
+//! * consistent rssi: witnesses whose signal from a beaconer barely varies,
+//!   which real radio links do not do
+//! * static witness sets: beaconers witnessed by the same gateways time after
+//!   time
+//!
+//! The anomalies are written as [`IotWitnessAnomaly`] records for operators
+//! to review and feed into the denylist, and reported as metrics.
+
+use crate::telemetry;
+use chrono::{DateTime, Duration, Utc};
+use file_store::{
+    file_info_poller::FileInfoStream,
+    file_sink::FileSinkClient,
+    iot_valid_poc::IotPoc,
+    iot_witness_anomaly::{IotWitnessAnomaly, IotWitnessAnomalyKind, IotWitnessAnomalyV1},
+};
+use futures::{future::LocalBoxFuture, StreamExt, TryFutureExt};
+use helium_crypto::PublicKeyBinary;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use task_manager::ManagedTask;
+use tokio::{sync::mpsc::Receiver, time};
+
+/// Witness graph analysis settings, under `[witness_graph]`
+#[derive(Debug, Clone, Deserialize)]
+pub struct WitnessGraphSettings {
+    /// Whether the witness graph is built and analyzed. Default false
+    #[serde(default)]
+    pub enabled: bool,
+    /// Hours of PoCs the graph is built from. Default 72 hours
+    #[serde(default = "default_window_hours")]
+    pub window_hours: i64,
+    /// Interval in seconds at which the graph is analyzed. Default 1 hour
+    #[serde(default = "default_interval")]
+    pub interval: i64,
+    /// Largest group of gateways only witnessing each other that is reported
+    /// as a closed cluster. Default 8
+    #[serde(default = "default_max_cluster_size")]
+    pub max_cluster_size: usize,
+    /// Number of PoCs a cluster, a beaconer and witness pair or a beaconer
+    /// needs to be in before it is analyzed. Default 20
+    #[serde(default = "default_min_pocs")]
+    pub min_pocs: u64,
+    /// Standard deviation in dB of the signal of a witness from a beaconer at
+    /// or below which the pair is reported. Default 1.0
+    #[serde(default = "default_max_rssi_stddev")]
+    pub max_rssi_stddev: f64,
+    /// Share of the PoCs of a beaconer witnessed by the same gateways at or
+    /// above which the beaconer is reported. Default 0.9
+    #[serde(default = "default_min_static_witness_share")]
+    pub min_static_witness_share: f64,
+}
+
+fn default_window_hours() -> i64 {
+    72
+}
+
+fn default_interval() -> i64 {
+    60 * 60
+}
+
+fn default_max_cluster_size() -> usize {
+    8
+}
+
+fn default_min_pocs() -> u64 {
+    20
+}
+
+fn default_max_rssi_stddev() -> f64 {
+    1.0
+}
+
+fn default_min_static_witness_share() -> f64 {
+    0.9
+}
+
+impl Default for WitnessGraphSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_hours: default_window_hours(),
+            interval: default_interval(),
+            max_cluster_size: default_max_cluster_size(),
+            min_pocs: default_min_pocs(),
+            max_rssi_stddev: default_max_rssi_stddev(),
+            min_static_witness_share: default_min_static_witness_share(),
+        }
+    }
+}
+
+impl WitnessGraphSettings {
+    pub fn window(&self) -> Duration {
+        Duration::hours(self.window_hours)
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::seconds(self.interval)
+    }
+}
+
+/// A beacon and the signal, in deci-dBm, of each of its witnesses
+#[derive(Debug, Clone)]
+struct Observation {
+    timestamp: DateTime<Utc>,
+    beaconer: PublicKeyBinary,
+    witnesses: Vec<(PublicKeyBinary, i32)>,
+}
+
+/// The anomalies found in a witness graph
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub gateways: usize,
+    pub edges: usize,
+    pub anomalies: Vec<IotWitnessAnomaly>,
+}
+
+#[derive(Debug, Default)]
+pub struct WitnessGraph {
+    observations: Vec<Observation>,
+}
+
+impl WitnessGraph {
+    pub fn insert(&mut self, poc: &IotPoc) {
+        let witnesses = poc
+            .selected_witnesses
+            .iter()
+            .chain(poc.unselected_witnesses.iter())
+            .map(|witness| (witness.report.pub_key.clone(), witness.report.signal))
+            .collect();
+        self.observations.push(Observation {
+            timestamp: poc.beacon_report.received_timestamp,
+            beaconer: poc.beacon_report.report.pub_key.clone(),
+            witnesses,
+        });
+    }
+
+    /// Drop the PoCs whose beacons were received before the given time
+    pub fn prune(&mut self, before: DateTime<Utc>) {
+        self.observations
+            .retain(|observation| observation.timestamp >= before);
+    }
+
+    pub fn analyze(
+        &self,
+        settings: &WitnessGraphSettings,
+        window_start: DateTime<Utc>,
+        timestamp: DateTime<Utc>,
+    ) -> Analysis {
+        let anomaly = |kind, score, gateways, pocs| IotWitnessAnomaly {
+            kind,
+            score,
+            gateways,
+            pocs,
+            window_start,
+            timestamp,
+        };
+
+        // signals of each witness, by beaconer
+        let mut edges: HashMap<(&PublicKeyBinary, &PublicKeyBinary), Vec<i32>> = HashMap::new();
+        // counts of each witness set, by beaconer
+        let mut witness_sets: HashMap<&PublicKeyBinary, HashMap<BTreeSet<&PublicKeyBinary>, u64>> =
+            HashMap::new();
+        for observation in &self.observations {
+            for (witness, signal) in &observation.witnesses {
+                edges
+                    .entry((&observation.beaconer, witness))
+                    .or_default()
+                    .push(*signal);
+            }
+            if !observation.witnesses.is_empty() {
+                let witness_set = observation.witnesses.iter().map(|(w, _)| w).collect();
+                *witness_sets
+                    .entry(&observation.beaconer)
+                    .or_default()
+                    .entry(witness_set)
+                    .or_default() += 1;
+            }
+        }
+
+        let mut analysis = Analysis {
+            edges: edges.len(),
+            ..Default::default()
+        };
+
+        // closed clusters are the small connected components of the graph
+        let mut components = Components::default();
+        for (beaconer, witness) in edges.keys() {
+            components.union(*beaconer, *witness);
+        }
+        analysis.gateways = components.len();
+        let mut clusters: HashMap<usize, Cluster> = HashMap::new();
+        for (beaconer, witness) in edges.keys() {
+            let cluster = clusters.entry(components.find(*beaconer)).or_default();
+            cluster.gateways.insert(*beaconer);
+            cluster.gateways.insert(*witness);
+            if beaconer != witness {
+                cluster.links.insert(ordered(*beaconer, *witness));
+            }
+        }
+        for observation in &self.observations {
+            if let Some(root) = components.get(&observation.beaconer) {
+                if let Some(cluster) = clusters.get_mut(&root) {
+                    cluster.pocs += u64::from(!observation.witnesses.is_empty());
+                }
+            }
+        }
+        for cluster in clusters.into_values() {
+            let size = cluster.gateways.len();
+            if size < 2 || size > settings.max_cluster_size || cluster.pocs < settings.min_pocs {
+                continue;
+            }
+            // the share of possible links between the members that exist
+            let density = cluster.links.len() as f64 / (size * (size - 1) / 2) as f64;
+            analysis.anomalies.push(anomaly(
+                IotWitnessAnomalyKind::ClosedCluster,
+                density,
+                cluster.gateways.into_iter().cloned().collect(),
+                cluster.pocs,
+            ));
+        }
+
+        for ((beaconer, witness), signals) in &edges {
+            let pocs = signals.len() as u64;
+            if pocs < settings.min_pocs {
+                continue;
+            }
+            let stddev = signal_stddev_db(signals);
+            if stddev > settings.max_rssi_stddev {
+                continue;
+            }
+            let score = if settings.max_rssi_stddev > 0.0 {
+                1.0 - stddev / settings.max_rssi_stddev
+            } else {
+                1.0
+            };
+            analysis.anomalies.push(anomaly(
+                IotWitnessAnomalyKind::ConsistentRssi,
+                score,
+                vec![(*beaconer).clone(), (*witness).clone()],
+                pocs,
+            ));
+        }
+
+        for (beaconer, sets) in &witness_sets {
+            let pocs: u64 = sets.values().sum();
+            if pocs < settings.min_pocs {
+                continue;
+            }
+            let Some((witness_set, count)) = sets
+                .iter()
+                .filter(|(witness_set, _)| witness_set.len() >= 2)
+                .max_by_key(|(_, count)| **count)
+            else {
+                continue;
+            };
+            let share = *count as f64 / pocs as f64;
+            if share < settings.min_static_witness_share {
+                continue;
+            }
+            let gateways = std::iter::once(*beaconer)
+                .chain(witness_set.iter().copied())
+                .cloned()
+                .collect();
+            analysis.anomalies.push(anomaly(
+                IotWitnessAnomalyKind::StaticWitnessSet,
+                share,
+                gateways,
+                pocs,
+            ));
+        }
+
+        analysis.anomalies.sort_by(|a, b| {
+            a.kind
+                .cmp(&b.kind)
+                .then(b.score.total_cmp(&a.score))
+                .then_with(|| a.gateways.cmp(&b.gateways))
+        });
+        analysis
+    }
+}
+
+#[derive(Default)]
+struct Cluster<'a> {
+    gateways: BTreeSet<&'a PublicKeyBinary>,
+    links: HashSet<(&'a PublicKeyBinary, &'a PublicKeyBinary)>,
+    pocs: u64,
+}
+
+fn ordered<'a>(
+    a: &'a PublicKeyBinary,
+    b: &'a PublicKeyBinary,
+) -> (&'a PublicKeyBinary, &'a PublicKeyBinary) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Standard deviation of signals given in deci-dBm, in dB
+fn signal_stddev_db(signals: &[i32]) -> f64 {
+    let n = signals.len() as f64;
+    let mean = signals.iter().map(|s| f64::from(*s)).sum::<f64>() / n;
+    let variance = signals
+        .iter()
+        .map(|s| (f64::from(*s) - mean).powi(2))
+        .sum::<f64>()
+        / n;
+    variance.sqrt() / 10.0
+}
+
+/// Union-find over gateways
+#[derive(Default)]
+struct Components<'a> {
+    index: HashMap<&'a PublicKeyBinary, usize>,
+    parents: Vec<usize>,
+}
+
+impl<'a> Components<'a> {
+    fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    fn id(&mut self, gateway: &'a PublicKeyBinary) -> usize {
+        let next = self.parents.len();
+        let id = *self.index.entry(gateway).or_insert(next);
+        if id == next {
+            self.parents.push(id);
+        }
+        id
+    }
+
+    fn root(&mut self, mut id: usize) -> usize {
+        while self.parents[id] != id {
+            self.parents[id] = self.parents[self.parents[id]];
+            id = self.parents[id];
+        }
+        id
+    }
+
+    fn union(&mut self, a: &'a PublicKeyBinary, b: &'a PublicKeyBinary) {
+        let (a, b) = (self.id(a), self.id(b));
+        let (a, b) = (self.root(a), self.root(b));
+        if a != b {
+            self.parents[b] = a;
+        }
+    }
+
+    fn find(&mut self, gateway: &'a PublicKeyBinary) -> usize {
+        let id = self.id(gateway);
+        self.root(id)
+    }
+
+    fn get(&mut self, gateway: &PublicKeyBinary) -> Option<usize> {
+        let id = *self.index.get(gateway)?;
+        Some(self.root(id))
+    }
+}
+
+pub struct WitnessGraphDaemon {
+    pool: PgPool,
+    settings: WitnessGraphSettings,
+    graph: WitnessGraph,
+    poc_receiver: Receiver<FileInfoStream<IotPoc>>,
+    anomaly_sink: FileSinkClient,
+}
+
+impl ManagedTask for WitnessGraphDaemon {
+    fn start_task(
+        self: Box<Self>,
+        shutdown: triggered::Listener,
+    ) -> LocalBoxFuture<'static, anyhow::Result<()>> {
+        let handle = tokio::spawn(self.run(shutdown));
+        Box::pin(
+            handle
+                .map_err(anyhow::Error::from)
+                .and_then(|result| async move { result.map_err(anyhow::Error::from) }),
+        )
+    }
+}
+
+impl WitnessGraphDaemon {
+    pub fn new(
+        pool: PgPool,
+        settings: WitnessGraphSettings,
+        poc_receiver: Receiver<FileInfoStream<IotPoc>>,
+        anomaly_sink: FileSinkClient,
+    ) -> Self {
+        Self {
+            pool,
+            settings,
+            graph: WitnessGraph::default(),
+            poc_receiver,
+            anomaly_sink,
+        }
+    }
+
+    pub async fn run(mut self, shutdown: triggered::Listener) -> anyhow::Result<()> {
+        tracing::info!("starting witness graph daemon");
+        let interval = self
+            .settings
+            .interval()
+            .to_std()
+            .expect("valid interval in seconds");
+        // give the graph an interval to fill before the first analysis
+        let mut trigger_timer = time::interval_at(time::Instant::now() + interval, interval);
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.clone() => break,
+                _ = trigger_timer.tick() => self.analyze().await?,
+                msg = self.poc_receiver.recv() => if let Some(stream) = msg {
+                    self.handle_pocs(stream).await?;
+                }
+            }
+        }
+        tracing::info!("stopping witness graph daemon");
+        Ok(())
+    }
+
+    async fn handle_pocs(
+        &mut self,
+        file_info_stream: FileInfoStream<IotPoc>,
+    ) -> anyhow::Result<()> {
+        // PoC files are not recorded as processed, so that the graph is
+        // rebuilt from the whole window after a restart
+        let mut transaction = self.pool.begin().await?;
+        let mut pocs = file_info_stream.into_stream(&mut transaction).await?;
+        while let Some(poc) = pocs.next().await {
+            self.graph.insert(&poc);
+        }
+        transaction.rollback().await?;
+        Ok(())
+    }
+
+    async fn analyze(&mut self) -> anyhow::Result<()> {
+        let now = Utc::now();
+        let window_start = now - self.settings.window();
+        self.graph.prune(window_start);
+        let analysis = self.graph.analyze(&self.settings, window_start, now);
+        tracing::info!(
+            gateways = analysis.gateways,
+            edges = analysis.edges,
+            anomalies = analysis.anomalies.len(),
+            "analyzed witness graph"
+        );
+
+        telemetry::witness_graph_size(analysis.gateways, analysis.edges);
+        for kind in [
+            IotWitnessAnomalyKind::ClosedCluster,
+            IotWitnessAnomalyKind::ConsistentRssi,
+            IotWitnessAnomalyKind::StaticWitnessSet,
+        ] {
+            let count = analysis
+                .anomalies
+                .iter()
+                .filter(|anomaly| anomaly.kind == kind)
+                .count();
+            telemetry::witness_anomalies(kind.as_str(), count);
+        }
+
+        for anomaly in analysis.anomalies {
+            self.anomaly_sink
+                .write(IotWitnessAnomalyV1::from(anomaly), [])
+                .await?;
+        }
+        self.anomaly_sink.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(n: u8) -> PublicKeyBinary {
+        PublicKeyBinary::from(vec![n; 33])
+    }
+
+    fn observe(graph: &mut WitnessGraph, beaconer: u8, witnesses: &[(u8, i32)]) {
+        graph.observations.push(Observation {
+            timestamp: Utc::now(),
+            beaconer: key(beaconer),
+            witnesses: witnesses
+                .iter()
+                .map(|(witness, signal)| (key(*witness), *signal))
+                .collect(),
+        });
+    }
+
+    fn settings() -> WitnessGraphSettings {
+        WitnessGraphSettings {
+            min_pocs: 3,
+            max_cluster_size: 3,
+            ..Default::default()
+        }
+    }
+
+    fn kinds(analysis: &Analysis) -> Vec<IotWitnessAnomalyKind> {
+        analysis.anomalies.iter().map(|a| a.kind).collect()
+    }
+
+    #[test]
+    fn reports_a_closed_pair_with_a_static_signal() {
+        let mut graph = WitnessGraph::default();
+        for _ in 0..3 {
+            observe(&mut graph, 1, &[(2, -800)]);
+            observe(&mut graph, 2, &[(1, -805)]);
+        }
+        let now = Utc::now();
+        let analysis = graph.analyze(&settings(), now - Duration::hours(1), now);
+        assert_eq!(2, analysis.gateways);
+        assert_eq!(2, analysis.edges);
+        assert_eq!(
+            vec![
+                IotWitnessAnomalyKind::ClosedCluster,
+                IotWitnessAnomalyKind::ConsistentRssi,
+                IotWitnessAnomalyKind::ConsistentRssi,
+            ],
+            kinds(&analysis)
+        );
+        let cluster = &analysis.anomalies[0];
+        assert_eq!(vec![key(1), key(2)], cluster.gateways);
+        assert_eq!(6, cluster.pocs);
+        assert_eq!(1.0, cluster.score);
+    }
+
+    #[test]
+    fn reports_static_witness_sets_but_not_varying_ones() {
+        let mut graph = WitnessGraph::default();
+        for signal in [-900, -700, -1100, -800] {
+            // beaconer 1 is witnessed by 2 and 3 every time
+            observe(&mut graph, 1, &[(2, signal), (3, signal + 300)]);
+        }
+        // beaconer 4 is witnessed by a different pair every time
+        observe(&mut graph, 4, &[(2, -900), (3, -700)]);
+        observe(&mut graph, 4, &[(3, -1100), (5, -800)]);
+        observe(&mut graph, 4, &[(5, -600), (6, -1000)]);
+        // and 6 ties the cluster to gateways beyond the cluster size
+        observe(&mut graph, 6, &[(7, -900), (8, -1200)]);
+        let now = Utc::now();
+        let analysis = graph.analyze(&settings(), now - Duration::hours(1), now);
+        assert_eq!(
+            vec![IotWitnessAnomalyKind::StaticWitnessSet],
+            kinds(&analysis)
+        );
+        let anomaly = &analysis.anomalies[0];
+        assert_eq!(vec![key(1), key(2), key(3)], anomaly.gateways);
+        assert_eq!(4, anomaly.pocs);
+        assert_eq!(1.0, anomaly.score);
+    }
+
+    #[test]
+    fn prunes_observations_before_the_window() {
+        let mut graph = WitnessGraph::default();
+        observe(&mut graph, 1, &[(2, -800)]);
+        graph.prune(Utc::now() + Duration::seconds(1));
+        assert!(graph.observations.is_empty());
+    }
+}
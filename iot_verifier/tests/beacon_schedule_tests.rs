@@ -0,0 +1,151 @@
+use chrono::{Duration, TimeZone, Utc};
+use helium_crypto::PublicKeyBinary;
+use iot_verifier::beacon_schedule::{
+    epoch, seed_margin, BeaconSchedule, BeaconScheduleError, ScheduleParams, ScheduleSeed,
+    ScheduleTxScales,
+};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use sqlx::PgPool;
+use std::{collections::HashMap, str::FromStr};
+
+const PUBKEY1: &str = "112bUuQaE7j73THS9ABShHGokm46Miip9L361FSyWv7zSYn8hZWf";
+const HEX: u64 = 0x8c2681a3064edff;
+
+#[sqlx::test]
+async fn the_earliest_entropy_seeds_the_epoch(pool: PgPool) -> anyhow::Result<()> {
+    let epoch_start = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+    let previous_epoch = epoch_start - epoch();
+    let now = previous_epoch + Duration::hours(12);
+
+    ScheduleSeed::propose(&pool, b"second", previous_epoch + Duration::hours(1), now).await?;
+    ScheduleSeed::propose(&pool, b"later", previous_epoch + Duration::hours(2), now).await?;
+    // entropy loaded out of order still replaces a later seed
+    ScheduleSeed::propose(&pool, b"first", previous_epoch, now).await?;
+    assert_eq!(
+        Some(b"first".to_vec()),
+        ScheduleSeed::get(&pool, epoch_start).await?
+    );
+
+    // entropy of the same time is ordered by its bytes
+    ScheduleSeed::propose(&pool, b"another", previous_epoch, now).await?;
+    assert_eq!(
+        Some(b"another".to_vec()),
+        ScheduleSeed::get(&pool, epoch_start).await?
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn seeds_are_fixed_once_the_epoch_starts(pool: PgPool) -> anyhow::Result<()> {
+    let epoch_start = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+    let previous_epoch = epoch_start - epoch();
+
+    ScheduleSeed::propose(&pool, b"late", previous_epoch, epoch_start).await?;
+    assert_eq!(None, ScheduleSeed::get(&pool, epoch_start).await?);
+
+    ScheduleSeed::propose(
+        &pool,
+        b"second",
+        previous_epoch + Duration::hours(1),
+        previous_epoch + Duration::hours(12),
+    )
+    .await?;
+    ScheduleSeed::propose(&pool, b"first", previous_epoch, epoch_start).await?;
+    assert_eq!(
+        Some(b"second".to_vec()),
+        ScheduleSeed::get(&pool, epoch_start).await?
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn entropy_within_the_margin_seeds_nothing(pool: PgPool) -> anyhow::Result<()> {
+    let epoch_start = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+    let now = epoch_start - seed_margin() / 4;
+
+    ScheduleSeed::propose(&pool, b"late", epoch_start - seed_margin() / 2, now).await?;
+    assert_eq!(None, ScheduleSeed::get(&pool, epoch_start).await?);
+
+    ScheduleSeed::propose(&pool, b"early", epoch_start - seed_margin() * 2, now).await?;
+    assert_eq!(
+        Some(b"early".to_vec()),
+        ScheduleSeed::get(&pool, epoch_start).await?
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn unseeded_or_unscaled_epochs_are_not_scheduled(pool: PgPool) -> anyhow::Result<()> {
+    let epoch_start = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+    let previous_epoch = epoch_start - epoch();
+    let pub_key = PublicKeyBinary::from_str(PUBKEY1)?;
+    let params = ScheduleParams::default();
+    let resolve =
+        || BeaconSchedule::resolve(&pool, &pub_key, HEX, epoch_start, Decimal::ONE, &params);
+
+    assert!(matches!(
+        resolve().await,
+        Err(BeaconScheduleError::MissingSeed(missing)) if missing == epoch_start
+    ));
+
+    ScheduleSeed::propose(&pool, b"seed", previous_epoch, previous_epoch).await?;
+    assert!(matches!(
+        resolve().await,
+        Err(BeaconScheduleError::MissingTxScales(missing)) if missing == epoch_start
+    ));
+
+    ScheduleTxScales::save(&pool, &HashMap::new(), previous_epoch).await?;
+    assert_eq!(
+        BeaconSchedule::new(b"seed", &pub_key, epoch_start, Decimal::ONE, &params),
+        resolve().await?
+    );
+    Ok(())
+}
+
+#[sqlx::test]
+async fn schedules_are_scaled_by_the_last_refresh_before_the_epoch(
+    pool: PgPool,
+) -> anyhow::Result<()> {
+    let epoch_start = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+    let previous_epoch = epoch_start - epoch();
+    let pub_key = PublicKeyBinary::from_str(PUBKEY1)?;
+    let params = ScheduleParams::default();
+    ScheduleSeed::propose(&pool, b"seed", previous_epoch, previous_epoch).await?;
+
+    let tx_scales = |tx_scale| HashMap::from([(HEX, tx_scale)]);
+    ScheduleTxScales::save(&pool, &tx_scales(dec!(1.0)), previous_epoch).await?;
+    ScheduleTxScales::save(
+        &pool,
+        &tx_scales(dec!(0.5)),
+        epoch_start - Duration::minutes(5),
+    )
+    .await?;
+    // refreshes during the epoch fix the scales of the next one
+    ScheduleTxScales::save(&pool, &tx_scales(dec!(0.1)), epoch_start).await?;
+    ScheduleTxScales::save(
+        &pool,
+        &tx_scales(dec!(0.2)),
+        epoch_start + Duration::hours(1),
+    )
+    .await?;
+
+    assert_eq!(
+        Some(Some(dec!(0.5))),
+        ScheduleTxScales::get(&pool, epoch_start, HEX).await?
+    );
+    assert_eq!(
+        Some(Some(dec!(0.2))),
+        ScheduleTxScales::get(&pool, epoch_start + epoch(), HEX).await?
+    );
+    assert_eq!(
+        Some(None),
+        ScheduleTxScales::get(&pool, epoch_start, HEX + 1).await?
+    );
+
+    let schedule =
+        BeaconSchedule::resolve(&pool, &pub_key, HEX, epoch_start, Decimal::ONE, &params).await?;
+    assert_eq!(dec!(0.5), schedule.tx_scale);
+    assert_eq!(3, schedule.slots.len());
+    Ok(())
+}
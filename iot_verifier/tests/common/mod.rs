@@ -3,8 +3,9 @@ use chrono::{DateTime, Utc};
 use file_store::{
     file_sink::{FileSinkClient, Message as SinkMessage},
     iot_beacon_report::{IotBeaconIngestReport, IotBeaconReport},
+    iot_poc_rules::IotPocRules,
     iot_witness_report::{IotWitnessIngestReport, IotWitnessReport},
-    traits::{IngestId, MsgTimestamp},
+    traits::{IngestId, MsgDecode, MsgTimestamp},
 };
 use helium_crypto::PublicKeyBinary;
 use helium_proto::{
@@ -76,6 +77,15 @@ impl MockFileSinkReceiver {
         }
     }
 
+    pub async fn receive_poc_rules(&mut self) -> IotPocRules {
+        match self.receive().await {
+            Some(bytes) => {
+                IotPocRules::decode(bytes.as_slice()).expect("failed to decode expected poc rules")
+            }
+            None => panic!("failed to receive poc rules"),
+        }
+    }
+
     pub async fn receive_invalid_beacon(&mut self) -> LoraInvalidBeaconReportV1 {
         match self.receive().await {
             Some(bytes) => LoraInvalidBeaconReportV1::decode(bytes.as_slice())
@@ -5,7 +5,10 @@ use helium_proto::services::poc_lora::{
     InvalidParticipantSide, InvalidReason, LoraBeaconReportReqV1, LoraWitnessReportReqV1,
 };
 
-use iot_verifier::{poc_report::Report, purger::Purger};
+use iot_verifier::{
+    poc_report::{FailureReason, Report},
+    purger::Purger,
+};
 use sqlx::{PgPool, Pool, Postgres};
 use std::{self, str::FromStr, time::Duration};
 
@@ -116,6 +119,85 @@ async fn test_purger(pool: PgPool) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[sqlx::test]
+async fn test_purger_requeue(pool: PgPool) -> anyhow::Result<()> {
+    let (invalid_beacon_client, mut invalid_beacons) = common::create_file_sink();
+    let (invalid_witness_client, mut invalid_witnesses) = common::create_file_sink();
+    let stale_period = ChronoDuration::seconds(1);
+    let purger = Purger {
+        base_stale_period: ChronoDuration::seconds(0),
+        beacon_stale_period: stale_period,
+        witness_stale_period: stale_period,
+        entropy_stale_period: stale_period,
+        pool: pool.clone(),
+        invalid_beacon_sink: invalid_beacon_client,
+        invalid_witness_sink: invalid_witness_client,
+    };
+
+    // inject a beacon and a witness but no entropy, neither can be verified
+    let entropy_ts = Utc.timestamp_millis_opt(common::ENTROPY_TIMESTAMP).unwrap();
+    let report_ts = entropy_ts + ChronoDuration::minutes(1);
+    let beacon_to_inject = common::create_valid_beacon_report(common::BEACONER1, report_ts);
+    let witness_to_inject = common::create_valid_witness_report(common::WITNESS1, report_ts);
+    common::inject_beacon_report(pool.clone(), beacon_to_inject).await?;
+    common::inject_witness_report(pool.clone(), witness_to_inject).await?;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    purger.handle_db_tick().await?;
+    invalid_beacons.receive_invalid_beacon().await;
+    invalid_witnesses.receive_invalid_witness().await;
+
+    // the purged reports are kept with the reason they were never verified
+    let reasons: Vec<(String, FailureReason)> = sqlx::query_as(
+        "select report_type::text, reason from purged_poc_report order by report_type",
+    )
+    .fetch_all(&pool)
+    .await?;
+    assert_eq!(
+        vec![
+            ("witness".to_string(), FailureReason::BeaconMissing),
+            ("beacon".to_string(), FailureReason::EntropyMissing),
+        ],
+        reasons
+    );
+
+    // re-queueing outside of the window of the reports leaves them purged
+    let requeued = Report::requeue_purged(
+        &pool,
+        report_ts + ChronoDuration::minutes(1),
+        report_ts + ChronoDuration::minutes(2),
+        None,
+        None,
+    )
+    .await?;
+    assert_eq!(0, requeued);
+
+    // re-queue only the beacon
+    let requeued = Report::requeue_purged(
+        &pool,
+        report_ts - ChronoDuration::minutes(1),
+        report_ts + ChronoDuration::minutes(1),
+        None,
+        Some(FailureReason::EntropyMissing),
+    )
+    .await?;
+    assert_eq!(1, requeued);
+    let ready: Vec<String> = sqlx::query_scalar(
+        "select report_type::text from poc_report where status = 'ready' and attempts = 0",
+    )
+    .fetch_all(&pool)
+    .await?;
+    assert_eq!(vec!["beacon".to_string()], ready);
+    assert_eq!(1, get_purged_report_count(&pool).await?);
+    Ok(())
+}
+
+pub async fn get_purged_report_count(db: &Pool<Postgres>) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(" select count(id) from purged_poc_report ")
+        .fetch_one(db)
+        .await
+}
+
 pub async fn get_entropy_report_count(db: &Pool<Postgres>) -> Result<i64, sqlx::Error> {
     sqlx::query_scalar(" select count(id) from entropy ")
         .fetch_one(db)
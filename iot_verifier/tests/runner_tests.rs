@@ -17,7 +17,8 @@ use iot_config::{
 use iot_verifier::witness_updater::WitnessUpdater;
 use iot_verifier::{
     gateway_cache::GatewayCache, gateway_updater::GatewayUpdater, poc_report::Report,
-    region_cache::RegionCache, runner::Runner, tx_scaler::Server as DensityScaler,
+    poc_rules::PocRules, region_cache::RegionCache, runner::Runner,
+    tx_scaler::Server as DensityScaler,
 };
 use lazy_static::lazy_static;
 use sqlx::PgPool;
@@ -60,6 +61,7 @@ impl Gateways for MockIotConfigClient {
 struct TestContext {
     runner: Runner<MockIotConfigClient>,
     valid_pocs: MockFileSinkReceiver,
+    poc_rules: MockFileSinkReceiver,
     invalid_beacons: MockFileSinkReceiver,
     invalid_witnesses: MockFileSinkReceiver,
     entropy_ts: DateTime<Utc>,
@@ -71,6 +73,7 @@ impl TestContext {
         let (invalid_beacon_client, invalid_beacons) = common::create_file_sink();
         let (invalid_witness_client, invalid_witnesses) = common::create_file_sink();
         let (valid_poc_client, valid_pocs) = common::create_file_sink();
+        let (poc_rules_client, poc_rules) = common::create_file_sink();
 
         // create our mock iot config client
         let iot_config_client = MockIotConfigClient {
@@ -107,8 +110,10 @@ impl TestContext {
             invalid_beacon_sink: invalid_beacon_client,
             invalid_witness_sink: invalid_witness_client,
             poc_sink: valid_poc_client,
+            poc_rules_sink: poc_rules_client,
             hex_density_map: density_scaler.hex_density_map.clone(),
             witness_updater,
+            poc_rules: PocRules::default(),
         };
 
         // generate a datetime based on a hardcoded timestamp
@@ -127,6 +132,7 @@ impl TestContext {
         Ok(Self {
             runner,
             valid_pocs,
+            poc_rules,
             invalid_beacons,
             invalid_witnesses,
             entropy_ts: report_ts,
@@ -180,6 +186,13 @@ async fn valid_beacon_and_witness(pool: PgPool) -> anyhow::Result<()> {
     let valid_poc = ctx.valid_pocs.receive_valid_poc().await;
     assert_eq!(1, valid_poc.selected_witnesses.len());
     assert_eq!(0, valid_poc.unselected_witnesses.len());
+    // the rules the poc was verified with are recorded next to it
+    let poc_rules = ctx.poc_rules.receive_poc_rules().await;
+    assert_eq!(valid_poc.poc_id, poc_rules.poc_id);
+    assert_eq!(
+        PocRules::default().active_at(now).to_records(),
+        poc_rules.rules
+    );
     let valid_beacon = valid_poc.beacon_report.unwrap().report.clone().unwrap();
     let valid_witness_report = valid_poc.selected_witnesses[0].clone();
     let valid_witness = valid_witness_report.report.unwrap();
@@ -2,6 +2,7 @@ use crate::{burner::Burner, event_ids::EventIdPurger, settings::Settings};
 use anyhow::{bail, Result};
 use chrono::{TimeZone, Utc};
 use file_store::{
+    dead_letter,
     file_info_poller::{FileInfoStream, LookbackBehavior},
     file_sink::FileSinkClient,
     file_source, file_upload,
@@ -148,6 +149,14 @@ impl Cmd {
         .create()
         .await?;
 
+        let (session_dead_letters, session_dead_letters_server) = dead_letter::file_sink(
+            &FileType::DataTransferSessionIngestReport.to_string(),
+            store_base_path,
+        )
+        .file_upload(Some(file_upload.clone()))
+        .create()
+        .await?;
+
         let burner = Burner::new(valid_sessions, solana);
 
         let file_store = FileStore::from_settings(&settings.ingest).await?;
@@ -161,6 +170,7 @@ impl Cmd {
                 ))
                 .prefix(FileType::DataTransferSessionIngestReport.to_string())
                 .lookback(LookbackBehavior::StartAfter(settings.start_after()))
+                .dead_letter(Some(session_dead_letters))
                 .create()
                 .await?;
 
@@ -183,6 +193,7 @@ impl Cmd {
             .add_task(file_upload_server)
             .add_task(valid_sessions_server)
             .add_task(invalid_sessions_server)
+            .add_task(session_dead_letters_server)
             .add_task(reports_server)
             .add_task(event_id_purger)
             .add_task(daemon)
@@ -8,8 +8,8 @@ use crate::{
 use anyhow::Result;
 use chrono::Duration;
 use file_store::{
-    coverage::CoverageObjectIngestReport, file_info_poller::LookbackBehavior, file_sink,
-    file_source, file_upload, heartbeat::CbrsHeartbeatIngestReport,
+    coverage::CoverageObjectIngestReport, dead_letter, file_info_poller::LookbackBehavior,
+    file_sink, file_source, file_upload, heartbeat::CbrsHeartbeatIngestReport,
     mobile_subscriber::SubscriberLocationIngestReport, mobile_transfer::ValidDataTransferSession,
     speedtest::CellSpeedtestIngestReport, wifi_heartbeat::WifiHeartbeatIngestReport, FileStore,
     FileType,
@@ -52,23 +52,41 @@ impl Cmd {
         let (price_tracker, price_daemon) = PriceTracker::new_tm(&settings.price_tracker).await?;
 
         // CBRS Heartbeats
+        let (cbrs_heartbeat_dead_letters, cbrs_heartbeat_dead_letters_server) =
+            dead_letter::file_sink(
+                &FileType::CbrsHeartbeatIngestReport.to_string(),
+                store_base_path,
+            )
+            .file_upload(Some(file_upload.clone()))
+            .create()
+            .await?;
         let (cbrs_heartbeats, cbrs_heartbeats_server) =
             file_source::continuous_source::<CbrsHeartbeatIngestReport, _>()
                 .state(pool.clone())
                 .store(report_ingest.clone())
                 .lookback(LookbackBehavior::StartAfter(settings.start_after()))
                 .prefix(FileType::CbrsHeartbeatIngestReport.to_string())
+                .dead_letter(Some(cbrs_heartbeat_dead_letters))
                 .queue_size(1)
                 .create()
                 .await?;
 
         // Wifi Heartbeats
+        let (wifi_heartbeat_dead_letters, wifi_heartbeat_dead_letters_server) =
+            dead_letter::file_sink(
+                &FileType::WifiHeartbeatIngestReport.to_string(),
+                store_base_path,
+            )
+            .file_upload(Some(file_upload.clone()))
+            .create()
+            .await?;
         let (wifi_heartbeats, wifi_heartbeats_server) =
             file_source::continuous_source::<WifiHeartbeatIngestReport, _>()
                 .state(pool.clone())
                 .store(report_ingest.clone())
                 .lookback(LookbackBehavior::StartAfter(settings.start_after()))
                 .prefix(FileType::WifiHeartbeatIngestReport.to_string())
+                .dead_letter(Some(wifi_heartbeat_dead_letters))
                 .create()
                 .await?;
 
@@ -130,12 +148,20 @@ impl Cmd {
         );
 
         // Speedtests
+        let (speedtest_dead_letters, speedtest_dead_letters_server) = dead_letter::file_sink(
+            &FileType::CellSpeedtestIngestReport.to_string(),
+            store_base_path,
+        )
+        .file_upload(Some(file_upload.clone()))
+        .create()
+        .await?;
         let (speedtests, speedtests_server) =
             file_source::continuous_source::<CellSpeedtestIngestReport, _>()
                 .state(pool.clone())
                 .store(report_ingest.clone())
                 .lookback(LookbackBehavior::StartAfter(settings.start_after()))
                 .prefix(FileType::CellSpeedtestIngestReport.to_string())
+                .dead_letter(Some(speedtest_dead_letters))
                 .create()
                 .await?;
 
@@ -170,12 +196,20 @@ impl Cmd {
         );
 
         // Coverage objects
+        let (coverage_obj_dead_letters, coverage_obj_dead_letters_server) = dead_letter::file_sink(
+            &FileType::CoverageObjectIngestReport.to_string(),
+            store_base_path,
+        )
+        .file_upload(Some(file_upload.clone()))
+        .create()
+        .await?;
         let (coverage_objs, coverage_objs_server) =
             file_source::continuous_source::<CoverageObjectIngestReport, _>()
                 .state(pool.clone())
                 .store(report_ingest.clone())
                 .lookback(LookbackBehavior::StartAfter(settings.start_after()))
                 .prefix(FileType::CoverageObjectIngestReport.to_string())
+                .dead_letter(Some(coverage_obj_dead_letters))
                 .create()
                 .await?;
 
@@ -232,12 +266,21 @@ impl Cmd {
         );
 
         // subscriber location
+        let (subscriber_location_dead_letters, subscriber_location_dead_letters_server) =
+            dead_letter::file_sink(
+                &FileType::SubscriberLocationIngestReport.to_string(),
+                store_base_path,
+            )
+            .file_upload(Some(file_upload.clone()))
+            .create()
+            .await?;
         let (subscriber_location_ingest, subscriber_location_ingest_server) =
             file_source::continuous_source::<SubscriberLocationIngestReport, _>()
                 .state(pool.clone())
                 .store(report_ingest.clone())
                 .lookback(LookbackBehavior::StartAfter(settings.start_after()))
                 .prefix(FileType::SubscriberLocationIngestReport.to_string())
+                .dead_letter(Some(subscriber_location_dead_letters))
                 .create()
                 .await?;
 
@@ -261,12 +304,20 @@ impl Cmd {
         );
 
         // data transfers
+        let (data_session_dead_letters, data_session_dead_letters_server) = dead_letter::file_sink(
+            &FileType::ValidDataTransferSession.to_string(),
+            store_base_path,
+        )
+        .file_upload(Some(file_upload.clone()))
+        .create()
+        .await?;
         let (data_session_ingest, data_session_ingest_server) =
             file_source::continuous_source::<ValidDataTransferSession, _>()
                 .state(pool.clone())
                 .store(data_transfer_ingest.clone())
                 .lookback(LookbackBehavior::StartAfter(settings.start_after()))
                 .prefix(FileType::ValidDataTransferSession.to_string())
+                .dead_letter(Some(data_session_dead_letters))
                 .create()
                 .await?;
 
@@ -274,6 +325,12 @@ impl Cmd {
 
         TaskManager::builder()
             .add_task(file_upload_server)
+            .add_task(cbrs_heartbeat_dead_letters_server)
+            .add_task(wifi_heartbeat_dead_letters_server)
+            .add_task(speedtest_dead_letters_server)
+            .add_task(coverage_obj_dead_letters_server)
+            .add_task(subscriber_location_dead_letters_server)
+            .add_task(data_session_dead_letters_server)
             .add_task(cbrs_heartbeats_server)
             .add_task(wifi_heartbeats_server)
             .add_task(valid_heartbeats_server)